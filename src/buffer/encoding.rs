@@ -0,0 +1,300 @@
+use std::string::FromUtf8Error;
+
+use anyhow::{Result, bail};
+
+/// Bytes looked at to tell text from binary data
+const SNIFF_LEN: usize = 8192;
+/// Bytes shown on each line of the binary view
+const DUMP_WIDTH: usize = 16;
+
+/// How lines end in the file, the `fileformat` option
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileFormat {
+    /// `\n`
+    #[default]
+    Unix,
+    /// `\r\n`
+    Dos,
+    /// `\r`
+    Mac,
+}
+
+impl FileFormat {
+    pub fn parse(name: &str) -> Option<FileFormat> {
+        match name {
+            "unix" => Some(FileFormat::Unix),
+            "dos" => Some(FileFormat::Dos),
+            "mac" => Some(FileFormat::Mac),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FileFormat::Unix => "unix",
+            FileFormat::Dos => "dos",
+            FileFormat::Mac => "mac",
+        }
+    }
+
+    pub fn line_end(self) -> &'static str {
+        match self {
+            FileFormat::Unix => "\n",
+            FileFormat::Dos => "\r\n",
+            FileFormat::Mac => "\r",
+        }
+    }
+
+    /// Dos when every line ends with `\r\n`, mac when lines only end with `\r`, unix otherwise
+    /// With mixed endings the stray `\r`s stay in the lines, like vim keeps them
+    pub fn detect(text: &str) -> FileFormat {
+        let newlines = text.matches('\n').count();
+        if newlines == 0 && text.contains('\r') {
+            FileFormat::Mac
+        } else if newlines > 0 && text.matches("\r\n").count() == newlines {
+            FileFormat::Dos
+        } else {
+            FileFormat::Unix
+        }
+    }
+}
+
+/// How the file's bytes are turned into text, the `fileencoding` option
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileEncoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// One byte per char, for files that are not valid UTF-8
+    Latin1,
+}
+
+impl FileEncoding {
+    pub fn parse(name: &str) -> Option<FileEncoding> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(FileEncoding::Utf8),
+            "utf-16le" | "ucs-2le" => Some(FileEncoding::Utf16Le),
+            "utf-16" | "utf-16be" | "ucs-2" => Some(FileEncoding::Utf16Be),
+            "latin1" | "iso-8859-1" => Some(FileEncoding::Latin1),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FileEncoding::Utf8 => "utf-8",
+            FileEncoding::Utf16Le => "utf-16le",
+            FileEncoding::Utf16Be => "utf-16",
+            FileEncoding::Latin1 => "latin1",
+        }
+    }
+
+    fn bom(self) -> &'static [u8] {
+        match self {
+            FileEncoding::Utf8 => b"\xef\xbb\xbf",
+            FileEncoding::Utf16Le => b"\xff\xfe",
+            FileEncoding::Utf16Be => b"\xfe\xff",
+            FileEncoding::Latin1 => b"",
+        }
+    }
+
+    /// The bytes of `text`, after a byte order mark when `bom` is set
+    pub fn encode(self, text: &str, bom: bool) -> Result<Vec<u8>> {
+        let mut bytes = if bom { self.bom().to_vec() } else { Vec::new() };
+        match self {
+            FileEncoding::Utf8 => bytes.extend_from_slice(text.as_bytes()),
+            FileEncoding::Utf16Le => bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+            FileEncoding::Utf16Be => bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes)),
+            FileEncoding::Latin1 => {
+                for c in text.chars() {
+                    match u8::try_from(u32::from(c)) {
+                        Ok(byte) => bytes.push(byte),
+                        Err(_) => bail!("E513: Write error, conversion failed for {c:?} in latin1"),
+                    }
+                }
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn decode(self, bytes: &[u8]) -> Option<String> {
+        match self {
+            FileEncoding::Utf8 => String::from_utf8(bytes.to_vec()).ok(),
+            FileEncoding::Utf16Le | FileEncoding::Utf16Be => {
+                if !bytes.len().is_multiple_of(2) {
+                    return None;
+                }
+                let units = bytes.chunks_exact(2).map(|pair| match self {
+                    FileEncoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                    _ => u16::from_be_bytes([pair[0], pair[1]]),
+                });
+                char::decode_utf16(units)
+                    .collect::<Result<String, _>>()
+                    .ok()
+            }
+            FileEncoding::Latin1 => Some(bytes.iter().map(|&byte| char::from(byte)).collect()),
+        }
+    }
+}
+
+/// How a file is laid out on disk besides its lines, kept so writing it gives back the same bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileOptions {
+    pub fileformat: FileFormat,
+    pub fileencoding: FileEncoding,
+    /// The file starts with a byte order mark
+    pub bomb: bool,
+    /// The last line ends with a line break
+    pub endofline: bool,
+}
+
+impl Default for FileOptions {
+    fn default() -> Self {
+        Self {
+            fileformat: FileFormat::default(),
+            fileencoding: FileEncoding::default(),
+            bomb: false,
+            endofline: true,
+        }
+    }
+}
+
+/// A file's text with the encoding it was read in and whether it had a byte order mark
+/// The bytes come back when they aren't text
+pub fn decode(bytes: Vec<u8>) -> Result<(String, FileEncoding, bool), Vec<u8>> {
+    let boms = [
+        FileEncoding::Utf8,
+        FileEncoding::Utf16Le,
+        FileEncoding::Utf16Be,
+    ];
+    let (encoding, bomb) = match boms
+        .into_iter()
+        .find(|encoding| bytes.starts_with(encoding.bom()))
+    {
+        Some(encoding) => (encoding, true),
+        None => (guess_encoding(&bytes), false),
+    };
+    let start = if bomb { encoding.bom().len() } else { 0 };
+    let text = match encoding {
+        // UTF-8 is taken as it is so a large file isn't copied
+        FileEncoding::Utf8 => {
+            let mut text = String::from_utf8(bytes).map_err(FromUtf8Error::into_bytes)?;
+            if !looks_like_text(&text[start..]) {
+                return Err(text.into_bytes());
+            }
+            text.replace_range(..start, "");
+            text
+        }
+        _ => match encoding.decode(&bytes[start..]) {
+            Some(text) if looks_like_text(&text) => text,
+            _ => return Err(bytes),
+        },
+    };
+    Ok((text, encoding, bomb))
+}
+
+/// Break text into lines at the line breaks it uses, noting whether the last line has one
+/// Empty text is one empty line that gets a line break once there is something on it
+pub fn split_lines(text: &str) -> (Vec<String>, FileOptions) {
+    let fileformat = FileFormat::detect(text);
+    let line_end = fileformat.line_end();
+    let endofline = text.is_empty() || text.ends_with(line_end);
+    let lines = text
+        .strip_suffix(line_end)
+        .unwrap_or(text)
+        .split(line_end)
+        .map(str::to_string)
+        .collect();
+    let options = FileOptions {
+        fileformat,
+        endofline,
+        ..FileOptions::default()
+    };
+    (lines, options)
+}
+
+/// UTF-16 when most other bytes are zero, UTF-8 when the bytes are valid, latin1 otherwise
+/// UTF-16 goes first as ASCII in it is also valid UTF-8, only full of NULs
+fn guess_encoding(bytes: &[u8]) -> FileEncoding {
+    let sniffed = &bytes[..bytes.len().min(SNIFF_LEN)];
+    let zeros_at = |parity: usize| {
+        sniffed
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|&&byte| byte == 0)
+            .count()
+    };
+    let pairs = sniffed.len() / 2;
+    // ASCII in UTF-16 has a zero in the high byte of every unit
+    if pairs > 0 && zeros_at(0) == 0 && zeros_at(1) * 2 > pairs {
+        FileEncoding::Utf16Le
+    } else if pairs > 0 && zeros_at(1) == 0 && zeros_at(0) * 2 > pairs {
+        FileEncoding::Utf16Be
+    } else if std::str::from_utf8(bytes).is_ok() {
+        FileEncoding::Utf8
+    } else {
+        FileEncoding::Latin1
+    }
+}
+
+/// Whether the first bytes of a file look like UTF-8 text, a char cut off at their end is taken to go on
+pub fn looks_like_utf8(start: &[u8]) -> bool {
+    let sniffed = &start[..start.len().min(SNIFF_LEN)];
+    let text = match std::str::from_utf8(sniffed) {
+        Ok(text) => text,
+        Err(err) if err.error_len().is_none() => {
+            std::str::from_utf8(&sniffed[..err.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return false,
+    };
+    guess_encoding(text.as_bytes()) == FileEncoding::Utf8 && looks_like_text(text)
+}
+
+/// Text has no NULs and few control chars besides line breaks, tabs, form feeds and escapes
+fn looks_like_text(text: &str) -> bool {
+    let sniffed = text.chars().take(SNIFF_LEN);
+    let (mut chars, mut controls) = (0, 0);
+    for c in sniffed {
+        if c == '\0' {
+            return false;
+        }
+        chars += 1;
+        if c.is_control() && !"\t\n\r\x0c\x1b".contains(c) {
+            controls += 1;
+        }
+    }
+    controls * 10 <= chars
+}
+
+/// Lines showing `bytes` as offsets, hex and the printable ones, like `xxd`
+pub fn hex_dump(bytes: &[u8]) -> Vec<String> {
+    let lines: Vec<String> = bytes
+        .chunks(DUMP_WIDTH)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let hex: Vec<String> = chunk
+                .chunks(2)
+                .map(|pair| pair.iter().map(|byte| format!("{byte:02x}")).collect())
+                .collect();
+            let printable: String = chunk
+                .iter()
+                .map(|&byte| match byte {
+                    0x20..0x7f => char::from(byte),
+                    _ => '.',
+                })
+                .collect();
+            format!(
+                "{:08x}: {:<40} {printable}",
+                index * DUMP_WIDTH,
+                hex.join(" ")
+            )
+        })
+        .collect();
+    if lines.is_empty() {
+        vec![String::new()]
+    } else {
+        lines
+    }
+}
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+/// Name of the language a file is written in, from its name
+pub fn detect(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    let by_name = match name {
+        "Makefile" | "makefile" | "GNUmakefile" => Some("make"),
+        "Cargo.lock" => Some("toml"),
+        "Dockerfile" => Some("dockerfile"),
+        ".bashrc" | ".bash_profile" | ".profile" | ".zshrc" => Some("sh"),
+        _ => None,
+    };
+    if by_name.is_some() {
+        return by_name;
+    }
+    let filetype = match path.extension()?.to_str()? {
+        "rs" => "rust",
+        "json" => "json",
+        "toml" => "toml",
+        "md" | "markdown" => "markdown",
+        "sh" | "bash" | "zsh" => "sh",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "ts" => "typescript",
+        "c" | "h" => "c",
+        "go" => "go",
+        "lua" => "lua",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "yaml" | "yml" => "yaml",
+        _ => return None,
+    };
+    Some(filetype)
+}
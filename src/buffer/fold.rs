@@ -0,0 +1,353 @@
+use std::cmp::Reverse;
+
+use anyhow::{Result, bail};
+
+use super::{BufferOptions, undo::Edit};
+
+const OPEN_MARKER: &str = "{{{";
+const CLOSE_MARKER: &str = "}}}";
+
+/// Where folds come from, the `foldmethod` option
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FoldMethod {
+    /// Made with `zf`
+    #[default]
+    Manual,
+    /// Lines indented further than the ones around them
+    Indent,
+    /// From a line with `{{{` to one with `}}}`
+    Marker,
+    /// Where the syntax tree has blocks, like function bodies
+    Expr,
+}
+
+impl FoldMethod {
+    pub fn parse(name: &str) -> Option<FoldMethod> {
+        match name {
+            "manual" => Some(FoldMethod::Manual),
+            "indent" => Some(FoldMethod::Indent),
+            "marker" => Some(FoldMethod::Marker),
+            "expr" | "syntax" => Some(FoldMethod::Expr),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FoldMethod::Manual => "manual",
+            FoldMethod::Indent => "indent",
+            FoldMethod::Marker => "marker",
+            FoldMethod::Expr => "expr",
+        }
+    }
+}
+
+/// Lines `start` up to `end` that can be hidden behind one summary line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+    pub closed: bool,
+}
+
+impl Fold {
+    fn contains(&self, line: usize) -> bool {
+        (self.start..self.end).contains(&line)
+    }
+}
+
+/// A buffer's folds, nested ones included, ordered by start with outer folds first
+#[derive(Debug, Default)]
+pub struct Folds {
+    method: FoldMethod,
+    folds: Vec<Fold>,
+    /// The lines changed since the folds were last worked out from them
+    stale: bool,
+    /// The tabstop and shift width indent folds were last worked out with
+    measured: (usize, usize),
+}
+
+impl Folds {
+    pub fn method(&self) -> FoldMethod {
+        self.method
+    }
+
+    /// Switch method, manual folding keeps the folds there are
+    /// Folding by syntax needs a build with the syntax trees
+    pub fn set_method(&mut self, method: FoldMethod) -> Result<()> {
+        if method == FoldMethod::Expr && !cfg!(feature = "tree-sitter") {
+            bail!("E474: foldmethod=expr needs reovim built with tree-sitter");
+        }
+        self.method = method;
+        self.stale = method != FoldMethod::Manual;
+        Ok(())
+    }
+
+    /// Whether the folds have to be worked out from the lines again before use, indent folds also
+    /// when the buffer's indent options changed
+    pub fn is_stale(&self, options: &BufferOptions) -> bool {
+        self.stale
+            || (self.method == FoldMethod::Indent
+                && self.measured != (options.tabstop, options.shift_width()))
+    }
+
+    /// Work the folds out again for the method, folds starting where a closed one did stay closed
+    pub fn refresh(
+        &mut self,
+        lines: &[String],
+        options: &BufferOptions,
+        syntax_folds: impl FnOnce() -> Vec<(usize, usize)>,
+    ) {
+        self.stale = false;
+        self.measured = (options.tabstop, options.shift_width());
+        let mut folds = match self.method {
+            FoldMethod::Manual => return,
+            FoldMethod::Indent => indent_folds(lines, options),
+            FoldMethod::Marker => marker_folds(lines),
+            // Syntax tree ranges include their last line
+            FoldMethod::Expr => syntax_folds()
+                .into_iter()
+                .map(|(start, end)| Fold {
+                    start,
+                    end: end + 1,
+                    closed: false,
+                })
+                .collect(),
+        };
+        sort(&mut folds);
+        let closed: Vec<_> = levels(&self.folds)
+            .filter(|(fold, _)| fold.closed)
+            .map(|(fold, level)| (fold.start, level))
+            .collect();
+        let still_closed: Vec<_> = levels(&folds)
+            .map(|(fold, level)| closed.contains(&(fold.start, level)))
+            .collect();
+        for (fold, closed) in folds.iter_mut().zip(still_closed) {
+            fold.closed = closed;
+        }
+        self.folds = folds;
+    }
+
+    /// Drop every fold, for when the text is replaced as a whole
+    pub fn reset(&mut self) {
+        if self.method == FoldMethod::Manual {
+            self.folds.clear();
+        }
+        self.stale = self.method != FoldMethod::Manual;
+    }
+
+    /// Move the folds along with lines inserted or removed above them, or grow and shrink them
+    pub fn edited(&mut self, edit: &Edit) {
+        let (at, inserted, removed) = match edit {
+            Edit::Insert { at, lines } => (*at, lines.len(), 0),
+            Edit::Remove { at, lines } => (*at, 0, lines.len()),
+            Edit::Replace { .. } => {
+                self.stale = self.method != FoldMethod::Manual;
+                return;
+            }
+        };
+        // Lines inserted at a fold's first line go above it, at its end they go below it
+        let shift = |line: usize, is_end: bool| {
+            if removed > 0 {
+                if line >= at + removed {
+                    line - removed
+                } else {
+                    line.min(at)
+                }
+            } else if line > at || (line == at && !is_end) {
+                line + inserted
+            } else {
+                line
+            }
+        };
+        for fold in &mut self.folds {
+            fold.start = shift(fold.start, false);
+            fold.end = shift(fold.end, true);
+        }
+        self.folds.retain(|fold| fold.end > fold.start);
+        sort(&mut self.folds);
+        self.stale = self.method != FoldMethod::Manual;
+    }
+
+    /// Fold lines `start` up to `end` by hand, the new fold starts closed
+    pub fn create(&mut self, start: usize, end: usize) -> Result<()> {
+        if self.method != FoldMethod::Manual {
+            bail!("E350: Cannot create fold with current 'foldmethod'");
+        }
+        self.folds.push(Fold {
+            start,
+            end,
+            closed: true,
+        });
+        sort(&mut self.folds);
+        Ok(())
+    }
+
+    /// Open the outermost closed fold holding `line`, for `zo`
+    pub fn open_at(&mut self, line: usize) -> Result<()> {
+        let fold = self
+            .folds
+            .iter_mut()
+            .find(|fold| fold.closed && fold.contains(line));
+        match fold {
+            Some(fold) => fold.closed = false,
+            None => bail!("E490: No fold found"),
+        }
+        Ok(())
+    }
+
+    /// Close the innermost open fold holding `line`, for `zc`
+    /// Already closed folds stay as they are
+    pub fn close_at(&mut self, line: usize) -> Result<()> {
+        if !self.folds.iter().any(|fold| fold.contains(line)) {
+            bail!("E490: No fold found");
+        }
+        let fold = self
+            .folds
+            .iter_mut()
+            .rev()
+            .find(|fold| !fold.closed && fold.contains(line));
+        if let Some(fold) = fold {
+            fold.closed = true;
+        }
+        Ok(())
+    }
+
+    /// Open the fold at `line` when it is closed, close it otherwise, for `za`
+    pub fn toggle_at(&mut self, line: usize) -> Result<()> {
+        if self
+            .folds
+            .iter()
+            .any(|fold| fold.closed && fold.contains(line))
+        {
+            self.open_at(line)
+        } else {
+            self.close_at(line)
+        }
+    }
+
+    /// Open or close every fold, for `zR` and `zM`
+    pub fn set_all(&mut self, closed: bool) {
+        for fold in &mut self.folds {
+            fold.closed = closed;
+        }
+    }
+
+    /// Where the next fold below `line` starts, for `zj`
+    pub fn next_start(&self, line: usize) -> Option<usize> {
+        self.folds
+            .iter()
+            .map(|fold| fold.start)
+            .filter(|&start| start > line)
+            .min()
+    }
+
+    /// The last line of the nearest fold above `line`, for `zk`
+    pub fn previous_end(&self, line: usize) -> Option<usize> {
+        self.folds
+            .iter()
+            .map(|fold| fold.end - 1)
+            .filter(|&last| last < line)
+            .max()
+    }
+
+    /// The closed folds that aren't inside another closed fold, each shown as one line
+    pub fn closed(&self) -> Vec<(usize, usize)> {
+        let mut shown = Vec::new();
+        let mut line = 0;
+        for fold in self.folds.iter().filter(|fold| fold.closed) {
+            if fold.start >= line {
+                shown.push((fold.start, fold.end));
+                line = fold.end;
+            }
+        }
+        shown
+    }
+}
+
+fn sort(folds: &mut [Fold]) {
+    folds.sort_by_key(|fold| (fold.start, Reverse(fold.end)));
+}
+
+/// Each fold with how many folds it is inside
+fn levels(folds: &[Fold]) -> impl Iterator<Item = (&Fold, usize)> {
+    folds.iter().enumerate().map(|(index, fold)| {
+        let level = folds[..index]
+            .iter()
+            .filter(|outer| outer.start <= fold.start && fold.end <= outer.end)
+            .count();
+        (fold, level)
+    })
+}
+
+/// Folds for runs of lines indented at least one more level than the lines around them
+/// Blank lines take the level of the lines either side, whichever is lower
+/// A level is `shiftwidth` columns of indent, tabs reaching to the next `tabstop`
+fn indent_folds(lines: &[String], options: &BufferOptions) -> Vec<Fold> {
+    let indents: Vec<Option<usize>> = lines
+        .iter()
+        .map(|line| {
+            if line.trim().is_empty() {
+                return None;
+            }
+            Some(options.indent_width(line) / options.shift_width())
+        })
+        .collect();
+    let mut levels = vec![0; lines.len()];
+    let mut above = 0;
+    for (index, indent) in indents.iter().enumerate() {
+        levels[index] = match indent {
+            Some(level) => *level,
+            None => {
+                let below = indents[index..]
+                    .iter()
+                    .flatten()
+                    .next()
+                    .copied()
+                    .unwrap_or(0);
+                above.min(below)
+            }
+        };
+        if let Some(level) = indent {
+            above = *level;
+        }
+    }
+    // Folds still open at each level, by the line they start on
+    let mut open: Vec<usize> = Vec::new();
+    let mut folds = Vec::new();
+    for (index, &level) in levels.iter().chain(&[0]).enumerate() {
+        while open.len() > level {
+            let start = open.pop().expect("checked by the loop");
+            folds.push(Fold {
+                start,
+                end: index,
+                closed: false,
+            });
+        }
+        while open.len() < level {
+            open.push(index);
+        }
+    }
+    folds
+}
+
+/// Folds from lines with `{{{` to lines with `}}}`, markers nest and unmatched ones are ignored
+fn marker_folds(lines: &[String]) -> Vec<Fold> {
+    let mut open: Vec<usize> = Vec::new();
+    let mut folds = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        for _ in 0..line.matches(OPEN_MARKER).count() {
+            open.push(index);
+        }
+        for _ in 0..line.matches(CLOSE_MARKER).count() {
+            if let Some(start) = open.pop() {
+                folds.push(Fold {
+                    start,
+                    end: index + 1,
+                    closed: false,
+                });
+            }
+        }
+    }
+    folds
+}
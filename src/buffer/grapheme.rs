@@ -0,0 +1,91 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Columns a grapheme cluster takes on screen
+/// Wide chars and emoji sequences take two, a cluster of only zero width chars is drawn on a blank of its own
+pub fn cluster_width(cluster: &str) -> usize {
+    cluster.width().max(1)
+}
+
+/// Columns `text` takes on screen
+pub fn width(text: &str) -> usize {
+    text.graphemes(true).map(cluster_width).sum()
+}
+
+/// Each grapheme cluster of `text` with the char column it starts at
+pub fn clusters(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut col = 0;
+    text.graphemes(true).map(move |cluster| {
+        let start = col;
+        col += cluster.chars().count();
+        (start, cluster)
+    })
+}
+
+/// The char column the cluster holding char `col` starts at, `col` itself past the end of the text
+pub fn cluster_start(text: &str, col: usize) -> usize {
+    let mut found = col;
+    for (start, cluster) in clusters(text) {
+        if start > col {
+            break;
+        }
+        if col < start + cluster.chars().count() {
+            found = start;
+        }
+    }
+    found
+}
+
+/// The char column after the cluster holding char `col`, one further past the end of the text
+pub fn next_cluster(text: &str, col: usize) -> usize {
+    clusters(text)
+        .map(|(start, cluster)| start + cluster.chars().count())
+        .find(|&end| end > col)
+        .unwrap_or(col + 1)
+}
+
+/// The char column of the cluster before the one holding char `col`
+pub fn previous_cluster(text: &str, col: usize) -> usize {
+    let start = cluster_start(text, col);
+    clusters(text)
+        .map(|(start, _)| start)
+        .take_while(|&before| before < start)
+        .last()
+        .unwrap_or(0)
+}
+
+/// The byte offset of the cluster boundary at or after byte `byte`, the end of the text past it
+pub fn ceil_boundary(text: &str, byte: usize) -> usize {
+    text.grapheme_indices(true)
+        .map(|(start, _)| start)
+        .find(|&start| start >= byte)
+        .unwrap_or(text.len())
+}
+
+/// Screen columns before char `col`, chars past the end of the text take one each
+pub fn display_col(text: &str, col: usize) -> usize {
+    let mut display = 0;
+    let mut end = 0;
+    for (start, cluster) in clusters(text) {
+        if start >= col {
+            return display;
+        }
+        display += cluster_width(cluster);
+        end = start + cluster.chars().count();
+    }
+    display + col.saturating_sub(end)
+}
+
+/// The char column of the cluster drawn over screen column `display`, the end of the text when it is narrower
+pub fn col_at_display(text: &str, display: usize) -> usize {
+    let mut x = 0;
+    let mut end = 0;
+    for (start, cluster) in clusters(text) {
+        x += cluster_width(cluster);
+        if x > display {
+            return start;
+        }
+        end = start + cluster.chars().count();
+    }
+    end
+}
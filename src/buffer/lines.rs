@@ -0,0 +1,456 @@
+use std::{
+    cell::{Cell, OnceCell},
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    ops::{Index, Range},
+};
+
+use anyhow::{Result, bail};
+use tracing::{debug, warn};
+
+use super::{
+    encoding::{self, FileFormat, FileOptions},
+    undo::Edit,
+};
+
+/// Lines read together from a large file, the start of every one this many lines apart is indexed
+const BLOCK: usize = 64;
+/// Bytes read at a time while indexing a large file
+const CHUNK: usize = 1 << 20;
+/// Blocks of a large file's lines kept once read, the ones asked for longest ago are let go of past these
+const CACHED_BLOCKS: usize = 256;
+
+/// A buffer's lines, all in memory, or for a large file read from it a block at a time as they are asked for
+pub struct Lines {
+    /// Every line, from the start for text that isn't a large file's, or once something needed all of them
+    all: OnceCell<Vec<String>>,
+    /// The large file the lines not edited yet are read from
+    file: Option<FileLines>,
+    /// What went wrong reading from the file, kept once it is no longer read from
+    read_error: Option<String>,
+}
+
+/// A large file's lines as runs of the ones in the file and the ones edited
+struct FileLines {
+    file: File,
+    /// Bytes in the file
+    size: u64,
+    /// Where each block of lines starts in the file
+    starts: Vec<u64>,
+    /// Lines end with `\r\n`
+    dos: bool,
+    /// Lines in the file as it was indexed
+    lines: usize,
+    /// The blocks read and kept
+    blocks: Vec<OnceCell<Vec<String>>>,
+    /// When each block was last asked for, going by `clock`
+    used: Vec<Cell<u64>>,
+    clock: Cell<u64>,
+    /// How many blocks are kept
+    loaded: Cell<usize>,
+    /// The first read that failed, the lines it was for show up empty
+    error: OnceCell<String>,
+    pieces: Vec<Piece>,
+    len: usize,
+}
+
+enum Piece {
+    /// These lines of the file, as it was indexed
+    File(Range<usize>),
+    /// Lines typed, changed or inserted
+    Edited(Vec<String>),
+}
+
+impl Piece {
+    fn len(&self) -> usize {
+        match self {
+            Piece::File(lines) => lines.len(),
+            Piece::Edited(lines) => lines.len(),
+        }
+    }
+}
+
+impl From<Vec<String>> for Lines {
+    fn from(lines: Vec<String>) -> Self {
+        Lines {
+            all: OnceCell::from(lines),
+            file: None,
+            read_error: None,
+        }
+    }
+}
+
+impl Lines {
+    /// Index a large file's lines without reading them in, `None` when it isn't UTF-8 text and has to be
+    /// read as a whole to be decoded
+    pub fn index(mut file: File) -> Result<Option<(Lines, FileOptions)>> {
+        let size = file.metadata()?.len();
+        let mut chunk = vec![0; CHUNK];
+        let mut filled = file.read(&mut chunk)?;
+        let bomb = chunk[..filled].starts_with(b"\xef\xbb\xbf");
+        let text_start = if bomb { 3 } else { 0 };
+        if !encoding::looks_like_utf8(&chunk[text_start..filled]) {
+            return Ok(None);
+        }
+        let mut starts = vec![text_start as u64];
+        let (mut newlines, mut dos_ends, mut any_cr) = (0, 0, false);
+        let mut previous = 0;
+        // Where the chunk is in the file, and the bytes at its start of a char the last one cut off
+        let (mut at, mut kept) = (0, 0);
+        loop {
+            let bytes = &chunk[..filled];
+            let whole = match std::str::from_utf8(bytes) {
+                Ok(_) => filled,
+                Err(err) if err.error_len().is_none() => err.valid_up_to(),
+                Err(_) => return Ok(None),
+            };
+            for (offset, &byte) in bytes.iter().enumerate().skip(kept) {
+                match byte {
+                    b'\n' => {
+                        newlines += 1;
+                        if previous == b'\r' {
+                            dos_ends += 1;
+                        }
+                        if newlines % BLOCK == 0 {
+                            starts.push(at + offset as u64 + 1);
+                        }
+                    }
+                    b'\r' => any_cr = true,
+                    _ => {}
+                }
+                previous = byte;
+            }
+            chunk.copy_within(whole..filled, 0);
+            kept = filled - whole;
+            at += whole as u64;
+            let read = file.read(&mut chunk[kept..])?;
+            if read == 0 {
+                break;
+            }
+            filled = kept + read;
+        }
+        if kept > 0 || (newlines == 0 && any_cr) {
+            // A char cut off by the end of the file, or lines ending with `\r` alone
+            return Ok(None);
+        }
+        let endofline = previous == b'\n';
+        let len = (newlines + usize::from(!endofline)).max(1);
+        let dos = newlines > 0 && dos_ends == newlines;
+        let options = FileOptions {
+            fileformat: if dos {
+                FileFormat::Dos
+            } else {
+                FileFormat::Unix
+            },
+            endofline,
+            bomb,
+            ..FileOptions::default()
+        };
+        let file = FileLines {
+            file,
+            size,
+            starts,
+            dos,
+            lines: len,
+            blocks: (0..len.div_ceil(BLOCK)).map(|_| OnceCell::new()).collect(),
+            used: (0..len.div_ceil(BLOCK)).map(|_| Cell::new(0)).collect(),
+            clock: Cell::new(0),
+            loaded: Cell::new(0),
+            error: OnceCell::new(),
+            pieces: vec![Piece::File(0..len)],
+            len,
+        };
+        let lines = Lines {
+            all: OnceCell::new(),
+            file: Some(file),
+            read_error: None,
+        };
+        Ok(Some((lines, options)))
+    }
+
+    pub fn len(&self) -> usize {
+        match (self.all.get(), &self.file) {
+            (Some(all), _) => all.len(),
+            (None, Some(file)) => file.len,
+            (None, None) => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, line: usize) -> Option<&str> {
+        match (self.all.get(), &self.file) {
+            (Some(all), _) => all.get(line).map(String::as_str),
+            (None, Some(file)) => file.get(line),
+            (None, None) => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &str> {
+        (0..self.len()).map(|line| &self[line])
+    }
+
+    /// Copies of the lines in `lines`
+    pub fn cloned(&self, lines: Range<usize>) -> Vec<String> {
+        lines.map(|line| self[line].to_string()).collect()
+    }
+
+    /// Every line, reading in the rest of a large file first, for work that goes over all of them
+    pub fn as_slice(&self) -> &[String] {
+        self.all.get_or_init(|| {
+            debug!("reading every line of a large file in");
+            self.iter().map(str::to_string).collect()
+        })
+    }
+
+    /// Every line in turn, the lines of a large file not kept in memory are read without being kept
+    /// Fails when reading from the file does, rather than giving lines that aren't there
+    pub fn each(&self, mut f: impl FnMut(&str) -> Result<()>) -> Result<()> {
+        match (self.all.get(), &self.file) {
+            (Some(all), _) => all.iter().try_for_each(|line| f(line)),
+            (None, Some(file)) => file.each(f),
+            (None, None) => Ok(()),
+        }
+    }
+
+    /// Read every line in and stop reading from the file, for when it is about to change
+    pub fn read_all(&mut self) {
+        self.as_slice();
+        self.drop_file();
+    }
+
+    /// What went wrong reading lines from the file, the lines it was for are empty and not what is there
+    pub fn read_error(&self) -> Option<&str> {
+        let file_error = self.file.as_ref().and_then(|file| file.error.get());
+        self.read_error
+            .as_deref()
+            .or(file_error.map(String::as_str))
+    }
+
+    /// Let go of a large file's blocks asked for longest ago past the ones kept, or of the file once
+    /// every line is in memory
+    pub fn forget_unused(&mut self) {
+        if self.all.get().is_some() {
+            self.drop_file();
+        } else if let Some(file) = &mut self.file {
+            file.forget_unused();
+        }
+    }
+
+    /// How many blocks of a large file's lines are kept in memory
+    #[cfg(test)]
+    pub fn kept_blocks(&self) -> usize {
+        self.file.as_ref().map_or(0, |file| file.loaded.get())
+    }
+
+    /// Stop reading from the file, keeping what went wrong reading it
+    fn drop_file(&mut self) {
+        if let Some(file) = self.file.take() {
+            self.read_error = self.read_error.take().or(file.error.into_inner());
+        }
+    }
+
+    /// Whether lines are still read from a large file as they are needed
+    pub fn is_lazy(&self) -> bool {
+        self.all.get().is_none()
+    }
+
+    /// Apply the edit, to the lines in memory or to the runs of a large file's lines
+    pub fn apply(&mut self, edit: &Edit) {
+        if let Some(all) = self.all.get_mut() {
+            edit.apply(all);
+            self.drop_file();
+            return;
+        }
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        match edit {
+            Edit::Insert { at, lines } => {
+                let at = file.split((*at).min(file.len));
+                file.pieces.insert(at, Piece::Edited(lines.clone()));
+                file.len += lines.len();
+            }
+            Edit::Remove { at, lines } => {
+                let start = (*at).min(file.len);
+                let end = (start + lines.len()).min(file.len);
+                file.len -= end - start;
+                let (start, end) = (file.split(start), file.split(end));
+                file.pieces.drain(start..end);
+            }
+            Edit::Replace { at, after, .. } => {
+                if *at >= file.len {
+                    return;
+                }
+                let (start, end) = (file.split(*at), file.split(at + 1));
+                file.pieces
+                    .splice(start..end, [Piece::Edited(vec![after.clone()])]);
+            }
+        }
+        file.join();
+        file.forget_unused();
+    }
+}
+
+impl Index<usize> for Lines {
+    type Output = str;
+
+    fn index(&self, line: usize) -> &str {
+        self.get(line).expect("line index in bounds")
+    }
+}
+
+impl FileLines {
+    /// The line at `line` in the buffer
+    fn get(&self, mut line: usize) -> Option<&str> {
+        for piece in &self.pieces {
+            match piece {
+                Piece::File(lines) if line < lines.len() => {
+                    return Some(self.file_line(lines.start + line));
+                }
+                Piece::Edited(lines) if line < lines.len() => return Some(&lines[line]),
+                piece => line -= piece.len(),
+            }
+        }
+        None
+    }
+
+    /// The line numbered `line` in the file, reading in its block when it isn't kept
+    /// A block that can't be read is empty, the error is kept for `Lines::read_error`
+    fn file_line(&self, line: usize) -> &str {
+        let block = line / BLOCK;
+        self.used[block].set(self.clock.get());
+        self.clock.set(self.clock.get() + 1);
+        let lines = self.blocks[block].get_or_init(|| {
+            self.loaded.set(self.loaded.get() + 1);
+            self.read_block(block).unwrap_or_else(|err| {
+                warn!("could not read lines from the file: {err}");
+                let _ = self.error.set(err.to_string());
+                Vec::new()
+            })
+        });
+        lines.get(line % BLOCK).map_or("", String::as_str)
+    }
+
+    /// Every line in turn, reading the blocks that aren't kept without keeping them
+    fn each(&self, mut f: impl FnMut(&str) -> Result<()>) -> Result<()> {
+        if let Some(err) = self.error.get() {
+            bail!("{err}");
+        }
+        for piece in &self.pieces {
+            let lines = match piece {
+                Piece::Edited(lines) => {
+                    lines.iter().try_for_each(|line| f(line))?;
+                    continue;
+                }
+                Piece::File(lines) => lines,
+            };
+            let mut line = lines.start;
+            while line < lines.end {
+                let block = line / BLOCK;
+                let end = lines.end.min((block + 1) * BLOCK);
+                let read;
+                let block_lines = match self.blocks[block].get() {
+                    Some(kept) => kept,
+                    None => {
+                        read = self.read_block(block)?;
+                        &read
+                    }
+                };
+                block_lines[line % BLOCK..end - block * BLOCK]
+                    .iter()
+                    .try_for_each(|line| f(line))?;
+                line = end;
+            }
+        }
+        Ok(())
+    }
+
+    /// The lines of block `block`, failing when the file changed since it was indexed so they aren't there
+    fn read_block(&self, block: usize) -> Result<Vec<String>> {
+        let start = self.starts[block];
+        let end = self.starts.get(block + 1).copied().unwrap_or(self.size);
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(start))?;
+        let mut bytes = Vec::new();
+        file.take(end.saturating_sub(start))
+            .read_to_end(&mut bytes)?;
+        let Ok(text) = String::from_utf8(bytes) else {
+            bail!("the file changed since it was read, it isn't UTF-8 any more");
+        };
+        let expected = (self.lines - block * BLOCK).min(BLOCK);
+        let lines: Vec<String> = text
+            .split('\n')
+            .take(expected)
+            .map(|line| match self.dos {
+                true => line.strip_suffix('\r').unwrap_or(line).to_string(),
+                false => line.to_string(),
+            })
+            .collect();
+        if lines.len() < expected {
+            bail!(
+                "the file changed since it was read, line {} is gone",
+                block * BLOCK + lines.len() + 1
+            );
+        }
+        Ok(lines)
+    }
+
+    /// Let go of the blocks asked for longest ago, keeping `CACHED_BLOCKS`
+    fn forget_unused(&mut self) {
+        let excess = self.loaded.get().saturating_sub(CACHED_BLOCKS);
+        if excess == 0 {
+            return;
+        }
+        let mut kept: Vec<usize> = (0..self.blocks.len())
+            .filter(|&block| self.blocks[block].get().is_some())
+            .collect();
+        kept.sort_unstable_by_key(|&block| self.used[block].get());
+        for &block in &kept[..excess.min(kept.len())] {
+            self.blocks[block].take();
+        }
+        self.loaded.set(kept.len() - excess.min(kept.len()));
+    }
+
+    /// Split the piece `line` is in so a piece starts at it, returning that piece's index
+    fn split(&mut self, mut line: usize) -> usize {
+        for index in 0..self.pieces.len() {
+            let len = self.pieces[index].len();
+            if line == 0 {
+                return index;
+            }
+            if line < len {
+                let rest = match &mut self.pieces[index] {
+                    Piece::File(lines) => {
+                        let rest = lines.start + line..lines.end;
+                        lines.end = rest.start;
+                        Piece::File(rest)
+                    }
+                    Piece::Edited(lines) => Piece::Edited(lines.split_off(line)),
+                };
+                self.pieces.insert(index + 1, rest);
+                return index + 1;
+            }
+            line -= len;
+        }
+        self.pieces.len()
+    }
+
+    /// Put runs next to each other that can be one back together, and drop empty ones
+    fn join(&mut self) {
+        let mut pieces: Vec<Piece> = Vec::with_capacity(self.pieces.len());
+        for piece in self.pieces.drain(..) {
+            match (pieces.last_mut(), piece) {
+                (_, piece) if piece.len() == 0 => {}
+                (Some(Piece::Edited(last)), Piece::Edited(lines)) => last.extend(lines),
+                (Some(Piece::File(last)), Piece::File(lines)) if last.end == lines.start => {
+                    last.end = lines.end;
+                }
+                (_, piece) => pieces.push(piece),
+            }
+        }
+        self.pieces = pieces;
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::Result;
+
+use crate::buffer::{Buffer, BufferId};
+
+/// Every loaded buffer, shown or hidden, in the order they were opened
+/// Buffers are shared with the components displaying them
+pub struct BufferList {
+    buffers: Vec<Rc<RefCell<Buffer>>>,
+    next_id: BufferId,
+}
+
+impl Default for BufferList {
+    fn default() -> Self {
+        Self {
+            buffers: Vec::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl BufferList {
+    /// Add a buffer to the list, assigning its buffer number
+    pub fn add(&mut self, mut buffer: Buffer) -> Rc<RefCell<Buffer>> {
+        buffer.id = self.next_id;
+        self.next_id += 1;
+        let buffer = Rc::new(RefCell::new(buffer));
+        self.buffers.push(buffer.clone());
+        buffer
+    }
+
+    /// The buffer editing `path`, by whichever name it was opened with, `./` and `..` and symlinks
+    /// included
+    pub fn find(&self, path: &Path) -> Option<Rc<RefCell<Buffer>>> {
+        let path = canonical(path);
+        self.buffers
+            .iter()
+            .find(|buffer| buffer.borrow().file_path().map(canonical).as_ref() == Some(&path))
+            .cloned()
+    }
+
+    /// The buffer already editing `path`, or a newly loaded one
+    pub fn open(&mut self, path: &Path) -> Result<Rc<RefCell<Buffer>>> {
+        if let Some(buffer) = self.find(path) {
+            return Ok(buffer);
+        }
+        Ok(self.add(Buffer::from_file_path(path)?))
+    }
+
+    pub fn get(&self, id: BufferId) -> Option<Rc<RefCell<Buffer>>> {
+        self.buffers
+            .iter()
+            .find(|buffer| buffer.borrow().id() == id)
+            .cloned()
+    }
+
+    fn position(&self, id: BufferId) -> Option<usize> {
+        self.buffers
+            .iter()
+            .position(|buffer| buffer.borrow().id() == id)
+    }
+
+    /// The buffer after `id`, wrapping around to the first
+    pub fn next(&self, id: BufferId, count: usize) -> Option<Rc<RefCell<Buffer>>> {
+        let position = self.position(id)?;
+        let index = (position + count) % self.buffers.len();
+        self.buffers.get(index).cloned()
+    }
+
+    /// The buffer before `id`, wrapping around to the last
+    pub fn prev(&self, id: BufferId, count: usize) -> Option<Rc<RefCell<Buffer>>> {
+        let position = self.position(id)?;
+        let len = self.buffers.len();
+        let index = (position + len - count % len) % len;
+        self.buffers.get(index).cloned()
+    }
+
+    /// Remove a buffer from the list, returning it
+    pub fn remove(&mut self, id: BufferId) -> Option<Rc<RefCell<Buffer>>> {
+        let position = self.position(id)?;
+        Some(self.buffers.remove(position))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rc<RefCell<Buffer>>> {
+        self.buffers.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    /// 1-based position of the buffer in the list, for the status line
+    pub fn index_of(&self, id: BufferId) -> Option<usize> {
+        self.position(id).map(|position| position + 1)
+    }
+}
+
+/// `path` with symlinks and `..` resolved when the file exists, as it is otherwise
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
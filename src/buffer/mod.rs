@@ -0,0 +1,1163 @@
+use std::{
+    cell::RefCell,
+    fs::{self, File},
+    io::{BufWriter, ErrorKind, Read, Seek, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use tracing::warn;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    lsp::protocol::{Diagnostic, Severity},
+    syntax::{Highlighter, Span, SyntaxTree, TextObject, languages::Languages},
+    vcs::{
+        VcsInfo, VcsStatus,
+        diff::{Hunk, diff},
+    },
+};
+
+pub mod encoding;
+pub mod filetype;
+pub mod fold;
+pub mod grapheme;
+pub mod lines;
+pub mod list;
+pub mod swap;
+#[cfg(test)]
+mod tests;
+pub mod undo;
+
+use encoding::FileOptions;
+use fold::Folds;
+use lines::Lines;
+use swap::{SwapFile, SwapText};
+use undo::{Edit, UndoHistory, content_hash, hash_more};
+
+pub type BufferId = usize;
+
+/// Files larger than this open without highlighting, git, language servers or completion
+const LARGE_FILE_SIZE: usize = 20 * 1024 * 1024;
+
+/// Where swap files or undo histories, the `kind` of file, are kept, under `$XDG_STATE_HOME/reovim`
+fn state_dir(kind: &str) -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(base.join("reovim").join(kind))
+}
+
+/// The file of `kind` kept for `file`, named after its whole path with `%` for each separator as vim does
+fn state_file(kind: &str, file: &Path, suffix: &str) -> Option<PathBuf> {
+    let name = file
+        .to_string_lossy()
+        .replace(std::path::MAIN_SEPARATOR, "%");
+    Some(state_dir(kind)?.join(format!("{name}{suffix}")))
+}
+
+/// Options that apply to a single buffer
+#[derive(Debug, Clone, Copy)]
+pub struct BufferOptions {
+    /// Refuse edits and writes
+    pub read_only: bool,
+    /// Columns between tab stops when tabs are drawn
+    pub tabstop: usize,
+    /// Columns `>>`, `<<` and auto-indent move by, `tabstop` when 0
+    pub shiftwidth: usize,
+    /// Columns Tab and Backspace in insert mode move by, off when 0
+    pub softtabstop: usize,
+    /// Indent with spaces instead of tabs
+    pub expandtab: bool,
+    /// New lines start with the indent of the line they were opened from
+    pub autoindent: bool,
+    /// Save the undo history when the file is written, so undo reaches back past this session
+    pub undofile: bool,
+    /// Line breaks, encoding and byte order mark to write the file with
+    pub file: FileOptions,
+}
+
+impl Default for BufferOptions {
+    fn default() -> Self {
+        Self {
+            read_only: false,
+            tabstop: 8,
+            shiftwidth: 8,
+            softtabstop: 0,
+            expandtab: false,
+            autoindent: true,
+            undofile: false,
+            file: FileOptions::default(),
+        }
+    }
+}
+
+impl BufferOptions {
+    /// The on/off option called `name`, by its full or short name
+    pub fn switch(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "expandtab" | "et" => Some(&mut self.expandtab),
+            "autoindent" | "ai" => Some(&mut self.autoindent),
+            "undofile" | "udf" => Some(&mut self.undofile),
+            "bomb" => Some(&mut self.file.bomb),
+            "endofline" | "eol" => Some(&mut self.file.endofline),
+            _ => None,
+        }
+    }
+
+    /// The number option called `name`, by its full or short name
+    pub fn number(&mut self, name: &str) -> Option<&mut usize> {
+        match name {
+            "tabstop" | "ts" => Some(&mut self.tabstop),
+            "shiftwidth" | "sw" => Some(&mut self.shiftwidth),
+            "softtabstop" | "sts" => Some(&mut self.softtabstop),
+            _ => None,
+        }
+    }
+
+    /// Columns one level of indent takes
+    pub fn shift_width(&self) -> usize {
+        match self.shiftwidth {
+            0 => self.tabstop.max(1),
+            width => width,
+        }
+    }
+
+    /// Columns `text` takes from the start of a line, tabs reach to the next tab stop
+    pub fn display_width(&self, text: &str) -> usize {
+        let tabstop = self.tabstop.max(1);
+        text.graphemes(true)
+            .fold(0, |width, cluster| match cluster {
+                "\t" => width + tabstop - width % tabstop,
+                _ => width + grapheme::cluster_width(cluster),
+            })
+    }
+
+    /// Columns the blanks at the start of `text` take
+    pub fn indent_width(&self, text: &str) -> usize {
+        let blanks = text.len() - text.trim_start_matches([' ', '\t']).len();
+        self.display_width(&text[..blanks])
+    }
+
+    /// Blanks that go from screen column `from` to `to`, tabs where they fit unless `expandtab` is set
+    pub fn blanks(&self, from: usize, to: usize) -> String {
+        let tabstop = self.tabstop.max(1);
+        let mut blanks = String::new();
+        let mut at = from;
+        if !self.expandtab {
+            while at + tabstop - at % tabstop <= to {
+                blanks.push('\t');
+                at += tabstop - at % tabstop;
+            }
+        }
+        blanks.push_str(&" ".repeat(to.saturating_sub(at)));
+        blanks
+    }
+}
+
+/// The text of a file (or stdin) loaded into the editor
+/// Lines are stored without their line endings, there is always at least one line
+pub struct Buffer {
+    id: BufferId,
+    file_path: Option<PathBuf>,
+    lines: Lines,
+    pub options: BufferOptions,
+    /// The file did not exist when opened, nothing has been written to disk yet
+    is_new: bool,
+    /// The contents were piped in on stdin
+    from_stdin: bool,
+    undo: UndoHistory,
+    /// Undo state when the buffer was last read or written
+    saved_state: u64,
+    /// How the file was laid out when last read or written, changing it is a change to write
+    saved_file: FileOptions,
+    /// `content_hash` of the file when last read or written, a saved undo history is only good for it
+    saved_hash: u64,
+    /// When the lines last changed, from `swap::next_version`
+    version: u64,
+    /// The version in the swap file, `None` while there is none
+    swapped: Option<u64>,
+    /// The text handed to the swap file's writer, kept up by `swap_edits` rather than copied again
+    swap_text: Option<SwapText>,
+    /// Edits made since `swap_text` last caught up
+    swap_edits: Vec<Edit>,
+    /// The file isn't text, its bytes are shown as a read-only hex dump
+    binary: bool,
+    /// The file is over `LARGE_FILE_SIZE`, features that go over every line are off for it
+    large: bool,
+    /// (line, col) of the cursor when the buffer was last shown
+    pub cursor: (usize, usize),
+    /// First visible line when the buffer was last shown
+    pub scroll: usize,
+    /// Highlighting for the filetype, if there is a grammar for it
+    syntax: Option<RefCell<Highlighter>>,
+    /// Parsed with tree-sitter when it is built in and has the filetype's grammar
+    tree: Option<RefCell<SyntaxTree>>,
+    /// Edits since the last `take_changes`, once something keeps a copy of the text in step
+    changes: Option<Changes>,
+    /// What the language server last reported, positions in UTF-16 code units
+    diagnostics: Vec<Diagnostic>,
+    /// What git has for the file, once it has been read
+    vcs: Option<VcsInfo>,
+    /// Git was asked about the file, its answer arrives later
+    vcs_requested: bool,
+    /// How the lines differ from the index, worked out again after edits
+    hunks: RefCell<Option<Vec<Hunk>>>,
+    folds: Folds,
+}
+
+/// How the lines changed since they were last looked at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Changes {
+    /// These edits, in the order they were made
+    Edits(Vec<Edit>),
+    /// Replaced as a whole, like when the file is read again
+    Reloaded,
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            file_path: None,
+            lines: Lines::from(vec![String::new()]),
+            options: BufferOptions::default(),
+            is_new: false,
+            from_stdin: false,
+            undo: UndoHistory::default(),
+            saved_state: 0,
+            saved_file: FileOptions::default(),
+            saved_hash: 0,
+            version: 0,
+            swapped: None,
+            swap_text: None,
+            swap_edits: Vec::new(),
+            binary: false,
+            large: false,
+            cursor: (0, 0),
+            scroll: 0,
+            syntax: None,
+            tree: None,
+            changes: None,
+            diagnostics: Vec::new(),
+            vcs: None,
+            vcs_requested: false,
+            hunks: RefCell::new(None),
+            folds: Folds::default(),
+        }
+    }
+}
+
+/// Text from `start` up to but not including `end`, both (line, col)
+/// An end at col 0 of the line after takes the line break too
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextRange {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl TextRange {
+    /// The cols of `line` inside the range, up to its length for lines it continues past
+    pub fn cols_on(&self, line: usize, len: usize) -> Option<(usize, usize)> {
+        if line < self.start.0 || line > self.end.0 {
+            return None;
+        }
+        let start = if line == self.start.0 {
+            self.start.1
+        } else {
+            0
+        };
+        let end = if line == self.end.0 { self.end.1 } else { len };
+        let end = end.min(len);
+        (start < end).then_some((start, end))
+    }
+}
+
+/// Byte offset of the char at `col`, or the end of the line
+pub fn byte_index(line: &str, col: usize) -> usize {
+    line.char_indices()
+        .nth(col)
+        .map(|(byte, _)| byte)
+        .unwrap_or(line.len())
+}
+
+impl Buffer {
+    pub fn from_text(contents: &str) -> Buffer {
+        let (lines, file) = encoding::split_lines(contents);
+        Buffer {
+            lines: Lines::from(lines),
+            options: BufferOptions {
+                file,
+                ..Default::default()
+            },
+            saved_file: file,
+            ..Default::default()
+        }
+    }
+
+    /// Text in whichever encoding it looks to be in, or a hex dump of bytes that aren't text
+    pub fn from_bytes(bytes: Vec<u8>) -> Buffer {
+        let large = bytes.len() > LARGE_FILE_SIZE;
+        let mut buffer = match encoding::decode(bytes) {
+            Ok((text, fileencoding, bomb)) => {
+                let mut buffer = Buffer::from_text(&text);
+                buffer.options.file.fileencoding = fileencoding;
+                buffer.options.file.bomb = bomb;
+                buffer.saved_file = buffer.options.file;
+                buffer
+            }
+            Err(bytes) => Buffer {
+                lines: Lines::from(encoding::hex_dump(&bytes)),
+                options: BufferOptions {
+                    read_only: true,
+                    ..Default::default()
+                },
+                binary: true,
+                ..Default::default()
+            },
+        };
+        buffer.large = large;
+        buffer
+    }
+
+    /// Load a file, a missing file becomes a new empty buffer that is not created until written
+    pub fn from_file_path(path: &Path) -> Result<Buffer> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let mut buffer = Buffer {
+                    file_path: Some(path.to_path_buf()),
+                    is_new: true,
+                    ..Default::default()
+                };
+                buffer.detect_syntax();
+                buffer.options.undofile = undo::undo_dir().is_some_and(|dir| dir.is_dir());
+                return Ok(buffer);
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if file.metadata()?.len() > LARGE_FILE_SIZE as u64 {
+            // Only the lines shown or edited are read in, the rest stay in the file
+            if let Some((lines, file)) = Lines::index(file.try_clone()?)? {
+                return Ok(Buffer {
+                    file_path: Some(path.to_path_buf()),
+                    lines,
+                    options: BufferOptions {
+                        file,
+                        ..Default::default()
+                    },
+                    saved_file: file,
+                    large: true,
+                    ..Default::default()
+                });
+            }
+            file.rewind()?;
+        }
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        let mut buffer = Buffer {
+            file_path: Some(path.to_path_buf()),
+            saved_hash: content_hash(&contents),
+            ..Buffer::from_bytes(contents)
+        };
+        buffer.detect_syntax();
+        if !buffer.large {
+            buffer.options.undofile = undo::undo_dir().is_some_and(|dir| dir.is_dir());
+            buffer.load_undo(path);
+        }
+        Ok(buffer)
+    }
+
+    pub fn from_reader(mut reader: impl Read) -> Result<Buffer> {
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents)?;
+        Ok(Buffer {
+            from_stdin: true,
+            ..Buffer::from_bytes(contents)
+        })
+    }
+
+    pub fn id(&self) -> BufferId {
+        self.id
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+
+    /// The file name shown in the status line
+    pub fn file_name(&self) -> &str {
+        self.file_path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .and_then(|os_string| os_string.to_str())
+            .unwrap_or("[No Name]")
+    }
+
+    /// The path relative to the working directory where possible, used by `:ls`
+    pub fn display_name(&self) -> String {
+        match &self.file_path {
+            Some(path) => {
+                let relative = std::env::current_dir()
+                    .ok()
+                    .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf));
+                relative
+                    .unwrap_or_else(|| path.clone())
+                    .display()
+                    .to_string()
+            }
+            None => "[No Name]".to_string(),
+        }
+    }
+
+    /// Language of the file, from its name
+    pub fn filetype(&self) -> Option<&'static str> {
+        self.file_path.as_deref().and_then(filetype::detect)
+    }
+
+    pub fn is_new(&self) -> bool {
+        self.is_new
+    }
+
+    pub fn is_from_stdin(&self) -> bool {
+        self.from_stdin
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.undo.state() != self.saved_state || self.options.file != self.saved_file
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.binary
+    }
+
+    pub fn is_large(&self) -> bool {
+        self.large
+    }
+
+    pub fn lines(&self) -> &Lines {
+        &self.lines
+    }
+
+    /// Let go of the lines of a large file read longest ago, past the ones kept in memory
+    pub fn forget_unused_lines(&mut self) {
+        self.lines.forget_unused();
+    }
+
+    pub fn line(&self, line: usize) -> Option<&str> {
+        self.lines.get(line)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Number of chars on the line
+    pub fn line_len(&self, line: usize) -> usize {
+        self.line(line)
+            .map(|line| line.chars().count())
+            .unwrap_or(0)
+    }
+
+    /// The whole text with a newline after every line
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for line in self.lines.iter() {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text
+    }
+
+    /// The bytes to write, in the file's encoding and with its line breaks
+    pub fn file_contents(&self) -> Result<Vec<u8>> {
+        let mut contents = Vec::new();
+        self.write_contents(&mut contents)?;
+        Ok(contents)
+    }
+
+    /// Write the bytes of `file_contents` to `out` a line at a time, returning how many there were and
+    /// their `content_hash`
+    fn write_contents(&self, out: &mut impl Write) -> Result<(usize, u64)> {
+        let file = self.options.file;
+        let utf8 = file.fileencoding == encoding::FileEncoding::Utf8;
+        let line_end = file
+            .fileencoding
+            .encode(file.fileformat.line_end(), false)?;
+        let (mut written, mut hash) = (0, content_hash(&[]));
+        let mut put = |bytes: &[u8]| -> Result<()> {
+            out.write_all(bytes)?;
+            written += bytes.len();
+            hash = hash_more(hash, bytes);
+            Ok(())
+        };
+        put(&file.fileencoding.encode("", file.bomb)?)?;
+        let mut first = true;
+        self.lines.each(|line| {
+            if !first {
+                put(&line_end)?;
+            }
+            first = false;
+            match utf8 {
+                true => put(line.as_bytes()),
+                false => put(&file.fileencoding.encode(line, false)?),
+            }
+        })?;
+        if file.endofline {
+            put(&line_end)?;
+        }
+        Ok((written, hash))
+    }
+
+    /// Write the text to `path`, or to the buffer's own file, returning where it was written and how many bytes
+    pub fn write(&mut self, path: Option<&Path>) -> Result<(PathBuf, usize)> {
+        if self.options.read_only {
+            bail!("E45: 'readonly' option is set");
+        }
+        let path = match path.or(self.file_path.as_deref()) {
+            Some(path) => path.to_path_buf(),
+            None => bail!("E32: No file name"),
+        };
+        let own_file = self.file_path.as_deref() == Some(path.as_path());
+        if own_file
+            && self.lines.is_lazy()
+            && self.options.file.fileencoding != encoding::FileEncoding::Utf8
+        {
+            // Only UTF-8 is indexed again once written, text in another encoding is read in whole first
+            self.lines.read_all();
+        }
+        if let Some(err) = self.lines.read_error() {
+            bail!("Can't write, reading the file failed: {err}");
+        }
+        let (written, hash) = if own_file && self.lines.is_lazy() {
+            self.write_large(&path)?
+        } else {
+            let contents = self.file_contents()?;
+            fs::write(&path, &contents)?;
+            (contents.len(), content_hash(&contents))
+        };
+        if self.file_path.as_deref() == Some(path.as_path()) || self.file_path.is_none() {
+            if self.file_path.is_none() {
+                self.file_path = Some(path.clone());
+                self.detect_syntax();
+            }
+            self.is_new = false;
+            self.mark_saved();
+            self.saved_hash = hash;
+            self.remove_swap(&path);
+        }
+        Ok((path, written))
+    }
+
+    /// Write a large UTF-8 file the lines are read from as they are needed, and read them from what was written
+    /// The lines go to a file beside it that is moved over it once all of them are on disk, so the file
+    /// they are read from is never cut short by a write that fails
+    fn write_large(&mut self, path: &Path) -> Result<(usize, u64)> {
+        // Through a symlink the file it points to is replaced, not the link
+        let path = fs::canonicalize(path)?;
+        let mut partial = path.clone().into_os_string();
+        partial.push(format!(".reovim-{}.partial", std::process::id()));
+        let partial = PathBuf::from(partial);
+        let written = (|| {
+            let file = File::create(&partial)?;
+            file.set_permissions(fs::metadata(&path)?.permissions())?;
+            let mut out = BufWriter::new(file);
+            let written = self.write_contents(&mut out)?;
+            out.into_inner()?.sync_all()?;
+            fs::rename(&partial, &path)?;
+            anyhow::Ok(written)
+        })();
+        if written.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        let written = written?;
+        self.lines = match Lines::index(File::open(&path)?)? {
+            Some((lines, _)) => lines,
+            None => Buffer::from_bytes(fs::read(&path)?).lines,
+        };
+        Ok(written)
+    }
+
+    /// Pick up the undo history saved for the file, if it was saved for the contents just read
+    fn load_undo(&mut self, path: &Path) {
+        if self.binary {
+            return;
+        }
+        match UndoHistory::load(path, self.saved_hash) {
+            Ok(Some(history)) => {
+                self.undo = history;
+                self.saved_state = self.undo.state();
+            }
+            Ok(None) => {}
+            Err(err) => warn!(
+                "could not read the undo history of {}: {err}",
+                path.display()
+            ),
+        }
+    }
+
+    /// Save the undo history for later sessions, when `undofile` is set and the text is as last written
+    pub fn write_undo(&mut self) -> Result<()> {
+        match &self.file_path {
+            Some(path) if self.options.undofile && !self.large && !self.is_modified() => {
+                self.undo.save(path, self.saved_hash)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Remove the swap file once the text is saved, or return what to write to it when the text changed
+    /// since it was last written: the file it is for and the text, caught up with the edits since
+    /// A large file goes without, its text is too much to copy when it first changes
+    pub fn sync_swap(&mut self) -> Option<(PathBuf, SwapText)> {
+        if self.large {
+            return None;
+        }
+        let path = self.file_path.clone()?;
+        if !self.is_modified() {
+            self.remove_swap(&path);
+            return None;
+        }
+        if self.swapped == Some(self.version) {
+            return None;
+        }
+        match &self.swap_text {
+            Some(text) => {
+                if !text.update(&self.swap_edits, self.version, self.cursor) {
+                    return None;
+                }
+                self.swap_edits.clear();
+            }
+            None => {
+                let lines = self.lines.as_slice().to_vec();
+                self.swap_text = Some(SwapText::new(lines, self.version, self.cursor));
+            }
+        }
+        self.swapped = Some(self.version);
+        Some((path, self.swap_text.clone()?))
+    }
+
+    /// Delete the swap file for `path` if there is one and stop keeping its text
+    fn remove_swap(&mut self, path: &Path) {
+        self.swap_text = None;
+        self.swap_edits.clear();
+        if self.swapped.take().is_some() {
+            swap::remove(path, self.version);
+        }
+    }
+
+    /// Replace the text with what a swap file kept, as one change that undo takes back to the file's text
+    pub fn recover(&mut self, swap: SwapFile) {
+        let mut lines = swap.lines;
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        let recovered = lines.len();
+        let replaced = self.lines.cloned(0..self.lines.len());
+        self.begin_change(self.cursor);
+        self.apply(Edit::Insert { at: 0, lines }, self.cursor);
+        self.apply(
+            Edit::Remove {
+                at: recovered,
+                lines: replaced,
+            },
+            self.cursor,
+        );
+        self.end_change();
+        let line = swap.cursor.0.min(recovered - 1);
+        self.cursor = (line, swap.cursor.1.min(self.line_len(line)));
+    }
+
+    /// Treat the current text as what is on disk
+    pub fn mark_saved(&mut self) {
+        self.saved_state = self.undo.state();
+        self.saved_file = self.options.file;
+    }
+
+    /// Replace the text with the file's contents, with the undo history saved for them if there is one
+    pub fn reload(&mut self) -> Result<()> {
+        if let Some(path) = self.file_path.clone() {
+            let reloaded = Buffer::from_file_path(&path)?;
+            self.lines = reloaded.lines;
+            self.options.file = reloaded.options.file;
+            self.saved_file = reloaded.saved_file;
+            self.options.read_only |= reloaded.binary;
+            self.binary = reloaded.binary;
+            self.large = reloaded.large;
+            if let Some(syntax) = &self.syntax {
+                syntax.borrow_mut().reset();
+            }
+            if let Some(tree) = &self.tree {
+                tree.borrow_mut().reset();
+            }
+            if self.changes.is_some() {
+                self.changes = Some(Changes::Reloaded);
+            }
+            self.hunks.replace(None);
+            self.folds.reset();
+            self.is_new = reloaded.is_new;
+            self.undo = reloaded.undo;
+            self.saved_state = reloaded.saved_state;
+            self.saved_hash = reloaded.saved_hash;
+            self.version = swap::next_version();
+            // The swap file's text can't follow, it is copied again if the text changes
+            self.swap_text = None;
+            self.swap_edits.clear();
+            self.cursor.0 = self.cursor.0.min(self.lines.len() - 1);
+        }
+        Ok(())
+    }
+
+    fn check_editable(&self) -> Result<()> {
+        if self.options.read_only {
+            bail!("E21: Cannot make changes, 'readonly' is set");
+        }
+        Ok(())
+    }
+
+    /// Pick the grammars for the file's type, a large file goes without as they read it from the start
+    fn detect_syntax(&mut self) {
+        let filetype = self.filetype().filter(|_| !self.large);
+        self.syntax = filetype
+            .and_then(|filetype| Languages::builtin().get(filetype))
+            .map(|grammar| RefCell::new(Highlighter::new(grammar)));
+        self.tree = filetype.and_then(SyntaxTree::new).map(RefCell::new);
+    }
+
+    /// Highlighted spans of a line, in byte offsets
+    /// The syntax tree's highlighting is used over the regex grammar's when there is one
+    pub fn highlights(&self, line: usize) -> Vec<Span> {
+        if let Some(tree) = &self.tree {
+            return tree.borrow_mut().highlights(self.lines.as_slice(), line);
+        }
+        match &self.syntax {
+            Some(syntax) => syntax
+                .borrow_mut()
+                .spans(self.lines.as_slice(), line)
+                .to_vec(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The function or class around `cursor`, needs a syntax tree
+    pub fn text_object(
+        &self,
+        cursor: (usize, usize),
+        object: TextObject,
+        inner: bool,
+    ) -> Option<TextRange> {
+        let tree = self.tree.as_ref()?;
+        tree.borrow_mut()
+            .text_object(self.lines.as_slice(), cursor, object, inner)
+    }
+
+    /// The syntax node just larger than `range`
+    pub fn expand_node(&self, range: TextRange) -> Option<TextRange> {
+        let tree = self.tree.as_ref()?;
+        tree.borrow_mut().expand(self.lines.as_slice(), range)
+    }
+
+    /// The language of the syntax tree and the kinds of the nodes at `cursor`, innermost first
+    pub fn syntax_nodes(&self, cursor: (usize, usize)) -> Option<(&'static str, Vec<String>)> {
+        let tree = self.tree.as_ref()?;
+        let mut tree = tree.borrow_mut();
+        let nodes = tree.nodes_at(self.lines.as_slice(), cursor);
+        Some((tree.language(), nodes))
+    }
+
+    /// Line ranges the syntax tree says can be folded
+    pub fn syntax_folds(&self) -> Vec<(usize, usize)> {
+        match &self.tree {
+            Some(tree) => tree.borrow_mut().folds(self.lines.as_slice()),
+            None => Vec::new(),
+        }
+    }
+
+    /// Change the lines, keeping the highlighting and syntax tree up with them
+    fn edit_lines(&mut self, edit: &Edit) {
+        self.lines.apply(edit);
+        self.version = swap::next_version();
+        if let Some(syntax) = &self.syntax {
+            syntax.borrow_mut().edited(edit);
+        }
+        if let Some(tree) = &self.tree {
+            tree.borrow_mut().edited(self.lines.as_slice(), edit);
+        }
+        if let Some(Changes::Edits(edits)) = &mut self.changes {
+            edits.push(edit.clone());
+        }
+        if self.swap_text.is_some() {
+            self.swap_edits.push(edit.clone());
+        }
+        self.hunks.replace(None);
+        self.folds.edited(edit);
+    }
+
+    /// The buffer's folds, worked out again first when the lines changed since
+    pub fn folds(&mut self) -> &mut Folds {
+        if self.folds.is_stale(&self.options) {
+            let (lines, tree) = (self.lines.as_slice(), &self.tree);
+            self.folds.refresh(lines, &self.options, || match tree {
+                Some(tree) => tree.borrow_mut().folds(lines),
+                None => Vec::new(),
+            });
+        }
+        &mut self.folds
+    }
+
+    /// Start keeping the edits made from now on, for `take_changes`
+    pub fn track_changes(&mut self) {
+        self.changes = Some(Changes::Edits(Vec::new()));
+    }
+
+    /// The changes since the last call, `None` when changes aren't tracked or there were none
+    pub fn take_changes(&mut self) -> Option<Changes> {
+        let changes = self.changes.as_mut()?;
+        if *changes == Changes::Edits(Vec::new()) {
+            return None;
+        }
+        Some(std::mem::replace(changes, Changes::Edits(Vec::new())))
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics = diagnostics;
+    }
+
+    /// The most severe diagnostic starting on `line`, for the gutter
+    pub fn line_severity(&self, line: usize) -> Option<Severity> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.range.start.line == line)
+            .map(|diagnostic| diagnostic.severity)
+            .min()
+    }
+
+    /// The file to ask git about, the first time only
+    pub fn wants_vcs(&mut self) -> Option<PathBuf> {
+        if self.vcs_requested || self.large {
+            return None;
+        }
+        let path = self.file_path.clone()?;
+        self.vcs_requested = true;
+        Some(path)
+    }
+
+    pub fn vcs(&self) -> Option<&VcsInfo> {
+        self.vcs.as_ref()
+    }
+
+    pub fn set_vcs(&mut self, vcs: Option<VcsInfo>) {
+        self.vcs = vcs;
+        self.vcs_requested = true;
+        self.hunks.replace(None);
+    }
+
+    /// How the lines differ from the file in the git index, an untracked file is all added
+    pub fn hunks(&self) -> Vec<Hunk> {
+        self.with_hunks(<[Hunk]>::to_vec)
+    }
+
+    /// The gutter status of `line`
+    pub fn line_vcs_status(&self, line: usize) -> VcsStatus {
+        self.with_hunks(|hunks| VcsStatus::of(hunks, line))
+    }
+
+    /// Look at the hunks, diffing the lines again if they changed since last time
+    fn with_hunks<T>(&self, f: impl FnOnce(&[Hunk]) -> T) -> T {
+        let Some(vcs) = &self.vcs else {
+            return f(&[]);
+        };
+        let mut hunks = self.hunks.borrow_mut();
+        f(hunks.get_or_insert_with(|| {
+            diff(
+                vcs.base.as_deref().unwrap_or_default(),
+                self.lines.as_slice(),
+            )
+        }))
+    }
+
+    fn apply(&mut self, edit: Edit, cursor: (usize, usize)) {
+        self.edit_lines(&edit);
+        self.undo.record(edit, cursor);
+    }
+
+    /// Group every edit until `end_change` into one undo step
+    pub fn begin_change(&mut self, cursor: (usize, usize)) {
+        self.undo.begin(cursor);
+    }
+
+    pub fn end_change(&mut self) {
+        self.undo.end();
+    }
+
+    /// Replace the contents of a line
+    pub fn set_line(&mut self, at: usize, text: String, cursor: (usize, usize)) -> Result<()> {
+        self.check_editable()?;
+        let Some(before) = self.lines.get(at).map(str::to_string) else {
+            return Ok(());
+        };
+        if before != text {
+            self.apply(
+                Edit::Replace {
+                    at,
+                    before,
+                    after: text,
+                },
+                cursor,
+            );
+        }
+        Ok(())
+    }
+
+    /// Insert a char before `col` on `line`
+    pub fn insert_char(&mut self, line: usize, col: usize, character: char) -> Result<()> {
+        let Some(mut text) = self.lines.get(line).map(str::to_string) else {
+            return Ok(());
+        };
+        text.insert(byte_index(&text, col), character);
+        self.set_line(line, text, (line, col))
+    }
+
+    /// Remove the grapheme cluster holding char `col` on `line`, returning it
+    pub fn remove_cluster(&mut self, line: usize, col: usize) -> Result<Option<String>> {
+        let Some(mut text) = self.lines.get(line).map(str::to_string) else {
+            return Ok(None);
+        };
+        let start = grapheme::cluster_start(&text, col);
+        let bytes =
+            byte_index(&text, start)..byte_index(&text, grapheme::next_cluster(&text, start));
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+        let removed = text.drain(bytes).collect();
+        self.set_line(line, text, (line, start))?;
+        Ok(Some(removed))
+    }
+
+    /// Give `line` an indent `width` columns wide, made of blanks by the buffer's options
+    pub fn set_indent(&mut self, line: usize, width: usize, cursor: (usize, usize)) -> Result<()> {
+        let Some(text) = self.lines.get(line) else {
+            return Ok(());
+        };
+        let indented = format!(
+            "{}{}",
+            self.options.blanks(0, width),
+            text.trim_start_matches([' ', '\t'])
+        );
+        if indented != *text {
+            self.set_line(line, indented, cursor)?;
+        }
+        Ok(())
+    }
+
+    /// Move `lines` a `shiftwidth` right or left as one change, empty lines are left alone
+    pub fn shift_lines(
+        &mut self,
+        lines: Range<usize>,
+        right: bool,
+        cursor: (usize, usize),
+    ) -> Result<()> {
+        self.check_editable()?;
+        self.begin_change(cursor);
+        let shift = self.options.shift_width();
+        for line in lines {
+            let Some(text) = self.lines.get(line).filter(|text| !text.is_empty()) else {
+                continue;
+            };
+            let indent = self.options.indent_width(text);
+            let width = if right {
+                indent + shift
+            } else {
+                indent.saturating_sub(shift)
+            };
+            self.set_indent(line, width, cursor)?;
+        }
+        self.end_change();
+        Ok(())
+    }
+
+    /// Break `line` at `col`, moving the rest of it onto a new line below
+    pub fn split_line(&mut self, line: usize, col: usize) -> Result<()> {
+        self.check_editable()?;
+        let Some(text) = self.lines.get(line).map(str::to_string) else {
+            return Ok(());
+        };
+        let byte = byte_index(&text, col);
+        let (head, tail) = text.split_at(byte);
+        let (head, tail) = (head.to_string(), tail.to_string());
+        self.set_line(line, head, (line, col))?;
+        self.apply(
+            Edit::Insert {
+                at: line + 1,
+                lines: vec![tail],
+            },
+            (line, col),
+        );
+        Ok(())
+    }
+
+    /// Append the line below onto `line`
+    pub fn join_line(&mut self, line: usize) -> Result<()> {
+        self.check_editable()?;
+        if line + 1 >= self.lines.len() {
+            return Ok(());
+        }
+        let joined = format!("{}{}", &self.lines[line], &self.lines[line + 1]);
+        let cursor = (line, self.line_len(line));
+        self.set_line(line, joined, cursor)?;
+        self.remove_lines(line + 1, 1, cursor)?;
+        Ok(())
+    }
+
+    /// Insert whole lines before `at`
+    pub fn insert_lines(
+        &mut self,
+        at: usize,
+        lines: Vec<String>,
+        cursor: (usize, usize),
+    ) -> Result<()> {
+        self.check_editable()?;
+        if !lines.is_empty() {
+            let at = at.min(self.lines.len());
+            self.apply(Edit::Insert { at, lines }, cursor);
+        }
+        Ok(())
+    }
+
+    /// Remove the text in `range`, joining what is left of its first and last lines
+    pub fn remove_range(&mut self, range: TextRange, cursor: (usize, usize)) -> Result<()> {
+        self.check_editable()?;
+        let last = self.lines.len() - 1;
+        let (mut start, mut end) = (range.start, range.end);
+        if end.0 > last {
+            // Nothing follows the last line, take the line break before the range instead
+            end = (last, self.line_len(last));
+            if start.1 == 0 && start.0 > 0 {
+                start = (start.0 - 1, self.line_len(start.0 - 1));
+            }
+        }
+        if start >= end {
+            return Ok(());
+        }
+        let first = &self.lines[start.0];
+        let last = &self.lines[end.0];
+        let joined = format!(
+            "{}{}",
+            &first[..byte_index(first, start.1)],
+            &last[byte_index(last, end.1)..]
+        );
+        let grouped = self.undo.is_grouping();
+        self.begin_change(cursor);
+        self.set_line(start.0, joined, cursor)?;
+        self.remove_lines(start.0 + 1, end.0 - start.0, cursor)?;
+        if !grouped {
+            self.end_change();
+        }
+        Ok(())
+    }
+
+    /// Replace the text in `range` with `text`, which may hold line breaks
+    /// The text is taken to end with a line break after the last line, which a range may reach past
+    pub fn replace_range(
+        &mut self,
+        range: TextRange,
+        text: &str,
+        cursor: (usize, usize),
+    ) -> Result<()> {
+        self.check_editable()?;
+        let count = self.lines.len();
+        let start = range.start.min((count, 0));
+        let end = range.end.min((count, 0)).max(start);
+        let line_at = |line: usize| self.lines.get(line).unwrap_or_default();
+        let (first, last) = (line_at(start.0), line_at(end.0));
+        let joined = format!(
+            "{}{text}{}",
+            &first[..byte_index(first, start.1)],
+            &last[byte_index(last, end.1)..]
+        );
+        let mut replacement: Vec<String> = joined.split('\n').map(str::to_string).collect();
+        // Past the last line is the empty line after the final line break, which isn't stored
+        if end.0 == count && replacement.last().is_some_and(String::is_empty) {
+            replacement.pop();
+        }
+        let at = start.0.min(count);
+        let replaced = (end.0 + 1).min(count) - at;
+        let kept = replaced.min(replacement.len());
+        let grouped = self.undo.is_grouping();
+        self.begin_change(cursor);
+        let added = replacement.split_off(kept);
+        for (offset, line) in replacement.into_iter().enumerate() {
+            self.set_line(at + offset, line, cursor)?;
+        }
+        if added.is_empty() {
+            self.remove_lines(at + kept, replaced - kept, cursor)?;
+        } else {
+            self.insert_lines(at + kept, added, cursor)?;
+        }
+        if !grouped {
+            self.end_change();
+        }
+        Ok(())
+    }
+
+    /// Remove `count` lines starting at `at`, the buffer keeps one empty line if all are removed
+    pub fn remove_lines(
+        &mut self,
+        at: usize,
+        count: usize,
+        cursor: (usize, usize),
+    ) -> Result<Vec<String>> {
+        self.check_editable()?;
+        let end = (at + count).min(self.lines.len());
+        if at >= end {
+            return Ok(Vec::new());
+        }
+        let removed = self.lines.cloned(at..end);
+        let grouped = self.undo.is_grouping();
+        self.begin_change(cursor);
+        self.apply(
+            Edit::Remove {
+                at,
+                lines: removed.clone(),
+            },
+            cursor,
+        );
+        if self.lines.is_empty() {
+            self.apply(
+                Edit::Insert {
+                    at: 0,
+                    lines: vec![String::new()],
+                },
+                cursor,
+            );
+        }
+        if !grouped {
+            self.end_change();
+        }
+        Ok(removed)
+    }
+
+    /// Revert the last change, returning where the cursor was before it
+    pub fn undo(&mut self) -> Option<(usize, usize)> {
+        let step = self.undo.undo()?;
+        for edit in step.edits.iter().rev() {
+            self.edit_lines(&edit.inverse());
+        }
+        Some(step.cursor)
+    }
+
+    /// Re-apply the last undone change, returning where the cursor was before it
+    pub fn redo(&mut self) -> Option<(usize, usize)> {
+        let step = self.undo.redo()?;
+        for edit in &step.edits {
+            self.edit_lines(edit);
+        }
+        Some(step.cursor)
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, MutexGuard, TryLockError,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{state_file, undo::Edit};
+
+/// The text of a modified buffer when the editor was last idle, to get it back after a crash
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SwapFile {
+    /// Process that wrote it
+    pub pid: u32,
+    /// Seconds since the epoch when it was written
+    pub time: u64,
+    /// (line, col) of the cursor
+    pub cursor: (usize, usize),
+    pub lines: Vec<String>,
+}
+
+/// A swap file as it is written, from the text a `SwapText` holds
+#[derive(Serialize)]
+struct SwapFileRef<'a> {
+    pid: u32,
+    time: u64,
+    cursor: (usize, usize),
+    lines: &'a [String],
+}
+
+/// A modified buffer's text for its swap file, shared with the worker that writes it
+/// The buffer copies its text in once, when it first differs from the file, and after that only hands
+/// over the edits made since, so the editor never copies all of it while idle
+#[derive(Clone)]
+pub struct SwapText(Arc<Mutex<Snapshot>>);
+
+struct Snapshot {
+    /// The buffer version the lines are at
+    version: u64,
+    cursor: (usize, usize),
+    lines: Vec<String>,
+}
+
+/// Swap files this process wrote or removed, with the buffer version each was last written or removed at
+/// Workers write them in any order, a write older than the last one for its file is dropped
+struct Swaps {
+    /// The editor exited, nothing more is written
+    closed: bool,
+    /// The version and whether the file is there
+    files: BTreeMap<PathBuf, (u64, bool)>,
+}
+
+impl Swaps {
+    /// The editor is still running and nothing later than `version` was written or removed at `path`
+    fn takes(&self, path: &Path, version: u64) -> bool {
+        !self.closed && self.files.get(path).is_none_or(|&(last, _)| last < version)
+    }
+}
+
+static SWAPS: Mutex<Swaps> = Mutex::new(Swaps {
+    closed: false,
+    files: BTreeMap::new(),
+});
+
+/// The swap files, still usable after a worker panicked holding them
+fn swaps() -> MutexGuard<'static, Swaps> {
+    SWAPS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Versions handed out so far, shared by every buffer so one reopened after `:bdelete` follows on
+static VERSIONS: AtomicU64 = AtomicU64::new(0);
+
+/// A version later than every one before it, for buffers to tell their swap files apart by
+pub fn next_version() -> u64 {
+    VERSIONS.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Where the swap file for `file` is kept
+fn swap_path(file: &Path) -> Option<PathBuf> {
+    state_file("swap", file, ".swp")
+}
+
+/// Seconds since the epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+impl SwapFile {
+    /// The swap file left for `file`, if there is one
+    pub fn read(file: &Path) -> Result<Option<SwapFile>> {
+        let Some(path) = swap_path(file) else {
+            return Ok(None);
+        };
+        match fs::read(&path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Seconds since it was written
+    pub fn age(&self) -> u64 {
+        now().saturating_sub(self.time)
+    }
+
+    /// The process that wrote it is still running, perhaps editing the file in another terminal
+    #[cfg(unix)]
+    pub fn is_running(&self) -> bool {
+        let Ok(pid) = libc::pid_t::try_from(self.pid) else {
+            return false;
+        };
+        // Signal 0 only checks the process is there, one of another user's can't be signalled but is
+        // SAFETY: kill has no memory safety requirements
+        let signalled = unsafe { libc::kill(pid, 0) };
+        signalled == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+
+    /// Without a way to look for the process, take it to be gone
+    #[cfg(not(unix))]
+    pub fn is_running(&self) -> bool {
+        false
+    }
+}
+
+impl SwapText {
+    pub fn new(lines: Vec<String>, version: u64, cursor: (usize, usize)) -> SwapText {
+        SwapText(Arc::new(Mutex::new(Snapshot {
+            version,
+            cursor,
+            lines,
+        })))
+    }
+
+    /// Catch up with the `edits` made since, `false` while a worker is writing the text and they have to
+    /// wait for the next time
+    pub fn update(&self, edits: &[Edit], version: u64, cursor: (usize, usize)) -> bool {
+        let mut snapshot = match self.0.try_lock() {
+            Ok(snapshot) => snapshot,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return false,
+        };
+        for edit in edits {
+            edit.apply(&mut snapshot.lines);
+        }
+        snapshot.version = version;
+        snapshot.cursor = cursor;
+        true
+    }
+
+    /// Write it as the swap file for `file`, unless the file was written or removed for a later version
+    pub fn write(&self, file: &Path) -> Result<()> {
+        let Some(path) = swap_path(file) else {
+            return Ok(());
+        };
+        let snapshot = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !swaps().takes(&path, snapshot.version) {
+            return Ok(());
+        }
+        // The text is written without holding the swap files, `:w` removing one doesn't wait for it
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let swap = SwapFileRef {
+            pid: std::process::id(),
+            time: now(),
+            cursor: snapshot.cursor,
+            lines: &snapshot.lines,
+        };
+        // Written aside and moved over the old one, a crash mid-write leaves the last whole swap file
+        let partial = path.with_extension("swp.partial");
+        fs::write(&partial, serde_json::to_vec(&swap)?)?;
+        let mut swaps = swaps();
+        if !swaps.takes(&path, snapshot.version) {
+            // Removed or written again meanwhile
+            delete(&partial);
+            return Ok(());
+        }
+        fs::rename(&partial, &path)?;
+        swaps.files.insert(path, (snapshot.version, true));
+        Ok(())
+    }
+}
+
+/// Delete the swap file for `file`, a write still on its way for `version` or before doesn't bring it back
+pub fn remove(file: &Path, version: u64) {
+    let Some(path) = swap_path(file) else {
+        return;
+    };
+    let mut swaps = swaps();
+    delete(&path);
+    swaps.files.insert(path, (version, false));
+}
+
+/// Delete every swap file this process wrote and write no more, for when the editor exits normally
+pub fn close() {
+    let mut swaps = swaps();
+    swaps.closed = true;
+    for (path, &(_, there)) in &swaps.files {
+        if there {
+            delete(path);
+        }
+    }
+}
+
+fn delete(path: &Path) {
+    match fs::remove_file(path) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => warn!("could not remove swap file {}: {err}", path.display()),
+    }
+}
@@ -0,0 +1,756 @@
+use std::{
+    fs::{self, File},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use proptest::prelude::*;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{
+    Buffer, BufferOptions, LARGE_FILE_SIZE, byte_index,
+    encoding::{self, FileEncoding, FileFormat, FileOptions},
+    fold::{FoldMethod, Folds},
+    grapheme,
+    lines::Lines,
+    list::BufferList,
+    state_file,
+    swap::SwapFile,
+    undo::{self, Edit, UndoHistory},
+};
+use crate::tui::{
+    listchars::ListChars,
+    terminal_buffer::TerminalBuffer,
+    wrap::{self, WrapOptions},
+};
+
+/// Lines mixing plain text with wide chars, emoji sequences, flags and combining marks
+fn unicode_line() -> impl Strategy<Value = String> {
+    let piece = prop_oneof![
+        "[a-z ,.-]{1,4}",
+        "\\PC",
+        prop::sample::select(vec![
+            "\u{301}",
+            "\u{200d}",
+            "\u{fe0f}",
+            "中",
+            "文",
+            "👍",
+            "🏽",
+            "👩‍👩‍👧",
+            "e\u{301}",
+            "\u{1f1ef}\u{1f1f5}",
+            "\u{a0}",
+            "ｗ",
+            "\t",
+        ])
+        .prop_map(str::to_string),
+    ];
+    prop::collection::vec(piece, 0..24).prop_map(|pieces| pieces.concat())
+}
+
+fn wrap_options() -> impl Strategy<Value = WrapOptions> {
+    (
+        any::<[bool; 4]>(),
+        "(|>|↳ |中)",
+        1usize..9,
+        prop_oneof![
+            Just("tab:> ,trail:-,eol:$"),
+            Just("tab:<->"),
+            Just("nbsp:+")
+        ],
+    )
+        .prop_map(
+            |([wrap, linebreak, breakindent, list], showbreak, tabstop, listchars)| WrapOptions {
+                wrap,
+                linebreak,
+                breakindent,
+                showbreak,
+                list,
+                listchars: ListChars::parse(listchars).unwrap(),
+                tabstop,
+            },
+        )
+}
+
+/// Char columns where a cluster starts, and the end of the line
+fn cluster_bounds(text: &str) -> Vec<usize> {
+    let mut bounds: Vec<usize> = grapheme::clusters(text).map(|(start, _)| start).collect();
+    bounds.push(text.chars().count());
+    bounds
+}
+
+proptest! {
+    #[test]
+    fn clusters_cover_the_text(text in unicode_line()) {
+        let clusters: Vec<_> = grapheme::clusters(&text).collect();
+        prop_assert_eq!(clusters.iter().map(|(_, cluster)| *cluster).collect::<String>(), text.clone());
+        let mut col = 0;
+        for (start, cluster) in clusters {
+            prop_assert_eq!(start, col);
+            prop_assert!(grapheme::cluster_width(cluster) >= 1);
+            col += cluster.chars().count();
+        }
+        prop_assert_eq!(
+            grapheme::width(&text),
+            text.graphemes(true).map(grapheme::cluster_width).sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn cursor_motions_land_on_cluster_starts(text in unicode_line()) {
+        let bounds = cluster_bounds(&text);
+        let len = text.chars().count();
+        for col in 0..=len {
+            let start = grapheme::cluster_start(&text, col);
+            prop_assert!(start <= col && bounds.contains(&start));
+            let next = grapheme::next_cluster(&text, col);
+            prop_assert!(next > col);
+            prop_assert!(next > len || bounds.contains(&next));
+            let previous = grapheme::previous_cluster(&text, col);
+            prop_assert!(bounds.contains(&previous));
+            prop_assert!(previous < start || start == 0);
+        }
+        for byte in (0..=text.len()).filter(|&byte| text.is_char_boundary(byte)) {
+            let ceil = grapheme::ceil_boundary(&text, byte);
+            prop_assert!(ceil >= byte);
+            prop_assert!(ceil == text.len() || text.grapheme_indices(true).any(|(start, _)| start == ceil));
+        }
+    }
+
+    #[test]
+    fn display_columns_round_trip(text in unicode_line()) {
+        for start in cluster_bounds(&text) {
+            let display = grapheme::display_col(&text, start);
+            prop_assert_eq!(grapheme::col_at_display(&text, display), start);
+        }
+        // Every screen column belongs to the cluster drawn over it
+        let bounds = cluster_bounds(&text);
+        for display in 0..grapheme::width(&text) {
+            let col = grapheme::col_at_display(&text, display);
+            prop_assert!(bounds.contains(&col));
+            let from = grapheme::display_col(&text, col);
+            let to = grapheme::display_col(&text, grapheme::next_cluster(&text, col));
+            prop_assert!(from <= display && display < to);
+        }
+    }
+
+    #[test]
+    fn screen_lines_keep_clusters_whole(
+        text in unicode_line(),
+        width in 1usize..30,
+        left_col in 0usize..30,
+        options in wrap_options(),
+    ) {
+        let bounds = cluster_bounds(&text);
+        let lines = wrap::screen_lines(&text, width, left_col, &options);
+        prop_assert!(!lines.is_empty());
+        for line in &lines {
+            prop_assert!(bounds.contains(&line.start) && bounds.contains(&line.end));
+            prop_assert!(line.start <= line.end);
+            let shown = &text[byte_index(&text, line.start)..byte_index(&text, line.end)];
+            // A row takes one cluster however wide, any more have to fit
+            if shown.graphemes(true).count() > 1 {
+                let shown_width = wrap::display_col(&text, line.end, &options)
+                    - wrap::display_col(&text, line.start, &options);
+                prop_assert!(line.prefix(&options) + shown_width <= width);
+            }
+        }
+        if options.wrap {
+            prop_assert_eq!(lines[0].start, 0);
+            prop_assert_eq!(lines[lines.len() - 1].end, text.chars().count());
+            for pair in lines.windows(2) {
+                prop_assert_eq!(pair[0].end, pair[1].start);
+                prop_assert!(pair[0].start < pair[0].end);
+            }
+        }
+        for &col in &bounds {
+            let (row, screen_col) = wrap::screen_position(&text, &lines, col, &options);
+            if (lines[row].start..lines[row].end).contains(&col) {
+                prop_assert_eq!(wrap::col_at(&text, &lines, row, screen_col, &options), col);
+            }
+        }
+    }
+
+    #[test]
+    fn nowrap_scrolling_keeps_the_cursor_in_view(
+        text in unicode_line(),
+        width in 8usize..30,
+        left_col in 0usize..30,
+        options in wrap_options(),
+    ) {
+        let options = WrapOptions { wrap: false, ..options };
+        for col in cluster_bounds(&text) {
+            let left_col = wrap::left_col(&text, col, left_col, width, &options);
+            let lines = wrap::screen_lines(&text, width, left_col, &options);
+            prop_assert!(lines[0].start <= col);
+            prop_assert!(col < lines[0].end || col == text.chars().count());
+        }
+    }
+
+    #[test]
+    fn drawn_text_is_as_wide_as_laid_out(text in unicode_line(), options in wrap_options()) {
+        let drawn = wrap::drawn(&text, &options);
+        let mut display = 0;
+        for cluster in &drawn {
+            let col = text[..cluster.bytes.start].chars().count();
+            prop_assert_eq!(wrap::display_col(&text, col, &options), display);
+            prop_assert!(!cluster.text.contains('\t'));
+            display += grapheme::width(&cluster.text);
+        }
+        prop_assert_eq!(wrap::display_col(&text, text.chars().count(), &options), display);
+    }
+
+    #[test]
+    fn terminal_buffer_measures_display_width(text in unicode_line()) {
+        let mut buffer = TerminalBuffer::new(u16::MAX, 1);
+        buffer.write(&text);
+        let (width, _) = buffer.measure_content();
+        let options = BufferOptions { tabstop: 8, ..BufferOptions::default() };
+        prop_assert_eq!(width as usize, options.display_width(&text));
+    }
+
+    #[test]
+    fn blanks_reach_the_column_asked_for(
+        from in 0usize..40,
+        width in 0usize..40,
+        tabstop in 1usize..9,
+        expandtab in any::<bool>(),
+    ) {
+        let options = BufferOptions { tabstop, expandtab, ..BufferOptions::default() };
+        let text = format!("{}{}", "x".repeat(from), options.blanks(from, from + width));
+        prop_assert_eq!(options.display_width(&text), from + width);
+        let indent = options.blanks(0, width);
+        prop_assert_eq!(options.indent_width(&format!("{indent}x")), width);
+        prop_assert!(!expandtab || !indent.contains('\t'));
+    }
+
+    #[test]
+    fn removing_a_cluster_leaves_the_rest(text in unicode_line(), index in any::<prop::sample::Index>()) {
+        let clusters: Vec<_> = text.graphemes(true).collect();
+        prop_assume!(!clusters.is_empty());
+        let index = index.index(clusters.len());
+        let col: usize = clusters[..index].iter().map(|cluster| cluster.chars().count()).sum();
+        // Any char of the cluster removes all of it
+        let inside = col + clusters[index].chars().count() - 1;
+        let mut buffer = Buffer::from_text(&text);
+        let removed = buffer.remove_cluster(0, inside).unwrap();
+        prop_assert_eq!(removed.as_deref(), Some(clusters[index]));
+        let mut expected = clusters.clone();
+        expected.remove(index);
+        prop_assert_eq!(buffer.line(0).unwrap_or_default(), expected.concat());
+    }
+
+    #[test]
+    fn files_are_written_back_as_they_were_read(
+        lines in prop::collection::vec("[a-z \t,.é中👍]{0,12}", 1..6),
+        fileformat in prop::sample::select(vec![FileFormat::Unix, FileFormat::Dos, FileFormat::Mac]),
+        fileencoding in prop::sample::select(vec![
+            FileEncoding::Utf8,
+            FileEncoding::Utf16Le,
+            FileEncoding::Utf16Be,
+            FileEncoding::Latin1,
+        ]),
+        bomb in any::<bool>(),
+        endofline in any::<bool>(),
+    ) {
+        let text = lines.concat();
+        // Without a line break there is nothing to tell the format by
+        prop_assume!(lines.len() > 1 || endofline || fileformat == FileFormat::Unix);
+        // A last empty line without a line break is the line break of the one before
+        prop_assume!(endofline || !lines[lines.len() - 1].is_empty());
+        // Latin1 has no byte order mark and only the first 256 chars
+        prop_assume!(fileencoding != FileEncoding::Latin1 || !bomb && text.chars().all(|c| u32::from(c) < 0x100));
+        // Without a mark UTF-16 is only told apart by the zero bytes of ASCII
+        prop_assume!(!matches!(fileencoding, FileEncoding::Utf16Le | FileEncoding::Utf16Be) || bomb || text.is_ascii() && !text.is_empty());
+        let file = FileOptions { fileformat, fileencoding, bomb, endofline };
+        let mut buffer = Buffer::from_text("");
+        buffer.lines = lines.clone().into();
+        buffer.options.file = file;
+        let bytes = buffer.file_contents().unwrap();
+        // Latin1 that happens to be valid UTF-8 reads back as UTF-8
+        prop_assume!(fileencoding != FileEncoding::Latin1 || std::str::from_utf8(&bytes).is_err());
+        let read = Buffer::from_bytes(bytes.clone());
+        prop_assert!(!read.is_binary());
+        prop_assert_eq!(read.lines().as_slice(), lines.as_slice());
+        prop_assert_eq!(read.options.file, file);
+        prop_assert_eq!(read.file_contents().unwrap(), bytes);
+    }
+
+    #[test]
+    fn bytes_that_are_not_text_are_shown_as_hex(
+        bytes in prop::collection::vec(any::<u8>(), 0..200),
+        at in any::<prop::sample::Index>(),
+    ) {
+        let mut bytes = bytes;
+        bytes.insert(at.index(bytes.len() + 1), 0);
+        bytes.insert(0, b'x');
+        let buffer = Buffer::from_bytes(bytes.clone());
+        prop_assume!(buffer.options.file.fileencoding == FileEncoding::Utf8 || buffer.is_binary());
+        prop_assert!(buffer.is_binary() && buffer.options.read_only);
+        prop_assert_eq!(buffer.line_count(), bytes.len().div_ceil(16));
+    }
+
+    #[test]
+    fn saved_undo_history_undoes_the_same(
+        lines in prop::collection::vec(unicode_line(), 1..6),
+        edits in prop::collection::vec((any::<prop::sample::Index>(), unicode_line(), any::<bool>()), 1..8),
+    ) {
+        let mut buffer = Buffer::from_text(&lines.join("\n"));
+        let original = buffer.lines().as_slice().to_vec();
+        for (index, text, insert) in edits {
+            let at = index.index(buffer.line_count());
+            if insert {
+                buffer.insert_lines(at, vec![text], (at, 0)).unwrap();
+            } else {
+                buffer.set_line(at, text, (at, 0)).unwrap();
+            }
+        }
+        let saved = serde_json::to_vec(&buffer.undo).unwrap();
+        let mut restored = Buffer::from_text("");
+        restored.lines = buffer.lines().as_slice().to_vec().into();
+        restored.undo = serde_json::from_slice(&saved).unwrap();
+        prop_assert_eq!(restored.undo.state(), buffer.undo.state());
+        while let Some(cursor) = buffer.undo() {
+            prop_assert_eq!(restored.undo(), Some(cursor));
+            prop_assert_eq!(restored.lines().as_slice(), buffer.lines().as_slice());
+        }
+        prop_assert_eq!(restored.undo(), None);
+        prop_assert_eq!(buffer.lines().as_slice(), original);
+    }
+}
+
+#[test]
+fn every_name_for_a_file_opens_the_same_buffer() {
+    let dir = std::env::temp_dir().join(format!("reovim-list-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    let file = dir.join("a.txt");
+    std::fs::write(&file, "text\n").unwrap();
+    let mut buffers = BufferList::default();
+    let opened = buffers.open(&file).unwrap();
+    let mut names = vec![dir.join("./a.txt"), dir.join("sub/../a.txt")];
+    #[cfg(unix)]
+    {
+        let link = dir.join("link.txt");
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+        names.push(link);
+    }
+    for name in names {
+        let again = buffers.open(&name).unwrap();
+        assert!(
+            Rc::ptr_eq(&opened, &again),
+            "{} opened another buffer",
+            name.display()
+        );
+    }
+    // A file that isn't there yet is told apart by its name as given
+    let new = buffers.open(&dir.join("new.txt")).unwrap();
+    assert!(Rc::ptr_eq(
+        &new,
+        &buffers.open(&dir.join("./new.txt")).unwrap()
+    ));
+    assert_eq!(buffers.len(), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Manual folds over lines 2 up to 5 and 8 up to 10
+fn two_folds() -> Folds {
+    let mut folds = Folds::default();
+    folds.create(2, 5).unwrap();
+    folds.create(8, 10).unwrap();
+    folds
+}
+
+fn insert(at: usize, count: usize) -> Edit {
+    Edit::Insert {
+        at,
+        lines: vec![String::new(); count],
+    }
+}
+
+fn remove(at: usize, count: usize) -> Edit {
+    Edit::Remove {
+        at,
+        lines: vec![String::new(); count],
+    }
+}
+
+#[test]
+fn folds_move_with_inserted_lines() {
+    let edited = |edit| {
+        let mut folds = two_folds();
+        folds.edited(&edit);
+        folds.closed()
+    };
+    // Above a fold, and at its first line, the fold moves down
+    assert_eq!(edited(insert(0, 2)), [(4, 7), (10, 12)]);
+    assert_eq!(edited(insert(2, 1)), [(3, 6), (9, 11)]);
+    // Inside it grows
+    assert_eq!(edited(insert(3, 2)), [(2, 7), (10, 12)]);
+    // At its end and below it stays
+    assert_eq!(edited(insert(5, 1)), [(2, 5), (9, 11)]);
+    assert_eq!(edited(insert(10, 3)), [(2, 5), (8, 10)]);
+}
+
+#[test]
+fn folds_shrink_and_go_with_removed_lines() {
+    let edited = |edit| {
+        let mut folds = two_folds();
+        folds.edited(&edit);
+        folds.closed()
+    };
+    assert_eq!(edited(remove(0, 1)), [(1, 4), (7, 9)]);
+    assert_eq!(edited(remove(3, 1)), [(2, 4), (7, 9)]);
+    // Across the first line the fold keeps the lines left of it
+    assert_eq!(edited(remove(1, 2)), [(1, 3), (6, 8)]);
+    // Across the last line likewise
+    assert_eq!(edited(remove(4, 2)), [(2, 4), (6, 8)]);
+    // Removing every line of a fold removes the fold
+    assert_eq!(edited(remove(2, 3)), [(5, 7)]);
+    assert_eq!(edited(remove(1, 5)), [(3, 5)]);
+    assert_eq!(edited(remove(0, 11)), []);
+}
+
+#[test]
+fn nested_folds_follow_edits() {
+    let mut folds = Folds::default();
+    folds.create(0, 6).unwrap();
+    folds.create(2, 4).unwrap();
+    folds.edited(&insert(3, 1));
+    folds.open_at(0).unwrap();
+    assert_eq!(folds.closed(), [(2, 5)]);
+    folds.edited(&remove(2, 3));
+    // The inner fold went with its lines, the outer one is left
+    assert_eq!(folds.closed(), []);
+    assert_eq!(folds.next_start(0), None);
+    assert_eq!(folds.previous_end(4), Some(3));
+}
+
+#[test]
+fn syntax_folds_need_tree_sitter() {
+    let mut folds = Folds::default();
+    assert_eq!(
+        folds.set_method(FoldMethod::Expr).is_ok(),
+        cfg!(feature = "tree-sitter")
+    );
+    folds.set_method(FoldMethod::Indent).unwrap();
+    assert_eq!(folds.method(), FoldMethod::Indent);
+}
+
+#[test]
+fn indent_folds_follow_the_indent_options() {
+    let mut buffer = Buffer::from_text("a\n  b\n    c\n  d\ne\n\tf\n");
+    buffer.folds().set_method(FoldMethod::Indent).unwrap();
+    buffer.options.shiftwidth = 2;
+    buffer.folds().set_all(true);
+    assert_eq!(buffer.folds().closed(), [(1, 4), (5, 6)]);
+    // Four columns a level puts `b` and `d` at the top level with `a`
+    buffer.options.shiftwidth = 4;
+    buffer.options.tabstop = 4;
+    buffer.folds().set_all(true);
+    assert_eq!(buffer.folds().closed(), [(2, 3), (5, 6)]);
+}
+
+/// Write `bytes` to a file of the test's own
+fn temp_file(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("reovim-lines-{}-{name}", std::process::id()));
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+fn numbered(count: usize) -> Vec<String> {
+    (0..count).map(|line| format!("line {line}")).collect()
+}
+
+#[test]
+fn indexed_lines_are_the_lines_of_the_file() {
+    // A char cut in two by where the first chunk read ends
+    let long = format!("a{}", "é".repeat(600_000));
+    let cases = [
+        ("unix", numbered(200).join("\n") + "\n"),
+        ("dos", numbered(200).join("\r\n") + "\r\n"),
+        ("mixed", numbered(200).join("\n") + "\r\nlast"),
+        ("bom", format!("\u{feff}{}", numbered(64).join("\n"))),
+        ("long", format!("{long}\n{long}\n")),
+    ];
+    for (name, text) in cases {
+        let path = temp_file(name, text.as_bytes());
+        let (lines, options) = Lines::index(File::open(&path).unwrap()).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let (expected, expected_options) =
+            encoding::split_lines(text.strip_prefix('\u{feff}').unwrap_or(&text));
+        assert!(lines.is_lazy(), "{name}");
+        assert_eq!(lines.len(), expected.len(), "{name}");
+        assert!(
+            lines.iter().eq(expected.iter().map(String::as_str)),
+            "{name}"
+        );
+        assert_eq!(options.fileformat, expected_options.fileformat, "{name}");
+        assert_eq!(options.endofline, expected_options.endofline, "{name}");
+        assert_eq!(options.bomb, name == "bom", "{name}");
+    }
+}
+
+#[test]
+fn files_that_are_not_utf8_are_not_indexed() {
+    let mut latin1 = numbered(2000).join("\n").into_bytes();
+    // Past the start looked at before reading on
+    latin1.extend(b"\ncaf\xe9\n");
+    let utf16: Vec<u8> = "text\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    for (name, bytes) in [("latin1", latin1), ("utf16", utf16)] {
+        let path = temp_file(name, &bytes);
+        let indexed = Lines::index(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(indexed.is_none(), "{name}");
+    }
+}
+
+#[test]
+fn large_files_are_read_as_needed_and_written_back() {
+    // Long lines, so there are few of them to make
+    let count = LARGE_FILE_SIZE / 1000 + 10;
+    let numbered: Vec<String> = (0..count).map(|line| format!("{line:>999}")).collect();
+    let text = numbered.join("\n") + "\n";
+    let path = temp_file("large", text.as_bytes());
+    let mut buffer = Buffer::from_file_path(&path).unwrap();
+    assert!(buffer.is_large() && buffer.lines().is_lazy());
+    assert_eq!(buffer.line_count(), count);
+    assert_eq!(buffer.line(count - 1), Some(numbered[count - 1].as_str()));
+    // Nothing is kept aside to undo across sessions or recover
+    assert!(!buffer.options.undofile);
+    buffer
+        .insert_lines(1, vec!["new".to_string()], (0, 0))
+        .unwrap();
+    buffer.remove_lines(3, 2, (0, 0)).unwrap();
+    buffer
+        .set_line(count - 2, "changed".to_string(), (0, 0))
+        .unwrap();
+    assert!(buffer.sync_swap().is_none());
+    let mut expected = numbered;
+    expected.insert(1, "new".to_string());
+    expected.drain(3..5);
+    expected[count - 2] = "changed".to_string();
+    buffer.write(None).unwrap();
+    // Read from what was written, not the file as it was indexed
+    assert!(buffer.lines().is_lazy());
+    assert_eq!(buffer.line_count(), expected.len());
+    for line in [0, 1, 3, count - 3, count - 2] {
+        assert_eq!(buffer.line(line), Some(expected[line].as_str()));
+    }
+    let written = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(written, expected.join("\n") + "\n");
+}
+
+/// A buffer reading the lines of the file at `path` as they are needed, as a large file's are
+fn indexed_buffer(path: &Path) -> Buffer {
+    let (lines, file) = Lines::index(File::open(path).unwrap()).unwrap().unwrap();
+    Buffer {
+        file_path: Some(path.to_path_buf()),
+        lines,
+        options: BufferOptions {
+            file,
+            ..Default::default()
+        },
+        saved_file: file,
+        large: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn only_the_blocks_of_lines_asked_for_last_are_kept() {
+    let path = temp_file("blocks", (numbered(64 * 300).join("\n") + "\n").as_bytes());
+    let mut buffer = indexed_buffer(&path);
+    for line in (0..buffer.line_count()).step_by(64) {
+        buffer.line(line);
+    }
+    assert_eq!(buffer.lines().kept_blocks(), 300);
+    buffer.forget_unused_lines();
+    assert_eq!(buffer.lines().kept_blocks(), 256);
+    // The last ones asked for are still there, the first one is read again
+    assert_eq!(buffer.line(64 * 299), Some("line 19136"));
+    assert_eq!(buffer.lines().kept_blocks(), 256);
+    assert_eq!(buffer.line(0), Some("line 0"));
+    assert_eq!(buffer.lines().kept_blocks(), 257);
+    buffer.forget_unused_lines();
+    assert_eq!(buffer.lines().kept_blocks(), 256);
+    assert!(buffer.lines().read_error().is_none());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn a_file_cut_short_behind_the_buffer_is_not_written_over() {
+    let text = numbered(200).join("\n") + "\n";
+    let path = temp_file("cut-short", text.as_bytes());
+    let mut buffer = indexed_buffer(&path);
+    assert_eq!(buffer.line(0), Some("line 0"));
+    fs::write(&path, &text[..100]).unwrap();
+    // What was read is still there, the lines that aren't any more are missing
+    assert_eq!(buffer.line(1), Some("line 1"));
+    assert_eq!(buffer.line(150), Some(""));
+    assert!(buffer.lines().read_error().is_some());
+    let err = buffer.write(None).unwrap_err().to_string();
+    assert!(
+        err.starts_with("Can't write, reading the file failed"),
+        "{err}"
+    );
+    // Nor once every line is in memory
+    buffer.lines.read_all();
+    assert!(buffer.file_contents().is_ok());
+    assert!(buffer.write(None).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), &text[..100]);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn large_files_are_saved_through_a_file_beside_them() {
+    let dir = std::env::temp_dir().join(format!("reovim-lines-{}-beside", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let target = dir.join("target.txt");
+    fs::write(&target, numbered(300).join("\n") + "\n").unwrap();
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o640)).unwrap();
+    let link = dir.join("link.txt");
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+    let mut buffer = indexed_buffer(&link);
+    buffer.set_line(250, "changed".to_string(), (0, 0)).unwrap();
+    let (_, written) = buffer.write(None).unwrap();
+    let mut expected = numbered(300);
+    expected[250] = "changed".to_string();
+    let expected = expected.join("\n") + "\n";
+    assert_eq!(fs::read_to_string(&target).unwrap(), expected);
+    assert_eq!(written, expected.len());
+    assert_eq!(buffer.saved_hash, undo::content_hash(expected.as_bytes()));
+    // The link is kept and the file it points to replaced as it was, with nothing left beside it
+    assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+    let mode = fs::metadata(&target).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    assert!(buffer.lines().is_lazy() && !buffer.is_modified());
+    assert_eq!(buffer.line(250), Some("changed"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// An edit somewhere in `count` lines, `Index`es picking where
+fn edit_in(count: usize, kind: u8, at: prop::sample::Index, lines: usize) -> Edit {
+    match kind {
+        0 => Edit::Insert {
+            at: at.index(count + 1),
+            lines: (0..lines).map(|line| format!("new {line}")).collect(),
+        },
+        1 if count > 0 => Edit::Remove {
+            at: at.index(count),
+            lines: vec![String::new(); lines],
+        },
+        _ if count > 0 => Edit::Replace {
+            at: at.index(count),
+            before: String::new(),
+            after: "replaced".to_string(),
+        },
+        _ => Edit::Insert {
+            at: 0,
+            lines: vec!["first".to_string()],
+        },
+    }
+}
+
+#[test]
+fn swap_files_keep_up_with_edits_and_recover_them() {
+    let path = temp_file("swap", b"one\ntwo\n");
+    let mut buffer = Buffer::from_file_path(&path).unwrap();
+    // Nothing to keep while the text is the file's
+    assert!(buffer.sync_swap().is_none());
+    buffer.set_line(1, "2".to_string(), (1, 0)).unwrap();
+    let (_, text) = buffer.sync_swap().unwrap();
+    assert!(buffer.sync_swap().is_none());
+    buffer
+        .insert_lines(2, vec!["three".to_string()], (1, 0))
+        .unwrap();
+    buffer.cursor = (2, 3);
+    // The text handed over before catches up with the edits since
+    buffer.sync_swap().unwrap();
+    text.write(&path).unwrap();
+    let swap = SwapFile::read(&path).unwrap().unwrap();
+    assert_eq!(swap.lines, ["one", "2", "three"]);
+    assert_eq!(swap.cursor, (2, 3));
+
+    let mut recovered = Buffer::from_file_path(&path).unwrap();
+    recovered.recover(swap);
+    assert_eq!(recovered.lines().as_slice(), ["one", "2", "three"]);
+    assert_eq!(recovered.cursor, (2, 3));
+    assert!(recovered.is_modified());
+    // Recovering is one change, undo takes the text back to the file's
+    recovered.undo();
+    assert_eq!(recovered.lines().as_slice(), ["one", "two"]);
+    assert!(!recovered.is_modified());
+    recovered.redo();
+    assert_eq!(recovered.lines().as_slice(), ["one", "2", "three"]);
+
+    // Writing the text removes the swap file
+    buffer.write(None).unwrap();
+    assert_eq!(SwapFile::read(&path).unwrap(), None);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn saved_undo_history_is_only_used_for_the_text_it_was_saved_with() {
+    let path = temp_file("undo", b"one\n");
+    let mut buffer = Buffer::from_file_path(&path).unwrap();
+    buffer.options.undofile = true;
+    buffer.set_line(0, "uno".to_string(), (0, 0)).unwrap();
+    buffer.write(None).unwrap();
+    buffer.write_undo().unwrap();
+
+    let mut reopened = Buffer::from_file_path(&path).unwrap();
+    // A recovered swap file is undone first, then the history saved with the file
+    reopened.recover(SwapFile {
+        pid: 0,
+        time: 0,
+        cursor: (0, 0),
+        lines: vec!["eins".to_string()],
+    });
+    assert_eq!(reopened.undo(), Some((0, 0)));
+    assert_eq!(reopened.lines().as_slice(), ["uno"]);
+    assert_eq!(reopened.undo(), Some((0, 0)));
+    assert_eq!(reopened.lines().as_slice(), ["one"]);
+
+    // Changed by something else since, the history would undo into the wrong text
+    std::fs::write(&path, "uno\nmore\n").unwrap();
+    let mut changed = Buffer::from_file_path(&path).unwrap();
+    assert_eq!(changed.undo(), None);
+    assert_eq!(changed.lines().as_slice(), ["uno", "more"]);
+    let hash = undo::content_hash(b"uno\nmore\n");
+    assert!(UndoHistory::load(&path, hash).unwrap().is_none());
+
+    if let Some(undo_file) = state_file("undo", &path, "") {
+        std::fs::remove_file(undo_file).unwrap();
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+proptest! {
+    #[test]
+    fn edits_to_indexed_lines_match_edits_in_memory(
+        edits in prop::collection::vec((0..3u8, any::<prop::sample::Index>(), 0..80usize), 1..30),
+        read_all_after in any::<prop::sample::Index>(),
+    ) {
+        let path = temp_file("edits", (numbered(300).join("\n") + "\n").as_bytes());
+        let (mut lines, _) = Lines::index(File::open(&path).unwrap()).unwrap().unwrap();
+        let mut expected = numbered(300);
+        let read_all_after = read_all_after.index(edits.len());
+        for (index, (kind, at, count)) in edits.into_iter().enumerate() {
+            let edit = edit_in(expected.len(), kind, at, count);
+            lines.apply(&edit);
+            edit.apply(&mut expected);
+            prop_assert_eq!(lines.len(), expected.len());
+            prop_assert!(lines.iter().eq(expected.iter().map(String::as_str)));
+            if index == read_all_after {
+                lines.read_all();
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+        prop_assert_eq!(lines.as_slice(), expected.as_slice());
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{state_dir, state_file};
+
+/// A single reversible change to the lines of a buffer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Edit {
+    /// Lines inserted before index `at`
+    Insert { at: usize, lines: Vec<String> },
+    /// Lines removed starting at index `at`
+    Remove { at: usize, lines: Vec<String> },
+    /// The line at `at` changed from `before` to `after`
+    Replace {
+        at: usize,
+        before: String,
+        after: String,
+    },
+}
+
+impl Edit {
+    /// The edit that reverts this one
+    pub fn inverse(&self) -> Edit {
+        match self {
+            Edit::Insert { at, lines } => Edit::Remove {
+                at: *at,
+                lines: lines.clone(),
+            },
+            Edit::Remove { at, lines } => Edit::Insert {
+                at: *at,
+                lines: lines.clone(),
+            },
+            Edit::Replace { at, before, after } => Edit::Replace {
+                at: *at,
+                before: after.clone(),
+                after: before.clone(),
+            },
+        }
+    }
+
+    /// Apply the edit to the lines
+    pub fn apply(&self, lines: &mut Vec<String>) {
+        match self {
+            Edit::Insert {
+                at,
+                lines: inserted,
+            } => {
+                let at = (*at).min(lines.len());
+                lines.splice(at..at, inserted.iter().cloned());
+            }
+            Edit::Remove { at, lines: removed } => {
+                let start = (*at).min(lines.len());
+                let end = (start + removed.len()).min(lines.len());
+                lines.drain(start..end);
+            }
+            Edit::Replace { at, after, .. } => {
+                if let Some(line) = lines.get_mut(*at) {
+                    *line = after.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Edits undone and redone together, with the cursor to restore on undo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoStep {
+    /// Identifies the buffer state after this step, used for the modified flag
+    pub id: u64,
+    pub edits: Vec<Edit>,
+    /// (line, col) before the first edit of the step
+    pub cursor: (usize, usize),
+}
+
+/// Undo and redo stacks for a buffer
+/// Edits made between `begin` and `end` are grouped into one step, such as a whole insert
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UndoHistory {
+    undo: Vec<UndoStep>,
+    redo: Vec<UndoStep>,
+    /// The step being recorded, if a group is open
+    #[serde(skip)]
+    open: Option<UndoStep>,
+    next_id: u64,
+}
+
+impl UndoHistory {
+    /// Start grouping edits into a single step
+    pub fn begin(&mut self, cursor: (usize, usize)) {
+        if self.open.is_none() {
+            self.next_id += 1;
+            self.open = Some(UndoStep {
+                id: self.next_id,
+                edits: Vec::new(),
+                cursor,
+            });
+        }
+    }
+
+    /// Close the current group, empty groups are dropped
+    pub fn end(&mut self) {
+        if let Some(step) = self.open.take()
+            && !step.edits.is_empty()
+        {
+            self.undo.push(step);
+            self.redo.clear();
+        }
+    }
+
+    pub fn is_grouping(&self) -> bool {
+        self.open.is_some()
+    }
+
+    /// Record an edit that has already been applied
+    pub fn record(&mut self, edit: Edit, cursor: (usize, usize)) {
+        let grouped = self.open.is_some();
+        self.begin(cursor);
+        if let Some(step) = self.open.as_mut() {
+            step.edits.push(edit);
+        }
+        if !grouped {
+            self.end();
+        }
+    }
+
+    /// Pop the last step, the caller applies the inverse of its edits in reverse order
+    pub fn undo(&mut self) -> Option<UndoStep> {
+        self.end();
+        let step = self.undo.pop()?;
+        self.redo.push(step.clone());
+        Some(step)
+    }
+
+    /// Pop the last undone step, the caller re-applies its edits in order
+    pub fn redo(&mut self) -> Option<UndoStep> {
+        self.end();
+        let step = self.redo.pop()?;
+        self.undo.push(step.clone());
+        Some(step)
+    }
+
+    /// Identifies the current state, 0 when no step has been applied
+    pub fn state(&self) -> u64 {
+        match &self.open {
+            Some(step) if !step.edits.is_empty() => step.id,
+            _ => self.undo.last().map(|step| step.id).unwrap_or(0),
+        }
+    }
+}
+
+/// An undo history saved for a file, only good for the contents it was saved with
+/// Saved from a borrowed history and read back into an owned one
+#[derive(Serialize, Deserialize)]
+struct UndoFile<H> {
+    /// `content_hash` of the file when the history was saved
+    hash: u64,
+    history: H,
+}
+
+/// Where undo histories are saved, they are saved by default once it exists
+pub fn undo_dir() -> Option<PathBuf> {
+    state_dir("undo")
+}
+
+/// Hash of a file's bytes, FNV-1a as it is saved and has to come out the same in every build
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    hash_more(0xcbf2_9ce4_8422_2325, bytes)
+}
+
+/// `content_hash` carried on over `bytes` from `hash`, the hash of the bytes before them
+pub fn hash_more(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl UndoHistory {
+    /// The history saved for `file`, unless the file changed since, going by the `hash` of its contents
+    pub fn load(file: &Path, hash: u64) -> Result<Option<UndoHistory>> {
+        let Some(path) = state_file("undo", file, "") else {
+            return Ok(None);
+        };
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let saved: UndoFile<UndoHistory> = serde_json::from_slice(&bytes)?;
+        Ok((saved.hash == hash).then_some(saved.history))
+    }
+
+    /// Save the history for `file`, whose contents hash to `hash`, closing a group that is open
+    pub fn save(&mut self, file: &Path, hash: u64) -> Result<()> {
+        let Some(path) = state_file("undo", file, "") else {
+            return Ok(());
+        };
+        self.end();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let saved = UndoFile {
+            hash,
+            history: &*self,
+        };
+        fs::write(&path, serde_json::to_vec(&saved)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+
+#[cfg(test)]
+mod tests;
+
+pub const USAGE: &str = "\
+usage: reovim [options] [file ...]
+
+arguments:
+  file              open each file as a buffer, missing files open as new buffers
+  -                 read the initial buffer from stdin
+
+options:
+  +N                start at line N
+  +                 start at the last line
+  +/pattern         start at the first line containing pattern
+  -R                open buffers read-only
+  -d                compare the first two files side by side in diff mode
+  --clean           skip loading user configuration
+  --log <path>      write logs to path (default: reovim.log)
+  --version         print the version and exit
+  -h, --help        print this help and exit
+  --                treat every following argument as a file
+";
+
+/// A file argument, either a path on disk or stdin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileArg {
+    Path(PathBuf),
+    Stdin,
+}
+
+/// Where the cursor should start in the first buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartPosition {
+    /// 1-based line number
+    Line(usize),
+    /// The last line of the buffer
+    LastLine,
+    /// The first line containing the pattern
+    Pattern(String),
+}
+
+impl StartPosition {
+    /// Resolve the start position to a 0-based line index for the given lines, only a pattern reads them
+    pub fn resolve<'a, I>(&self, lines: I) -> usize
+    where
+        I: IntoIterator<Item = &'a str>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let mut lines = lines.into_iter();
+        let last = lines.len().saturating_sub(1);
+        match self {
+            StartPosition::Line(line) => line.saturating_sub(1).min(last),
+            StartPosition::LastLine => last,
+            StartPosition::Pattern(pattern) => lines
+                .position(|line| line.contains(pattern.as_str()))
+                .unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Args {
+    pub files: Vec<FileArg>,
+    pub start: Option<StartPosition>,
+    pub read_only: bool,
+    /// Start in diff mode comparing the first two files
+    pub diff: bool,
+    pub clean: bool,
+    pub log_file: PathBuf,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            start: None,
+            read_only: false,
+            diff: false,
+            clean: false,
+            log_file: PathBuf::from("reovim.log"),
+        }
+    }
+}
+
+impl Args {
+    /// Directory user configuration is loaded from, `None` when started with `--clean`
+    pub fn config_dir(&self) -> Option<PathBuf> {
+        if self.clean {
+            return None;
+        }
+        match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("reovim")),
+            _ => std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".config").join("reovim")),
+        }
+    }
+}
+
+/// What the command line asked reovim to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Run(Args),
+    Version,
+    Help,
+}
+
+/// Parse command line arguments, excluding the program name
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Action> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+    let mut only_files = false;
+
+    while let Some(arg) = args.next() {
+        if only_files {
+            parsed.files.push(FileArg::Path(PathBuf::from(arg)));
+            continue;
+        }
+        match arg.as_str() {
+            "--" => only_files = true,
+            "--help" | "-h" => return Ok(Action::Help),
+            "--version" => return Ok(Action::Version),
+            "-R" => parsed.read_only = true,
+            "-d" => parsed.diff = true,
+            "--clean" => parsed.clean = true,
+            "--log" => match args.next() {
+                Some(path) => parsed.log_file = PathBuf::from(path),
+                None => bail!("--log requires a path"),
+            },
+            "-" => parsed.files.push(FileArg::Stdin),
+            "+" => parsed.start = Some(StartPosition::LastLine),
+            _ if arg.starts_with("--log=") => {
+                parsed.log_file = PathBuf::from(&arg["--log=".len()..]);
+            }
+            _ if arg.starts_with("+/") => {
+                parsed.start = Some(StartPosition::Pattern(arg[2..].to_string()));
+            }
+            _ if arg.starts_with('+') => match arg[1..].parse::<usize>() {
+                Ok(line) => parsed.start = Some(StartPosition::Line(line)),
+                Err(_) => bail!("invalid start position '{arg}'"),
+            },
+            _ if arg.starts_with('-') => bail!("unknown option '{arg}'"),
+            _ => parsed.files.push(FileArg::Path(PathBuf::from(arg))),
+        }
+    }
+
+    if parsed
+        .files
+        .iter()
+        .filter(|file| **file == FileArg::Stdin)
+        .count()
+        > 1
+    {
+        bail!("stdin can only be read once");
+    }
+
+    Ok(Action::Run(parsed))
+}
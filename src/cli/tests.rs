@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+
+use super::*;
+
+fn run(args: &[&str]) -> Result<Args> {
+    match parse(args.iter().map(|arg| arg.to_string()))? {
+        Action::Run(args) => Ok(args),
+        action => bail!("expected to run, got {action:?}"),
+    }
+}
+
+fn path(name: &str) -> FileArg {
+    FileArg::Path(PathBuf::from(name))
+}
+
+#[test]
+fn start_positions() {
+    let lines = ["one", "two", "three"];
+    let start = |args: &[&str]| run(args).unwrap().start.unwrap();
+
+    assert_eq!(start(&["+2", "a"]), StartPosition::Line(2));
+    assert_eq!(start(&["+2"]).resolve(lines), 1);
+    // Past the end is the last line, `+0` the first
+    assert_eq!(start(&["+9"]).resolve(lines), 2);
+    assert_eq!(start(&["+0"]).resolve(lines), 0);
+    assert_eq!(start(&["+"]), StartPosition::LastLine);
+    assert_eq!(start(&["+"]).resolve(lines), 2);
+    assert_eq!(start(&["+/hre"]), StartPosition::Pattern("hre".to_string()));
+    assert_eq!(start(&["+/hre"]).resolve(lines), 2);
+    assert_eq!(start(&["+/missing"]).resolve(lines), 0);
+    // The last one given wins
+    assert_eq!(start(&["+1", "+"]), StartPosition::LastLine);
+    assert!(run(&["+x"]).is_err());
+}
+
+#[test]
+fn log_file() {
+    assert_eq!(run(&[]).unwrap().log_file, PathBuf::from("reovim.log"));
+    assert_eq!(
+        run(&["--log", "x.log"]).unwrap().log_file,
+        PathBuf::from("x.log")
+    );
+    assert_eq!(
+        run(&["--log=y.log"]).unwrap().log_file,
+        PathBuf::from("y.log")
+    );
+    assert!(run(&["--log"]).is_err());
+}
+
+#[test]
+fn files_after_double_dash() {
+    let args = run(&["a", "--", "-R", "+3", "--", "-"]).unwrap();
+    assert_eq!(
+        args.files,
+        [path("a"), path("-R"), path("+3"), path("--"), path("-")]
+    );
+    assert!(!args.read_only);
+    assert_eq!(args.start, None);
+}
+
+#[test]
+fn stdin_is_read_once() {
+    let args = run(&["-", "a"]).unwrap();
+    assert_eq!(args.files, [FileArg::Stdin, path("a")]);
+    assert!(run(&["-", "a", "-"]).is_err());
+}
+
+#[test]
+fn options() {
+    let args = run(&["-R", "-d", "--clean", "a", "b"]).unwrap();
+    assert!(args.read_only && args.diff && args.clean);
+    assert_eq!(args.config_dir(), None);
+    assert_eq!(parse(["--version".to_string()]).unwrap(), Action::Version);
+    assert_eq!(parse(["-h".to_string()]).unwrap(), Action::Help);
+    // `-v` is vi mode in vim, not the version
+    assert!(run(&["-v"]).is_err());
+    assert!(run(&["--bogus"]).is_err());
+    assert!(run(&["-x", "a"]).is_err());
+}
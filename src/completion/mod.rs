@@ -0,0 +1,212 @@
+pub mod sources;
+#[cfg(test)]
+mod tests;
+
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Result;
+
+use crate::completion::sources::{BufferLines, BufferWords, FilePaths};
+
+/// Which completion was asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    /// `Ctrl-N` and `Ctrl-P`, words
+    Keyword,
+    /// `Ctrl-X Ctrl-F`, file paths
+    File,
+    /// `Ctrl-X Ctrl-L`, whole lines
+    Line,
+}
+
+impl CompletionKind {
+    /// Column where the text being completed starts, for a cursor at `col`
+    pub fn start(self, line: &str, col: usize) -> usize {
+        let before: Vec<char> = line.chars().take(col).collect();
+        match self {
+            CompletionKind::Keyword => before
+                .iter()
+                .rposition(|&character| !is_keyword(character))
+                .map_or(0, |index| index + 1),
+            CompletionKind::File => before
+                .iter()
+                .rposition(|&character| !is_path(character))
+                .map_or(0, |index| index + 1),
+            // Lines are completed from their first non-blank, keeping the indent
+            CompletionKind::Line => before
+                .iter()
+                .position(|character| !character.is_whitespace())
+                .unwrap_or(before.len()),
+        }
+    }
+
+    /// Whether a character can be part of what this kind completes, typing anything else closes the menu
+    pub fn continues(self, character: char) -> bool {
+        match self {
+            CompletionKind::Keyword => is_keyword(character),
+            CompletionKind::File => is_path(character),
+            CompletionKind::Line => true,
+        }
+    }
+}
+
+/// Characters words are made of
+pub fn is_keyword(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+fn is_path(character: char) -> bool {
+    !character.is_whitespace()
+        && !matches!(character, '"' | '\'' | '`' | '(' | ')' | '<' | '>' | '=')
+}
+
+/// A candidate to complete with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    /// Replaces what was typed
+    pub text: String,
+    /// Short note shown next to the text in the menu
+    pub detail: Option<String>,
+    /// Longer text shown beside the menu while the item is selected
+    pub preview: Option<String>,
+    /// A regular file whose first lines preview it, only read once the item is selected
+    pub file: Option<PathBuf>,
+}
+
+impl CompletionItem {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            detail: None,
+            preview: None,
+            file: None,
+        }
+    }
+}
+
+/// What a source gets to work with, copied from the editor so it can go to a worker thread
+#[derive(Debug, Clone)]
+pub struct CompletionRequest {
+    pub kind: CompletionKind,
+    /// What was typed, from the start column to the cursor
+    pub prefix: String,
+    /// Line and column of the cursor
+    pub cursor: (usize, usize),
+    /// Lines of the buffer being edited
+    pub lines: Arc<Vec<String>>,
+    /// The file being edited, if it has one
+    pub path: Option<PathBuf>,
+    /// Directory relative paths are completed from
+    pub cwd: PathBuf,
+}
+
+/// The candidates one source found for a request
+#[derive(Debug, Clone)]
+pub struct CompletionResponse {
+    /// The request answered, answers to a request that's been replaced are dropped
+    pub id: u64,
+    /// Name of the source
+    pub source: String,
+    pub items: Vec<CompletionItem>,
+}
+
+/// Suggests completions, implement it to complete from anything else
+/// `complete` runs on a worker thread so it can take its time without holding up typing
+pub trait CompletionSource: Send + Sync {
+    /// Shown next to the source's items in the menu
+    fn name(&self) -> &str;
+
+    /// Whether the source answers this kind of completion
+    fn handles(&self, kind: CompletionKind) -> bool;
+
+    /// Candidates for the request, ranking against what was typed happens afterwards
+    fn complete(&self, request: &CompletionRequest) -> Result<Vec<CompletionItem>>;
+}
+
+/// Every source completions are asked of
+#[derive(Clone)]
+pub struct CompletionSources {
+    sources: Vec<Arc<dyn CompletionSource>>,
+}
+
+impl Default for CompletionSources {
+    /// Words and lines of the buffer and paths on disk
+    fn default() -> Self {
+        let mut sources = Self {
+            sources: Vec::new(),
+        };
+        sources.register(BufferWords);
+        sources.register(FilePaths);
+        sources.register(BufferLines);
+        sources
+    }
+}
+
+impl CompletionSources {
+    pub fn register(&mut self, source: impl CompletionSource + 'static) {
+        self.sources.push(Arc::new(source));
+    }
+
+    /// Sources answering a kind of completion, in the order they were registered
+    pub fn for_kind(&self, kind: CompletionKind) -> Vec<Arc<dyn CompletionSource>> {
+        self.sources
+            .iter()
+            .filter(|source| source.handles(kind))
+            .cloned()
+            .collect()
+    }
+}
+
+/// How well `text` matches what was typed, `None` when it doesn't hold the typed characters in order
+/// Matching from the start, runs of consecutive characters and word boundaries score higher
+/// Case is ignored unless the pattern has an uppercase letter
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    // Nothing typed yet, everything matches equally
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    let ignore_case = !pattern.chars().any(char::is_uppercase);
+    let same = |a: char, b: char| {
+        if ignore_case {
+            a.to_lowercase().eq(b.to_lowercase())
+        } else {
+            a == b
+        }
+    };
+    let text: Vec<char> = text.chars().collect();
+    let mut score = 0i64;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in pattern.chars() {
+        let found = (next..text.len()).find(|&index| same(text[index], wanted))?;
+        score += 1;
+        if found == 0 {
+            score += 8;
+        } else if previous == Some(found - 1) {
+            score += 5;
+        } else {
+            let before = text[found - 1];
+            if !before.is_alphanumeric() || (before.is_lowercase() && text[found].is_uppercase()) {
+                score += 3;
+            }
+            score -= (found - previous.map_or(0, |previous| previous + 1)).min(5) as i64;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    // Shorter candidates are closer to what was typed
+    score -= (text.len() - pattern.chars().count()).min(10) as i64 / 2;
+    Some(score)
+}
+
+/// Indexes of the candidates matching what was typed, best first
+/// Equal matches keep their order, so sources decide between them
+pub fn rank<'a>(pattern: &str, texts: impl IntoIterator<Item = &'a str>) -> Vec<usize> {
+    let mut scored: Vec<(usize, i64)> = texts
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, text)| fuzzy_score(pattern, text).map(|score| (index, score)))
+        .collect();
+    scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    scored.into_iter().map(|(index, _)| index).collect()
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+
+use crate::completion::{
+    CompletionItem, CompletionKind, CompletionRequest, CompletionSource, is_keyword,
+};
+
+/// Lines of a file shown as its preview
+const PREVIEW_LINES: usize = 8;
+
+/// Words in the buffer, nearest to the cursor first
+pub struct BufferWords;
+
+impl CompletionSource for BufferWords {
+    fn name(&self) -> &str {
+        "buf"
+    }
+
+    fn handles(&self, kind: CompletionKind) -> bool {
+        kind == CompletionKind::Keyword
+    }
+
+    fn complete(&self, request: &CompletionRequest) -> Result<Vec<CompletionItem>> {
+        let (cursor_line, cursor_col) = request.cursor;
+        let typed_start = cursor_col - request.prefix.chars().count();
+        // Lines from the cursor to the end, then wrapping around from the top
+        let count = request.lines.len();
+        let order = (cursor_line..count).chain(0..cursor_line.min(count));
+
+        let mut seen = HashSet::new();
+        let mut items = Vec::new();
+        for index in order {
+            let line = &request.lines[index];
+            let mut word = String::new();
+            let mut word_start = 0;
+            for (col, character) in line.chars().chain(std::iter::once(' ')).enumerate() {
+                if is_keyword(character) {
+                    if word.is_empty() {
+                        word_start = col;
+                    }
+                    word.push(character);
+                    continue;
+                }
+                // The word being typed doesn't complete itself
+                let typing = index == cursor_line && word_start == typed_start;
+                if word.chars().count() > 1 && !typing && seen.insert(word.clone()) {
+                    items.push(CompletionItem {
+                        preview: Some(format!("{}: {}", index + 1, line.trim())),
+                        ..CompletionItem::new(word.clone())
+                    });
+                }
+                word.clear();
+            }
+        }
+        Ok(items)
+    }
+}
+
+/// Paths on disk, relative to the working directory unless what's typed is absolute
+/// A leading `./` is the directory of the file being edited, like in vim's `path`
+pub struct FilePaths;
+
+impl CompletionSource for FilePaths {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn handles(&self, kind: CompletionKind) -> bool {
+        kind == CompletionKind::File
+    }
+
+    fn complete(&self, request: &CompletionRequest) -> Result<Vec<CompletionItem>> {
+        // Everything up to the last separator is the directory, the rest is matched against its entries
+        let (directory, partial) = match request.prefix.rfind('/') {
+            Some(index) => request.prefix.split_at(index + 1),
+            None => ("", request.prefix.as_str()),
+        };
+        let file_directory = request.path.as_deref().and_then(Path::parent);
+        let base = if let Some(rest) = directory.strip_prefix("~/") {
+            std::env::var_os("HOME")
+                .map(PathBuf::from)
+                .unwrap_or_default()
+                .join(rest)
+        } else if let (Some(rest), Some(file_directory)) =
+            (directory.strip_prefix("./"), file_directory)
+        {
+            file_directory.join(rest)
+        } else {
+            request.cwd.join(directory)
+        };
+
+        let mut items = Vec::new();
+        for entry in fs::read_dir(&base)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Hidden files only when asked for
+            if name.starts_with('.') && !partial.starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            // Entries are only looked at, opening a FIFO or a device could block
+            let metadata = fs::metadata(&path).ok();
+            let is_dir = metadata.as_ref().is_some_and(fs::Metadata::is_dir);
+            let is_file = metadata.as_ref().is_some_and(fs::Metadata::is_file);
+            let mut text = format!("{directory}{name}");
+            if is_dir {
+                text.push('/');
+            }
+            items.push(CompletionItem {
+                detail: is_dir.then(|| "dir".to_string()),
+                file: is_file.then_some(path),
+                ..CompletionItem::new(text)
+            });
+        }
+        items.sort_by(|a, b| a.text.cmp(&b.text));
+        Ok(items)
+    }
+}
+
+/// The first lines of a text file
+pub fn preview_file(path: &Path) -> Option<String> {
+    let file = fs::File::open(path).ok()?;
+    let lines: Vec<String> = BufReader::new(file)
+        .lines()
+        .take(PREVIEW_LINES)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    Some(lines.join("\n")).filter(|preview| !preview.is_empty())
+}
+
+/// Other lines of the buffer, without their indent
+pub struct BufferLines;
+
+impl CompletionSource for BufferLines {
+    fn name(&self) -> &str {
+        "line"
+    }
+
+    fn handles(&self, kind: CompletionKind) -> bool {
+        kind == CompletionKind::Line
+    }
+
+    fn complete(&self, request: &CompletionRequest) -> Result<Vec<CompletionItem>> {
+        let mut seen = HashSet::new();
+        let mut items = Vec::new();
+        for (index, line) in request.lines.iter().enumerate() {
+            let text = line.trim();
+            if index == request.cursor.0 || text.is_empty() || !seen.insert(text) {
+                continue;
+            }
+            items.push(CompletionItem {
+                detail: Some(format!("{}", index + 1)),
+                ..CompletionItem::new(text)
+            });
+        }
+        Ok(items)
+    }
+}
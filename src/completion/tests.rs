@@ -0,0 +1,110 @@
+use std::{ffi::CString, fs, os::unix::ffi::OsStrExt, path::PathBuf, sync::Arc};
+
+use super::{
+    CompletionItem, CompletionKind, CompletionRequest, CompletionSource, fuzzy_score, rank,
+    sources::{BufferLines, BufferWords, FilePaths, preview_file},
+};
+
+fn request(kind: CompletionKind, lines: &[&str], cursor: (usize, usize)) -> CompletionRequest {
+    let line = lines.get(cursor.0).copied().unwrap_or_default();
+    let start = kind.start(line, cursor.1);
+    CompletionRequest {
+        kind,
+        prefix: line.chars().skip(start).take(cursor.1 - start).collect(),
+        cursor,
+        lines: Arc::new(lines.iter().map(|line| line.to_string()).collect()),
+        path: None,
+        cwd: PathBuf::new(),
+    }
+}
+
+fn texts(items: &[CompletionItem]) -> Vec<&str> {
+    items.iter().map(|item| item.text.as_str()).collect()
+}
+
+#[test]
+fn fuzzy_scores_favour_starts_runs_and_word_boundaries() {
+    assert_eq!(fuzzy_score("", "anything"), Some(0));
+    // The typed characters have to be there in order
+    assert_eq!(fuzzy_score("abc", "acb"), None);
+    assert!(fuzzy_score("fo", "foo") > fuzzy_score("fo", "afo"));
+    assert!(fuzzy_score("ab", "abx") > fuzzy_score("ab", "axb"));
+    assert!(fuzzy_score("b", "a_b") > fuzzy_score("b", "axb"));
+    assert!(fuzzy_score("b", "aBc") > fuzzy_score("b", "abc"));
+    assert!(fuzzy_score("ab", "ab") > fuzzy_score("ab", "ab_long_tail"));
+    // Case only matters once something uppercase is typed
+    assert!(fuzzy_score("fo", "FOO").is_some());
+    assert_eq!(fuzzy_score("Fo", "foo"), None);
+    assert!(fuzzy_score("Fo", "Foo").is_some());
+}
+
+#[test]
+fn ranking_puts_the_best_first_and_keeps_ties_in_order() {
+    let texts = ["xaxb", "ab", "cab", "zz", "abd"];
+    // `ab` and `abd` score the same, the one given first stays first
+    assert_eq!(fuzzy_score("ab", "ab"), fuzzy_score("ab", "abd"));
+    assert_eq!(rank("ab", texts), [1, 4, 2, 0]);
+    assert_eq!(rank("", texts), [0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn buffer_words_come_nearest_to_the_cursor_first() {
+    let lines = ["alpha gamma", "beta gamma", "delta a"];
+    let items = BufferWords
+        .complete(&request(CompletionKind::Keyword, &lines, (1, 4)))
+        .unwrap();
+    // The word being typed is left out, as are repeats and single letters
+    assert_eq!(texts(&items), ["gamma", "delta", "alpha"]);
+    assert_eq!(items[0].preview.as_deref(), Some("2: beta gamma"));
+}
+
+#[test]
+fn file_paths_list_a_directory_without_opening_its_entries() {
+    let dir = std::env::temp_dir().join(format!("reovim-completion-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("notes.txt"), "first\nsecond\n").unwrap();
+    fs::write(dir.join(".hidden"), "").unwrap();
+    // Opening a FIFO blocks until something writes to it
+    let fifo = CString::new(dir.join("pipe").as_os_str().as_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+
+    let mut in_dir = request(CompletionKind::File, &[""], (0, 0));
+    in_dir.cwd = dir.clone();
+    let items = FilePaths.complete(&in_dir).unwrap();
+    assert_eq!(texts(&items), ["notes.txt", "pipe", "src/"]);
+    assert_eq!(items[2].detail.as_deref(), Some("dir"));
+    // Only the regular file is previewed, and not until it is selected
+    assert!(items.iter().all(|item| item.preview.is_none()));
+    let files: Vec<_> = items.iter().map(|item| item.file.clone()).collect();
+    assert_eq!(files, [Some(dir.join("notes.txt")), None, None]);
+    assert_eq!(
+        preview_file(&dir.join("notes.txt")).as_deref(),
+        Some("first\nsecond")
+    );
+
+    // Hidden files once a `.` is typed
+    let mut hidden = request(CompletionKind::File, &["."], (0, 1));
+    hidden.cwd = dir.clone();
+    assert!(texts(&FilePaths.complete(&hidden).unwrap()).contains(&".hidden"));
+
+    // `./` is the directory of the file being edited
+    let mut beside = request(CompletionKind::File, &["./"], (0, 2));
+    beside.path = Some(dir.join("notes.txt"));
+    assert_eq!(
+        texts(&FilePaths.complete(&beside).unwrap()),
+        ["./notes.txt", "./pipe", "./src/"]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn buffer_lines_are_the_other_distinct_lines() {
+    let lines = ["  fn a() {", "", "  fn a() {", "x", "    y  "];
+    let items = BufferLines
+        .complete(&request(CompletionKind::Line, &lines, (3, 1)))
+        .unwrap();
+    assert_eq!(texts(&items), ["fn a() {", "y"]);
+    let details: Vec<_> = items.iter().map(|item| item.detail.as_deref()).collect();
+    assert_eq!(details, [Some("1"), Some("5")]);
+}
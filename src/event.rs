@@ -1,8 +1,43 @@
 use crossterm::event::{KeyEvent, MouseEvent};
 
+use crate::{
+    buffer::BufferId, completion::CompletionResponse, lsp::LspEvent, tui::window::WindowId,
+    vcs::VcsInfo,
+};
+
 #[derive(Debug, Clone)]
 pub enum ReovimEvent {
     Key(KeyEvent),
     Mouse(MouseEvent),
     Resize(u16, u16),
+    /// The lines of a buffer changed, every view of it should catch up
+    BufferChanged(BufferId),
+    /// A `:` command to run, for keys that are shorthand for one like `gt`
+    Command(String),
+    /// Candidates a completion source found, delivered from a worker thread
+    Completion(CompletionResponse),
+    /// A message from a language server, read on the server's own thread
+    Lsp(LspEvent),
+    /// What git has for a buffer's file, read on a worker thread
+    Vcs(BufferId, Option<VcsInfo>),
+    /// A hunk of a buffer was staged on a worker thread and what git has for the file read again,
+    /// or why it couldn't be
+    Staged(BufferId, Result<Option<VcsInfo>, String>),
+    /// A window in diff mode moved its cursor to a row and scrolled, the window it is compared with follows
+    DiffScroll(WindowId, usize, usize),
+    /// No input came for a while, time for work that would get in the way of typing
+    Idle,
+    /// A buffer's swap file was written on a worker, or why it couldn't be
+    Swap(BufferId, Result<(), String>),
+}
+
+/// Requests a component makes of the application hosting the component tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostRequest {
+    /// Stop the event loop and exit with the given status code
+    Quit(i32),
+    /// Write the bytes to stdout once the terminal has been restored
+    Output(Vec<u8>),
+    /// Give the terminal back to the shell and stop until the shell continues the editor
+    Suspend,
 }
@@ -0,0 +1,283 @@
+use std::{
+    collections::HashMap,
+    io::BufReader,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use crossbeam::channel::{Sender, unbounded};
+use serde_json::{Value, json};
+use tracing::{debug, warn};
+
+use crate::{
+    event::ReovimEvent,
+    lsp::{
+        LspEvent, Request, ServerConfig, ServerId,
+        protocol::{self, path_to_uri},
+    },
+};
+
+/// Times to check for a server to exit after being asked to, 10ms apart, before killing it
+const EXIT_POLLS: usize = 50;
+
+/// A running language server, talked to over its stdin and stdout
+/// Messages are written and read on threads of their own so a busy server never stalls the editor
+pub struct LanguageServer {
+    pub id: ServerId,
+    pub filetype: &'static str,
+    /// The directory the server was started for
+    pub root: PathBuf,
+    pub command: String,
+    /// Taken when the server is dropped, to wait for it to exit
+    child: Option<Child>,
+    outgoing: Sender<Value>,
+    next_request: u64,
+    /// Requests waiting for their response, with what to do with it
+    pending: HashMap<u64, Request>,
+    /// The server answered `initialize`, until then messages wait in `queued`
+    initialized: bool,
+    queued: Vec<Value>,
+    /// What the server said it supports
+    pub capabilities: Value,
+}
+
+impl LanguageServer {
+    /// Start the server and send `initialize`, its messages come back to the event loop as `ReovimEvent::Lsp`
+    pub fn start(
+        id: ServerId,
+        filetype: &'static str,
+        config: &ServerConfig,
+        root: &Path,
+        events: Sender<ReovimEvent>,
+    ) -> Result<Self> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("could not start {}", config.command))?;
+        let mut stdin = child.stdin.take().context("server has no stdin")?;
+        let stdout = child.stdout.take().context("server has no stdout")?;
+
+        let (outgoing, queue) = unbounded::<Value>();
+        let name = config.command.clone();
+        thread::Builder::new()
+            .name(format!("reovim-lsp-{id}-write"))
+            .spawn(move || {
+                for message in queue {
+                    if let Err(err) = protocol::write_message(&mut stdin, &message) {
+                        warn!("could not write to {name}: {err}");
+                        break;
+                    }
+                }
+            })?;
+        let name = config.command.clone();
+        thread::Builder::new()
+            .name(format!("reovim-lsp-{id}-read"))
+            .spawn(move || {
+                let mut reader = BufReader::new(stdout);
+                loop {
+                    let message = match protocol::read_message(&mut reader) {
+                        Ok(message) => message,
+                        Err(err) => {
+                            warn!("could not read from {name}: {err}");
+                            None
+                        }
+                    };
+                    let done = message.is_none();
+                    if events
+                        .send(ReovimEvent::Lsp(LspEvent {
+                            server: id,
+                            message,
+                        }))
+                        .is_err()
+                        || done
+                    {
+                        break;
+                    }
+                }
+            })?;
+
+        let mut server = Self {
+            id,
+            filetype,
+            root: root.to_path_buf(),
+            command: config.command.clone(),
+            child: Some(child),
+            outgoing,
+            next_request: 0,
+            pending: HashMap::new(),
+            initialized: false,
+            queued: Vec::new(),
+            capabilities: Value::Null,
+        };
+        let params = json!({
+            "processId": std::process::id(),
+            "clientInfo": { "name": "reovim", "version": env!("CARGO_PKG_VERSION") },
+            "rootUri": path_to_uri(root),
+            "workspaceFolders": [{
+                "uri": path_to_uri(root),
+                "name": root.file_name().map(|name| name.to_string_lossy()).unwrap_or_default(),
+            }],
+            "capabilities": client_capabilities(),
+        });
+        let id = server.next_id();
+        server.pending.insert(id, Request::Initialize);
+        server.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "initialize",
+            "params": params,
+        }));
+        Ok(server)
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_request += 1;
+        self.next_request
+    }
+
+    fn send(&mut self, message: Value) {
+        if self.outgoing.send(message).is_err() {
+            warn!("{} is no longer reading messages", self.command);
+        }
+    }
+
+    /// Send a message once the server is initialized, right away if it already is
+    fn send_when_ready(&mut self, message: Value) {
+        if self.initialized {
+            self.send(message);
+        } else {
+            self.queued.push(message);
+        }
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) {
+        debug!("lsp {} <- {method}", self.id);
+        self.send_when_ready(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        }));
+    }
+
+    /// Send a request, what it was for comes back with its response
+    pub fn request(&mut self, method: &str, params: Value, request: Request) {
+        debug!("lsp {} <- {method}", self.id);
+        let id = self.next_id();
+        self.pending.insert(id, request);
+        self.send_when_ready(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }));
+    }
+
+    /// Answer a request the server made
+    pub fn respond(&mut self, id: Value, result: Value) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result,
+        }));
+    }
+
+    /// Take the request a response answers, finishing initialization when it answers `initialize`
+    pub fn response(&mut self, id: &Value, result: &Value) -> Option<Request> {
+        let request = self.pending.remove(&id.as_u64()?)?;
+        if request == Request::Initialize {
+            self.capabilities = result["capabilities"].clone();
+            self.initialized = true;
+            self.send(json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }));
+            for message in std::mem::take(&mut self.queued) {
+                self.send(message);
+            }
+            return None;
+        }
+        Some(request)
+    }
+
+    /// Whether the server said it handles a request, like `hoverProvider`
+    pub fn supports(&self, capability: &str) -> bool {
+        // Until `initialize` is answered nothing is known, the request waits with the rest
+        !self.initialized
+            || match &self.capabilities[capability] {
+                Value::Bool(supported) => *supported,
+                Value::Null => false,
+                _ => true,
+            }
+    }
+
+    /// How the server wants document changes, 2 for incremental, 1 for the whole text and 0 for none
+    pub fn sync_kind(&self) -> u64 {
+        match &self.capabilities["textDocumentSync"] {
+            Value::Number(kind) => kind.as_u64().unwrap_or(1),
+            Value::Object(options) => options.get("change").and_then(Value::as_u64).unwrap_or(0),
+            _ => 1,
+        }
+    }
+}
+
+impl Drop for LanguageServer {
+    fn drop(&mut self) {
+        // Ask the server to exit, then make sure it doesn't outlive the editor if it won't
+        let id = self.next_id();
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": "shutdown" }));
+        self.send(json!({ "jsonrpc": "2.0", "method": "exit" }));
+        let Some(mut child) = self.child.take() else {
+            return;
+        };
+        let reaped = thread::Builder::new()
+            .name("reovim-lsp-reap".to_string())
+            .spawn(move || {
+                for _ in 0..EXIT_POLLS {
+                    if let Ok(Some(_)) = child.try_wait() {
+                        return;
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                let _ = child.kill();
+                let _ = child.wait();
+            });
+        if let Err(err) = reaped {
+            warn!("could not wait for the server to exit: {err}");
+        }
+    }
+}
+
+/// What the editor can do with what servers send back
+fn client_capabilities() -> Value {
+    json!({
+        "general": { "positionEncodings": ["utf-16"] },
+        "workspace": {
+            "applyEdit": true,
+            "workspaceEdit": { "documentChanges": true },
+            "configuration": false,
+        },
+        "textDocument": {
+            "synchronization": { "didSave": true, "dynamicRegistration": false },
+            "publishDiagnostics": { "relatedInformation": false },
+            "hover": { "contentFormat": ["plaintext", "markdown"] },
+            "definition": { "linkSupport": true },
+            "references": {},
+            "rename": { "prepareSupport": false },
+            "codeAction": {
+                "codeActionLiteralSupport": {
+                    "codeActionKind": {
+                        "valueSet": [
+                            "", "quickfix", "refactor", "refactor.extract", "refactor.inline",
+                            "refactor.rewrite", "source", "source.organizeImports",
+                        ],
+                    },
+                },
+            },
+            "formatting": {},
+        },
+    })
+}
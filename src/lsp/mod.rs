@@ -0,0 +1,696 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use crossbeam::channel::Sender;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::{info, warn};
+
+use crate::{
+    buffer::{Buffer, BufferId, Changes, TextRange, undo::Edit},
+    event::ReovimEvent,
+};
+
+mod client;
+pub mod protocol;
+#[cfg(test)]
+mod tests;
+
+use client::LanguageServer;
+use protocol::{
+    CodeAction, Diagnostic, Location, Position, Range, TextEdit, WorkspaceEdit, path_to_uri,
+    utf16_col,
+};
+
+pub type ServerId = usize;
+
+/// A message from a language server, or `None` once it has exited
+#[derive(Debug, Clone)]
+pub struct LspEvent {
+    pub server: ServerId,
+    pub message: Option<Value>,
+}
+
+/// What a request sent to a server was for, to know what to do with the response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    Initialize,
+    Hover,
+    Definition,
+    References,
+    Rename,
+    CodeActions,
+    Formatting { buffer: BufferId },
+    ExecuteCommand,
+}
+
+/// How to start the server for a filetype
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+    pub command: String,
+    pub args: Vec<String>,
+    /// Files or directories marking the project root, looked for in each parent of a file
+    pub root_markers: Vec<String>,
+}
+
+impl ServerConfig {
+    fn new(command: &str, args: &[&str], root_markers: &[&str]) -> Self {
+        Self {
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            root_markers: root_markers
+                .iter()
+                .map(|marker| marker.to_string())
+                .collect(),
+        }
+    }
+
+    /// The server usually used for a filetype
+    fn usual(filetype: &str) -> Option<Self> {
+        let server = match filetype {
+            "rust" => Self::new("rust-analyzer", &[], &["Cargo.toml"]),
+            "c" => Self::new("clangd", &[], &["compile_commands.json"]),
+            "go" => Self::new("gopls", &[], &["go.mod"]),
+            "python" => Self::new("pylsp", &[], &["pyproject.toml"]),
+            "typescript" | "javascript" => Self::new(
+                "typescript-language-server",
+                &["--stdio"],
+                &["package.json"],
+            ),
+            "sh" => Self::new("bash-language-server", &["start"], &[]),
+            _ => return None,
+        };
+        Some(server)
+    }
+}
+
+/// A filetype's table in `lsp.toml`, what it leaves out is taken from the filetype's usual server
+#[derive(Debug, Deserialize)]
+struct ServerTable {
+    command: Option<String>,
+    args: Option<Vec<String>>,
+    root_markers: Option<Vec<String>>,
+}
+
+/// The language server for each filetype, none are started unless `<config>/lsp.toml` asks for them
+/// A table per filetype, like an empty `[rust]`, starts its usual server, `command`, `args` and
+/// `root_markers` in it change how
+#[derive(Debug, Clone, Default)]
+pub struct LspConfig {
+    servers: HashMap<String, ServerConfig>,
+}
+
+impl LspConfig {
+    /// The servers the file at `path` asks for, if it exists
+    pub fn load(path: Option<&Path>) -> Self {
+        let mut config = Self::default();
+        let Some(path) = path else {
+            return config;
+        };
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return config,
+            Err(err) => {
+                warn!("could not read {}: {err}", path.display());
+                return config;
+            }
+        };
+        let tables = match toml::from_str::<HashMap<String, ServerTable>>(&contents) {
+            Ok(tables) => tables,
+            Err(err) => {
+                warn!("invalid {}: {err}", path.display());
+                return config;
+            }
+        };
+        for (filetype, table) in tables {
+            // A command of its own doesn't take the usual server's arguments
+            let server = match (table.command, ServerConfig::usual(&filetype)) {
+                (Some(command), usual) => ServerConfig {
+                    command,
+                    args: table.args.unwrap_or_default(),
+                    root_markers: table
+                        .root_markers
+                        .or(usual.map(|usual| usual.root_markers))
+                        .unwrap_or_default(),
+                },
+                (None, Some(usual)) => ServerConfig {
+                    command: usual.command,
+                    args: table.args.unwrap_or(usual.args),
+                    root_markers: table.root_markers.unwrap_or(usual.root_markers),
+                },
+                (None, None) => {
+                    warn!(
+                        "no usual language server for {filetype}, `command` is needed to start one"
+                    );
+                    continue;
+                }
+            };
+            config.servers.insert(filetype, server);
+        }
+        info!(
+            "loaded {} language servers from {}",
+            config.servers.len(),
+            path.display()
+        );
+        config
+    }
+
+    pub fn get(&self, filetype: &str) -> Option<&ServerConfig> {
+        self.servers.get(filetype)
+    }
+}
+
+/// What the editor should do about a message from a server
+#[derive(Debug, Clone, PartialEq)]
+pub enum LspReply {
+    /// New diagnostics for the file, replacing the ones before
+    Diagnostics {
+        path: PathBuf,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Hover(String),
+    /// Where the symbol under the cursor is defined
+    Definition(Vec<Location>),
+    /// Everywhere the symbol under the cursor is used
+    References(Vec<Location>),
+    /// Text edits across files, from a rename, a code action or the server asking
+    Edit(WorkspaceEdit),
+    /// The edits formatting a buffer makes
+    Format {
+        buffer: BufferId,
+        edits: Vec<TextEdit>,
+    },
+    /// Titles of the code actions offered, applied by number with `:codeaction N`
+    CodeActions(Vec<String>),
+    Message {
+        text: String,
+        error: bool,
+    },
+}
+
+/// A buffer a server has been told about
+struct Document {
+    server: ServerId,
+    uri: String,
+    version: i64,
+}
+
+/// The language servers running for the open buffers, started the first time a buffer of their filetype is shown
+/// Buffers are kept in step with their server by sending each edit as it is made
+pub struct Lsp {
+    config: LspConfig,
+    /// Where the servers' messages go, the event loop hands them back to `handle`
+    events: Sender<ReovimEvent>,
+    servers: Vec<LanguageServer>,
+    next_server: ServerId,
+    /// Filetypes and roots whose server could not be started, not tried again
+    failed: Vec<(&'static str, PathBuf)>,
+    documents: HashMap<BufferId, Document>,
+    /// The code actions last offered and the server offering them
+    actions: Option<(ServerId, Vec<CodeAction>)>,
+}
+
+impl Lsp {
+    pub fn new(config: LspConfig, events: Sender<ReovimEvent>) -> Self {
+        Self {
+            config,
+            events,
+            servers: Vec::new(),
+            next_server: 1,
+            failed: Vec::new(),
+            documents: HashMap::new(),
+            actions: None,
+        }
+    }
+
+    fn server(&mut self, id: ServerId) -> Option<&mut LanguageServer> {
+        self.servers.iter_mut().find(|server| server.id == id)
+    }
+
+    /// The server for a file, starting it if it isn't running yet
+    fn server_for(&mut self, filetype: &'static str, path: &Path) -> Option<ServerId> {
+        let config = self.config.get(filetype)?.clone();
+        let root = find_root(path, &config.root_markers);
+        let running = self
+            .servers
+            .iter()
+            .find(|server| server.filetype == filetype && server.root == root);
+        if let Some(server) = running {
+            return Some(server.id);
+        }
+        if self.failed.contains(&(filetype, root.clone())) {
+            return None;
+        }
+        let id = self.next_server;
+        match LanguageServer::start(id, filetype, &config, &root, self.events.clone()) {
+            Ok(server) => {
+                info!(
+                    "started {} for {} in {}",
+                    config.command,
+                    filetype,
+                    root.display()
+                );
+                self.next_server += 1;
+                self.servers.push(server);
+                Some(id)
+            }
+            Err(err) => {
+                warn!("{err:#}");
+                self.failed.push((filetype, root));
+                None
+            }
+        }
+    }
+
+    /// Tell the buffer's server about edits made since the last sync, opening the document first if needed
+    pub fn sync(&mut self, buffer: &mut Buffer) {
+        let Some(document) = self.documents.get_mut(&buffer.id()) else {
+            self.open(buffer);
+            return;
+        };
+        let Some(changes) = buffer.take_changes() else {
+            return;
+        };
+        document.version += 1;
+        let (server_id, uri, version) = (document.server, document.uri.clone(), document.version);
+        let Some(server) = self.server(server_id) else {
+            return;
+        };
+        let content_changes = match (server.sync_kind(), changes) {
+            (0, _) => return,
+            (2, Changes::Edits(edits)) => edits.iter().map(content_change).collect(),
+            _ => vec![json!({ "text": buffer.text() })],
+        };
+        server.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": version },
+                "contentChanges": content_changes,
+            }),
+        );
+    }
+
+    fn open(&mut self, buffer: &mut Buffer) {
+        // A large file would be sent whole on every change
+        if buffer.is_large() {
+            return;
+        }
+        let (Some(filetype), Some(path)) = (buffer.filetype(), buffer.file_path()) else {
+            return;
+        };
+        let path = path.to_path_buf();
+        let Some(server_id) = self.server_for(filetype, &path) else {
+            return;
+        };
+        let uri = path_to_uri(&path);
+        buffer.track_changes();
+        if let Some(server) = self.server(server_id) {
+            server.notify(
+                "textDocument/didOpen",
+                json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": filetype,
+                        "version": 0,
+                        "text": buffer.text(),
+                    },
+                }),
+            );
+        }
+        self.documents.insert(
+            buffer.id(),
+            Document {
+                server: server_id,
+                uri,
+                version: 0,
+            },
+        );
+    }
+
+    /// Tell the server the buffer was written
+    pub fn saved(&mut self, buffer: &mut Buffer) {
+        self.sync(buffer);
+        let Some(document) = self.documents.get(&buffer.id()) else {
+            return;
+        };
+        let uri = document.uri.clone();
+        if let Some(server) = self.server(document.server) {
+            server.notify(
+                "textDocument/didSave",
+                json!({ "textDocument": { "uri": uri } }),
+            );
+        }
+    }
+
+    /// Tell the server the buffer is gone
+    pub fn close(&mut self, buffer: BufferId) {
+        let Some(document) = self.documents.remove(&buffer) else {
+            return;
+        };
+        if let Some(server) = self.server(document.server) {
+            server.notify(
+                "textDocument/didClose",
+                json!({ "textDocument": { "uri": document.uri } }),
+            );
+        }
+    }
+
+    /// Send a request about a buffer, `params` get the document added to them
+    fn request(
+        &mut self,
+        buffer: &mut Buffer,
+        capability: &str,
+        method: &str,
+        mut params: Value,
+        request: Request,
+    ) -> Result<()> {
+        self.sync(buffer);
+        let Some(document) = self.documents.get(&buffer.id()) else {
+            bail!("No language server for this buffer");
+        };
+        params["textDocument"] = json!({ "uri": document.uri });
+        let Some(server) = self.server(document.server) else {
+            bail!("No language server for this buffer");
+        };
+        if !server.supports(capability) {
+            bail!("{} does not support {method}", server.command);
+        }
+        server.request(method, params, request);
+        Ok(())
+    }
+
+    pub fn hover(&mut self, buffer: &mut Buffer, cursor: (usize, usize)) -> Result<()> {
+        let position = Position::of(buffer.lines().as_slice(), cursor).to_json();
+        self.request(
+            buffer,
+            "hoverProvider",
+            "textDocument/hover",
+            json!({ "position": position }),
+            Request::Hover,
+        )
+    }
+
+    pub fn definition(&mut self, buffer: &mut Buffer, cursor: (usize, usize)) -> Result<()> {
+        let position = Position::of(buffer.lines().as_slice(), cursor).to_json();
+        self.request(
+            buffer,
+            "definitionProvider",
+            "textDocument/definition",
+            json!({ "position": position }),
+            Request::Definition,
+        )
+    }
+
+    pub fn references(&mut self, buffer: &mut Buffer, cursor: (usize, usize)) -> Result<()> {
+        let position = Position::of(buffer.lines().as_slice(), cursor).to_json();
+        self.request(
+            buffer,
+            "referencesProvider",
+            "textDocument/references",
+            json!({ "position": position, "context": { "includeDeclaration": true } }),
+            Request::References,
+        )
+    }
+
+    pub fn rename(
+        &mut self,
+        buffer: &mut Buffer,
+        cursor: (usize, usize),
+        new_name: &str,
+    ) -> Result<()> {
+        let position = Position::of(buffer.lines().as_slice(), cursor).to_json();
+        self.request(
+            buffer,
+            "renameProvider",
+            "textDocument/rename",
+            json!({ "position": position, "newName": new_name }),
+            Request::Rename,
+        )
+    }
+
+    /// Ask for the code actions on the lines of `range`, with the diagnostics there
+    pub fn code_actions(&mut self, buffer: &mut Buffer, range: TextRange) -> Result<()> {
+        let lines = buffer.lines().as_slice();
+        let range = Range {
+            start: Position::of(lines, range.start),
+            end: Position::of(lines, range.end),
+        };
+        let diagnostics: Vec<Value> = buffer
+            .diagnostics()
+            .iter()
+            .filter(|diagnostic| {
+                diagnostic.range.start.line <= range.end.line
+                    && diagnostic.range.end.line >= range.start.line
+            })
+            .map(|diagnostic| diagnostic.raw.clone())
+            .collect();
+        self.request(
+            buffer,
+            "codeActionProvider",
+            "textDocument/codeAction",
+            json!({ "range": range.to_json(), "context": { "diagnostics": diagnostics } }),
+            Request::CodeActions,
+        )
+    }
+
+    pub fn format(&mut self, buffer: &mut Buffer) -> Result<()> {
+        let id = buffer.id();
+        let options = json!({
+            "tabSize": buffer.options.shift_width(),
+            "insertSpaces": buffer.options.expandtab,
+        });
+        self.request(
+            buffer,
+            "documentFormattingProvider",
+            "textDocument/formatting",
+            json!({ "options": options }),
+            Request::Formatting { buffer: id },
+        )
+    }
+
+    /// The edit of the code action numbered `number` in the last list, running its command if it has one
+    pub fn code_action(&mut self, number: usize) -> Result<Option<WorkspaceEdit>> {
+        let Some((server_id, actions)) = &self.actions else {
+            bail!("No code actions, use :codeaction to list them");
+        };
+        let Some(action) = number.checked_sub(1).and_then(|index| actions.get(index)) else {
+            bail!("No code action {number}");
+        };
+        let action = action.clone();
+        let server_id = *server_id;
+        let edit = action.edit.map(protocol::workspace_edit).transpose()?;
+        if let Some(command) = action.command
+            && let Some(server) = self.server(server_id)
+        {
+            server.request(
+                "workspace/executeCommand",
+                json!({ "command": command["command"], "arguments": command["arguments"] }),
+                Request::ExecuteCommand,
+            );
+        }
+        Ok(edit)
+    }
+
+    /// The running servers and the buffers they have open, for `:lsp`
+    pub fn describe(&self) -> String {
+        if self.servers.is_empty() {
+            return "No language servers running".to_string();
+        }
+        self.servers
+            .iter()
+            .map(|server| {
+                let documents = self
+                    .documents
+                    .values()
+                    .filter(|document| document.server == server.id)
+                    .count();
+                format!(
+                    "{} ({}) in {}, {documents} buffers",
+                    server.command,
+                    server.filetype,
+                    server.root.display()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Handle a message a server sent, returning what the editor should do about it
+    pub fn handle(&mut self, event: LspEvent) -> Option<LspReply> {
+        let Some(message) = event.message else {
+            return self.exited(event.server);
+        };
+        let server = self.server(event.server)?;
+        let method = message["method"].as_str();
+        match (method, message.get("id")) {
+            // A request from the server, every one needs an answer
+            (Some(method), Some(id)) => {
+                let params = &message["params"];
+                let (result, reply) = match method {
+                    "workspace/applyEdit" => match protocol::workspace_edit(params["edit"].clone())
+                    {
+                        Ok(edit) => (json!({ "applied": true }), Some(LspReply::Edit(edit))),
+                        Err(err) => (
+                            json!({ "applied": false, "failureReason": err.to_string() }),
+                            None,
+                        ),
+                    },
+                    "workspace/configuration" => {
+                        let count = params["items"].as_array().map_or(0, Vec::len);
+                        (Value::Array(vec![Value::Null; count]), None)
+                    }
+                    _ => (Value::Null, None),
+                };
+                server.respond(id.clone(), result);
+                reply
+            }
+            (Some("textDocument/publishDiagnostics"), None) => {
+                let path = protocol::uri_to_path(message["params"]["uri"].as_str()?)?;
+                Some(LspReply::Diagnostics {
+                    path,
+                    diagnostics: protocol::diagnostics(&message["params"]),
+                })
+            }
+            (Some("window/showMessage"), None) => Some(LspReply::Message {
+                text: message["params"]["message"].as_str()?.to_string(),
+                error: message["params"]["type"].as_u64() == Some(1),
+            }),
+            (Some(_), None) => None,
+            (None, Some(id)) => {
+                let result = message.get("result").cloned().unwrap_or(Value::Null);
+                let request = server.response(id, &result)?;
+                if let Some(error) = message.get("error") {
+                    let text = error["message"].as_str().unwrap_or("request failed");
+                    return Some(LspReply::Message {
+                        text: format!("{}: {text}", server.command),
+                        error: true,
+                    });
+                }
+                self.response(event.server, request, result)
+            }
+            (None, None) => None,
+        }
+    }
+
+    fn response(&mut self, server: ServerId, request: Request, result: Value) -> Option<LspReply> {
+        let reply = match request {
+            Request::Initialize | Request::ExecuteCommand => return None,
+            Request::Hover => LspReply::Hover(protocol::hover_text(&result["contents"])),
+            Request::Definition => LspReply::Definition(protocol::locations(result)),
+            Request::References => LspReply::References(protocol::locations(result)),
+            Request::Rename if result.is_null() => LspReply::Message {
+                text: "Nothing to rename".to_string(),
+                error: true,
+            },
+            Request::Rename => match protocol::workspace_edit(result) {
+                Ok(edit) => LspReply::Edit(edit),
+                Err(err) => LspReply::Message {
+                    text: err.to_string(),
+                    error: true,
+                },
+            },
+            Request::CodeActions => {
+                let actions = protocol::code_actions(result);
+                let titles = actions.iter().map(|action| action.title.clone()).collect();
+                self.actions = Some((server, actions));
+                LspReply::CodeActions(titles)
+            }
+            Request::Formatting { buffer } => LspReply::Format {
+                buffer,
+                edits: serde_json::from_value(result).unwrap_or_default(),
+            },
+        };
+        Some(reply)
+    }
+
+    /// Forget a server that exited, its buffers are opened with a new one the next time they sync
+    fn exited(&mut self, id: ServerId) -> Option<LspReply> {
+        let index = self.servers.iter().position(|server| server.id == id)?;
+        let server = self.servers.remove(index);
+        self.documents.retain(|_, document| document.server != id);
+        // Starting it again would most likely fail the same way
+        self.failed.push((server.filetype, server.root.clone()));
+        Some(LspReply::Message {
+            text: format!("{} exited", server.command),
+            error: true,
+        })
+    }
+}
+
+/// Make a server's edits to a buffer as one undo step
+/// Their ranges are all from before any of them, so they are made from the last one back
+pub fn apply_edits(buffer: &mut Buffer, mut edits: Vec<TextEdit>) -> Result<()> {
+    // Edits at the same place go in the order they were given
+    edits.sort_by_key(|edit| (edit.range.start.line, edit.range.start.character));
+    let cursor = buffer.cursor;
+    buffer.begin_change(cursor);
+    let result = edits.iter().rev().try_for_each(|edit| {
+        let range = edit.range.to_text_range(buffer.lines().as_slice());
+        buffer.replace_range(range, &edit.new_text, cursor)
+    });
+    buffer.end_change();
+    result
+}
+
+/// The change an edit makes to the text as the server sees it, every line followed by a line break
+fn content_change(edit: &Edit) -> Value {
+    let (range, text) = match edit {
+        Edit::Insert { at, lines } => {
+            let start = Position {
+                line: *at,
+                character: 0,
+            };
+            let text: String = lines.iter().map(|line| format!("{line}\n")).collect();
+            (Range { start, end: start }, text)
+        }
+        Edit::Remove { at, lines } => {
+            let range = Range {
+                start: Position {
+                    line: *at,
+                    character: 0,
+                },
+                end: Position {
+                    line: at + lines.len(),
+                    character: 0,
+                },
+            };
+            (range, String::new())
+        }
+        Edit::Replace { at, before, after } => {
+            let range = Range {
+                start: Position {
+                    line: *at,
+                    character: 0,
+                },
+                end: Position {
+                    line: *at,
+                    character: utf16_col(before, usize::MAX),
+                },
+            };
+            (range, after.clone())
+        }
+    };
+    json!({ "range": range.to_json(), "text": text })
+}
+
+/// The closest directory above `path` holding one of the markers, or a version control directory
+/// Without either the file's own directory is the root
+fn find_root(path: &Path, markers: &[String]) -> PathBuf {
+    let start = path.parent().unwrap_or(path);
+    let has_any =
+        |dir: &Path, markers: &[&str]| markers.iter().any(|marker| dir.join(marker).exists());
+    let markers: Vec<&str> = markers.iter().map(String::as_str).collect();
+    let found = start
+        .ancestors()
+        .find(|dir| has_any(dir, &markers))
+        .or_else(|| {
+            start
+                .ancestors()
+                .find(|dir| has_any(dir, &[".git", ".hg", ".jj"]))
+        });
+    found.unwrap_or(start).to_path_buf()
+}
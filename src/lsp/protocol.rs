@@ -0,0 +1,348 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::buffer::TextRange;
+
+/// Send one message with its `Content-Length` header
+pub fn write_message(writer: &mut impl Write, message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read the next message, `None` once the stream has ended
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+    let Some(length) = length else {
+        bail!("message without a Content-Length header");
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Bytes kept as they are in a `file://` URI, everything else is percent encoded
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte)
+}
+
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for &byte in path.to_string_lossy().as_bytes() {
+        if is_unreserved(byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut index = 0;
+    while index < encoded.len() {
+        let escaped = (encoded[index] == b'%')
+            .then(|| encoded.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                bytes.push(byte);
+                index += 3;
+            }
+            None => {
+                bytes.push(encoded[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// UTF-16 code units before the char at `col`, the unit LSP positions count in
+pub fn utf16_col(line: &str, col: usize) -> usize {
+    line.chars().take(col).map(char::len_utf16).sum()
+}
+
+/// The char column a UTF-16 offset falls on, clamped to the end of the line
+pub fn char_col(line: &str, utf16: usize) -> usize {
+    let mut units = 0;
+    for (col, character) in line.chars().enumerate() {
+        if units >= utf16 {
+            return col;
+        }
+        units += character.len_utf16();
+    }
+    line.chars().count()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+impl Position {
+    /// The position of the char at (line, col) in `lines`
+    pub fn of(lines: &[String], (line, col): (usize, usize)) -> Position {
+        let text = lines.get(line).map(String::as_str).unwrap_or_default();
+        Position {
+            line,
+            character: utf16_col(text, col),
+        }
+    }
+
+    /// (line, col) in chars, lines past the end are kept so ranges can reach the end of the text
+    pub fn to_cursor(self, lines: &[String]) -> (usize, usize) {
+        match lines.get(self.line) {
+            Some(text) => (self.line, char_col(text, self.character)),
+            None => (self.line, 0),
+        }
+    }
+
+    pub fn to_json(self) -> Value {
+        json!({ "line": self.line, "character": self.character })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    pub fn to_text_range(self, lines: &[String]) -> TextRange {
+        TextRange {
+            start: self.start.to_cursor(lines),
+            end: self.end.to_cursor(lines),
+        }
+    }
+
+    pub fn to_json(self) -> Value {
+        json!({ "start": self.start.to_json(), "end": self.end.to_json() })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Location {
+    pub uri: String,
+    pub range: Range,
+}
+
+/// Also accepts the `LocationLink` shape some servers answer definitions with
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AnyLocation {
+    Location(Location),
+    #[serde(rename_all = "camelCase")]
+    Link {
+        target_uri: String,
+        target_selection_range: Range,
+    },
+}
+
+/// The locations in a definition or references result, which may be one, a list or null
+pub fn locations(result: Value) -> Vec<Location> {
+    let list = match result {
+        Value::Array(list) => list,
+        Value::Null => Vec::new(),
+        single => vec![single],
+    };
+    list.into_iter()
+        .filter_map(|location| serde_json::from_value(location).ok())
+        .map(|location| match location {
+            AnyLocation::Location(location) => location,
+            AnyLocation::Link {
+                target_uri,
+                target_selection_range,
+            } => Location {
+                uri: target_uri,
+                range: target_selection_range,
+            },
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextEdit {
+    pub range: Range,
+    pub new_text: String,
+}
+
+/// Edits to make across files, by path
+pub type WorkspaceEdit = HashMap<PathBuf, Vec<TextEdit>>;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawWorkspaceEdit {
+    changes: Option<HashMap<String, Vec<TextEdit>>>,
+    document_changes: Option<Vec<Value>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentEdit {
+    text_document: TextDocumentIdentifier,
+    edits: Vec<TextEdit>,
+}
+
+#[derive(Deserialize)]
+struct TextDocumentIdentifier {
+    uri: String,
+}
+
+/// The text edits of a `WorkspaceEdit`, file creates, renames and deletes are left out
+pub fn workspace_edit(edit: Value) -> Result<WorkspaceEdit> {
+    let raw: RawWorkspaceEdit = serde_json::from_value(edit).context("invalid workspace edit")?;
+    let mut edits = WorkspaceEdit::new();
+    let mut add = |uri: &str, text_edits: Vec<TextEdit>| {
+        if let Some(path) = uri_to_path(uri) {
+            edits.entry(path).or_default().extend(text_edits);
+        }
+    };
+    for change in raw.document_changes.unwrap_or_default() {
+        // Resource operations have a `kind`, only text edits are applied
+        if let Ok(document) = serde_json::from_value::<TextDocumentEdit>(change) {
+            add(&document.text_document.uri, document.edits);
+        }
+    }
+    for (uri, text_edits) in raw.changes.unwrap_or_default() {
+        add(&uri, text_edits);
+    }
+    Ok(edits)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl Severity {
+    fn from_number(number: Option<u64>) -> Severity {
+        match number {
+            Some(2) => Severity::Warning,
+            Some(3) => Severity::Information,
+            Some(4) => Severity::Hint,
+            _ => Severity::Error,
+        }
+    }
+
+    /// The letter shown in the gutter
+    pub fn sign(self) -> char {
+        match self {
+            Severity::Error => 'E',
+            Severity::Warning => 'W',
+            Severity::Information => 'I',
+            Severity::Hint => 'H',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Severity,
+    pub message: String,
+    /// The diagnostic as the server sent it, given back when asking for code actions
+    pub raw: Value,
+}
+
+/// The diagnostics of a `textDocument/publishDiagnostics` notification
+pub fn diagnostics(params: &Value) -> Vec<Diagnostic> {
+    let Some(list) = params["diagnostics"].as_array() else {
+        return Vec::new();
+    };
+    list.iter()
+        .filter_map(|raw| {
+            Some(Diagnostic {
+                range: serde_json::from_value(raw["range"].clone()).ok()?,
+                severity: Severity::from_number(raw["severity"].as_u64()),
+                message: raw["message"].as_str()?.to_string(),
+                raw: raw.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Hover contents as plain text, from any of the shapes the protocol allows
+pub fn hover_text(contents: &Value) -> String {
+    match contents {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .map(hover_text)
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        Value::Object(object) => object
+            .get("value")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        _ => String::new(),
+    }
+}
+
+/// A code action or command a server offered for a range
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeAction {
+    pub title: String,
+    pub edit: Option<Value>,
+    /// A `Command` to run with `workspace/executeCommand` after any edit
+    pub command: Option<Value>,
+}
+
+pub fn code_actions(result: Value) -> Vec<CodeAction> {
+    let Value::Array(list) = result else {
+        return Vec::new();
+    };
+    list.into_iter()
+        .filter_map(|action| {
+            let title = action["title"].as_str()?.to_string();
+            // A bare `Command` has a string `command`, a `CodeAction` may carry one
+            if action["command"].is_string() {
+                return Some(CodeAction {
+                    title,
+                    edit: None,
+                    command: Some(action),
+                });
+            }
+            Some(CodeAction {
+                title,
+                edit: action.get("edit").cloned(),
+                command: action.get("command").cloned(),
+            })
+        })
+        .collect()
+}
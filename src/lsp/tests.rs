@@ -0,0 +1,351 @@
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
+};
+
+use crossbeam::channel::{Receiver, unbounded};
+
+use super::{protocol::Severity, *};
+use crate::buffer::list::BufferList;
+
+/// The server in `tests/support/fake_lsp.rs`, built by cargo as the `fake-lsp` example
+fn fake_server() -> PathBuf {
+    // Test binaries are in target/<profile>/deps, examples in target/<profile>/examples
+    let exe = std::env::current_exe().expect("test binary has a path");
+    let profile = exe
+        .parent()
+        .and_then(Path::parent)
+        .expect("in target/<profile>/deps");
+    let server = profile.join("examples").join("fake-lsp");
+    assert!(
+        server.exists(),
+        "{} is missing, run the tests with `cargo test` so examples are built",
+        server.display()
+    );
+    server
+}
+
+/// A directory of its own for each test, with `lsp.toml` pointing rust files at `command`
+fn workspace(name: &str, command: &Path) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("reovim-lsp-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("lsp.toml"),
+        format!(
+            "[rust]\ncommand = {:?}\nroot_markers = [\"lsp.toml\"]\n",
+            command
+        ),
+    )
+    .unwrap();
+    dir
+}
+
+fn start(dir: &Path) -> (Lsp, Receiver<ReovimEvent>) {
+    let (sender, events) = unbounded();
+    let config = LspConfig::load(Some(&dir.join("lsp.toml")));
+    (Lsp::new(config, sender), events)
+}
+
+fn open(buffers: &mut BufferList, dir: &Path, name: &str, text: &str) -> Rc<RefCell<Buffer>> {
+    let path = dir.join(name);
+    fs::write(&path, text).unwrap();
+    buffers.add(Buffer::from_file_path(&path).unwrap())
+}
+
+/// Hand the server's messages to `handle` until one needs the editor to act
+fn reply(lsp: &mut Lsp, events: &Receiver<ReovimEvent>) -> LspReply {
+    loop {
+        let event = events
+            .recv_timeout(Duration::from_secs(10))
+            .expect("the server answers in time");
+        let ReovimEvent::Lsp(event) = event else {
+            panic!("unexpected event {event:?}");
+        };
+        if let Some(reply) = lsp.handle(event) {
+            return reply;
+        }
+    }
+}
+
+/// Lines and UTF-16 columns of the diagnostics in the next publish
+fn diagnostics(lsp: &mut Lsp, events: &Receiver<ReovimEvent>) -> Vec<(usize, usize, Severity)> {
+    match reply(lsp, events) {
+        LspReply::Diagnostics { diagnostics, .. } => diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let start = diagnostic.range.start;
+                (start.line, start.character, diagnostic.severity)
+            })
+            .collect(),
+        other => panic!("expected diagnostics, got {other:?}"),
+    }
+}
+
+#[test]
+fn diagnostics_follow_edits() {
+    let dir = workspace("diagnostics", &fake_server());
+    let (mut lsp, events) = start(&dir);
+    let mut buffers = BufferList::default();
+    let buffer = open(
+        &mut buffers,
+        &dir,
+        "main.rs",
+        "fn main() {\n    // TODO\n}\n",
+    );
+
+    lsp.sync(&mut buffer.borrow_mut());
+    assert_eq!(
+        diagnostics(&mut lsp, &events),
+        vec![(1, 7, Severity::Warning)]
+    );
+
+    // Columns are counted in UTF-16, the emoji takes two
+    buffer
+        .borrow_mut()
+        .insert_lines(0, vec!["// é😀 FIXME".to_string()], (0, 0))
+        .unwrap();
+    lsp.sync(&mut buffer.borrow_mut());
+    let mut found = diagnostics(&mut lsp, &events);
+    found.sort();
+    assert_eq!(
+        found,
+        vec![(0, 7, Severity::Error), (2, 7, Severity::Warning)]
+    );
+}
+
+#[test]
+fn edits_stay_in_step_with_the_server() {
+    let dir = workspace("sync", &fake_server());
+    let (mut lsp, events) = start(&dir);
+    let mut buffers = BufferList::default();
+    let buffer = open(&mut buffers, &dir, "lib.rs", "alpha beta\ngamma\ndelta\n");
+    lsp.sync(&mut buffer.borrow_mut());
+    diagnostics(&mut lsp, &events);
+
+    {
+        let mut buffer = buffer.borrow_mut();
+        buffer.insert_char(0, 0, '😀').unwrap();
+        buffer.insert_char(0, 2, 'é').unwrap();
+        buffer.split_line(1, 2).unwrap();
+        buffer.join_line(2).unwrap();
+        buffer.remove_lines(3, 1, (3, 0)).unwrap();
+        buffer
+            .insert_lines(3, vec!["epsilon".into(), "zeta".into()], (3, 0))
+            .unwrap();
+        buffer.undo();
+        buffer.undo();
+        buffer.redo();
+        buffer
+            .remove_range(
+                TextRange {
+                    start: (0, 3),
+                    end: (1, 1),
+                },
+                (0, 3),
+            )
+            .unwrap();
+    }
+    lsp.sync(&mut buffer.borrow_mut());
+    diagnostics(&mut lsp, &events);
+
+    // The server hovers with the whole line it has, every line should match the buffer's
+    let lines = buffer.borrow().lines().as_slice().to_vec();
+    for (line, text) in lines.iter().enumerate() {
+        lsp.hover(&mut buffer.borrow_mut(), (line, 0)).unwrap();
+        let LspReply::Hover(hover) = reply(&mut lsp, &events) else {
+            panic!("expected hover");
+        };
+        assert_eq!(
+            hover.split_once('\n').map(|(_, line)| line),
+            Some(text.as_str())
+        );
+    }
+}
+
+#[test]
+fn definition_and_references() {
+    let dir = workspace("locations", &fake_server());
+    let (mut lsp, events) = start(&dir);
+    let mut buffers = BufferList::default();
+    let buffer = open(
+        &mut buffers,
+        &dir,
+        "main.rs",
+        "fn helper() {}\nfn main() {\n    helper();\n    helper();\n}\n",
+    );
+
+    lsp.definition(&mut buffer.borrow_mut(), (2, 6)).unwrap();
+    // Opening the document publishes diagnostics first
+    let found = loop {
+        match reply(&mut lsp, &events) {
+            LspReply::Definition(found) => break found,
+            LspReply::Diagnostics { .. } => continue,
+            other => panic!("expected a definition, got {other:?}"),
+        }
+    };
+    let lines = buffer.borrow().lines().as_slice().to_vec();
+    let starts: Vec<_> = found
+        .iter()
+        .map(|location| location.range.start.to_cursor(&lines))
+        .collect();
+    assert_eq!(starts, vec![(0, 3)]);
+    assert_eq!(
+        protocol::uri_to_path(&found[0].uri),
+        Some(dir.join("main.rs"))
+    );
+
+    lsp.references(&mut buffer.borrow_mut(), (0, 4)).unwrap();
+    let LspReply::References(found) = reply(&mut lsp, &events) else {
+        panic!("expected references");
+    };
+    let starts: Vec<_> = found
+        .iter()
+        .map(|location| location.range.start.to_cursor(&lines))
+        .collect();
+    assert_eq!(starts, vec![(0, 3), (2, 4), (3, 4)]);
+}
+
+#[test]
+fn rename_and_formatting_edit_the_buffer() {
+    let dir = workspace("edits", &fake_server());
+    let (mut lsp, events) = start(&dir);
+    let mut buffers = BufferList::default();
+    let buffer = open(
+        &mut buffers,
+        &dir,
+        "main.rs",
+        "let value = 1;   \nprint(value, value);\t\n\tvalue\t\n",
+    );
+    lsp.sync(&mut buffer.borrow_mut());
+    diagnostics(&mut lsp, &events);
+
+    lsp.rename(&mut buffer.borrow_mut(), (1, 8), "amount")
+        .unwrap();
+    let LspReply::Edit(edit) = reply(&mut lsp, &events) else {
+        panic!("expected an edit");
+    };
+    let edits = edit[&dir.join("main.rs")].clone();
+    apply_edits(&mut buffer.borrow_mut(), edits).unwrap();
+    assert_eq!(
+        buffer.borrow().text(),
+        "let amount = 1;   \nprint(amount, amount);\t\n\tamount\t\n"
+    );
+
+    // The server is told to indent as the buffer does
+    buffer.borrow_mut().options.shiftwidth = 2;
+    buffer.borrow_mut().options.expandtab = true;
+    lsp.format(&mut buffer.borrow_mut()).unwrap();
+    let edits = loop {
+        match reply(&mut lsp, &events) {
+            LspReply::Format { edits, .. } => break edits,
+            LspReply::Diagnostics { .. } => continue,
+            other => panic!("expected formatting, got {other:?}"),
+        }
+    };
+    apply_edits(&mut buffer.borrow_mut(), edits).unwrap();
+    assert_eq!(
+        buffer.borrow().text(),
+        "let amount = 1;\nprint(amount, amount);\n  amount\n"
+    );
+
+    // Both edits undo as one step each
+    buffer.borrow_mut().undo();
+    buffer.borrow_mut().undo();
+    assert_eq!(
+        buffer.borrow().text(),
+        "let value = 1;   \nprint(value, value);\t\n\tvalue\t\n"
+    );
+}
+
+#[test]
+fn code_actions_apply_edits_and_run_commands() {
+    let dir = workspace("actions", &fake_server());
+    let (mut lsp, events) = start(&dir);
+    let mut buffers = BufferList::default();
+    let buffer = open(
+        &mut buffers,
+        &dir,
+        "main.rs",
+        "fn main() {}\n// TODO: more\n",
+    );
+    lsp.sync(&mut buffer.borrow_mut());
+    diagnostics(&mut lsp, &events);
+
+    let range = TextRange {
+        start: (1, 0),
+        end: (1, 13),
+    };
+    lsp.code_actions(&mut buffer.borrow_mut(), range).unwrap();
+    assert_eq!(
+        reply(&mut lsp, &events),
+        LspReply::CodeActions(vec!["Mark done".to_string(), "Shout".to_string()])
+    );
+
+    let edit = lsp.code_action(1).unwrap().expect("the action has an edit");
+    apply_edits(&mut buffer.borrow_mut(), edit[&dir.join("main.rs")].clone()).unwrap();
+    assert_eq!(buffer.borrow().text(), "fn main() {}\n// DONE: more\n");
+    lsp.sync(&mut buffer.borrow_mut());
+
+    // The command makes its change by asking the editor to apply an edit
+    assert_eq!(lsp.code_action(2).unwrap(), None);
+    let edit = loop {
+        match reply(&mut lsp, &events) {
+            LspReply::Edit(edit) => break edit,
+            LspReply::Diagnostics { .. } => continue,
+            other => panic!("expected an edit, got {other:?}"),
+        }
+    };
+    apply_edits(&mut buffer.borrow_mut(), edit[&dir.join("main.rs")].clone()).unwrap();
+    assert_eq!(buffer.borrow().text(), "FN MAIN() {}\n// DONE: more\n");
+    assert!(lsp.code_action(3).is_err());
+}
+
+#[test]
+fn missing_server_is_reported_not_retried() {
+    let dir = workspace("missing", Path::new("/nonexistent/reovim-fake-lsp"));
+    let (mut lsp, events) = start(&dir);
+    let mut buffers = BufferList::default();
+    let buffer = open(&mut buffers, &dir, "main.rs", "fn main() {}\n");
+
+    lsp.sync(&mut buffer.borrow_mut());
+    let err = lsp.hover(&mut buffer.borrow_mut(), (0, 0)).unwrap_err();
+    assert_eq!(err.to_string(), "No language server for this buffer");
+    assert_eq!(lsp.failed.len(), 1);
+    assert!(events.try_recv().is_err());
+}
+
+#[test]
+fn servers_are_only_started_when_configured() {
+    assert!(LspConfig::load(None).servers.is_empty());
+    let dir = std::env::temp_dir().join(format!("reovim-lsp-{}-config", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("lsp.toml");
+    assert!(LspConfig::load(Some(&path)).servers.is_empty());
+
+    fs::write(
+        &path,
+        "[rust]\n[python]\nargs = [\"-v\"]\n[typescript]\ncommand = \"deno\"\nargs = [\"lsp\"]\n[zig]\n",
+    )
+    .unwrap();
+    let config = LspConfig::load(Some(&path));
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        config.get("rust"),
+        Some(&ServerConfig::new("rust-analyzer", &[], &["Cargo.toml"]))
+    );
+    assert_eq!(
+        config.get("python"),
+        Some(&ServerConfig::new("pylsp", &["-v"], &["pyproject.toml"]))
+    );
+    assert_eq!(
+        config.get("typescript"),
+        Some(&ServerConfig::new("deno", &["lsp"], &["package.json"]))
+    );
+    // Without a usual server there is nothing to start
+    assert_eq!(config.get("zig"), None);
+    assert_eq!(config.get("go"), None);
+}
@@ -1,36 +1,72 @@
+mod buffer;
+mod cli;
 mod color;
+mod completion;
 mod event;
+mod lsp;
+mod snippet;
+mod syntax;
+mod task;
+mod terminal;
 mod tui;
+mod vcs;
 
 use std::{
-    fs::{File, OpenOptions},
-    io::{Read, Write, stdout},
-    ops::{Index, IndexMut, Range},
-    path::PathBuf,
+    cell::RefCell,
+    fs::OpenOptions,
+    io::{Write, stdin, stdout},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
 };
 
-use anyhow::Result;
-use crossterm::{
-    ExecutableCommand,
-    event::{DisableMouseCapture, EnableMouseCapture, KeyModifiers},
-    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
-};
-use tracing::info;
+use anyhow::{Result, bail};
+use crossbeam::channel::{Receiver, after, never, select, unbounded};
+use tracing::{info, warn};
 use tracing_subscriber::EnvFilter;
 mod render;
 
-use crate::tui::{editor::Editor, tree::ComponentTree};
+use crate::{
+    buffer::{
+        Buffer,
+        list::BufferList,
+        swap::{self, SwapFile},
+    },
+    cli::{Action, FileArg},
+    completion::CompletionSources,
+    event::HostRequest,
+    lsp::{Lsp, LspConfig},
+    snippet::library::SnippetLibrary,
+    task::Tasks,
+    tui::{editor::Editor, tree::ComponentTree},
+};
+
+/// Quiet time after input before the editor is told it is idle, like vim's 'updatetime'
+const IDLE_TIME: Duration = Duration::from_secs(4);
 
 fn main() -> Result<()> {
-    let mut args = std::env::args();
-    let _program_name = args.next();
-    let file_name = args.next();
-    // Set up file logging (logs to reovim.log)
+    let args = match cli::parse(std::env::args().skip(1)) {
+        Ok(Action::Run(args)) => args,
+        Ok(Action::Version) => {
+            println!("reovim {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Ok(Action::Help) => {
+            print!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Err(err) => {
+            eprintln!("reovim: {err}");
+            eprint!("{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    // Set up file logging (logs to reovim.log unless --log is given)
     let log_file = OpenOptions::new()
         .write(true)
         .truncate(true)
         .create(true)
-        .open("reovim.log")?;
+        .open(&args.log_file)?;
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env().add_directive(tracing::Level::DEBUG.into()))
         .with_writer(log_file)
@@ -38,127 +74,201 @@ fn main() -> Result<()> {
         .init();
 
     info!("reovim starting");
+    match args.config_dir() {
+        Some(dir) => info!("using config directory {}", dir.display()),
+        None => info!("--clean given, skipping user configuration"),
+    }
 
-    // Enter alternate screen buffer and enable raw mode
-    terminal::enable_raw_mode()?;
-    stdout()
-        .execute(EnterAlternateScreen)?
-        .execute(EnableMouseCapture)?;
-
-    let mut editor = match file_name {
-        Some(file_name) => {
-            info!("opening file {file_name}");
-            let mut path_buf = PathBuf::new();
-            path_buf.push(std::env::current_dir()?);
-            let path_buf = path_buf.join(file_name);
-            Buffer::from_file_path(&path_buf)?
-        }
-        None => Buffer::default(),
-    };
-    let result = editor.run();
+    // Load every buffer before touching the terminal so stdin is fully consumed
+    let mut buffers = BufferList::default();
+    for file in &args.files {
+        let mut buffer = match file {
+            FileArg::Path(file_name) => {
+                info!("opening file {}", file_name.display());
+                let path_buf = std::env::current_dir()?.join(file_name);
+                // Another name for a file already open, like `./a.txt` after `a.txt`
+                if buffers.find(&path_buf).is_some() {
+                    continue;
+                }
+                let mut buffer = Buffer::from_file_path(&path_buf)?;
+                attend_swap_file(&mut buffer, &path_buf)?;
+                buffer
+            }
+            FileArg::Stdin => {
+                info!("reading buffer from stdin");
+                Buffer::from_reader(stdin())?
+            }
+        };
+        buffer.options.read_only |= args.read_only;
+        buffers.add(buffer);
+    }
+    if buffers.is_empty() {
+        buffers.add(Buffer::default());
+    }
+    if let (Some(start), Some(first)) = (&args.start, buffers.iter().next()) {
+        let mut first = first.borrow_mut();
+        first.cursor.0 = start.resolve(first.lines().iter());
+    }
+    let mut session = Session::new(buffers, args.config_dir(), args.diff);
 
-    // Always restore terminal state, even if run() fails
-    stdout()
-        .execute(LeaveAlternateScreen)?
-        .execute(DisableMouseCapture)?;
-    terminal::disable_raw_mode()?;
+    // Draw on the terminal even when stdout is piped into another command
+    let mut terminal_output = terminal::output()?;
 
-    info!("reovim shutting down");
-    result
-}
+    // Enter alternate screen buffer and enable raw mode, the guard and the panic hook leave them however run() ends
+    terminal::install_panic_hook();
+    let guard = terminal::TerminalGuard;
+    terminal::enter(&mut terminal_output)?;
 
-struct Line {
-    line_number: usize,
-    /// Need's to be string for mutability, womp womp
-    contents: String,
-}
+    let result = session.run(&mut terminal_output);
 
-impl Default for Line {
-    fn default() -> Self {
-        Self {
-            line_number: 0,
-            contents: Default::default(),
-        }
+    terminal::restore(&mut terminal_output)?;
+    drop(guard);
+
+    info!("reovim shutting down");
+    let exit_code = result?;
+    // The event loop only ends without an error when the editor asked to quit, having checked for unsaved
+    // changes, any other way out leaves the swap files to recover from
+    swap::close();
+    // Only now is stdout free of terminal drawing, emit the text written with :w
+    if let Some(output) = session.output.take() {
+        let mut stdout = stdout();
+        stdout.write_all(&output)?;
+        stdout.flush()?;
+    }
+    if exit_code != 0 {
+        std::process::exit(exit_code);
     }
+    Ok(())
 }
 
-impl Line {
-    /// First we get the cols_width_between, then we access perform to action
-    fn new(line_number: usize, contents: &str) -> Self {
-        Self {
-            line_number,
-            contents: String::from(contents),
+/// A swap file left for `path` means an editor stopped before saving, ask whether to recover the text it kept
+/// Asked on the terminal before the editor takes it over, nobody there to answer opens the file read-only
+fn attend_swap_file(buffer: &mut Buffer, path: &Path) -> Result<()> {
+    let swap = match SwapFile::read(path) {
+        Ok(Some(swap)) => swap,
+        Ok(None) => return Ok(()),
+        Err(err) => {
+            warn!("could not read the swap file of {}: {err}", path.display());
+            return Ok(());
         }
+    };
+    let running = swap.is_running();
+    // Left behind with the text as saved, there is nothing to recover
+    if !running
+        && swap
+            .lines
+            .iter()
+            .map(String::as_str)
+            .eq(buffer.lines().iter())
+    {
+        swap::remove(path, 0);
+        return Ok(());
     }
-}
-
-impl IndexMut<Range<usize>> for Line {
-    fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
-        let len = self.contents.len();
-        let start = index.start.min(len);
-        let end = index.end.min(len).max(start); // ensure end >= start
-        &mut self.contents[start..end]
+    let age = match swap.age() {
+        seconds @ ..120 => format!("{seconds} seconds"),
+        seconds @ ..7200 => format!("{} minutes", seconds / 60),
+        seconds @ ..172_800 => format!("{} hours", seconds / 3600),
+        seconds => format!("{} days", seconds / 86_400),
+    };
+    let state = if running {
+        "still running, editing the file elsewhere"
+    } else {
+        "no longer running"
+    };
+    let question = format!(
+        "Found a swap file for \"{}\"\n\
+         written {age} ago by process {} ({state})\n\
+         [R]ecover the unsaved text, open [O] read-only, [D]elete the swap file: ",
+        path.display(),
+        swap.pid,
+    );
+    loop {
+        match terminal::ask(&question)?.as_deref() {
+            Some("r" | "R") => buffer.recover(swap),
+            Some("o" | "O") | None => buffer.options.read_only = true,
+            Some("d" | "D") => swap::remove(path, 0),
+            Some(_) => continue,
+        }
+        return Ok(());
     }
 }
 
-impl<'a> Index<Range<usize>> for Line {
-    type Output = str;
-
-    fn index(&self, index: Range<usize>) -> &Self::Output {
-        let len = self.contents.len();
-        let start = index.start.min(len);
-        let end = index.end.min(len).max(start); // ensure end >= start
-        &self.contents[start..end]
+/// Read terminal events on their own thread so the event loop can wait on them alongside background work
+fn read_terminal_events() -> Receiver<crossterm::event::Event> {
+    let (sender, events) = unbounded();
+    let spawned = std::thread::Builder::new()
+        .name("reovim-input".to_string())
+        .spawn(move || {
+            loop {
+                match crossterm::event::read() {
+                    Ok(event) => {
+                        if sender.send(event).is_err() {
+                            break;
+                        }
+                    }
+                    Err(err) => {
+                        warn!("failed to read terminal event: {err}");
+                        break;
+                    }
+                }
+            }
+        });
+    if let Err(err) = spawned {
+        warn!("could not start input thread: {err}");
     }
+    events
 }
 
-struct Buffer {
-    file_path: Option<PathBuf>,
-    contents: String,
+/// The buffers opened from the command line and the state of the event loop
+struct Session {
+    buffers: Rc<RefCell<BufferList>>,
     dimensions: (u16, u16),
+    /// What to print on stdout after the terminal is restored
+    output: Option<Vec<u8>>,
+    /// User configuration, `None` with `--clean`
+    config_dir: Option<PathBuf>,
+    /// Started with `-d`, the first two buffers are compared
+    diff: bool,
 }
 
-impl Default for Buffer {
-    fn default() -> Self {
+impl Session {
+    fn new(buffers: BufferList, config_dir: Option<PathBuf>, diff: bool) -> Self {
         Self {
-            contents: String::new(),
+            buffers: Rc::new(RefCell::new(buffers)),
             dimensions: Default::default(),
-            file_path: Default::default(),
+            output: None,
+            config_dir,
+            diff,
         }
     }
-}
 
-impl Buffer {
-    fn from_file_path(path: &PathBuf) -> Result<Buffer> {
-        let mut file = match File::open(path) {
-            Ok(file) => file,
-            Err(_) => File::create_new(path)?,
-        };
-        let mut contents = String::new();
-        // todo: We can store the contents as a file buffer
-        file.read_to_string(&mut contents)?;
-        let mut lines = Vec::new();
-        for (line_number, line) in contents.lines().enumerate() {
-            lines.push(Line::new(line_number, line));
-        }
-        Ok(Buffer {
-            contents,
-            file_path: Some(path.clone()),
-            ..Default::default()
-        })
-    }
-    fn run(&mut self) -> Result<()> {
-        let mut stdout = stdout();
+    /// Run the event loop until a component asks to quit, returning the exit code
+    fn run(&mut self, stdout: &mut dyn Write) -> Result<i32> {
         self.dimensions = crossterm::terminal::size()?;
 
-        let file_name = self
-            .file_path
-            .as_ref()
-            .and_then(|path| path.file_name())
-            .and_then(|os_string| os_string.to_str())
-            .unwrap_or("[no file]");
-
-        let editor_component = Editor::new(self.contents.to_string(), file_name);
+        let (first, second) = {
+            let buffers = self.buffers.borrow();
+            let mut buffers = buffers.iter().cloned();
+            let first = buffers.next().expect("the session always has a buffer");
+            (first, buffers.next())
+        };
+        let (tasks, task_events) = Tasks::start();
+        let terminal_events = read_terminal_events();
+        let lsp_config = self.config_dir.as_ref().map(|dir| dir.join("lsp.toml"));
+        let lsp = Lsp::new(LspConfig::load(lsp_config.as_deref()), tasks.sender());
+        // In diff mode the second file opens on the right and the first to its left
+        let diff = second.filter(|_| self.diff);
+        let mut editor_component = Editor::new(
+            self.buffers.clone(),
+            diff.clone().unwrap_or_else(|| first.clone()),
+            tasks,
+            CompletionSources::default(),
+            SnippetLibrary::new(self.config_dir.as_ref().map(|dir| dir.join("snippets"))),
+            lsp,
+        );
+        if diff.is_some() {
+            editor_component.diff_with(first);
+        }
         let mut tree = ComponentTree::new(tui::tree::ComponentNode::Component(Box::new(
             editor_component,
         )));
@@ -167,30 +277,48 @@ impl Buffer {
         tree.initialize_pending_components()?;
         tree.layout(self.dimensions.0, self.dimensions.1);
         tree.mark_all_dirty();
-        tree.render(&mut stdout)?;
+        tree.render(stdout)?;
 
+        // Armed by input, fires once the input stops for `IDLE_TIME`
+        let mut idle = never();
         loop {
             self.dimensions = crossterm::terminal::size()?;
             tree.layout(self.dimensions.0, self.dimensions.1);
-            tree.render(&mut stdout)?;
+            tree.render(stdout)?;
             stdout.flush()?;
 
-            let crossterm_event = crossterm::event::read().expect("failed to read event");
+            // Background work finishing is handled like input, whichever comes first
+            let crossterm_event = select! {
+                recv(terminal_events) -> event => Some(event?),
+                recv(task_events) -> event => {
+                    tree.update(event?)?;
+                    None
+                }
+                recv(idle) -> _ => {
+                    idle = never();
+                    tree.update(event::ReovimEvent::Idle)?;
+                    None
+                }
+            };
+            if terminal::panicked() {
+                bail!("a background thread panicked, see the log");
+            }
+            if crossterm_event.is_some() {
+                idle = after(IDLE_TIME);
+            }
             // nowe we handle them events
             match crossterm_event {
-                crossterm::event::Event::FocusGained => {}
-                crossterm::event::Event::FocusLost => {}
-                crossterm::event::Event::Key(key_event) => {
-                    if key_event.code.is_char('u') && key_event.modifiers == KeyModifiers::CONTROL {
-                        break;
-                    }
+                None => {}
+                Some(crossterm::event::Event::FocusGained) => {}
+                Some(crossterm::event::Event::FocusLost) => {}
+                Some(crossterm::event::Event::Key(key_event)) => {
                     tree.update(event::ReovimEvent::Key(key_event))?;
                 }
-                crossterm::event::Event::Mouse(mouse_event) => {
+                Some(crossterm::event::Event::Mouse(mouse_event)) => {
                     tree.update(event::ReovimEvent::Mouse(mouse_event))?
                 }
-                crossterm::event::Event::Paste(_) => {}
-                crossterm::event::Event::Resize(x, y) => {
+                Some(crossterm::event::Event::Paste(_)) => {}
+                Some(crossterm::event::Event::Resize(x, y)) => {
                     // Update dimensions first
                     self.dimensions = (x, y);
                     // Then re-layout with new dimensions (this triggers measurement of Content components)
@@ -200,11 +328,22 @@ impl Buffer {
                     // Then handle the resize event
                     tree.update(event::ReovimEvent::Resize(x, y))?;
                     // Finally render
-                    tree.render(&mut stdout)?;
+                    tree.render(stdout)?;
                     stdout.flush()?;
                 }
             }
+
+            for request in tree.take_requests() {
+                match request {
+                    HostRequest::Quit(exit_code) => return Ok(exit_code),
+                    HostRequest::Output(text) => self.output = Some(text),
+                    HostRequest::Suspend => {
+                        terminal::suspend(stdout)?;
+                        // The shell drew over the screen while the editor was stopped
+                        tree.mark_all_dirty();
+                    }
+                }
+            }
         }
-        Ok(())
     }
 }
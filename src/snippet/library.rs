@@ -0,0 +1,133 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+/// Snippets in every filetype are kept under this name
+const ALL_FILETYPES: &str = "all";
+
+/// A snippet as written in a snippet file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetDefinition {
+    pub name: String,
+    /// The word that expands to the snippet when Tab is pressed after it
+    pub prefix: String,
+    pub body: String,
+    pub description: Option<String>,
+}
+
+/// One or several strings, snippet files allow either for prefixes and bodies
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            OneOrMany::One(one) => vec![one],
+            OneOrMany::Many(many) => many,
+        }
+    }
+}
+
+/// A snippet file entry, the same shape in JSON as VS Code uses and in TOML
+#[derive(Deserialize)]
+struct RawSnippet {
+    prefix: OneOrMany,
+    body: OneOrMany,
+    description: Option<String>,
+}
+
+/// Read the snippets in a `.json` or `.toml` file
+pub fn load_file(path: &Path) -> Result<Vec<SnippetDefinition>> {
+    let contents = fs::read_to_string(path)?;
+    let raw: BTreeMap<String, RawSnippet> = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&contents)?,
+        Some("toml") => toml::from_str(&contents)?,
+        _ => bail!("{} is not a .json or .toml snippet file", path.display()),
+    };
+    let mut definitions = Vec::new();
+    for (name, snippet) in raw {
+        let body = snippet.body.into_vec().join("\n");
+        for prefix in snippet.prefix.into_vec() {
+            definitions.push(SnippetDefinition {
+                name: name.clone(),
+                prefix,
+                body: body.clone(),
+                description: snippet.description.clone(),
+            });
+        }
+    }
+    Ok(definitions)
+}
+
+/// Every snippet by filetype, files are read the first time their filetype is asked for
+/// `<dir>/<filetype>.json` and `<dir>/<filetype>.toml` hold a filetype's snippets, `all.*` those for every filetype
+#[derive(Default)]
+pub struct SnippetLibrary {
+    dir: Option<PathBuf>,
+    loaded: RefCell<HashMap<String, Vec<SnippetDefinition>>>,
+}
+
+impl SnippetLibrary {
+    /// A library reading snippet files from `dir`, without one there are no snippets
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            loaded: RefCell::default(),
+        }
+    }
+
+    fn load(&self, filetype: &str) {
+        if self.loaded.borrow().contains_key(filetype) {
+            return;
+        }
+        let mut definitions = Vec::new();
+        if let Some(dir) = &self.dir {
+            for extension in ["json", "toml"] {
+                let path = dir.join(format!("{filetype}.{extension}"));
+                if !path.exists() {
+                    continue;
+                }
+                match load_file(&path) {
+                    Ok(loaded) => {
+                        info!("loaded {} snippets from {}", loaded.len(), path.display());
+                        definitions.extend(loaded);
+                    }
+                    Err(err) => warn!("could not load snippets from {}: {err}", path.display()),
+                }
+            }
+        }
+        self.loaded
+            .borrow_mut()
+            .insert(filetype.to_string(), definitions);
+    }
+
+    /// Snippets usable in a filetype, its own before those for every filetype
+    pub fn available(&self, filetype: Option<&str>) -> Vec<SnippetDefinition> {
+        let mut available = Vec::new();
+        for filetype in filetype.into_iter().chain([ALL_FILETYPES]) {
+            self.load(filetype);
+            if let Some(definitions) = self.loaded.borrow().get(filetype) {
+                available.extend(definitions.iter().cloned());
+            }
+        }
+        available
+    }
+
+    /// The snippet a word expands to
+    pub fn find(&self, filetype: Option<&str>, prefix: &str) -> Option<SnippetDefinition> {
+        self.available(filetype)
+            .into_iter()
+            .find(|definition| definition.prefix == prefix)
+    }
+}
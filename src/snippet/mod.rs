@@ -0,0 +1,506 @@
+pub mod library;
+pub mod session;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Result, bail};
+
+/// A piece of a parsed snippet body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Element {
+    Text(String),
+    /// `$1`, `${1}` or `${1:placeholder}`, the placeholder can hold further elements
+    TabStop {
+        number: u32,
+        placeholder: Vec<Element>,
+    },
+    /// `${1|one,two|}`, the first option is inserted and the others offered in the menu
+    Choice {
+        number: u32,
+        options: Vec<String>,
+    },
+    /// `$NAME`, `${NAME}` or `${NAME:default}`
+    Variable {
+        name: String,
+        default: Option<Vec<Element>>,
+    },
+}
+
+/// A snippet body in the LSP and VS Code snippet syntax
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    elements: Vec<Element>,
+}
+
+/// Cells of the expanded text on one line, columns are chars
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Where a tab stop ended up in the expanded text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabStop {
+    pub number: u32,
+    /// Where the cursor goes, the other ranges mirror what's typed there
+    pub ranges: Vec<Range>,
+    /// Options of a choice, empty for other tab stops
+    pub choices: Vec<String>,
+}
+
+/// A snippet's text with the variables filled in and where its tab stops are
+/// Lines after the first don't have the indent of the line the snippet is inserted on yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    pub lines: Vec<String>,
+    /// In the order Tab visits them, `$0` last
+    pub stops: Vec<TabStop>,
+}
+
+impl Snippet {
+    pub fn parse(body: &str) -> Result<Snippet> {
+        let mut parser = Parser {
+            chars: body.chars().collect(),
+            position: 0,
+        };
+        let elements = parser.elements(false)?;
+        Ok(Snippet { elements })
+    }
+
+    /// Fill in the variables and lay the text out in lines
+    pub fn expand(&self, variables: &Variables) -> Expansion {
+        let mut expander = Expander {
+            variables,
+            lines: vec![String::new()],
+            col: 0,
+            placeholders: HashMap::new(),
+            stops: BTreeMap::new(),
+            expanding: Vec::new(),
+        };
+        expander.collect_placeholders(&self.elements);
+        expander.emit(&self.elements);
+
+        let end = Range {
+            line: expander.lines.len() - 1,
+            start: expander.col,
+            end: expander.col,
+        };
+        let mut stops: Vec<TabStop> = expander
+            .stops
+            .into_values()
+            .map(|(mut stop, primary)| {
+                stop.ranges.swap(0, primary.unwrap_or(0));
+                stop
+            })
+            .collect();
+        // Without a `$0` the snippet is left from its end
+        match stops.iter().position(|stop| stop.number == 0) {
+            Some(index) => {
+                let last = stops.remove(index);
+                stops.push(last);
+            }
+            None => stops.push(TabStop {
+                number: 0,
+                ranges: vec![end],
+                choices: Vec::new(),
+            }),
+        }
+        Expansion {
+            lines: expander.lines,
+            stops,
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let character = self.peek()?;
+        self.position += 1;
+        Some(character)
+    }
+
+    fn expect(&mut self, wanted: char) -> Result<()> {
+        match self.next() {
+            Some(character) if character == wanted => Ok(()),
+            Some(character) => bail!("expected `{wanted}` but found `{character}` in snippet"),
+            None => bail!("expected `{wanted}` but the snippet ended"),
+        }
+    }
+
+    /// Elements up to the end of the body, or up to the `}` closing a placeholder
+    fn elements(&mut self, in_braces: bool) -> Result<Vec<Element>> {
+        let mut elements = Vec::new();
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None if in_braces => bail!("unclosed `${{` in snippet"),
+                None => break,
+                Some('}') if in_braces => {
+                    self.position += 1;
+                    break;
+                }
+                Some('\\') => {
+                    self.position += 1;
+                    match self.peek() {
+                        Some(character @ ('$' | '}' | '\\')) => {
+                            self.position += 1;
+                            text.push(character);
+                        }
+                        _ => text.push('\\'),
+                    }
+                }
+                Some('$') => {
+                    self.position += 1;
+                    match self.dollar()? {
+                        Some(element) => {
+                            if !text.is_empty() {
+                                elements.push(Element::Text(std::mem::take(&mut text)));
+                            }
+                            elements.push(element);
+                        }
+                        None => text.push('$'),
+                    }
+                }
+                Some(character) => {
+                    self.position += 1;
+                    text.push(character);
+                }
+            }
+        }
+        if !text.is_empty() {
+            elements.push(Element::Text(text));
+        }
+        Ok(elements)
+    }
+
+    fn number(&mut self) -> Result<u32> {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|character| character.is_ascii_digit())
+        {
+            self.position += 1;
+        }
+        let digits: String = self.chars[start..self.position].iter().collect();
+        Ok(digits.parse()?)
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|character| character.is_ascii_alphanumeric() || character == '_')
+        {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    /// What follows a `$`, `None` when it's just a dollar sign
+    fn dollar(&mut self) -> Result<Option<Element>> {
+        match self.peek() {
+            Some(character) if character.is_ascii_digit() => Ok(Some(Element::TabStop {
+                number: self.number()?,
+                placeholder: Vec::new(),
+            })),
+            Some(character) if character.is_ascii_alphabetic() || character == '_' => {
+                Ok(Some(Element::Variable {
+                    name: self.name(),
+                    default: None,
+                }))
+            }
+            Some('{') => {
+                self.position += 1;
+                self.braced().map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// What follows a `${`
+    fn braced(&mut self) -> Result<Element> {
+        if self
+            .peek()
+            .is_some_and(|character| character.is_ascii_digit())
+        {
+            let number = self.number()?;
+            return match self.next() {
+                Some('}') => Ok(Element::TabStop {
+                    number,
+                    placeholder: Vec::new(),
+                }),
+                Some(':') => Ok(Element::TabStop {
+                    number,
+                    placeholder: self.elements(true)?,
+                }),
+                Some('|') => {
+                    let options = self.choices()?;
+                    Ok(Element::Choice { number, options })
+                }
+                Some(character) => bail!("unexpected `{character}` after `${{{number}` in snippet"),
+                None => bail!("unclosed `${{` in snippet"),
+            };
+        }
+        let name = self.name();
+        if name.is_empty() {
+            bail!("expected a tab stop or variable after `${{` in snippet");
+        }
+        match self.next() {
+            Some('}') => Ok(Element::Variable {
+                name,
+                default: None,
+            }),
+            Some(':') => Ok(Element::Variable {
+                name,
+                default: Some(self.elements(true)?),
+            }),
+            Some('/') => bail!("transforms of `{name}` are not supported in snippets"),
+            Some(character) => bail!("unexpected `{character}` after `${{{name}` in snippet"),
+            None => bail!("unclosed `${{` in snippet"),
+        }
+    }
+
+    /// The options of a choice, after its `|` up to and including the closing `|}`
+    fn choices(&mut self) -> Result<Vec<String>> {
+        let mut options = Vec::new();
+        let mut option = String::new();
+        loop {
+            match self.next() {
+                None => bail!("unclosed choice in snippet"),
+                Some('\\') => match self.next() {
+                    Some(character @ ('$' | '}' | '\\' | ',' | '|')) => option.push(character),
+                    Some(character) => {
+                        option.push('\\');
+                        option.push(character);
+                    }
+                    None => bail!("unclosed choice in snippet"),
+                },
+                Some(',') => options.push(std::mem::take(&mut option)),
+                Some('|') => {
+                    self.expect('}')?;
+                    options.push(option);
+                    return Ok(options);
+                }
+                Some(character) => option.push(character),
+            }
+        }
+    }
+}
+
+struct Expander<'a> {
+    variables: &'a Variables,
+    lines: Vec<String>,
+    /// Chars on the last line so far
+    col: usize,
+    /// What each tab stop shows, the first placeholder given for its number
+    placeholders: HashMap<u32, Vec<Element>>,
+    /// Every tab stop with the index of the range that has the placeholder
+    stops: BTreeMap<u32, (TabStop, Option<usize>)>,
+    /// Tab stops being expanded, so a placeholder mirroring itself doesn't recurse forever
+    expanding: Vec<u32>,
+}
+
+impl Expander<'_> {
+    fn collect_placeholders(&mut self, elements: &[Element]) {
+        for element in elements {
+            match element {
+                Element::TabStop {
+                    number,
+                    placeholder,
+                } if !placeholder.is_empty() => {
+                    self.placeholders
+                        .entry(*number)
+                        .or_insert_with(|| placeholder.clone());
+                    self.collect_placeholders(placeholder);
+                }
+                Element::Choice { number, options } => {
+                    let first = options.first().cloned().unwrap_or_default();
+                    self.placeholders
+                        .entry(*number)
+                        .or_insert_with(|| vec![Element::Text(first)]);
+                }
+                Element::Variable {
+                    default: Some(default),
+                    ..
+                } => self.collect_placeholders(default),
+                _ => {}
+            }
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        for character in text.chars() {
+            if character == '\n' {
+                self.lines.push(String::new());
+                self.col = 0;
+            } else {
+                self.lines
+                    .last_mut()
+                    .expect("always a line")
+                    .push(character);
+                self.col += 1;
+            }
+        }
+    }
+
+    fn emit(&mut self, elements: &[Element]) {
+        for element in elements {
+            match element {
+                Element::Text(text) => self.text(text),
+                Element::TabStop {
+                    number,
+                    placeholder,
+                } => {
+                    let has_placeholder = !placeholder.is_empty();
+                    let contents = if has_placeholder {
+                        placeholder.clone()
+                    } else {
+                        self.placeholders.get(number).cloned().unwrap_or_default()
+                    };
+                    self.stop(*number, has_placeholder, Vec::new(), |expander| {
+                        expander.emit(&contents)
+                    });
+                }
+                Element::Choice { number, options } => {
+                    let first = options.first().cloned().unwrap_or_default();
+                    self.stop(*number, true, options.clone(), |expander| {
+                        expander.text(&first)
+                    });
+                }
+                Element::Variable { name, default } => match self.variables.resolve(name) {
+                    Some(value) => self.text(&value),
+                    None => match default {
+                        Some(default) => self.emit(default),
+                        // Unknown variables show their name, like VS Code
+                        None => self.text(name),
+                    },
+                },
+            }
+        }
+    }
+
+    /// Record a tab stop around whatever `contents` writes
+    fn stop(
+        &mut self,
+        number: u32,
+        has_placeholder: bool,
+        choices: Vec<String>,
+        contents: impl FnOnce(&mut Self),
+    ) {
+        let line = self.lines.len() - 1;
+        let start = self.col;
+        if !self.expanding.contains(&number) {
+            self.expanding.push(number);
+            contents(self);
+            self.expanding.pop();
+        }
+        // A placeholder over several lines can't be edited in place, the cursor just goes to its start
+        let end = if self.lines.len() - 1 == line {
+            self.col
+        } else {
+            start
+        };
+        let range = Range { line, start, end };
+        let (stop, primary) = self.stops.entry(number).or_insert_with(|| {
+            (
+                TabStop {
+                    number,
+                    ranges: Vec::new(),
+                    choices: Vec::new(),
+                },
+                None,
+            )
+        });
+        if !choices.is_empty() {
+            stop.choices = choices;
+        }
+        // The cursor goes to the first range with a placeholder, the others mirror it
+        if has_placeholder && primary.is_none() {
+            *primary = Some(stop.ranges.len());
+        }
+        stop.ranges.push(range);
+    }
+}
+
+/// Values of the `$TM_FILENAME` style variables where a snippet is expanded
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    pub path: Option<PathBuf>,
+    /// The line the snippet is expanded on and its index
+    pub line: String,
+    pub line_index: usize,
+    /// The word under the cursor, before the snippet's prefix replaces it
+    pub word: String,
+}
+
+impl Variables {
+    pub fn resolve(&self, name: &str) -> Option<String> {
+        let path = self.path.as_deref();
+        let value = match name {
+            "TM_FILENAME" => path?.file_name()?.to_string_lossy().to_string(),
+            "TM_FILENAME_BASE" => path?.file_stem()?.to_string_lossy().to_string(),
+            "TM_DIRECTORY" => path?.parent()?.display().to_string(),
+            "TM_FILEPATH" => path?.display().to_string(),
+            "TM_LINE_INDEX" => self.line_index.to_string(),
+            "TM_LINE_NUMBER" => (self.line_index + 1).to_string(),
+            "TM_CURRENT_LINE" => self.line.clone(),
+            "TM_CURRENT_WORD" => self.word.clone(),
+            "TM_SELECTED_TEXT" => String::new(),
+            "CURRENT_YEAR" | "CURRENT_MONTH" | "CURRENT_DATE" => {
+                let (year, month, day) = today();
+                match name {
+                    "CURRENT_YEAR" => format!("{year}"),
+                    "CURRENT_MONTH" => format!("{month:02}"),
+                    _ => format!("{day:02}"),
+                }
+            }
+            "CURRENT_SECONDS_UNIX" => now().to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Year, month and day in UTC
+fn today() -> (i64, u32, u32) {
+    // Days to a civil date, from Howard Hinnant's date algorithms
+    let days = (now() / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,224 @@
+use anyhow::Result;
+
+use crate::{
+    buffer::{Buffer, byte_index},
+    snippet::{Expansion, Range, TabStop},
+};
+
+/// An expanded snippet being filled in, Tab and Shift-Tab move between its tab stops
+/// Ranges follow the edits made inside the current placeholder, an edit anywhere else ends the session
+pub struct SnippetSession {
+    stops: Vec<TabStop>,
+    current: usize,
+    /// The placeholder was just jumped to, typing replaces it rather than adding to it
+    selected: bool,
+}
+
+impl SnippetSession {
+    /// Put an expansion in place of the text from `start` to `end` on `line`
+    pub fn insert(
+        buffer: &mut Buffer,
+        expansion: Expansion,
+        (line, start): (usize, usize),
+        end: usize,
+    ) -> Result<SnippetSession> {
+        let text = buffer.line(line).unwrap_or_default().to_string();
+        // Lines after the first line up with the one the snippet was expanded on, each tab at the start
+        // of a body line is a level of indent below it, made of the blanks `expandtab` asks for
+        let options = &buffer.options;
+        let base = options.indent_width(&text);
+        let mut lines = expansion.lines;
+        let mut shifts = vec![start as isize];
+        for expanded in lines.iter_mut().skip(1) {
+            let tabs = expanded.len() - expanded.trim_start_matches('\t').len();
+            let spaces = expanded[tabs..].len() - expanded[tabs..].trim_start_matches(' ').len();
+            let indent = options.blanks(0, base + tabs * options.shift_width() + spaces);
+            shifts.push(indent.chars().count() as isize - (tabs + spaces) as isize);
+            expanded.replace_range(..tabs + spaces, &indent);
+        }
+        lines[0].insert_str(0, &text[..byte_index(&text, start)]);
+        lines
+            .last_mut()
+            .expect("an expansion has a line")
+            .push_str(&text[byte_index(&text, end)..]);
+
+        let mut rest = lines.split_off(1);
+        let first = lines.remove(0);
+        buffer.set_line(line, first, (line, end))?;
+        if !rest.is_empty() {
+            buffer.insert_lines(line + 1, std::mem::take(&mut rest), (line, end))?;
+        }
+
+        let mut stops = expansion.stops;
+        for range in stops.iter_mut().flat_map(|stop| stop.ranges.iter_mut()) {
+            let shift = shifts[range.line];
+            range.line += line;
+            range.start = range.start.saturating_add_signed(shift);
+            range.end = range.end.saturating_add_signed(shift);
+        }
+        let mut session = SnippetSession {
+            stops,
+            current: 0,
+            selected: false,
+        };
+        session.select();
+        Ok(session)
+    }
+
+    pub fn current(&self) -> &TabStop {
+        &self.stops[self.current]
+    }
+
+    fn primary(&self) -> Range {
+        self.current().ranges[0]
+    }
+
+    /// Where the cursor goes for the current tab stop
+    pub fn cursor(&self) -> (usize, usize) {
+        let primary = self.primary();
+        (primary.line, primary.start)
+    }
+
+    /// Whether the cursor reached `$0`, where the snippet is done
+    pub fn is_finished(&self) -> bool {
+        self.current().number == 0
+    }
+
+    /// The placeholder to highlight
+    pub fn highlight(&self) -> Option<Range> {
+        let primary = self.primary();
+        (!self.is_finished() && primary.start < primary.end).then_some(primary)
+    }
+
+    fn select(&mut self) {
+        let primary = self.primary();
+        self.selected = primary.start < primary.end;
+    }
+
+    /// Keep whatever the placeholder holds, the next edit adds to it
+    pub fn deselect(&mut self) {
+        self.selected = false;
+    }
+
+    /// Move to the next or previous tab stop, returning false when there's none before the first
+    pub fn jump(&mut self, forward: bool) -> bool {
+        if forward {
+            self.current = (self.current + 1).min(self.stops.len() - 1);
+        } else if self.current > 0 {
+            self.current -= 1;
+        } else {
+            return false;
+        }
+        self.select();
+        true
+    }
+
+    /// Remove a selected placeholder before typing over it, returning whether there was one
+    pub fn clear_selection(&mut self, buffer: &mut Buffer) -> Result<bool> {
+        if !std::mem::take(&mut self.selected) {
+            return Ok(false);
+        }
+        let primary = self.primary();
+        // Tab stops nested in the placeholder go with it
+        for (index, stop) in self.stops.iter_mut().enumerate() {
+            if index == self.current {
+                continue;
+            }
+            for range in stop.ranges.iter_mut() {
+                let inside = range.line == primary.line
+                    && range.start >= primary.start
+                    && range.end <= primary.end;
+                if inside {
+                    *range = Range {
+                        end: primary.start,
+                        start: primary.start,
+                        ..primary
+                    };
+                }
+            }
+        }
+        self.replace((self.current, 0), "", buffer)?;
+        self.update_mirrors(buffer)?;
+        Ok(true)
+    }
+
+    /// Follow an edit at `col` on `line` that changed its length by `delta` chars
+    /// Returns false when the edit was outside the current placeholder, which ends the session
+    pub fn edited(
+        &mut self,
+        buffer: &mut Buffer,
+        line: usize,
+        col: usize,
+        delta: isize,
+    ) -> Result<bool> {
+        let primary = self.primary();
+        if line != primary.line || col < primary.start || col > primary.end {
+            return Ok(false);
+        }
+        self.shift(line, primary.end, delta, (self.current, 0));
+        self.stops[self.current].ranges[0].end = primary.end.saturating_add_signed(delta);
+        self.update_mirrors(buffer)?;
+        Ok(true)
+    }
+
+    /// Copy the placeholder's text to its mirrors
+    fn update_mirrors(&mut self, buffer: &mut Buffer) -> Result<()> {
+        let primary = self.primary();
+        let text: String = buffer
+            .line(primary.line)
+            .unwrap_or_default()
+            .chars()
+            .skip(primary.start)
+            .take(primary.end - primary.start)
+            .collect();
+        for mirror in 1..self.current().ranges.len() {
+            self.replace((self.current, mirror), &text, buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Replace the text of one range, moving the ranges after it on its line
+    fn replace(
+        &mut self,
+        (stop, index): (usize, usize),
+        text: &str,
+        buffer: &mut Buffer,
+    ) -> Result<()> {
+        let range = self.stops[stop].ranges[index];
+        let Some(current) = buffer.line(range.line) else {
+            return Ok(());
+        };
+        let mut replaced = current.to_string();
+        replaced.replace_range(
+            byte_index(current, range.start)..byte_index(current, range.end),
+            text,
+        );
+        if replaced == current {
+            return Ok(());
+        }
+        buffer.set_line(range.line, replaced, (range.line, range.start))?;
+        let length = text.chars().count();
+        let delta = length as isize - (range.end - range.start) as isize;
+        self.shift(range.line, range.end, delta, (stop, index));
+        self.stops[stop].ranges[index].end = range.start + length;
+        Ok(())
+    }
+
+    /// Move every range on `line` from `col` on by `delta`, except the one being edited
+    fn shift(&mut self, line: usize, col: usize, delta: isize, except: (usize, usize)) {
+        for (stop_index, stop) in self.stops.iter_mut().enumerate() {
+            for (index, range) in stop.ranges.iter_mut().enumerate() {
+                if (stop_index, index) == except || range.line != line {
+                    continue;
+                }
+                if range.start >= col {
+                    range.start = range.start.saturating_add_signed(delta);
+                    range.end = range.end.saturating_add_signed(delta);
+                } else if range.end >= col {
+                    // Ranges holding the edited one grow and shrink with it
+                    range.end = range.end.saturating_add_signed(delta).max(range.start);
+                }
+            }
+        }
+    }
+}
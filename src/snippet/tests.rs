@@ -0,0 +1,165 @@
+use super::{
+    Element::{self, Choice, TabStop as Stop, Text, Variable},
+    Range, Snippet, TabStop, Variables,
+    session::SnippetSession,
+};
+use crate::buffer::Buffer;
+
+fn parse(body: &str) -> Vec<Element> {
+    Snippet::parse(body).unwrap().elements
+}
+
+fn text(text: &str) -> Element {
+    Text(text.to_string())
+}
+
+fn stop(number: u32, placeholder: Vec<Element>) -> Element {
+    Stop {
+        number,
+        placeholder,
+    }
+}
+
+fn range(line: usize, start: usize, end: usize) -> Range {
+    Range { line, start, end }
+}
+
+#[test]
+fn tab_stops_and_placeholders() {
+    assert_eq!(
+        parse("a $1 ${2} ${3:three}"),
+        [
+            text("a "),
+            stop(1, vec![]),
+            text(" "),
+            stop(2, vec![]),
+            text(" "),
+            stop(3, vec![text("three")]),
+        ]
+    );
+    // A number runs as long as its digits
+    assert_eq!(parse("$10x"), [stop(10, vec![]), text("x")]);
+}
+
+#[test]
+fn placeholders_nest() {
+    assert_eq!(
+        parse("${1:outer ${2:inner $TM_FILENAME} end}"),
+        [stop(
+            1,
+            vec![
+                text("outer "),
+                stop(
+                    2,
+                    vec![
+                        text("inner "),
+                        Variable {
+                            name: "TM_FILENAME".to_string(),
+                            default: None,
+                        },
+                    ]
+                ),
+                text(" end"),
+            ]
+        )]
+    );
+    assert_eq!(
+        parse("${NAME:${1:x}}"),
+        [Variable {
+            name: "NAME".to_string(),
+            default: Some(vec![stop(1, vec![text("x")])]),
+        }]
+    );
+}
+
+#[test]
+fn escapes() {
+    assert_eq!(parse(r"\$1 \} \\ \n"), [text(r"$1 } \ \n")]);
+    assert_eq!(parse(r"${1:a\}b}"), [stop(1, vec![text("a}b")])]);
+    // A dollar sign not followed by a tab stop or variable is itself
+    assert_eq!(parse("$ 5$"), [text("$ 5$")]);
+}
+
+#[test]
+fn choices() {
+    assert_eq!(
+        parse(r"${1|one,t\,wo,th\|ree|}"),
+        [Choice {
+            number: 1,
+            options: vec!["one".to_string(), "t,wo".to_string(), "th|ree".to_string()],
+        }]
+    );
+}
+
+#[test]
+fn malformed_bodies() {
+    for body in [
+        "${1",
+        "${1:a",
+        "${1|a,b",
+        "${1|a|",
+        "${}",
+        "${1x}",
+        "${A/x/y/}",
+    ] {
+        assert!(Snippet::parse(body).is_err(), "{body}");
+    }
+}
+
+#[test]
+fn expansion_places_the_tab_stops() {
+    let expansion = Snippet::parse("fn ${1:name}($2) {\n\t$0\n}\n$1")
+        .unwrap()
+        .expand(&Variables::default());
+    assert_eq!(expansion.lines, ["fn name() {", "\t", "}", "name"]);
+    assert_eq!(
+        expansion.stops,
+        [
+            TabStop {
+                number: 1,
+                ranges: vec![range(0, 3, 7), range(3, 0, 4)],
+                choices: vec![],
+            },
+            TabStop {
+                number: 2,
+                ranges: vec![range(0, 8, 8)],
+                choices: vec![],
+            },
+            TabStop {
+                number: 0,
+                ranges: vec![range(1, 1, 1)],
+                choices: vec![],
+            },
+        ]
+    );
+    // Without `$0` the snippet ends after its text, choices start with their first option
+    let expansion = Snippet::parse("${1|a,b|} $UNKNOWN")
+        .unwrap()
+        .expand(&Variables::default());
+    assert_eq!(expansion.lines, ["a UNKNOWN"]);
+    assert_eq!(expansion.stops[0].choices, ["a", "b"]);
+    assert_eq!(expansion.stops[1].ranges, [range(0, 9, 9)]);
+}
+
+#[test]
+fn inserted_lines_take_the_indent_of_the_line_and_expandtab() {
+    let expansion = Snippet::parse("if $1 {\n\t$0\n}")
+        .unwrap()
+        .expand(&Variables::default());
+    let mut buffer = Buffer::from_text("    iff\n");
+    buffer.options.expandtab = true;
+    buffer.options.shiftwidth = 2;
+    let mut session = SnippetSession::insert(&mut buffer, expansion.clone(), (0, 4), 7).unwrap();
+    assert_eq!(buffer.lines().as_slice(), ["    if  {", "      ", "    }"]);
+    assert_eq!(session.cursor(), (0, 7));
+    assert!(session.jump(true));
+    assert_eq!(session.cursor(), (1, 6));
+
+    let mut buffer = Buffer::from_text("\tiff\n");
+    buffer.options.tabstop = 4;
+    buffer.options.shiftwidth = 4;
+    let mut session = SnippetSession::insert(&mut buffer, expansion, (0, 1), 4).unwrap();
+    assert_eq!(buffer.lines().as_slice(), ["\tif  {", "\t\t", "\t}"]);
+    assert!(session.jump(true));
+    assert_eq!(session.cursor(), (1, 2));
+}
@@ -0,0 +1,136 @@
+use regex::Regex;
+
+use crate::syntax::{HighlightGroup, Span};
+
+/// Where a line leaves off, the region still open at its end if any
+pub type State = Option<usize>;
+
+/// One pattern of a grammar, like a TextMate `match` or `begin`/`end` rule
+pub enum Rule {
+    /// Text matching `pattern`, only its first capture group when it has one
+    Match {
+        pattern: Regex,
+        group: HighlightGroup,
+    },
+    /// Text from `begin` up to the end of `end`, which may be lines later
+    Region {
+        begin: Regex,
+        end: Regex,
+        group: HighlightGroup,
+    },
+}
+
+impl Rule {
+    pub fn matching(pattern: &str, group: HighlightGroup) -> Rule {
+        Rule::Match {
+            pattern: Regex::new(pattern).expect("built-in patterns are valid"),
+            group,
+        }
+    }
+
+    pub fn region(begin: &str, end: &str, group: HighlightGroup) -> Rule {
+        Rule::Region {
+            begin: Regex::new(begin).expect("built-in patterns are valid"),
+            end: Regex::new(end).expect("built-in patterns are valid"),
+            group,
+        }
+    }
+
+    fn start_pattern(&self) -> &Regex {
+        match self {
+            Rule::Match { pattern, .. } => pattern,
+            Rule::Region { begin, .. } => begin,
+        }
+    }
+}
+
+/// The rules for highlighting a language, tried in order at each position
+/// The rule matching earliest in the line wins, the first one listed when several match at the same place
+pub struct Grammar {
+    pub name: &'static str,
+    rules: Vec<Rule>,
+}
+
+impl Grammar {
+    pub fn new(name: &'static str, rules: Vec<Rule>) -> Self {
+        Self { name, rules }
+    }
+
+    /// Spans of one line in byte offsets, starting inside the region `state` left open
+    pub fn highlight_line(&self, line: &str, mut state: State) -> (Vec<Span>, State) {
+        let mut spans = Vec::new();
+        let mut pos = 0;
+        while pos <= line.len() {
+            if let Some(index) = state {
+                let Rule::Region { end, group, .. } = &self.rules[index] else {
+                    // States only ever point at regions
+                    return (spans, None);
+                };
+                match end.find_at(line, pos) {
+                    Some(found) => {
+                        push(&mut spans, pos, found.end(), *group);
+                        pos = found.end();
+                        state = None;
+                    }
+                    None => {
+                        push(&mut spans, pos, line.len(), *group);
+                        return (spans, state);
+                    }
+                }
+                continue;
+            }
+
+            let mut best: Option<(usize, usize, usize)> = None;
+            for (index, rule) in self.rules.iter().enumerate() {
+                let Some(found) = rule.start_pattern().find_at(line, pos) else {
+                    continue;
+                };
+                // Empty matches would never move on
+                if found.is_empty() {
+                    continue;
+                }
+                if best.is_none_or(|(start, _, _)| found.start() < start) {
+                    best = Some((found.start(), found.end(), index));
+                }
+            }
+            let Some((start, end, index)) = best else {
+                break;
+            };
+            match &self.rules[index] {
+                Rule::Match { pattern, group } => {
+                    let captured = (pattern.captures_len() > 1)
+                        .then(|| pattern.captures_at(line, pos))
+                        .flatten()
+                        .and_then(|captures| captures.get(1));
+                    match captured {
+                        Some(captured) => {
+                            push(&mut spans, captured.start(), captured.end(), *group)
+                        }
+                        None => push(&mut spans, start, end, *group),
+                    }
+                }
+                Rule::Region { group, .. } => {
+                    push(&mut spans, start, end, *group);
+                    state = Some(index);
+                }
+            }
+            pos = end;
+        }
+        (spans, state)
+    }
+}
+
+/// Add a span, merged into the one before when they touch and share a group
+fn push(spans: &mut Vec<Span>, start: usize, end: usize, group: HighlightGroup) {
+    if start >= end {
+        return;
+    }
+    if let Some(last) = spans.last_mut()
+        && last.end == start
+        && last.group == group
+    {
+        last.end = end;
+        return;
+    }
+    spans.push(Span { start, end, group });
+}
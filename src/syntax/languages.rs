@@ -0,0 +1,149 @@
+use std::sync::{Arc, OnceLock};
+
+use crate::syntax::{
+    HighlightGroup::*,
+    grammar::{Grammar, Rule},
+};
+
+/// The grammars shipped with the editor, by the filetype they highlight
+pub struct Languages {
+    grammars: Vec<Arc<Grammar>>,
+}
+
+impl Languages {
+    /// The built-in languages, their patterns are compiled the first time this is called
+    pub fn builtin() -> &'static Languages {
+        static LANGUAGES: OnceLock<Languages> = OnceLock::new();
+        LANGUAGES.get_or_init(|| Languages {
+            grammars: vec![
+                Arc::new(rust()),
+                Arc::new(json()),
+                Arc::new(toml()),
+                Arc::new(markdown()),
+                Arc::new(sh()),
+            ],
+        })
+    }
+
+    /// The grammar for a filetype detected by `buffer::filetype::detect`
+    pub fn get(&self, filetype: &str) -> Option<Arc<Grammar>> {
+        self.grammars
+            .iter()
+            .find(|grammar| grammar.name == filetype)
+            .cloned()
+    }
+}
+
+/// A string with backslash escapes, up to and including its closing quote
+const DOUBLE_QUOTED_END: &str = r#"(?:[^"\\]|\\.)*""#;
+
+fn rust() -> Grammar {
+    Grammar::new(
+        "rust",
+        vec![
+            Rule::matching(r"//.*", Comment),
+            Rule::region(r"/\*", r"\*/", Comment),
+            Rule::region(r##"b?r#+""##, r##""#+"##, String),
+            Rule::region(r#"b?r""#, r#"""#, String),
+            Rule::region(r#"b?""#, DOUBLE_QUOTED_END, String),
+            Rule::matching(r"b?'(?:[^'\\]|\\.|\\u\{[0-9a-fA-F]+\})'", String),
+            Rule::matching(r"'[a-zA-Z_][a-zA-Z0-9_]*\b", Label),
+            Rule::matching(r"#!?\[[^\]]*\]?", Attribute),
+            Rule::matching(r"\b[a-zA-Z_][a-zA-Z0-9_]*!", Macro),
+            Rule::matching(
+                r"\b(?:as|async|await|break|const|continue|crate|dyn|else|enum|extern|fn|for|if|impl|in|let|loop|match|mod|move|mut|pub|ref|return|self|Self|static|struct|super|trait|type|unsafe|use|where|while|yield)\b",
+                Keyword,
+            ),
+            Rule::matching(r"\b(?:true|false|None|Some|Ok|Err)\b", Constant),
+            Rule::matching(r"\b[A-Z][A-Z0-9_]+\b", Constant),
+            Rule::matching(
+                r"\b(?:[A-Z][a-zA-Z0-9_]*|bool|char|str|[iu](?:8|16|32|64|128|size)|f32|f64)\b",
+                Type,
+            ),
+            Rule::matching(
+                r"\b(?:0x[0-9a-fA-F_]+|0o[0-7_]+|0b[01_]+|[0-9][0-9_]*(?:\.[0-9][0-9_]*)?(?:[eE][+-]?[0-9_]+)?)(?:[iuf](?:8|16|32|64|128|size))?\b",
+                Number,
+            ),
+            Rule::matching(r"\b([a-z_][a-zA-Z0-9_]*)\s*(?:::<[^>]*>)?\(", Function),
+        ],
+    )
+}
+
+fn json() -> Grammar {
+    Grammar::new(
+        "json",
+        vec![
+            Rule::matching(r#"("(?:[^"\\]|\\.)*")\s*:"#, Property),
+            Rule::matching(r#""(?:[^"\\]|\\.)*""#, String),
+            Rule::matching(r"-?\b[0-9]+(?:\.[0-9]+)?(?:[eE][+-]?[0-9]+)?\b", Number),
+            Rule::matching(r"\b(?:true|false|null)\b", Constant),
+        ],
+    )
+}
+
+fn toml() -> Grammar {
+    Grammar::new(
+        "toml",
+        vec![
+            Rule::matching(r"#.*", Comment),
+            Rule::matching(r"^\s*\[\[?[^\]]*\]\]?", Heading),
+            Rule::matching(r#"^\s*([A-Za-z0-9_\-."']+)\s*="#, Property),
+            Rule::region(r#"""""#, r#"""""#, String),
+            Rule::region(r"'''", r"'''", String),
+            Rule::matching(r#""(?:[^"\\]|\\.)*""#, String),
+            Rule::matching(r"'[^']*'", String),
+            Rule::matching(
+                r"\b[0-9]{4}-[0-9]{2}-[0-9]{2}(?:[T ][0-9]{2}:[0-9]{2}:[0-9]{2}(?:\.[0-9]+)?(?:Z|[+-][0-9]{2}:[0-9]{2})?)?",
+                Constant,
+            ),
+            Rule::matching(r"\b(?:true|false|inf|nan)\b", Constant),
+            Rule::matching(
+                r"[+-]?\b(?:0x[0-9a-fA-F_]+|0o[0-7_]+|0b[01_]+|[0-9][0-9_]*(?:\.[0-9_]+)?(?:[eE][+-]?[0-9_]+)?)\b",
+                Number,
+            ),
+        ],
+    )
+}
+
+fn markdown() -> Grammar {
+    Grammar::new(
+        "markdown",
+        vec![
+            Rule::region(r"^\s*```.*", r"^\s*```\s*$", Code),
+            Rule::region(r"^\s*~~~.*", r"^\s*~~~\s*$", Code),
+            Rule::matching(r"^#{1,6}\s.*", Heading),
+            Rule::matching(r"^\s*>.*", Comment),
+            Rule::matching(r"^\s*(?:[-*+]|[0-9]+[.)])\s", Operator),
+            Rule::matching(r"`[^`]+`", Code),
+            Rule::matching(r"\*\*[^*]+\*\*|__[^_]+__", Strong),
+            Rule::matching(r"\*[^*\s][^*]*\*|\b_[^_\s][^_]*_\b", Emphasis),
+            Rule::matching(r"!?\[[^\]]*\]\([^)]*\)|<https?://[^>]*>", Link),
+        ],
+    )
+}
+
+fn sh() -> Grammar {
+    Grammar::new(
+        "sh",
+        vec![
+            Rule::matching(r"(?:^|\s)(#.*)", Comment),
+            Rule::region(r#"""#, DOUBLE_QUOTED_END, String),
+            Rule::region(r"'", r"'", String),
+            Rule::matching(
+                r"\$(?:\{[^}]*\}|[a-zA-Z_][a-zA-Z0-9_]*|[@#?$!*0-9-])",
+                Variable,
+            ),
+            Rule::matching(
+                r"\b(?:if|then|else|elif|fi|for|while|until|do|done|case|esac|in|function|select|return|exit|local|export|readonly|declare|unset|shift|break|continue)\b",
+                Keyword,
+            ),
+            Rule::matching(
+                r"\b(?:echo|cd|printf|read|source|test|eval|exec|set|trap|alias|pwd)\b",
+                Function,
+            ),
+            Rule::matching(r"^\s*([a-zA-Z_][a-zA-Z0-9_]*)\s*\(\)", Function),
+            Rule::matching(r"\b[0-9]+\b", Number),
+            Rule::matching(r"&&|\|\||[|;&<>]", Operator),
+        ],
+    )
+}
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use crate::buffer::undo::Edit;
+
+pub mod grammar;
+pub mod languages;
+#[cfg(test)]
+mod tests;
+pub mod theme;
+#[cfg(feature = "tree-sitter")]
+mod tree;
+#[cfg(not(feature = "tree-sitter"))]
+#[path = "no_tree.rs"]
+mod tree;
+
+use grammar::{Grammar, State};
+pub use tree::SyntaxTree;
+
+/// A syntax node a text object command selects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextObject {
+    /// `af`/`if`, a function, method or closure
+    Function,
+    /// `ac`/`ic`, a type, trait, impl or module
+    Class,
+}
+
+/// The kind of text a span holds, themes pick a color for each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HighlightGroup {
+    Comment,
+    String,
+    /// Escapes and other special characters inside strings
+    Escape,
+    Number,
+    /// Booleans, null and named constants
+    Constant,
+    Keyword,
+    Type,
+    Function,
+    Macro,
+    Attribute,
+    /// Keys in tables and objects
+    Property,
+    Variable,
+    Operator,
+    Label,
+    Heading,
+    Emphasis,
+    Strong,
+    /// Inline and fenced code in prose
+    Code,
+    Link,
+}
+
+/// Highlighted bytes `start..end` of a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub group: HighlightGroup,
+}
+
+/// What is known about one line from the last time it was highlighted
+#[derive(Default)]
+struct LineHighlight {
+    /// State the line was highlighted from
+    start: State,
+    /// State the line left for the next one
+    end: State,
+    spans: Vec<Span>,
+    /// The line changed since, its spans can't be used
+    stale: bool,
+}
+
+impl LineHighlight {
+    fn stale() -> Self {
+        Self {
+            stale: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// Highlights a buffer's lines as they are asked for, keeping the results between edits
+/// Lines are highlighted in order from the first one not known to be up to date,
+/// an edit only sends that back to where it happened, and highlighting skips ahead again
+/// once it reaches an unchanged line that starts from the same state as before
+pub struct Highlighter {
+    grammar: Arc<Grammar>,
+    lines: Vec<LineHighlight>,
+    /// Lines before this one are up to date
+    valid_until: usize,
+}
+
+impl Highlighter {
+    pub fn new(grammar: Arc<Grammar>) -> Self {
+        Self {
+            grammar,
+            lines: Vec::new(),
+            valid_until: 0,
+        }
+    }
+
+    /// Follow an edit made to the buffer's lines
+    pub fn edited(&mut self, edit: &Edit) {
+        let at = match edit {
+            Edit::Insert { at, lines } => {
+                let at = (*at).min(self.lines.len());
+                self.lines
+                    .splice(at..at, lines.iter().map(|_| LineHighlight::stale()));
+                at
+            }
+            Edit::Remove { at, lines } => {
+                let start = (*at).min(self.lines.len());
+                let end = (start + lines.len()).min(self.lines.len());
+                self.lines.drain(start..end);
+                start
+            }
+            Edit::Replace { at, .. } => {
+                if let Some(line) = self.lines.get_mut(*at) {
+                    line.stale = true;
+                }
+                *at
+            }
+        };
+        self.valid_until = self.valid_until.min(at);
+    }
+
+    /// Forget everything, for when the whole text was replaced
+    pub fn reset(&mut self) {
+        self.lines.clear();
+        self.valid_until = 0;
+    }
+
+    /// The spans of `line`, highlighting whatever lines before it are out of date
+    pub fn spans(&mut self, text: &[String], line: usize) -> &[Span] {
+        if line >= text.len() {
+            return &[];
+        }
+        self.lines.resize_with(text.len(), LineHighlight::stale);
+        while self.valid_until <= line {
+            let index = self.valid_until;
+            let start = match index {
+                0 => None,
+                _ => self.lines[index - 1].end,
+            };
+            let cached = &self.lines[index];
+            if !cached.stale && cached.start == start {
+                // Unchanged and starting as it did, so it ends as it did too
+                // Each line is checked, the one after may have been highlighted from another state
+                self.valid_until += 1;
+                continue;
+            }
+            let (spans, end) = self.grammar.highlight_line(&text[index], start);
+            self.lines[index] = LineHighlight {
+                start,
+                end,
+                spans,
+                stale: false,
+            };
+            self.valid_until += 1;
+        }
+        &self.lines[line].spans
+    }
+}
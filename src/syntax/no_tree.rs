@@ -0,0 +1,51 @@
+use crate::{
+    buffer::{TextRange, undo::Edit},
+    syntax::{Span, TextObject},
+};
+
+/// Without the `tree-sitter` feature there are no grammars, so never a tree
+pub enum SyntaxTree {}
+
+impl SyntaxTree {
+    pub fn new(_filetype: &str) -> Option<SyntaxTree> {
+        None
+    }
+
+    pub fn language(&self) -> &'static str {
+        match *self {}
+    }
+
+    pub fn edited(&mut self, _lines: &[String], _edit: &Edit) {
+        match *self {}
+    }
+
+    pub fn reset(&mut self) {
+        match *self {}
+    }
+
+    pub fn highlights(&mut self, _lines: &[String], _line: usize) -> Vec<Span> {
+        match *self {}
+    }
+
+    pub fn text_object(
+        &mut self,
+        _lines: &[String],
+        _cursor: (usize, usize),
+        _object: TextObject,
+        _inner: bool,
+    ) -> Option<TextRange> {
+        match *self {}
+    }
+
+    pub fn expand(&mut self, _lines: &[String], _range: TextRange) -> Option<TextRange> {
+        match *self {}
+    }
+
+    pub fn nodes_at(&mut self, _lines: &[String], _cursor: (usize, usize)) -> Vec<String> {
+        match *self {}
+    }
+
+    pub fn folds(&mut self, _lines: &[String]) -> Vec<(usize, usize)> {
+        match *self {}
+    }
+}
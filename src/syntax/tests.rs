@@ -0,0 +1,469 @@
+use std::sync::Arc;
+
+use proptest::prelude::*;
+
+use super::{
+    HighlightGroup::{
+        self, Attribute, Code, Comment, Constant, Function, Heading, Keyword, Link, Macro, Number,
+        Operator, Property, Strong, Type, Variable,
+    },
+    Highlighter, Span,
+    grammar::Grammar,
+    languages::Languages,
+};
+use crate::buffer::undo::Edit;
+
+fn grammar(filetype: &str) -> Arc<Grammar> {
+    Languages::builtin().get(filetype).unwrap()
+}
+
+/// The text of each span of `line` with its group, highlighting from the start of the file
+fn highlighted<'a>(filetype: &str, line: &'a str) -> Vec<(&'a str, HighlightGroup)> {
+    let (spans, _) = grammar(filetype).highlight_line(line, None);
+    spans
+        .iter()
+        .map(|span| (&line[span.start..span.end], span.group))
+        .collect()
+}
+
+fn lines(text: &str) -> Vec<String> {
+    text.lines().map(str::to_string).collect()
+}
+
+fn all_spans(highlighter: &mut Highlighter, text: &[String]) -> Vec<Vec<Span>> {
+    (0..text.len())
+        .map(|line| highlighter.spans(text, line).to_vec())
+        .collect()
+}
+
+#[test]
+fn rust_lines_are_highlighted() {
+    assert_eq!(
+        highlighted("rust", "let x = foo(1); // done"),
+        [
+            ("let", Keyword),
+            ("foo", Function),
+            ("1", Number),
+            ("// done", Comment)
+        ]
+    );
+    assert_eq!(
+        highlighted(
+            "rust",
+            r#"#[derive(Debug)] struct A; println!("a\n", 'b');"#
+        ),
+        [
+            ("#[derive(Debug)]", Attribute),
+            ("struct", Keyword),
+            ("A", Type),
+            ("println!", Macro),
+            (r#""a\n""#, HighlightGroup::String),
+            ("'b'", HighlightGroup::String),
+        ]
+    );
+}
+
+#[test]
+fn json_lines_are_highlighted() {
+    assert_eq!(
+        highlighted(
+            "json",
+            r#"{"key": "value", "n": -1.5e3, "ok": true, "x": null}"#
+        ),
+        [
+            (r#""key""#, Property),
+            (r#""value""#, HighlightGroup::String),
+            (r#""n""#, Property),
+            ("-1.5e3", Number),
+            (r#""ok""#, Property),
+            ("true", Constant),
+            (r#""x""#, Property),
+            ("null", Constant),
+        ]
+    );
+}
+
+#[test]
+fn toml_lines_are_highlighted() {
+    assert_eq!(highlighted("toml", "[package]"), [("[package]", Heading)]);
+    assert_eq!(
+        highlighted("toml", r#"name = "reovim" # note"#),
+        [
+            ("name", Property),
+            (r#""reovim""#, HighlightGroup::String),
+            ("# note", Comment)
+        ]
+    );
+    assert_eq!(
+        highlighted("toml", "date = 2024-01-02"),
+        [("date", Property), ("2024-01-02", Constant)]
+    );
+}
+
+#[test]
+fn markdown_lines_are_highlighted() {
+    assert_eq!(highlighted("markdown", "# Title"), [("# Title", Heading)]);
+    assert_eq!(
+        highlighted("markdown", "- a `b` **c** [d](e)"),
+        [
+            ("- ", Operator),
+            ("`b`", Code),
+            ("**c**", Strong),
+            ("[d](e)", Link),
+        ]
+    );
+}
+
+#[test]
+fn sh_lines_are_highlighted() {
+    assert_eq!(
+        highlighted("sh", r#"if [ -n "$HOME" ]; then echo $1 # hi"#),
+        [
+            ("if", Keyword),
+            (r#""$HOME""#, HighlightGroup::String),
+            (";", Operator),
+            ("then", Keyword),
+            ("echo", Function),
+            ("$1", Variable),
+            ("# hi", Comment),
+        ]
+    );
+}
+
+#[test]
+fn regions_carry_on_to_the_lines_after() {
+    let rust = grammar("rust");
+    let (spans, state) = rust.highlight_line("let a = 1; /* one", None);
+    assert_eq!(spans.last().unwrap().group, Comment);
+    let (spans, state) = rust.highlight_line("two", state);
+    assert_eq!(
+        spans,
+        [Span {
+            start: 0,
+            end: 3,
+            group: Comment
+        }]
+    );
+    let (spans, state) = rust.highlight_line("*/ let", state);
+    assert_eq!(
+        spans,
+        [
+            Span {
+                start: 0,
+                end: 2,
+                group: Comment
+            },
+            Span {
+                start: 3,
+                end: 6,
+                group: Keyword
+            },
+        ]
+    );
+    assert_eq!(state, None);
+
+    let mut highlighter = Highlighter::new(grammar("sh"));
+    let text = lines("echo 'one\ntwo' done");
+    assert_eq!(
+        highlighter.spans(&text, 1),
+        [
+            Span {
+                start: 0,
+                end: 4,
+                group: HighlightGroup::String
+            },
+            Span {
+                start: 5,
+                end: 9,
+                group: Keyword
+            },
+        ]
+    );
+}
+
+#[test]
+fn edited_lines_are_highlighted_again_and_the_rest_skipped() {
+    let mut text: Vec<String> = (0..100)
+        .map(|line| format!("let a{line} = {line};"))
+        .collect();
+    let mut highlighter = Highlighter::new(grammar("rust"));
+    highlighter.spans(&text, 99);
+    // Spans no highlight would give, to tell the lines highlighted again from those skipped
+    let marked = vec![Span {
+        start: 0,
+        end: 1,
+        group: Link,
+    }];
+    highlighter.lines[60].spans = marked.clone();
+
+    let edit = Edit::Replace {
+        at: 10,
+        before: text[10].clone(),
+        after: "let b = 1;".to_string(),
+    };
+    edit.apply(&mut text);
+    highlighter.edited(&edit);
+    assert!(highlighter.lines[10].stale);
+    highlighter.spans(&text, 99);
+    // The edited line ends as it did, the lines after start from the same state and are kept
+    assert_eq!(highlighter.lines[60].spans, marked);
+    assert_eq!(highlighter.spans(&text, 10)[0].end, 3);
+
+    // A comment left open changes how every line after it starts, so they are all highlighted again
+    let edit = Edit::Replace {
+        at: 10,
+        before: text[10].clone(),
+        after: "/* let b = 1;".to_string(),
+    };
+    edit.apply(&mut text);
+    highlighter.edited(&edit);
+    // Asking for a line before the edit doesn't highlight past it
+    highlighter.spans(&text, 5);
+    assert_eq!(highlighter.valid_until, 10);
+    assert_eq!(
+        highlighter.spans(&text, 60),
+        [Span {
+            start: 0,
+            end: text[60].len(),
+            group: Comment
+        }]
+    );
+}
+
+#[test]
+fn an_edited_highlighter_matches_a_fresh_one() {
+    let mut text = lines("fn a() {\n    let s = \"x\";\n}\n/* c */\nfn b() {}");
+    let mut highlighter = Highlighter::new(grammar("rust"));
+    all_spans(&mut highlighter, &text);
+    let edits = [
+        Edit::Insert {
+            at: 1,
+            lines: lines("/* open\nstill"),
+        },
+        Edit::Replace {
+            at: 4,
+            before: "}".to_string(),
+            after: "} */ let b = \"two".to_string(),
+        },
+        Edit::Remove {
+            at: 0,
+            lines: lines("fn a() {"),
+        },
+        Edit::Insert {
+            at: 5,
+            lines: lines("lines\"; 3"),
+        },
+    ];
+    for edit in edits {
+        edit.apply(&mut text);
+        highlighter.edited(&edit);
+        let mut fresh = Highlighter::new(grammar("rust"));
+        assert_eq!(
+            all_spans(&mut highlighter, &text),
+            all_spans(&mut fresh, &text),
+            "after {edit:?}"
+        );
+    }
+}
+
+fn highlighter_edit(line_count: usize) -> impl Strategy<Value = Edit> {
+    let text = prop::sample::select(vec![
+        "",
+        "/* a",
+        "b */",
+        "\"c",
+        "d\" e",
+        "let f = 1;",
+        "// g",
+    ])
+    .prop_map(str::to_string);
+    prop_oneof![
+        (0..=line_count, prop::collection::vec(text.clone(), 1..3))
+            .prop_map(|(at, lines)| Edit::Insert { at, lines }),
+        (0..line_count, text).prop_map(|(at, after)| Edit::Replace {
+            at,
+            before: String::new(),
+            after,
+        }),
+        (0..line_count).prop_map(|at| Edit::Remove {
+            at,
+            lines: vec![String::new()],
+        }),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn spans_after_edits_match_a_fresh_highlight(
+        edits in prop::collection::vec(highlighter_edit(6), 1..8),
+        asked in prop::collection::vec(0usize..8, 1..4),
+    ) {
+        let mut text = lines("a\n/* b\nc */\n\"d\ne\"\nf");
+        let mut highlighter = Highlighter::new(grammar("rust"));
+        for edit in edits {
+            // The edits are made to fit the lines they land on as they're applied
+            let edit = match edit {
+                Edit::Insert { at, lines } => Edit::Insert { at: at.min(text.len()), lines },
+                Edit::Replace { at, after, .. } if at < text.len() => {
+                    Edit::Replace { at, before: text[at].clone(), after }
+                }
+                Edit::Remove { at, .. } if at < text.len() && text.len() > 1 => {
+                    Edit::Remove { at, lines: vec![text[at].clone()] }
+                }
+                _ => continue,
+            };
+            edit.apply(&mut text);
+            highlighter.edited(&edit);
+            // Only some lines are asked for between edits, like the ones shown on screen
+            for &line in &asked {
+                highlighter.spans(&text, line);
+            }
+        }
+        let mut fresh = Highlighter::new(grammar("rust"));
+        prop_assert_eq!(all_spans(&mut highlighter, &text), all_spans(&mut fresh, &text));
+    }
+}
+
+/// Tree-sitter's answers, the grammars above need no feature
+#[cfg(feature = "tree-sitter")]
+mod tree {
+    use proptest::prelude::*;
+
+    use super::lines;
+    use crate::{
+        buffer::undo::Edit,
+        syntax::{SyntaxTree, TextObject},
+    };
+
+    const SOURCE: &str = "fn one() {
+    let a = [1, 2];
+}
+
+struct Two {
+    b: u8,
+}
+
+fn three() -> u8 {
+    match 3 {
+        _ => 3,
+    }
+}";
+
+    /// Everything the editor asks the tree for, to compare an edited tree with one parsed afresh
+    fn answers(tree: &mut SyntaxTree, lines: &[String]) -> Vec<String> {
+        let mut answers = vec![format!("{:?}", tree.folds(lines))];
+        for (line, text) in lines.iter().enumerate() {
+            answers.push(format!("{:?}", tree.highlights(lines, line)));
+            for col in [0, text.chars().count() / 2] {
+                answers.push(format!("{:?}", tree.nodes_at(lines, (line, col))));
+                let function = tree.text_object(lines, (line, col), TextObject::Function, false);
+                answers.push(format!("{function:?}"));
+            }
+        }
+        answers
+    }
+
+    /// Apply `edits` to the source one after another, telling the tree about each and parsing it again
+    /// only after `parse_every` of them
+    fn edited_answers(edits: &[Edit], parse_every: usize) -> (Vec<String>, Vec<String>) {
+        let mut lines = lines(SOURCE);
+        let mut tree = SyntaxTree::new("rust").unwrap();
+        answers(&mut tree, &lines);
+        for (index, edit) in edits.iter().enumerate() {
+            edit.apply(&mut lines);
+            tree.edited(&lines, edit);
+            if index % parse_every == 0 {
+                tree.highlights(&lines, 0);
+            }
+        }
+        let mut fresh = SyntaxTree::new("rust").unwrap();
+        (answers(&mut tree, &lines), answers(&mut fresh, &lines))
+    }
+
+    #[test]
+    fn edits_anywhere_keep_the_tree_in_step() {
+        let edits = [
+            Edit::Replace {
+                at: 1,
+                before: "    let a = [1, 2];".to_string(),
+                after: "    let a = [1, 2, 3];".to_string(),
+            },
+            Edit::Insert {
+                at: 9,
+                lines: lines("fn four() {}\n"),
+            },
+            Edit::Remove {
+                at: 4,
+                lines: lines("struct Two {\n    b: u8,\n}"),
+            },
+            Edit::Insert {
+                at: 0,
+                lines: lines("// é\nfn zero() {\n}"),
+            },
+            Edit::Replace {
+                at: 10,
+                before: "    match 3 {".to_string(),
+                after: "    match \"ü\" {".to_string(),
+            },
+        ];
+        for parse_every in [1, 2, usize::MAX] {
+            let (edited, fresh) = edited_answers(&edits, parse_every);
+            assert_eq!(edited, fresh, "parsed every {parse_every} edits");
+        }
+    }
+
+    fn edit(line_count: usize) -> impl Strategy<Value = Edit> {
+        let text = prop::sample::select(vec![
+            "",
+            "}",
+            "fn x() {",
+            "let y = \"é\";",
+            "    ",
+            "struct Z;",
+        ])
+        .prop_map(str::to_string);
+        prop_oneof![
+            (0..=line_count, prop::collection::vec(text.clone(), 1..3))
+                .prop_map(|(at, lines)| Edit::Insert { at, lines }),
+            (0..line_count, text).prop_map(|(at, after)| Edit::Replace {
+                at,
+                before: String::new(),
+                after,
+            }),
+            (0..line_count).prop_map(|at| Edit::Remove {
+                at,
+                lines: vec![String::new()],
+            }),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn an_edited_tree_answers_as_a_fresh_one(
+            edits in prop::collection::vec(edit(SOURCE.lines().count()), 1..8),
+            parse_every in 1usize..4,
+        ) {
+            // The edits are made to fit the lines they land on as they're applied
+            let mut lines = lines(SOURCE);
+            let edits: Vec<Edit> = edits
+                .into_iter()
+                .filter_map(|edit| {
+                    let edit = match edit {
+                        Edit::Insert { at, lines: inserted } => Edit::Insert { at: at.min(lines.len()), lines: inserted },
+                        Edit::Replace { at, after, .. } if at < lines.len() => {
+                            Edit::Replace { at, before: lines[at].clone(), after }
+                        }
+                        Edit::Remove { at, .. } if at < lines.len() && lines.len() > 1 => {
+                            Edit::Remove { at, lines: vec![lines[at].clone()] }
+                        }
+                        _ => return None,
+                    };
+                    edit.apply(&mut lines);
+                    Some(edit)
+                })
+                .collect();
+            let (edited, fresh) = edited_answers(&edits, parse_every);
+            prop_assert_eq!(edited, fresh);
+        }
+    }
+}
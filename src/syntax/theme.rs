@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use crossterm::style::Color;
+
+use crate::syntax::HighlightGroup;
+
+/// The color text of each highlight group is drawn in, groups without one keep the default color
+pub struct Theme {
+    colors: HashMap<HighlightGroup, Color>,
+}
+
+impl Theme {
+    pub fn color(&self, group: HighlightGroup) -> Option<Color> {
+        self.colors.get(&group).copied()
+    }
+}
+
+impl Default for Theme {
+    /// Colors from the 16 color palette so they look right on any dark terminal
+    fn default() -> Self {
+        let colors = HashMap::from([
+            (HighlightGroup::Comment, Color::DarkGreen),
+            (HighlightGroup::String, Color::Green),
+            (HighlightGroup::Escape, Color::Magenta),
+            (HighlightGroup::Number, Color::Magenta),
+            (HighlightGroup::Constant, Color::Magenta),
+            (HighlightGroup::Keyword, Color::Yellow),
+            (HighlightGroup::Type, Color::Cyan),
+            (HighlightGroup::Function, Color::Blue),
+            (HighlightGroup::Macro, Color::DarkMagenta),
+            (HighlightGroup::Attribute, Color::DarkYellow),
+            (HighlightGroup::Property, Color::Cyan),
+            (HighlightGroup::Variable, Color::Cyan),
+            (HighlightGroup::Operator, Color::DarkYellow),
+            (HighlightGroup::Label, Color::Red),
+            (HighlightGroup::Heading, Color::Yellow),
+            (HighlightGroup::Emphasis, Color::White),
+            (HighlightGroup::Strong, Color::White),
+            (HighlightGroup::Code, Color::Green),
+            (HighlightGroup::Link, Color::Blue),
+        ]);
+        Self { colors }
+    }
+}
@@ -0,0 +1,482 @@
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    sync::{Arc, OnceLock},
+};
+
+use tree_sitter::{
+    InputEdit, Language, Node, Parser, Point, Query, QueryCursor, StreamingIterator, Tree,
+};
+
+use crate::{
+    buffer::{TextRange, byte_index, undo::Edit},
+    syntax::{HighlightGroup, Span, TextObject},
+};
+
+/// A compiled-in tree-sitter grammar with what the editor needs to know about its nodes
+pub struct TreeGrammar {
+    name: &'static str,
+    language: Language,
+    highlights: Query,
+    /// The highlight group of each of the query's captures
+    groups: Vec<Option<HighlightGroup>>,
+    /// Node kinds `af`/`if` select
+    functions: &'static [&'static str],
+    /// Node kinds `ac`/`ic` select
+    classes: &'static [&'static str],
+    /// Node kinds that can be folded when they span several lines
+    folds: &'static [&'static str],
+}
+
+impl TreeGrammar {
+    fn new(
+        name: &'static str,
+        language: Language,
+        highlights: &str,
+        (functions, classes, folds): (
+            &'static [&'static str],
+            &'static [&'static str],
+            &'static [&'static str],
+        ),
+    ) -> Self {
+        let highlights = Query::new(&language, highlights).expect("bundled queries are valid");
+        let groups = highlights
+            .capture_names()
+            .iter()
+            .map(|name| capture_group(name))
+            .collect();
+        Self {
+            name,
+            language,
+            highlights,
+            groups,
+            functions,
+            classes,
+            folds,
+        }
+    }
+
+    /// The grammar for a filetype, when one is compiled in
+    pub fn get(filetype: &str) -> Option<Arc<TreeGrammar>> {
+        static GRAMMARS: OnceLock<Vec<Arc<TreeGrammar>>> = OnceLock::new();
+        GRAMMARS
+            .get_or_init(|| {
+                vec![
+                    Arc::new(TreeGrammar::new(
+                        "rust",
+                        tree_sitter_rust::LANGUAGE.into(),
+                        tree_sitter_rust::HIGHLIGHTS_QUERY,
+                        (
+                            &[
+                                "function_item",
+                                "function_signature_item",
+                                "closure_expression",
+                            ],
+                            &[
+                                "struct_item",
+                                "enum_item",
+                                "union_item",
+                                "trait_item",
+                                "impl_item",
+                                "mod_item",
+                            ],
+                            &[
+                                "block",
+                                "declaration_list",
+                                "field_declaration_list",
+                                "enum_variant_list",
+                                "match_block",
+                                "use_list",
+                                "block_comment",
+                                "arguments",
+                                "array_expression",
+                            ],
+                        ),
+                    )),
+                    Arc::new(TreeGrammar::new(
+                        "json",
+                        tree_sitter_json::LANGUAGE.into(),
+                        tree_sitter_json::HIGHLIGHTS_QUERY,
+                        (&[], &[], &["object", "array"]),
+                    )),
+                    Arc::new(TreeGrammar::new(
+                        "sh",
+                        tree_sitter_bash::LANGUAGE.into(),
+                        tree_sitter_bash::HIGHLIGHT_QUERY,
+                        (
+                            &["function_definition"],
+                            &[],
+                            &[
+                                "compound_statement",
+                                "if_statement",
+                                "case_statement",
+                                "do_group",
+                                "heredoc_body",
+                            ],
+                        ),
+                    )),
+                ]
+            })
+            .iter()
+            .find(|grammar| grammar.name == filetype)
+            .cloned()
+    }
+}
+
+/// The group for a capture name from a grammar's `highlights.scm`, like `function.method`
+fn capture_group(name: &str) -> Option<HighlightGroup> {
+    let group = match name {
+        "string.special.key" => HighlightGroup::Property,
+        "function.macro" => HighlightGroup::Macro,
+        "variable.builtin" => HighlightGroup::Keyword,
+        "comment" | "comment.documentation" => HighlightGroup::Comment,
+        _ => match name.split('.').next()? {
+            "string" => HighlightGroup::String,
+            "escape" => HighlightGroup::Escape,
+            "number" => HighlightGroup::Number,
+            "constant" | "boolean" => HighlightGroup::Constant,
+            "keyword" => HighlightGroup::Keyword,
+            "type" | "constructor" => HighlightGroup::Type,
+            "function" => HighlightGroup::Function,
+            "attribute" => HighlightGroup::Attribute,
+            "property" => HighlightGroup::Property,
+            "operator" => HighlightGroup::Operator,
+            "label" => HighlightGroup::Label,
+            _ => return None,
+        },
+    };
+    Some(group)
+}
+
+/// A buffer's syntax tree, edited along with the buffer and reparsed the next time it's needed
+pub struct SyntaxTree {
+    grammar: Arc<TreeGrammar>,
+    parser: Parser,
+    tree: Option<Tree>,
+    /// The tree was edited since it was last parsed
+    edited: bool,
+    /// The text the tree was parsed from, every line followed by a line break
+    source: String,
+    /// Byte offset of each line in `source` once parsed, edits keep those of the lines before them
+    /// and drop the rest, to be counted again only as far as a later edit needs
+    offsets: Vec<usize>,
+}
+
+impl SyntaxTree {
+    /// A tree for a filetype with a compiled-in grammar
+    pub fn new(filetype: &str) -> Option<SyntaxTree> {
+        let grammar = TreeGrammar::get(filetype)?;
+        let mut parser = Parser::new();
+        parser.set_language(&grammar.language).ok()?;
+        Some(SyntaxTree {
+            grammar,
+            parser,
+            tree: None,
+            edited: true,
+            source: String::new(),
+            offsets: Vec::new(),
+        })
+    }
+
+    pub fn language(&self) -> &'static str {
+        self.grammar.name
+    }
+
+    /// Follow an edit that was just applied to `lines`
+    pub fn edited(&mut self, lines: &[String], edit: &Edit) {
+        self.edited = true;
+        let Some(tree) = &mut self.tree else {
+            return;
+        };
+        // Lines before the edit are the same as before it, so are their offsets
+        let at = match edit {
+            Edit::Insert { at, .. } | Edit::Remove { at, .. } | Edit::Replace { at, .. } => *at,
+        }
+        .min(lines.len());
+        while self.offsets.len() <= at {
+            let offset = match self.offsets.len() {
+                0 => 0,
+                next => self.offsets[next - 1] + lines[next - 1].len() + 1,
+            };
+            self.offsets.push(offset);
+        }
+        self.offsets.truncate(at + 1);
+        let start_byte = self.offsets[at];
+        let row_start = Point::new(at, 0);
+        let input = match edit {
+            Edit::Insert { lines, .. } => {
+                let inserted: usize = lines.iter().map(|line| line.len() + 1).sum();
+                InputEdit {
+                    start_byte,
+                    old_end_byte: start_byte,
+                    new_end_byte: start_byte + inserted,
+                    start_position: row_start,
+                    old_end_position: row_start,
+                    new_end_position: Point::new(at + lines.len(), 0),
+                }
+            }
+            Edit::Remove { lines, .. } => {
+                let removed: usize = lines.iter().map(|line| line.len() + 1).sum();
+                InputEdit {
+                    start_byte,
+                    old_end_byte: start_byte + removed,
+                    new_end_byte: start_byte,
+                    start_position: row_start,
+                    old_end_position: Point::new(at + lines.len(), 0),
+                    new_end_position: row_start,
+                }
+            }
+            Edit::Replace { before, after, .. } => {
+                // Only the part between what the two versions start and end with changed
+                let prefix = common_prefix(before, after);
+                let suffix = common_suffix(&before[prefix..], &after[prefix..]);
+                let old_end = before.len() - suffix;
+                let new_end = after.len() - suffix;
+                InputEdit {
+                    start_byte: start_byte + prefix,
+                    old_end_byte: start_byte + old_end,
+                    new_end_byte: start_byte + new_end,
+                    start_position: Point::new(at, prefix),
+                    old_end_position: Point::new(at, old_end),
+                    new_end_position: Point::new(at, new_end),
+                }
+            }
+        };
+        tree.edit(&input);
+    }
+
+    /// Parse from scratch the next time, for when the whole text was replaced
+    pub fn reset(&mut self) {
+        self.tree = None;
+        self.edited = true;
+    }
+
+    /// Bring the tree up to date with `lines`, reusing what the edits left of the old one
+    fn parse(&mut self, lines: &[String]) -> Option<&Tree> {
+        if self.edited || self.tree.is_none() {
+            self.source.clear();
+            self.offsets.clear();
+            for line in lines {
+                self.offsets.push(self.source.len());
+                self.source.push_str(line);
+                self.source.push('\n');
+            }
+            self.tree = self.parser.parse(&self.source, self.tree.as_ref());
+            self.edited = false;
+        }
+        self.tree.as_ref()
+    }
+
+    fn byte(&self, lines: &[String], (line, col): (usize, usize)) -> usize {
+        match self.offsets.get(line) {
+            Some(offset) => offset + byte_index(&lines[line], col),
+            None => self.source.len(),
+        }
+    }
+
+    fn position(&self, lines: &[String], byte: usize) -> (usize, usize) {
+        let line = self
+            .offsets
+            .partition_point(|&offset| offset <= byte)
+            .saturating_sub(1);
+        let text = lines.get(line).map(String::as_str).unwrap_or_default();
+        let in_line = (byte - self.offsets.get(line).copied().unwrap_or(0)).min(text.len());
+        (line, text[..in_line].chars().count())
+    }
+
+    fn range(&self, lines: &[String], node: Node) -> TextRange {
+        TextRange {
+            start: self.position(lines, node.start_byte()),
+            end: self.position(lines, node.end_byte()),
+        }
+    }
+
+    /// Highlighted spans of one line from the grammar's highlight query
+    pub fn highlights(&mut self, lines: &[String], line: usize) -> Vec<Span> {
+        let Some(tree) = self.parse(lines).cloned() else {
+            return Vec::new();
+        };
+        let Some(&start) = self.offsets.get(line) else {
+            return Vec::new();
+        };
+        let end = start + lines[line].len();
+        let mut groups: Vec<Option<HighlightGroup>> = vec![None; end - start];
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(start..end);
+        let mut captures = cursor.captures(
+            &self.grammar.highlights,
+            tree.root_node(),
+            self.source.as_bytes(),
+        );
+        // A node takes the group of the first pattern that captures it, nodes inside it paint over it
+        let mut painted = HashSet::new();
+        while let Some((found, index)) = captures.next() {
+            let capture = found.captures()[*index];
+            if !painted.insert(capture.node.id()) {
+                continue;
+            }
+            let Some(group) = self.grammar.groups[capture.index as usize] else {
+                continue;
+            };
+            let from = capture.node.start_byte().clamp(start, end) - start;
+            let to = capture.node.end_byte().clamp(start, end) - start;
+            groups[from..to].fill(Some(group));
+        }
+        let mut spans: Vec<Span> = Vec::new();
+        for (byte, group) in groups.into_iter().enumerate() {
+            let Some(group) = group else {
+                continue;
+            };
+            match spans.last_mut() {
+                Some(last) if last.end == byte && last.group == group => last.end += 1,
+                _ => spans.push(Span {
+                    start: byte,
+                    end: byte + 1,
+                    group,
+                }),
+            }
+        }
+        spans
+    }
+
+    /// The function or class around `cursor`, or just its body for `inner`
+    pub fn text_object(
+        &mut self,
+        lines: &[String],
+        cursor: (usize, usize),
+        object: TextObject,
+        inner: bool,
+    ) -> Option<TextRange> {
+        let tree = self.parse(lines)?.clone();
+        let kinds = match object {
+            TextObject::Function => self.grammar.functions,
+            TextObject::Class => self.grammar.classes,
+        };
+        let byte = self.byte(lines, cursor);
+        let mut node = tree.root_node().descendant_for_byte_range(byte, byte)?;
+        while !kinds.contains(&node.kind()) {
+            node = node.parent()?;
+        }
+        if !inner {
+            return Some(self.whole_lines(lines, self.range(lines, node)));
+        }
+        let body = node.child_by_field_name("body")?;
+        let count = body.child_count();
+        let (first, last) = (body.child(0)?, body.child(count.saturating_sub(1))?);
+        // Inside the braces when the body has them
+        let delimited = count >= 2 && !first.is_named() && !last.is_named();
+        if !delimited {
+            return Some(self.range(lines, body));
+        }
+        let range = TextRange {
+            start: self.position(lines, first.end_byte()),
+            end: self.position(lines, last.start_byte()),
+        };
+        // Braces on lines of their own leave whole lines between them
+        let opens_line_end = range.start.1 == lines[range.start.0].chars().count();
+        let closes_line_start = lines[range.end.0]
+            .chars()
+            .take(range.end.1)
+            .all(char::is_whitespace);
+        if range.end.0 > range.start.0 && opens_line_end && closes_line_start {
+            return Some(TextRange {
+                start: (range.start.0 + 1, 0),
+                end: (range.end.0, 0),
+            });
+        }
+        Some(range)
+    }
+
+    /// Widen a range that starts and ends its lines to the whole lines, line break included
+    fn whole_lines(&self, lines: &[String], range: TextRange) -> TextRange {
+        let starts_line = lines[range.start.0]
+            .chars()
+            .take(range.start.1)
+            .all(char::is_whitespace);
+        let ends_line = lines[range.end.0]
+            .chars()
+            .skip(range.end.1)
+            .all(char::is_whitespace);
+        if starts_line && ends_line {
+            TextRange {
+                start: (range.start.0, 0),
+                end: (range.end.0 + 1, 0),
+            }
+        } else {
+            range
+        }
+    }
+
+    /// The smallest node holding more than `range`, for growing a selection one node at a time
+    pub fn expand(&mut self, lines: &[String], range: TextRange) -> Option<TextRange> {
+        let tree = self.parse(lines)?.clone();
+        let (start, end) = (self.byte(lines, range.start), self.byte(lines, range.end));
+        let mut node = tree
+            .root_node()
+            .named_descendant_for_byte_range(start, end)?;
+        while node.start_byte() == start && node.end_byte() == end {
+            node = node.parent()?;
+        }
+        Some(self.range(lines, node))
+    }
+
+    /// The node kind under `cursor` and those it's in, innermost first
+    pub fn nodes_at(&mut self, lines: &[String], cursor: (usize, usize)) -> Vec<String> {
+        let Some(tree) = self.parse(lines).cloned() else {
+            return Vec::new();
+        };
+        let byte = self.byte(lines, cursor);
+        let mut kinds = Vec::new();
+        let mut node = tree.root_node().named_descendant_for_byte_range(byte, byte);
+        while let Some(current) = node {
+            kinds.push(current.kind().to_string());
+            node = current.parent();
+        }
+        kinds
+    }
+
+    /// Ranges of lines that can be folded, from the first line to the last
+    /// Only the outermost range starting on each line is kept
+    pub fn folds(&mut self, lines: &[String]) -> Vec<(usize, usize)> {
+        let Some(tree) = self.parse(lines).cloned() else {
+            return Vec::new();
+        };
+        let mut folds: Vec<(usize, usize)> = Vec::new();
+        let mut cursor = tree.walk();
+        'walk: loop {
+            let node = cursor.node();
+            let (start, end) = (node.start_position().row, node.end_position().row);
+            if end > start && self.grammar.folds.contains(&node.kind()) {
+                folds.push((start, end));
+            }
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    break 'walk;
+                }
+            }
+        }
+        folds.sort_unstable_by_key(|&(start, end)| (start, Reverse(end)));
+        folds.dedup_by_key(|fold| fold.0);
+        folds
+    }
+}
+
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((byte, _), _)| byte)
+}
+
+fn common_suffix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .rev()
+        .zip(b.chars().rev())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((byte, x), _)| {
+            a.len() - byte - x.len_utf8()
+        })
+}
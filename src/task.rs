@@ -0,0 +1,62 @@
+use std::thread;
+
+use crossbeam::channel::{Receiver, Sender, unbounded};
+use tracing::warn;
+
+use crate::event::ReovimEvent;
+
+/// Threads running background work, kept small so slow work never takes over the machine
+const WORKERS: usize = 2;
+
+type Job = Box<dyn FnOnce() -> ReovimEvent + Send>;
+
+/// Runs work off the main thread, each job's result comes back to the component tree as an event
+#[derive(Clone)]
+pub struct Tasks {
+    jobs: Sender<Job>,
+    /// Where job results go, for work that reports back on threads of its own
+    events: Sender<ReovimEvent>,
+}
+
+impl Tasks {
+    /// Start the workers, returning the handle to give jobs to and where their results arrive
+    pub fn start() -> (Tasks, Receiver<ReovimEvent>) {
+        let (jobs, queue) = unbounded::<Job>();
+        let (results, events) = unbounded();
+        for index in 0..WORKERS {
+            let queue = queue.clone();
+            let results = results.clone();
+            let spawned = thread::Builder::new()
+                .name(format!("reovim-worker-{index}"))
+                .spawn(move || {
+                    for job in queue {
+                        if results.send(job()).is_err() {
+                            break;
+                        }
+                    }
+                });
+            if let Err(err) = spawned {
+                warn!("could not start worker thread: {err}");
+            }
+        }
+        (
+            Tasks {
+                jobs,
+                events: results,
+            },
+            events,
+        )
+    }
+
+    /// Queue a job, the event it returns is dispatched once the event loop picks it up
+    pub fn spawn(&self, job: impl FnOnce() -> ReovimEvent + Send + 'static) {
+        if self.jobs.send(Box::new(job)).is_err() {
+            warn!("worker threads have stopped, dropping job");
+        }
+    }
+
+    /// Send events to the component tree from any thread, like a language server's messages
+    pub fn sender(&self) -> Sender<ReovimEvent> {
+        self.events.clone()
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    backtrace::Backtrace,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, IsTerminal, Write, stdin, stdout},
+    panic,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::Result;
+use crossterm::{
+    ExecutableCommand,
+    cursor::Show,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use tracing::error;
+
+/// The terminal is in raw mode on the alternate screen, so restoring it twice does nothing the second time
+static ENTERED: AtomicBool = AtomicBool::new(false);
+
+/// Stdout when it is a terminal, otherwise the controlling terminal at /dev/tty
+pub fn output() -> Result<Box<dyn Write>> {
+    if stdout().is_terminal() {
+        return Ok(Box::new(stdout()));
+    }
+    let tty = OpenOptions::new().write(true).open("/dev/tty")?;
+    Ok(Box::new(tty))
+}
+
+/// Ask on the terminal before the editor enters it, returning the trimmed answer or `None` at end of input
+/// Read from the controlling terminal when stdin is piped in
+pub fn ask(question: &str) -> Result<Option<String>> {
+    let mut output = output()?;
+    output.write_all(question.as_bytes())?;
+    output.flush()?;
+    let mut answer = String::new();
+    let read = if stdin().is_terminal() {
+        stdin().read_line(&mut answer)?
+    } else {
+        BufReader::new(File::open("/dev/tty")?).read_line(&mut answer)?
+    };
+    Ok((read > 0).then(|| answer.trim().to_string()))
+}
+
+/// Put the terminal in raw mode on the alternate screen with the mouse captured, for the editor to draw on
+pub fn enter(output: &mut dyn Write) -> Result<()> {
+    terminal::enable_raw_mode()?;
+    output
+        .execute(EnterAlternateScreen)?
+        .execute(EnableMouseCapture)?;
+    ENTERED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Give the terminal back the way the shell had it, when it was entered
+pub fn restore(output: &mut dyn Write) -> Result<()> {
+    if !ENTERED.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+    output
+        .execute(DisableMouseCapture)?
+        .execute(LeaveAlternateScreen)?
+        .execute(Show)?;
+    terminal::disable_raw_mode()?;
+    Ok(())
+}
+
+/// Restore the terminal wherever it can be written, for when the editor's own output is out of reach
+fn restore_terminal() {
+    if let Err(err) = output().and_then(|mut output| restore(&mut output)) {
+        error!("could not restore the terminal: {err}");
+    }
+}
+
+/// Restores the terminal when dropped, so an error or a panic unwinding out of the event loop does too
+pub struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Some thread panicked and gave the terminal back, the editor can't go on drawing on it
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+/// Restore the terminal before any panic is reported, and log every panic with a backtrace
+pub fn install_panic_hook() {
+    restore_on_panic(restore_terminal);
+}
+
+/// Call `restore` on whichever thread panics first, a worker's panic leaves the terminal as broken as the
+/// main thread's, later panics find it restored already
+pub fn restore_on_panic(restore: fn()) {
+    let report = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if !PANICKED.swap(true, Ordering::SeqCst) {
+            restore();
+        }
+        let thread = std::thread::current();
+        error!(
+            "thread {} {info}\n{}",
+            thread.name().unwrap_or("<unnamed>"),
+            Backtrace::force_capture()
+        );
+        report(info);
+    }));
+}
+
+/// Whether a thread panicked since the hook was installed, the editor stops rather than draw on a restored terminal
+pub fn panicked() -> bool {
+    PANICKED.load(Ordering::SeqCst)
+}
+
+/// Give the terminal back to the shell and stop, as `Ctrl-Z` does to other programs, entering it again
+/// once the shell continues the editor
+#[cfg(unix)]
+pub fn suspend(output: &mut dyn Write) -> Result<()> {
+    restore(output)?;
+    // The whole process group stops, language servers too, and continues with it on SIGCONT
+    // SAFETY: kill has no memory safety requirements
+    unsafe {
+        libc::kill(0, libc::SIGTSTP);
+    }
+    enter(output)
+}
+
+/// Without job control there is nothing to suspend to
+#[cfg(not(unix))]
+pub fn suspend(_output: &mut dyn Write) -> Result<()> {
+    Ok(())
+}
@@ -0,0 +1,182 @@
+//! Timings for the editor on large inputs, run with `cargo test --release -- --ignored --nocapture`
+
+use std::{
+    cell::RefCell,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{
+    buffer::{Buffer, list::BufferList},
+    completion::CompletionSources,
+    event::ReovimEvent,
+    lsp::{Lsp, LspConfig},
+    snippet::library::SnippetLibrary,
+    task::Tasks,
+    tui::{
+        editor::Editor,
+        tree::{ComponentId, ComponentNode, ComponentTree},
+    },
+};
+
+const WIDTH: u16 = 120;
+const HEIGHT: u16 = 40;
+
+/// A file of numbered lines about 100 bytes long, `size` bytes in all
+fn write_file(name: &str, size: usize) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("reovim-bench-{}-{name}", std::process::id()));
+    let mut file = BufWriter::new(File::create(&path).unwrap());
+    let mut written = 0;
+    let mut line = 0;
+    while written < size {
+        let text = format!(
+            "{line:>10} the quick brown fox jumps over the lazy dog, {line:x} {:>40}\n",
+            ""
+        );
+        file.write_all(text.as_bytes()).unwrap();
+        written += text.len();
+        line += 1;
+    }
+    file.flush().unwrap();
+    path
+}
+
+/// Components reachable from `id`, the ones the tree lays out and renders
+fn live_components(tree: &ComponentTree, id: ComponentId) -> usize {
+    let children = tree.children(id).unwrap_or_default();
+    1 + children
+        .into_iter()
+        .map(|child| live_components(tree, child))
+        .sum::<usize>()
+}
+
+/// Lay out and draw a frame, returning how long it took
+fn frame(tree: &mut ComponentTree) -> Duration {
+    let start = Instant::now();
+    tree.layout(WIDTH, HEIGHT);
+    tree.render(&mut std::io::sink()).unwrap();
+    start.elapsed()
+}
+
+fn key(code: KeyCode) -> ReovimEvent {
+    ReovimEvent::Key(KeyEvent::new(code, KeyModifiers::NONE))
+}
+
+/// Bytes of memory the process has in use, where the system says
+fn resident_bytes() -> Option<usize> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[test]
+#[ignore = "writes a 1 GB file, run with --release"]
+fn open_a_large_file_and_jump_to_the_end() {
+    let path = write_file("large.txt", 1 << 30);
+
+    let before = resident_bytes();
+    let start = Instant::now();
+    let buffer = Buffer::from_file_path(&path).unwrap();
+    let read = start.elapsed();
+    assert!(buffer.is_large() && buffer.lines().is_lazy());
+    let line_count = buffer.line_count();
+
+    let start = Instant::now();
+    let (mut tree, buffer) = open_editor(buffer);
+    let open = start.elapsed();
+
+    let start = Instant::now();
+    tree.update(key(KeyCode::Char('G'))).unwrap();
+    let jump_frame = frame(&mut tree);
+    let jump = start.elapsed();
+
+    let grown = before
+        .zip(resident_bytes())
+        .map(|(before, after)| after.saturating_sub(before));
+    fs::remove_file(&path).unwrap();
+
+    println!("{line_count} lines");
+    println!("read {read:?}, open {open:?}");
+    println!("jump to the end {jump:?} (frame {jump_frame:?})");
+    println!("memory grew {:?} MB", grown.map(|bytes| bytes >> 20));
+    assert_eq!(buffer.borrow().cursor.0, line_count - 1);
+    // Only the rows in view have components, however long the file
+    let components = live_components(&tree, 0);
+    println!("{components} components");
+    assert!(components < 10 * HEIGHT as usize);
+    // Reading goes over the file once to index it, and only the lines shown are kept
+    assert!(read < Duration::from_secs(10), "read took {read:?}");
+    assert!(
+        open + jump < Duration::from_secs(1),
+        "open and jump took {:?}",
+        open + jump
+    );
+    assert!(
+        grown.is_none_or(|bytes| bytes < 64 << 20),
+        "memory grew {grown:?} bytes"
+    );
+}
+
+/// An editor on `buffer` laid out and drawn once, as the event loop starts it
+fn open_editor(buffer: Buffer) -> (ComponentTree<'static>, Rc<RefCell<Buffer>>) {
+    let mut buffers = BufferList::default();
+    let buffer = buffers.add(buffer);
+    let (tasks, _task_events) = Tasks::start();
+    let lsp = Lsp::new(LspConfig::load(None), tasks.sender());
+    let editor = Editor::new(
+        Rc::new(RefCell::new(buffers)),
+        buffer.clone(),
+        tasks,
+        CompletionSources::default(),
+        SnippetLibrary::new(None),
+        lsp,
+    );
+    let mut tree = ComponentTree::new(ComponentNode::Component(Box::new(editor)));
+    tree.initialize_pending_components().unwrap();
+    tree.update(ReovimEvent::Resize(WIDTH, HEIGHT)).unwrap();
+    tree.layout(WIDTH, HEIGHT);
+    tree.mark_all_dirty();
+    frame(&mut tree);
+    (tree, buffer)
+}
+
+#[test]
+#[ignore = "times hundreds of frames, run with --release"]
+fn input_latency_stays_flat_as_the_tree_grows() {
+    let path = write_file("latency.txt", 1 << 20);
+    let mut latencies = Vec::new();
+    for tabs in [1, 10, 100] {
+        let (mut tree, _) = open_editor(Buffer::from_file_path(&path).unwrap());
+        // Tab pages in the background still have their windows in the tree
+        for _ in 1..tabs {
+            tree.update(ReovimEvent::Command(format!("tabnew {}", path.display())))
+                .unwrap();
+            frame(&mut tree);
+        }
+        let components = live_components(&tree, 0);
+        // Keys as the event loop handles them, each followed by a frame
+        let keys = 200;
+        let start = Instant::now();
+        for index in 0..keys {
+            let code = if index % 50 < 25 { 'j' } else { 'k' };
+            tree.update(key(KeyCode::Char(code))).unwrap();
+            frame(&mut tree);
+        }
+        let latency = start.elapsed() / keys;
+        println!("{tabs} tab pages, {components} components: {latency:?} per key");
+        latencies.push(latency);
+    }
+    fs::remove_file(&path).unwrap();
+    let fastest = latencies.iter().min().unwrap();
+    let slowest = latencies.iter().max().unwrap();
+    assert!(
+        *slowest < *fastest * 3,
+        "latency grew with the tree: {latencies:?}"
+    );
+}
@@ -1,28 +1,393 @@
-use crate::tui::{Component, Formatting, LayoutMode, Measurement, Overflow, terminal_buffer::TerminalBuffer};
+use std::{
+    cell::{Cell, RefCell},
+    path::PathBuf,
+    rc::Rc,
+};
 
-use anyhow::Result;
+use crate::buffer::BufferId;
+use crate::event::ReovimEvent;
+use crate::tui::{
+    Component, Formatting, LayoutMode, Measurement, Overflow, editor::Mode,
+    terminal_buffer::TerminalBuffer,
+};
+use crate::vcs::diff::Algorithm;
+
+use anyhow::{Result, bail};
+use crossterm::style::Color;
+
+/// A parsed `:` command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExCommand {
+    /// `:w [file]`
+    Write(Option<PathBuf>),
+    /// `:q` and `:q!`
+    Quit { force: bool },
+    /// `:wq [file]` and `:x`
+    WriteQuit(Option<PathBuf>),
+    /// `:cq`, quit with a non-zero exit code
+    QuitWithError,
+    /// `:sus` and `:st`, stop the editor like `Ctrl-Z`
+    Suspend,
+    /// `:e[!] [file]`, without a file the current buffer is reloaded
+    Edit { path: Option<PathBuf>, force: bool },
+    /// `:bn [N]`
+    BufferNext(usize),
+    /// `:bp [N]`
+    BufferPrev(usize),
+    /// `:b N`
+    Buffer(BufferId),
+    /// `:bd[!] [N]`, without a number the current buffer is deleted
+    BufferDelete {
+        buffer: Option<BufferId>,
+        force: bool,
+    },
+    /// `:ls`
+    ListBuffers,
+    /// `:snippets`, the snippets Tab expands in the current buffer
+    ListSnippets,
+    /// `:syntax`, the syntax tree nodes under the cursor
+    Syntax,
+    /// `:rename {name}`, rename the symbol under the cursor with the language server
+    Rename(String),
+    /// `:codeaction` lists the language server's code actions for the cursor line, `:codeaction N` applies one
+    CodeAction(Option<usize>),
+    /// `:format`, format the buffer with the language server
+    Format,
+    /// `:diagnostics`, what the language server reported for the buffer
+    Diagnostics,
+    /// `:lsp`, the running language servers
+    LspInfo,
+    /// `:previewhunk`, how the git hunk under the cursor differs from the index
+    PreviewHunk,
+    /// `:stagehunk`, put the hunk under the cursor into the git index
+    StageHunk,
+    /// `:resethunk`, put the index's lines back in place of the hunk under the cursor
+    ResetHunk,
+    /// `:set {option}={value}`, `:set {option}?` shows the value
+    /// `:set {option}` switches an on/off option on, `:set no{option}` off, and shows any other option's value
+    Set {
+        option: String,
+        value: Option<String>,
+    },
+    /// `:diffthis`, compare the window with the other window in diff mode
+    DiffThis,
+    /// `:diffoff` takes the window out of diff mode, `:diffoff!` every window in the tab
+    DiffOff { all: bool },
+    /// `:diffsplit {file}`, open the file to the left and compare it with the window
+    DiffSplit(PathBuf),
+    /// `:diffalgorithm [myers|patience]`, without a name the one in use is shown
+    DiffAlgorithm(Option<Algorithm>),
+    /// `:split [file]` stacks a new window above, `:vsplit [file]` puts one to the left
+    Split {
+        path: Option<PathBuf>,
+        mode: LayoutMode,
+    },
+    /// `:new` and `:vnew`, split with an empty buffer
+    New { mode: LayoutMode },
+    /// `:close`
+    Close,
+    /// `:only`
+    Only,
+    /// `:tabnew [file]`, without a file the tab shows an empty buffer
+    TabNew(Option<PathBuf>),
+    /// `:tabn [N]`, to tab N or the next one
+    TabNext(Option<usize>),
+    /// `:tabp [N]`
+    TabPrev(usize),
+    /// `:tabclose`
+    TabClose,
+    /// `:tabmove [N]`, `:tabmove +N` and `:tabmove -N`
+    TabMove(TabPosition),
+}
+
+/// Where `:tabmove` puts the current tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabPosition {
+    /// After tab N, 0 makes it the first
+    After(usize),
+    /// Some tabs to the right, or to the left when negative
+    Relative(isize),
+    Last,
+}
+
+impl TabPosition {
+    fn parse(argument: Option<&str>) -> Result<TabPosition> {
+        let Some(argument) = argument else {
+            return Ok(TabPosition::Last);
+        };
+        let invalid = || anyhow::anyhow!("E475: Invalid argument: {argument}");
+        let offset = |digits: &str| -> Result<isize> {
+            if digits.is_empty() {
+                return Ok(1);
+            }
+            digits.parse::<isize>().map_err(|_| invalid())
+        };
+        if let Some(digits) = argument.strip_prefix('+') {
+            Ok(TabPosition::Relative(offset(digits)?))
+        } else if let Some(digits) = argument.strip_prefix('-') {
+            Ok(TabPosition::Relative(-offset(digits)?))
+        } else {
+            let tab = argument.parse::<usize>().map_err(|_| invalid())?;
+            Ok(TabPosition::After(tab))
+        }
+    }
+}
+
+impl ExCommand {
+    pub fn parse(input: &str) -> Result<ExCommand> {
+        let input = input.trim();
+        // A command is a name, an optional `!` and an argument, `:b2` needs no space before the number
+        let name_end = input
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(input.len());
+        let (name, rest) = input.split_at(name_end);
+        let (force, rest) = match rest.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let argument = Some(rest.trim()).filter(|arg| !arg.is_empty());
+        let path = argument.map(PathBuf::from);
+        let count = || -> Result<usize> {
+            match argument {
+                Some(arg) => arg
+                    .parse::<usize>()
+                    .map_err(|_| anyhow::anyhow!("E488: Trailing characters: {arg}")),
+                None => Ok(1),
+            }
+        };
+        let command = match name {
+            "w" | "write" => ExCommand::Write(path),
+            "q" | "quit" => ExCommand::Quit { force },
+            "wq" | "x" | "xit" => ExCommand::WriteQuit(path),
+            "cq" | "cquit" => ExCommand::QuitWithError,
+            "sus" | "suspend" | "st" | "stop" => ExCommand::Suspend,
+            "e" | "edit" => ExCommand::Edit { path, force },
+            "bn" | "bnext" => ExCommand::BufferNext(count()?),
+            "bp" | "bprev" | "bprevious" | "bN" | "bNext" => ExCommand::BufferPrev(count()?),
+            "b" | "buffer" => match argument {
+                Some(_) => ExCommand::Buffer(count()?),
+                None => bail!("E471: Argument required"),
+            },
+            "bd" | "bdelete" => ExCommand::BufferDelete {
+                buffer: argument.map(|_| count()).transpose()?,
+                force,
+            },
+            "ls" | "buffers" | "files" => ExCommand::ListBuffers,
+            "snippets" => ExCommand::ListSnippets,
+            "syn" | "syntax" => ExCommand::Syntax,
+            "rename" => match argument {
+                Some(name) => ExCommand::Rename(name.to_string()),
+                None => bail!("E471: Argument required"),
+            },
+            "codeaction" => ExCommand::CodeAction(argument.map(|_| count()).transpose()?),
+            "format" => ExCommand::Format,
+            "diagnostics" => ExCommand::Diagnostics,
+            "lsp" => ExCommand::LspInfo,
+            "previewhunk" => ExCommand::PreviewHunk,
+            "stagehunk" => ExCommand::StageHunk,
+            "resethunk" => ExCommand::ResetHunk,
+            "se" | "set" => {
+                let Some(argument) = argument else {
+                    bail!("E471: Argument required");
+                };
+                match argument.split_once('=') {
+                    Some((option, value)) => ExCommand::Set {
+                        option: option.to_string(),
+                        value: Some(value.to_string()),
+                    },
+                    None => ExCommand::Set {
+                        option: argument.to_string(),
+                        value: None,
+                    },
+                }
+            }
+            "diffthis" => ExCommand::DiffThis,
+            "diffoff" => ExCommand::DiffOff { all: force },
+            "diffsplit" => match path {
+                Some(path) => ExCommand::DiffSplit(path),
+                None => bail!("E471: Argument required"),
+            },
+            "diffalgorithm" => ExCommand::DiffAlgorithm(
+                argument
+                    .map(|name| {
+                        Algorithm::parse(name)
+                            .ok_or_else(|| anyhow::anyhow!("E474: Invalid argument: {name}"))
+                    })
+                    .transpose()?,
+            ),
+            "sp" | "split" => ExCommand::Split {
+                path,
+                mode: LayoutMode::VerticalSplit,
+            },
+            "vs" | "vsplit" => ExCommand::Split {
+                path,
+                mode: LayoutMode::HorizontalSplit,
+            },
+            "new" => ExCommand::New {
+                mode: LayoutMode::VerticalSplit,
+            },
+            "vne" | "vnew" => ExCommand::New {
+                mode: LayoutMode::HorizontalSplit,
+            },
+            "clo" | "close" => ExCommand::Close,
+            "on" | "only" => ExCommand::Only,
+            "tabnew" | "tabe" | "tabedit" => ExCommand::TabNew(path),
+            "tabn" | "tabnext" => ExCommand::TabNext(argument.map(|_| count()).transpose()?),
+            "tabp" | "tabprevious" | "tabN" | "tabNext" => ExCommand::TabPrev(count()?),
+            "tabc" | "tabclose" => ExCommand::TabClose,
+            "tabm" | "tabmove" => ExCommand::TabMove(TabPosition::parse(argument)?),
+            "" => bail!("E471: Argument required"),
+            _ => bail!("E492: Not an editor command: {input}"),
+        };
+        Ok(command)
+    }
+}
+
+/// State of the command line, shared between the editor handling keys and the component drawing it
+#[derive(Debug, Default)]
+pub struct CommandLine {
+    /// Text typed after `:`, `None` while the command line is closed
+    input: Option<String>,
+    /// The last message or error, shown while the command line is closed
+    message: Option<(String, bool)>,
+}
+
+impl CommandLine {
+    pub fn open(&mut self) {
+        self.input = Some(String::new());
+        self.message = None;
+    }
+
+    pub fn close(&mut self) -> Option<String> {
+        self.input.take()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.input.is_some()
+    }
+
+    pub fn input_mut(&mut self) -> Option<&mut String> {
+        self.input.as_mut()
+    }
+
+    pub fn input_width(&self) -> usize {
+        self.input
+            .as_ref()
+            .map(|input| input.chars().count())
+            .unwrap_or(0)
+    }
+
+    pub fn set_message(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), false));
+    }
+
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), true));
+    }
+
+    /// Screen rows the command line takes, a message can span several
+    pub fn rows(&self) -> usize {
+        match (&self.input, &self.message) {
+            (None, Some((message, _))) => message.lines().count().max(1),
+            _ => 1,
+        }
+    }
+
+    /// Messages spanning several lines, like `:ls`, only stay until the next key
+    pub fn clear_multiline_message(&mut self) {
+        if let Some((message, _)) = &self.message
+            && message.contains('\n')
+        {
+            self.message = None;
+        }
+    }
+}
 
 pub struct CommandComponent {
     /// The command text
-    command: String,
+    command: Rc<RefCell<CommandLine>>,
+    /// Mode of the editor, shown when there is nothing else to show
+    mode: Rc<Cell<Mode>>,
+    /// Rows it took when last drawn
+    rows: Cell<usize>,
+}
+
+impl CommandComponent {
+    pub fn new(command: Rc<RefCell<CommandLine>>, mode: Rc<Cell<Mode>>) -> Self {
+        Self {
+            command,
+            mode,
+            rows: Cell::new(1),
+        }
+    }
 }
 
-impl<'a> Component for CommandComponent {
-    fn render(&self, _buffer: &mut TerminalBuffer, _query: crate::tui::ComponentQuery) -> Result<()> {
+impl Component for CommandComponent {
+    fn render(
+        &self,
+        buffer: &mut TerminalBuffer,
+        _query: crate::tui::ComponentQuery,
+    ) -> Result<()> {
+        let command = self.command.borrow();
+        self.rows.set(command.rows());
+        match (&command.input, &command.message) {
+            (Some(input), _) => {
+                buffer.write(":").write(input).write(" ");
+            }
+            (None, Some((message, is_error))) => {
+                if *is_error {
+                    buffer.set_foreground(Color::Red);
+                }
+                for (idx, line) in message.lines().enumerate() {
+                    if idx > 0 {
+                        buffer.newline();
+                    }
+                    buffer.write(line);
+                }
+            }
+            (None, None) => match self.mode.get() {
+                Mode::Insert => {
+                    buffer.write("-- INSERT --");
+                }
+                Mode::Visual => {
+                    buffer.write("-- VISUAL --");
+                }
+                Mode::Normal => {
+                    buffer.write(" ");
+                }
+            },
+        }
         Ok(())
     }
 
+    fn update(
+        &mut self,
+        _event: ReovimEvent,
+        commands: &mut crate::tui::tree::ComponentCommands,
+    ) -> Result<bool> {
+        // Whatever set a message of another height, the layout has to make room for it
+        let resized = self.command.borrow().rows() != self.rows.get();
+        // The editor edits the shared command line, keep the cursor after the typed text
+        if commands.has_focus() {
+            let col = self.command.borrow().input_width() as u16 + 1;
+            commands.set_cursor(col, 0);
+            return Ok(true);
+        }
+        Ok(resized)
+    }
+
     fn default_formatting(&self) -> Formatting {
         Formatting {
             preferred_x: Measurement::Cell(0),
             preferred_y: Measurement::Cell(0),
             preferred_width: Measurement::Percent(100),
-            preferred_height: Measurement::Cell(1),
+            preferred_height: Measurement::Content,
             overflow_x: Overflow::Hide,
             overflow_y: Overflow::Hide,
             request_focus: false,
             layout_mode: LayoutMode::VerticalSplit,
             focusable: true,
+            ..Default::default()
         }
     }
 }
@@ -0,0 +1,569 @@
+use std::{
+    cell::{Cell, RefCell},
+    path::PathBuf,
+    rc::Rc,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use anyhow::Result;
+use crossterm::{
+    event::{KeyCode, KeyEvent, KeyModifiers},
+    style::Color,
+};
+use tracing::warn;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    buffer::Buffer,
+    completion::{
+        CompletionItem, CompletionKind, CompletionRequest, CompletionResponse, CompletionSources,
+        rank, sources::preview_file,
+    },
+    event::ReovimEvent,
+    task::Tasks,
+    tui::{
+        Component, Formatting, LayoutMode, Measurement, Overflow,
+        decoration::BorderStyle,
+        overlay::{Anchor, Overlay},
+        terminal_buffer::TerminalBuffer,
+        tree::{ComponentCommands, ComponentId},
+    },
+};
+
+/// Rows the menu shows at once
+const MENU_HEIGHT: usize = 10;
+
+/// Requests are numbered across every window, answers are broadcast to all of them
+static NEXT_REQUEST: AtomicU64 = AtomicU64::new(1);
+
+/// The candidates in the menu, shared between the completion handling keys and the components drawing it
+#[derive(Default)]
+struct MenuState {
+    /// Every candidate so far with the source it came from, in the order they arrived
+    items: Vec<(String, CompletionItem)>,
+    /// Indexes into `items` of the candidates matching what's typed, best first
+    ranked: Vec<usize>,
+    /// Index into `ranked`
+    selected: usize,
+    /// First ranked candidate shown, moves to keep the selection in view
+    scroll: usize,
+    /// Sources that haven't answered yet
+    waiting: usize,
+    /// Opened with `Ctrl-P`, the selection starts on the last candidate
+    backward: bool,
+    /// The preview of a file candidate, read when it was first selected, with its index into `items`
+    file_preview: Option<(usize, Option<String>)>,
+}
+
+impl MenuState {
+    fn rerank(&mut self, prefix: &str) {
+        self.ranked = rank(
+            prefix,
+            self.items.iter().map(|(_, item)| item.text.as_str()),
+        );
+        let first = if self.backward {
+            self.ranked.len().saturating_sub(1)
+        } else {
+            0
+        };
+        self.select(first);
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index;
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + MENU_HEIGHT {
+            self.scroll = self.selected + 1 - MENU_HEIGHT;
+        }
+    }
+
+    /// Move the selection, wrapping around at either end
+    fn step(&mut self, forward: bool) {
+        let len = self.ranked.len();
+        if len == 0 {
+            return;
+        }
+        let index = if forward {
+            (self.selected + 1) % len
+        } else {
+            (self.selected + len - 1) % len
+        };
+        self.select(index);
+    }
+
+    fn selected(&self) -> Option<&(String, CompletionItem)> {
+        let index = *self.ranked.get(self.selected)?;
+        self.items.get(index)
+    }
+
+    /// What the selected candidate shows beside the menu, a file is only read once it is selected
+    fn preview(&mut self) -> Option<String> {
+        let index = *self.ranked.get(self.selected)?;
+        let (_, item) = self.items.get(index)?;
+        if item.preview.is_some() {
+            return item.preview.clone();
+        }
+        let path = item.file.as_deref()?;
+        match &self.file_preview {
+            Some((previewed, preview)) if *previewed == index => preview.clone(),
+            _ => {
+                let preview = preview_file(path);
+                self.file_preview = Some((index, preview.clone()));
+                preview
+            }
+        }
+    }
+}
+
+/// The popup listing candidates, next to the cursor
+struct CompletionMenu {
+    state: Rc<RefCell<MenuState>>,
+}
+
+impl Component for CompletionMenu {
+    fn render(
+        &self,
+        buffer: &mut TerminalBuffer,
+        _query: crate::tui::ComponentQuery,
+    ) -> Result<()> {
+        let state = self.state.borrow();
+        if state.ranked.is_empty() {
+            if state.waiting > 0 {
+                buffer
+                    .set_background(Color::DarkGrey)
+                    .set_foreground(Color::White)
+                    .write(" searching... ");
+            }
+            return Ok(());
+        }
+        let shown: Vec<(usize, String)> = state
+            .ranked
+            .iter()
+            .enumerate()
+            .skip(state.scroll)
+            .take(MENU_HEIGHT)
+            .map(|(position, &index)| {
+                let (source, item) = &state.items[index];
+                let detail = item.detail.as_deref().unwrap_or(source);
+                (position, format!(" {}  [{detail}] ", item.text))
+            })
+            .collect();
+        let width = shown.iter().map(|(_, row)| row.width()).max().unwrap_or(0);
+        for (row, (position, text)) in shown.iter().enumerate() {
+            if row > 0 {
+                buffer.newline();
+            }
+            if *position == state.selected {
+                buffer
+                    .set_background(Color::Grey)
+                    .set_foreground(Color::Black);
+            } else {
+                buffer
+                    .set_background(Color::DarkGrey)
+                    .set_foreground(Color::White);
+            }
+            buffer.write(text);
+            buffer.write(&" ".repeat(width - text.width()));
+        }
+        Ok(())
+    }
+
+    fn default_formatting(&self) -> Formatting {
+        Formatting {
+            preferred_width: Measurement::Content,
+            preferred_height: Measurement::Content,
+            overflow_x: Overflow::Hide,
+            overflow_y: Overflow::Hide,
+            request_focus: false,
+            layout_mode: LayoutMode::VerticalSplit,
+            focusable: false,
+            ..Default::default()
+        }
+    }
+}
+
+/// The selected candidate's preview, right of the menu
+struct CompletionPreview {
+    state: Rc<RefCell<MenuState>>,
+}
+
+impl Component for CompletionPreview {
+    fn render(
+        &self,
+        buffer: &mut TerminalBuffer,
+        _query: crate::tui::ComponentQuery,
+    ) -> Result<()> {
+        let Some(preview) = self.state.borrow_mut().preview() else {
+            return Ok(());
+        };
+        for (index, line) in preview.lines().enumerate() {
+            if index > 0 {
+                buffer.newline();
+            }
+            buffer.write(line);
+        }
+        Ok(())
+    }
+
+    fn default_formatting(&self) -> Formatting {
+        Formatting {
+            // Right of the menu, lined up with its top
+            preferred_x: Measurement::Percent(100),
+            preferred_y: Measurement::Cell(0),
+            preferred_width: Measurement::Content,
+            preferred_height: Measurement::Content,
+            overflow_x: Overflow::Hide,
+            overflow_y: Overflow::Hide,
+            request_focus: false,
+            layout_mode: LayoutMode::VerticalSplit,
+            focusable: false,
+            ..Default::default()
+        }
+    }
+}
+
+/// An open completion menu
+struct Active {
+    id: u64,
+    /// What the sources were asked, its cursor line is the one being completed
+    request: Arc<CompletionRequest>,
+    /// Column the completed text starts at
+    start: usize,
+    state: Rc<RefCell<MenuState>>,
+    menu_id: ComponentId,
+    preview_id: ComponentId,
+}
+
+/// What a key did to the completion menu
+pub enum CompletionKey {
+    /// The key was for the menu, nothing else should see it
+    Handled,
+    /// Replace the text from `start` to the cursor with the accepted candidate
+    Accept { start: usize, text: String },
+    /// Not for the menu, handle it as usual
+    Ignored,
+}
+
+/// Insert mode completion for one window: asks the sources, shows the menu and handles its keys
+pub struct Completion {
+    sources: CompletionSources,
+    tasks: Tasks,
+    /// The preview's border, `'winborder'`
+    border: Rc<Cell<Option<BorderStyle>>>,
+    active: Option<Active>,
+    /// `Ctrl-X` was pressed, the next key picks what to complete
+    pending_x: bool,
+}
+
+impl Completion {
+    pub fn new(
+        sources: CompletionSources,
+        tasks: Tasks,
+        border: Rc<Cell<Option<BorderStyle>>>,
+    ) -> Self {
+        Self {
+            sources,
+            tasks,
+            border,
+            active: None,
+            pending_x: false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Handle an insert mode key, the cursor is at `(line, col)`
+    pub fn key(
+        &mut self,
+        key: KeyEvent,
+        buffer: &Rc<RefCell<Buffer>>,
+        (line, col): (usize, usize),
+        commands: &mut ComponentCommands,
+    ) -> Result<CompletionKey> {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        if std::mem::take(&mut self.pending_x) {
+            let kind = match key.code {
+                KeyCode::Char('f') if control => Some((CompletionKind::File, false)),
+                KeyCode::Char('l') if control => Some((CompletionKind::Line, false)),
+                KeyCode::Char(character @ ('n' | 'p')) if control => {
+                    Some((CompletionKind::Keyword, character == 'p'))
+                }
+                _ => None,
+            };
+            if let Some((kind, backward)) = kind {
+                self.start(kind, backward, buffer, (line, col), commands)?;
+                return Ok(CompletionKey::Handled);
+            }
+        }
+        match key.code {
+            KeyCode::Char('x') if control => {
+                self.pending_x = true;
+                return Ok(CompletionKey::Handled);
+            }
+            KeyCode::Char(character @ ('n' | 'p')) if control => {
+                match &self.active {
+                    Some(active) => active.state.borrow_mut().step(character == 'n'),
+                    None => {
+                        let backward = character == 'p';
+                        self.start(
+                            CompletionKind::Keyword,
+                            backward,
+                            buffer,
+                            (line, col),
+                            commands,
+                        )?
+                    }
+                }
+                return Ok(CompletionKey::Handled);
+            }
+            _ => {}
+        }
+
+        let Some(active) = &self.active else {
+            return Ok(CompletionKey::Ignored);
+        };
+        match key.code {
+            KeyCode::Down => active.state.borrow_mut().step(true),
+            KeyCode::Up => active.state.borrow_mut().step(false),
+            KeyCode::Tab | KeyCode::Enter => {
+                let accepted = active
+                    .state
+                    .borrow()
+                    .selected()
+                    .map(|(_, item)| item.text.clone());
+                let start = active.start;
+                self.close(commands);
+                return Ok(match accepted {
+                    Some(text) => CompletionKey::Accept { start, text },
+                    // Nothing to accept, the key does what it usually does
+                    None => CompletionKey::Ignored,
+                });
+            }
+            KeyCode::Char('e') if control => self.close(commands),
+            // Leaving insert mode closes the menu too
+            KeyCode::Esc => {
+                self.close(commands);
+                return Ok(CompletionKey::Ignored);
+            }
+            _ => return Ok(CompletionKey::Ignored),
+        }
+        Ok(CompletionKey::Handled)
+    }
+
+    /// Ask every source of a kind for candidates and open the menu for their answers
+    /// Going `backward`, as `Ctrl-P` does, the last candidate is selected first
+    fn start(
+        &mut self,
+        kind: CompletionKind,
+        backward: bool,
+        buffer: &Rc<RefCell<Buffer>>,
+        (line, col): (usize, usize),
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        self.close(commands);
+        // Each request takes a copy of the lines, too much for a large file
+        if buffer.borrow().is_large() {
+            return Ok(());
+        }
+        let request = {
+            let buffer = buffer.borrow();
+            let text = buffer.line(line).unwrap_or_default();
+            let start = kind.start(text, col);
+            CompletionRequest {
+                kind,
+                prefix: text.chars().skip(start).take(col - start).collect(),
+                cursor: (line, col),
+                lines: Arc::new(buffer.lines().as_slice().to_vec()),
+                path: buffer.file_path().map(|path| path.to_path_buf()),
+                cwd: std::env::current_dir()?,
+            }
+        };
+        let start = col - request.prefix.chars().count();
+        let id = NEXT_REQUEST.fetch_add(1, Ordering::Relaxed);
+        let sources = self.sources.for_kind(kind);
+        let state = Rc::new(RefCell::new(MenuState {
+            waiting: sources.len(),
+            backward,
+            ..Default::default()
+        }));
+
+        let request = Arc::new(request);
+        for source in sources {
+            let request = request.clone();
+            self.tasks.spawn(move || {
+                let items = source.complete(&request).unwrap_or_else(|err| {
+                    warn!("completion source {} failed: {err}", source.name());
+                    Vec::new()
+                });
+                ReovimEvent::Completion(CompletionResponse {
+                    id,
+                    source: source.name().to_string(),
+                    items,
+                })
+            });
+        }
+        self.open(id, request, start, state, commands)
+    }
+
+    /// Open the menu on fixed candidates starting at `start`, like the options of a snippet choice
+    pub fn offer(
+        &mut self,
+        options: &[String],
+        (line, start): (usize, usize),
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        self.close(commands);
+        let request = Arc::new(CompletionRequest {
+            // Anything typed narrows the options down
+            kind: CompletionKind::Line,
+            prefix: String::new(),
+            cursor: (line, start),
+            lines: Arc::default(),
+            path: None,
+            cwd: PathBuf::new(),
+        });
+        let mut state = MenuState {
+            items: options
+                .iter()
+                .map(|option| ("choice".to_string(), CompletionItem::new(option.as_str())))
+                .collect(),
+            ..Default::default()
+        };
+        state.rerank("");
+        let id = NEXT_REQUEST.fetch_add(1, Ordering::Relaxed);
+        self.open(id, request, start, Rc::new(RefCell::new(state)), commands)
+    }
+
+    /// Show the menu and its preview for a request
+    fn open(
+        &mut self,
+        id: u64,
+        request: Arc<CompletionRequest>,
+        start: usize,
+        state: Rc<RefCell<MenuState>>,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        let menu_id = commands.add_overlay(
+            CompletionMenu {
+                state: state.clone(),
+            },
+            menu_overlay(request.prefix.width()),
+        )?;
+        let preview_id = commands.add_overlay(
+            CompletionPreview {
+                state: state.clone(),
+            },
+            Overlay {
+                anchor: Anchor::Component(menu_id),
+                z_index: 1,
+                border: self.border.get(),
+                padding: 0,
+                ..Default::default()
+            },
+        )?;
+        self.active = Some(Active {
+            id,
+            request,
+            start,
+            state,
+            menu_id,
+            preview_id,
+        });
+        Ok(())
+    }
+
+    /// Take in a source's answer, if it's for the open menu
+    pub fn receive(
+        &mut self,
+        response: CompletionResponse,
+        buffer: &Rc<RefCell<Buffer>>,
+        cursor: (usize, usize),
+    ) {
+        let Some(active) = &self.active else {
+            return;
+        };
+        if response.id != active.id {
+            return;
+        }
+        let prefix = self.typed(buffer, cursor);
+        let mut state = active.state.borrow_mut();
+        state.waiting = state.waiting.saturating_sub(1);
+        let source = response.source;
+        state.items.extend(
+            response
+                .items
+                .into_iter()
+                .map(|item| (source.clone(), item)),
+        );
+        if let Some(prefix) = prefix {
+            state.rerank(&prefix);
+        }
+    }
+
+    /// Text typed since the completed text started, `None` once the cursor has left it
+    fn typed(&self, buffer: &Rc<RefCell<Buffer>>, (line, col): (usize, usize)) -> Option<String> {
+        let active = self.active.as_ref()?;
+        let buffer = buffer.borrow();
+        if line != active.request.cursor.0 || col < active.start {
+            return None;
+        }
+        let text = buffer.line(line)?;
+        Some(
+            text.chars()
+                .skip(active.start)
+                .take(col - active.start)
+                .collect(),
+        )
+    }
+
+    /// Filter the menu again after typing, closing it once the cursor leaves the completed text
+    pub fn refresh(
+        &mut self,
+        buffer: &Rc<RefCell<Buffer>>,
+        cursor: (usize, usize),
+        commands: &mut ComponentCommands,
+    ) {
+        let Some(active) = &self.active else {
+            return;
+        };
+        let still_completing = self.typed(buffer, cursor).filter(|typed| {
+            typed
+                .chars()
+                .all(|character| active.request.kind.continues(character))
+        });
+        match still_completing {
+            Some(typed) => {
+                active.state.borrow_mut().rerank(&typed);
+                commands.set_overlay(active.menu_id, menu_overlay(typed.width()));
+            }
+            None => self.close(commands),
+        }
+    }
+
+    pub fn close(&mut self, commands: &mut ComponentCommands) {
+        if let Some(active) = self.active.take() {
+            commands.remove_component(active.preview_id);
+            commands.remove_component(active.menu_id);
+        }
+    }
+}
+
+/// The menu sits below the cursor, lined up with the start of the text being completed
+fn menu_overlay(typed_width: usize) -> Overlay {
+    Overlay {
+        anchor: Anchor::Cursor {
+            // The leading space of each row lines the candidates up with the text
+            col: -(typed_width as i16) - 1,
+            row: 0,
+        },
+        z_index: 1,
+        shadow: true,
+        ..Default::default()
+    }
+}
@@ -1,4 +1,6 @@
-use crate::tui::{Component, Formatting, LayoutMode, Measurement, Overflow, terminal_buffer::TerminalBuffer};
+use crate::tui::{
+    Component, Formatting, LayoutMode, Measurement, Overflow, terminal_buffer::TerminalBuffer,
+};
 
 use anyhow::Result;
 use crossterm::style::Color;
@@ -14,27 +16,31 @@ impl DebugComponent {
         DebugComponent {
             color,
             width,
-            height: Measurement::Fill,
+            height: Measurement::Fill(1),
         }
     }
     pub fn with_height(color: Color, height: Measurement) -> Self {
         DebugComponent {
             color,
-            width: Measurement::Fill,
+            width: Measurement::Fill(1),
             height,
         }
     }
     pub fn new(color: Color) -> Self {
         DebugComponent {
             color,
-            width: Measurement::Fill,
-            height: Measurement::Fill,
+            width: Measurement::Fill(1),
+            height: Measurement::Fill(1),
         }
     }
 }
 
 impl<'a> Component for DebugComponent {
-    fn render(&self, buffer: &mut TerminalBuffer, _query: crate::tui::ComponentQuery) -> Result<()> {
+    fn render(
+        &self,
+        buffer: &mut TerminalBuffer,
+        _query: crate::tui::ComponentQuery,
+    ) -> Result<()> {
         buffer.set_background(self.color);
 
         // Fill the entire buffer with the color
@@ -60,6 +66,7 @@ impl<'a> Component for DebugComponent {
             request_focus: false,
             layout_mode: LayoutMode::VerticalSplit,
             focusable: true,
+            ..Default::default()
         }
     }
 }
@@ -0,0 +1,277 @@
+use std::io::Write;
+
+use anyhow::Result;
+use crossterm::{
+    ExecutableCommand,
+    cursor::MoveTo,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+};
+use unicode_width::UnicodeWidthChar;
+
+use crate::tui::{Edges, Rect};
+
+/// The box characters a border is drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BorderStyle {
+    #[default]
+    Single,
+    Double,
+    Rounded,
+    Thick,
+    /// `+`, `-` and `|`, for terminals without box drawing characters
+    Ascii,
+}
+
+struct BoxChars {
+    horizontal: char,
+    vertical: char,
+    top_left: char,
+    top_right: char,
+    bottom_left: char,
+    bottom_right: char,
+}
+
+impl BorderStyle {
+    /// The style `'winborder'` names, `bold` as in vim for the thick one
+    pub fn parse(name: &str) -> Option<BorderStyle> {
+        match name {
+            "single" => Some(BorderStyle::Single),
+            "double" => Some(BorderStyle::Double),
+            "rounded" => Some(BorderStyle::Rounded),
+            "bold" => Some(BorderStyle::Thick),
+            "ascii" => Some(BorderStyle::Ascii),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BorderStyle::Single => "single",
+            BorderStyle::Double => "double",
+            BorderStyle::Rounded => "rounded",
+            BorderStyle::Thick => "bold",
+            BorderStyle::Ascii => "ascii",
+        }
+    }
+
+    fn chars(self) -> BoxChars {
+        let [
+            horizontal,
+            vertical,
+            top_left,
+            top_right,
+            bottom_left,
+            bottom_right,
+        ] = match self {
+            BorderStyle::Single => ['─', '│', '┌', '┐', '└', '┘'],
+            BorderStyle::Double => ['═', '║', '╔', '╗', '╚', '╝'],
+            BorderStyle::Rounded => ['─', '│', '╭', '╮', '╰', '╯'],
+            BorderStyle::Thick => ['━', '┃', '┏', '┓', '┗', '┛'],
+            BorderStyle::Ascii => ['-', '|', '+', '+', '+', '+'],
+        };
+        BoxChars {
+            horizontal,
+            vertical,
+            top_left,
+            top_right,
+            bottom_left,
+            bottom_right,
+        }
+    }
+
+    /// The scrollbar's thumb and the track it moves along
+    fn scrollbar(self) -> (char, char) {
+        match self {
+            BorderStyle::Ascii => ('#', '|'),
+            style => ('█', style.chars().vertical),
+        }
+    }
+}
+
+/// Where a title or footer sits in its border
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TitleAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// What the tree draws around a component, inside its rect
+/// The title and footer come from the component and are only drawn in a border
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Decoration {
+    pub border: Option<BorderStyle>,
+    pub title_align: TitleAlign,
+    pub footer_align: TitleAlign,
+    /// A scrollbar on the right edge, over the border when there is one, showing how far a container
+    /// with `Overflow::Scroll` is scrolled
+    pub scrollbar: bool,
+}
+
+/// How far a container is scrolled through its content, in rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScrollPosition {
+    /// Rows above the first one shown
+    pub offset: usize,
+    /// Rows shown at once
+    pub visible: usize,
+    /// Rows of content in all
+    pub content: usize,
+}
+
+impl Decoration {
+    /// Cells the decoration takes on each side of the rect
+    pub fn edges(&self) -> Edges {
+        match self.border {
+            Some(_) => Edges::all(1),
+            None => Edges {
+                right: u16::from(self.scrollbar),
+                ..Edges::default()
+            },
+        }
+    }
+
+    /// The rect less what the decoration takes
+    pub fn inner(&self, rect: Rect) -> Rect {
+        let edges = self.edges();
+        Rect {
+            x: rect.x + edges.left.min(rect.width),
+            y: rect.y + edges.top.min(rect.height),
+            width: rect.width.saturating_sub(edges.horizontal()),
+            height: rect.height.saturating_sub(edges.vertical()),
+        }
+    }
+
+    /// The cells the scrollbar's thumb moves along, in the rect's right column
+    pub fn track(&self, rect: Rect) -> Option<Rect> {
+        if !self.scrollbar || rect.width == 0 {
+            return None;
+        }
+        let inner = self.inner(rect);
+        let track = Rect {
+            x: rect.x + rect.width - 1,
+            y: inner.y,
+            width: 1,
+            height: inner.height,
+        };
+        (track.height > 0).then_some(track)
+    }
+
+    /// Draw the border with the title and footer in it, and the scrollbar when `scroll` says where it is
+    pub fn draw<W: Write + ?Sized>(
+        &self,
+        stdout: &mut W,
+        rect: Rect,
+        title: Option<&str>,
+        footer: Option<&str>,
+        scroll: Option<ScrollPosition>,
+    ) -> Result<()> {
+        let style = self.border.unwrap_or_default();
+        stdout.execute(SetForegroundColor(Color::DarkGrey))?;
+        if self.border.is_some() && rect.width >= 2 && rect.height >= 2 {
+            let chars = style.chars();
+            let right = rect.x + rect.width - 1;
+            let bottom = rect.y + rect.height - 1;
+            let top = edge_line(
+                chars.top_left,
+                chars.horizontal,
+                chars.top_right,
+                rect.width,
+                title,
+                self.title_align,
+            );
+            stdout.execute(MoveTo(rect.x, rect.y))?;
+            stdout.execute(Print(top))?;
+            for y in rect.y + 1..bottom {
+                stdout.execute(MoveTo(rect.x, y))?;
+                stdout.execute(Print(chars.vertical))?;
+                stdout.execute(MoveTo(right, y))?;
+                stdout.execute(Print(chars.vertical))?;
+            }
+            let bottom_line = edge_line(
+                chars.bottom_left,
+                chars.horizontal,
+                chars.bottom_right,
+                rect.width,
+                footer,
+                self.footer_align,
+            );
+            stdout.execute(MoveTo(rect.x, bottom))?;
+            stdout.execute(Print(bottom_line))?;
+        }
+        if let (Some(track), Some(scroll)) = (self.track(rect), scroll) {
+            let (thumb, track_char) = style.scrollbar();
+            let (start, length) = thumb_span(track.height, scroll);
+            for row in 0..track.height {
+                let shown = if (start..start + length).contains(&row) {
+                    thumb
+                } else {
+                    track_char
+                };
+                stdout.execute(MoveTo(track.x, track.y + row))?;
+                stdout.execute(Print(shown))?;
+            }
+        }
+        stdout.execute(ResetColor)?;
+        Ok(())
+    }
+}
+
+/// The top or bottom line of a border `width` cells wide, with `text` in it as `align` says
+fn edge_line(
+    left: char,
+    fill: char,
+    right: char,
+    width: u16,
+    text: Option<&str>,
+    align: TitleAlign,
+) -> String {
+    let inner_width = width.saturating_sub(2) as usize;
+    let mut label = String::new();
+    let mut used = 0;
+    if let Some(text) = text.filter(|text| !text.is_empty()) {
+        for character in format!(" {text} ").chars() {
+            let character_width = character.width().unwrap_or(0);
+            if used + character_width > inner_width {
+                break;
+            }
+            label.push(character);
+            used += character_width;
+        }
+    }
+    let free = inner_width - used;
+    let before = match align {
+        TitleAlign::Left => free.min(1),
+        TitleAlign::Center => free / 2,
+        TitleAlign::Right => free.saturating_sub(1),
+    };
+    let mut line = String::from(left);
+    line.extend(std::iter::repeat_n(fill, before));
+    line.push_str(&label);
+    line.extend(std::iter::repeat_n(fill, free - before));
+    line.push(right);
+    line
+}
+
+/// The first row of the thumb in a track `track` rows long and how many rows it covers
+pub fn thumb_span(track: u16, scroll: ScrollPosition) -> (u16, u16) {
+    if scroll.content <= scroll.visible || scroll.content == 0 {
+        return (0, track);
+    }
+    let track_rows = track as usize;
+    let length = (track_rows * scroll.visible / scroll.content).clamp(1, track_rows);
+    // The thumb reaches the end of the track when the last row is shown
+    let scrollable = scroll.content - scroll.visible;
+    let start = (track_rows - length) * scroll.offset.min(scrollable) / scrollable;
+    (start as u16, length as u16)
+}
+
+/// Rows into the content to scroll to for a click `row` rows down a track `track` rows long
+pub fn offset_at(track: u16, row: u16, scroll: ScrollPosition) -> usize {
+    if track <= 1 {
+        return 0;
+    }
+    let scrollable = scroll.content.saturating_sub(scroll.visible);
+    scrollable * row.min(track - 1) as usize / (track - 1) as usize
+}
@@ -0,0 +1,114 @@
+use std::ops::Range;
+
+use crate::{
+    tui::window::Row,
+    vcs::{
+        VcsStatus,
+        diff::{Algorithm, changed_cols, diff_with},
+    },
+};
+
+/// Unchanged lines kept in view around each change, the rest are folded
+const CONTEXT: usize = 6;
+
+/// How a line compares with the other window's text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineDiff {
+    /// `Add` for lines only this side has, `Modified` for lines paired with one on the other side
+    pub status: VcsStatus,
+    /// The chars that differ from the paired line
+    pub changed: Option<(usize, usize)>,
+}
+
+/// Lines that differ, as ranges of line indices in this window's buffer and the other's
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    pub ours: Range<usize>,
+    pub theirs: Range<usize>,
+}
+
+impl DiffHunk {
+    /// Whether the cursor on `line` is on this hunk, a hunk with no lines here is on the lines either side
+    pub fn touches(&self, line: usize) -> bool {
+        if self.ours.is_empty() {
+            line + 1 == self.ours.start || line == self.ours.start
+        } else {
+            self.ours.contains(&line)
+        }
+    }
+}
+
+/// How one side of a diff is drawn
+#[derive(Debug, Default)]
+pub struct DiffLayout {
+    /// What each row shows, lined up with the other side's rows
+    pub rows: Vec<Row>,
+    /// How each line differs, `None` for unchanged lines
+    pub lines: Vec<Option<LineDiff>>,
+    pub hunks: Vec<DiffHunk>,
+}
+
+/// Lay out `ours` against `theirs`, `first` when ours is the old side on the left
+pub fn layout(ours: &[String], theirs: &[String], first: bool, algorithm: Algorithm) -> DiffLayout {
+    let hunks: Vec<DiffHunk> = if first {
+        diff_with(algorithm, ours, theirs)
+            .into_iter()
+            .map(|hunk| DiffHunk {
+                ours: hunk.old,
+                theirs: hunk.new,
+            })
+            .collect()
+    } else {
+        diff_with(algorithm, theirs, ours)
+            .into_iter()
+            .map(|hunk| DiffHunk {
+                ours: hunk.new,
+                theirs: hunk.old,
+            })
+            .collect()
+    };
+    let mut lines = vec![None; ours.len()];
+    let mut rows = Vec::new();
+    let mut line = 0;
+    for (index, hunk) in hunks.iter().enumerate() {
+        let above = if index == 0 { 0 } else { CONTEXT };
+        unchanged(&mut rows, line..hunk.ours.start, above, CONTEXT);
+        for (offset, line) in hunk.ours.clone().enumerate() {
+            let paired = (offset < hunk.theirs.len()).then(|| &theirs[hunk.theirs.start + offset]);
+            lines[line] = Some(match paired {
+                Some(other) => {
+                    let (changed, _) = changed_cols(&ours[line], other);
+                    LineDiff {
+                        status: VcsStatus::Modified,
+                        changed: (changed.0 < changed.1).then_some(changed),
+                    }
+                }
+                None => LineDiff {
+                    status: VcsStatus::Add,
+                    changed: None,
+                },
+            });
+            rows.push(Row::Line(line));
+        }
+        // The shorter side makes up the difference so the next unchanged lines line up
+        let missing = hunk.theirs.len().saturating_sub(hunk.ours.len());
+        rows.extend(std::iter::repeat_n(Row::Filler, missing));
+        line = hunk.ours.end;
+    }
+    let above = if hunks.is_empty() { 0 } else { CONTEXT };
+    unchanged(&mut rows, line..ours.len(), above, 0);
+    DiffLayout { rows, lines, hunks }
+}
+
+/// Rows for a run of unchanged lines, folding all but `above` lines at its start and `below` at its end
+fn unchanged(rows: &mut Vec<Row>, lines: Range<usize>, above: usize, below: usize) {
+    // Folding a single line saves nothing
+    if lines.len() <= above + below + 1 {
+        rows.extend(lines.map(Row::Line));
+        return;
+    }
+    let (start, end) = (lines.start + above, lines.end - below);
+    rows.extend((lines.start..start).map(Row::Line));
+    rows.push(Row::Fold { start, end });
+    rows.extend((end..lines.end).map(Row::Line));
+}
@@ -0,0 +1,223 @@
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+use anyhow::Result;
+
+use super::Editor;
+use crate::{
+    buffer::{Buffer, BufferId},
+    event::{HostRequest, ReovimEvent},
+    tui::tree::ComponentCommands,
+    vcs,
+};
+
+impl Editor {
+    /// Show another buffer in the window with focus
+    pub(super) fn show_buffer(
+        &mut self,
+        buffer: Rc<RefCell<Buffer>>,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        if Rc::ptr_eq(&buffer, &self.buffer()) {
+            return Ok(());
+        }
+        self.window().borrow_mut().show(buffer.clone());
+        self.rebuild_windows(commands)?;
+        let buffer = buffer.borrow();
+        self.command_line.borrow_mut().set_message(format!(
+            "\"{}\" {}L",
+            buffer.display_name(),
+            buffer.line_count()
+        ));
+        Ok(())
+    }
+
+    pub(super) fn show_buffer_id(
+        &mut self,
+        id: BufferId,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        let buffer = self.buffers.borrow().get(id);
+        match buffer {
+            Some(buffer) => self.show_buffer(buffer, commands),
+            None => anyhow::bail!("E86: Buffer {id} does not exist"),
+        }
+    }
+
+    /// The buffer `:split file` and friends open, or the current one without a file
+    pub(super) fn open_path(
+        &mut self,
+        path: Option<PathBuf>,
+    ) -> Result<Option<Rc<RefCell<Buffer>>>> {
+        let Some(path) = path else {
+            return Ok(None);
+        };
+        let path = std::env::current_dir()?.join(path);
+        let buffer = self.buffers.borrow_mut().open(&path)?;
+        Ok(Some(buffer))
+    }
+
+    /// Write the buffer to the path, or to the file it was opened from
+    pub(super) fn write(
+        &mut self,
+        path: Option<PathBuf>,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        let buffer = self.buffer();
+        let mut buffer = buffer.borrow_mut();
+        if path.is_none() && buffer.file_path().is_none() && buffer.is_from_stdin() {
+            commands.request(HostRequest::Output(buffer.file_contents()?));
+            buffer.mark_saved();
+            self.command_line
+                .borrow_mut()
+                .set_message("written to stdout on exit");
+            return Ok(());
+        }
+        let (path, bytes) = buffer.write(path.as_deref())?;
+        self.lsp.borrow_mut().saved(&mut buffer);
+        // The index may have moved on since the file was opened, with a commit or `git add`
+        if buffer.file_path() == Some(path.as_path()) {
+            vcs::load_in_background(&self.tasks, buffer.id(), path.clone());
+            buffer
+                .write_undo()
+                .map_err(|err| anyhow::anyhow!("E828: Cannot write undo file: {err}"))?;
+        }
+        self.command_line.borrow_mut().set_message(format!(
+            "\"{}\" {}L, {bytes}B written",
+            path.display(),
+            buffer.line_count(),
+        ));
+        Ok(())
+    }
+
+    /// Bring the swap files of modified buffers up to date with their text, writing them on a worker
+    pub(super) fn sync_swap_files(&self) {
+        // Kept with the text, so a recovered buffer opens where the editing was
+        self.buffer().borrow_mut().cursor = self.window().borrow().cursor;
+        for buffer in self.buffers.borrow().iter() {
+            let mut buffer = buffer.borrow_mut();
+            let Some((path, text)) = buffer.sync_swap() else {
+                continue;
+            };
+            let id = buffer.id();
+            self.tasks.spawn(move || {
+                let written = text.write(&path).map_err(|err| err.to_string());
+                ReovimEvent::Swap(id, written)
+            });
+        }
+    }
+
+    pub(super) fn edit(
+        &mut self,
+        path: Option<PathBuf>,
+        force: bool,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        match self.open_path(path)? {
+            Some(buffer) => self.show_buffer(buffer, commands),
+            None => {
+                let buffer = self.buffer();
+                if !force && buffer.borrow().is_modified() {
+                    anyhow::bail!("E37: No write since last change (add ! to override)");
+                }
+                if buffer.borrow().file_path().is_none() {
+                    anyhow::bail!("E32: No file name");
+                }
+                buffer.borrow_mut().reload()?;
+                // Rebuild the rows of every window showing the reloaded lines
+                let last_line = buffer.borrow().line_count() - 1;
+                for window in self.all_windows() {
+                    let mut window = window.borrow_mut();
+                    if Rc::ptr_eq(&window.buffer, &buffer) {
+                        window.cursor.0 = window.cursor.0.min(last_line);
+                    }
+                }
+                self.rebuild_tabs(commands)
+            }
+        }
+    }
+
+    pub(super) fn delete_buffer(
+        &mut self,
+        id: Option<BufferId>,
+        force: bool,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        let id = id.unwrap_or_else(|| self.buffer().borrow().id());
+        let Some(buffer) = self.buffers.borrow().get(id) else {
+            anyhow::bail!("E516: No buffers were deleted");
+        };
+        if !force && buffer.borrow().is_modified() {
+            anyhow::bail!("E89: No write since last change for buffer {id} (add ! to override)");
+        }
+        // Windows showing the buffer switch to their alternate buffer, or the next one, or a fresh empty buffer
+        let mut shown = false;
+        for window in self.all_windows() {
+            if !Rc::ptr_eq(&window.borrow().buffer, &buffer) {
+                continue;
+            }
+            shown = true;
+            let alternate = window.borrow().alternate;
+            let replacement = alternate
+                .filter(|&alternate| alternate != id)
+                .and_then(|alternate| self.buffers.borrow().get(alternate))
+                .or_else(|| {
+                    self.buffers
+                        .borrow()
+                        .next(id, 1)
+                        .filter(|next| !Rc::ptr_eq(next, &buffer))
+                });
+            let replacement = match replacement {
+                Some(replacement) => replacement,
+                None => self.buffers.borrow_mut().add(Buffer::default()),
+            };
+            let mut window = window.borrow_mut();
+            window.show(replacement);
+            if window.alternate == Some(id) {
+                window.alternate = None;
+            }
+        }
+        self.buffers.borrow_mut().remove(id);
+        self.lsp.borrow_mut().close(id);
+        for window in self.all_windows() {
+            let mut window = window.borrow_mut();
+            if window.alternate == Some(id) {
+                window.alternate = None;
+            }
+        }
+        if shown {
+            self.rebuild_tabs(commands)?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn list_buffers(&self) -> String {
+        let window = self.window();
+        let window = window.borrow();
+        let current = window.buffer.borrow().id();
+        let windows = self.all_windows();
+        let mut listing = Vec::new();
+        for buffer in self.buffers.borrow().iter() {
+            let buffer = buffer.borrow();
+            let flag = if buffer.id() == current {
+                '%'
+            } else if Some(buffer.id()) == window.alternate {
+                '#'
+            } else {
+                ' '
+            };
+            // Buffers shown in a window are active, the rest are hidden
+            let active = windows
+                .iter()
+                .any(|window| window.borrow().buffer.borrow().id() == buffer.id());
+            let state = if active { 'a' } else { 'h' };
+            let modified = if buffer.is_modified() { "+" } else { " " };
+            listing.push(format!(
+                "{:>3} {flag}{state} {modified} {:<30} line {}",
+                buffer.id(),
+                format!("\"{}\"", buffer.display_name()),
+                buffer.cursor.0 + 1
+            ));
+        }
+        listing.join("\n")
+    }
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use crossterm::event::KeyCode;
+
+use super::EditableText;
+
+impl EditableText {
+    /// Handle the key after `z` or `zf`, returning where the cursor moves to
+    /// Keys that aren't fold commands do nothing
+    pub(super) fn fold_key(
+        &mut self,
+        pending: &str,
+        code: KeyCode,
+        (line, col): (usize, usize),
+    ) -> Result<Option<(usize, usize)>> {
+        let KeyCode::Char(character) = code else {
+            return Ok(None);
+        };
+        let mut buffer = self.buffer.borrow_mut();
+        match (pending, character) {
+            ("z", 'f') => {
+                self.pending = "zf".to_string();
+                return Ok(None);
+            }
+            ("zf", 'j' | 'k' | 'G') => {
+                let other = match character {
+                    'j' => line + 1,
+                    'k' => line.saturating_sub(1),
+                    _ => usize::MAX,
+                };
+                let last = other.min(buffer.line_count() - 1);
+                buffer.folds().create(line.min(last), line.max(last) + 1)?;
+            }
+            ("z", 'o' | 'c' | 'a' | 'R' | 'M') => {
+                let folds = buffer.folds();
+                match character {
+                    'o' => folds.open_at(line)?,
+                    'c' => folds.close_at(line)?,
+                    'a' => folds.toggle_at(line)?,
+                    'R' => folds.set_all(false),
+                    _ => folds.set_all(true),
+                }
+            }
+            ("z", 'j' | 'k') => {
+                let found = if character == 'j' {
+                    buffer.folds().next_start(line)
+                } else {
+                    buffer.folds().previous_end(line)
+                };
+                return Ok(found.map(|line| (line, col)));
+            }
+            _ => return Ok(None),
+        }
+        Ok(Some((line, col)))
+    }
+}
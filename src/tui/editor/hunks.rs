@@ -0,0 +1,188 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use anyhow::{Result, bail};
+
+use super::{EditableText, Editor};
+use crate::{
+    buffer::{Buffer, TextRange},
+    tui::{hover::HoverPopup, tree::ComponentCommands},
+    vcs::{self, diff::Hunk},
+};
+
+impl EditableText {
+    /// Handle `]c` and `[c`, which go to the next or previous hunk, and `do` and `dp`, which only
+    /// work in diff mode, returning where the cursor moves to
+    pub(super) fn diff_key(
+        &mut self,
+        pending: &str,
+        character: char,
+        (line, col): (usize, usize),
+    ) -> Result<Option<(usize, usize)>> {
+        match pending {
+            "]" | "[" => Ok(self.next_hunk(line, pending == "]").map(|line| (line, 0))),
+            _ if self.window.borrow().diff_with.is_none() => {
+                bail!("E99: Current buffer is not in diff mode")
+            }
+            _ => Ok(self
+                .put_hunk(line, character == 'o')?
+                .map(|line| (line, col))),
+        }
+    }
+
+    /// The first line of the hunk after `line`, or before it when going back, for `]c` and `[c`
+    /// In diff mode these are the changes against the other window, otherwise against git's index
+    fn next_hunk(&self, line: usize, forward: bool) -> Option<usize> {
+        let ranges: Vec<_> = if self.window.borrow().diff_with.is_some() {
+            self.diff_hunks
+                .iter()
+                .map(|hunk| hunk.ours.clone())
+                .collect()
+        } else {
+            let hunks = self.buffer.borrow().hunks();
+            hunks.into_iter().map(|hunk| hunk.new).collect()
+        };
+        let starts = ranges
+            .iter()
+            .map(|range| range.start.min(range.end.saturating_sub(1)))
+            .collect::<Vec<_>>();
+        let found = if forward {
+            starts.into_iter().find(|&start| start > line)
+        } else {
+            starts.into_iter().rev().find(|&start| start < line)
+        };
+        if found.is_none() {
+            self.command_line
+                .borrow_mut()
+                .set_error("No more hunks".to_string());
+        }
+        found
+    }
+
+    /// Copy the diff hunk at `line` from the other window for `do`, or over to it for `dp`
+    /// Returns the line the cursor goes to
+    fn put_hunk(&mut self, line: usize, obtain: bool) -> Result<Option<usize>> {
+        let Some(partner) = self.window.borrow().diff_with.clone() else {
+            return Ok(None);
+        };
+        let Some(hunk) = self
+            .diff_hunks
+            .iter()
+            .find(|hunk| hunk.touches(line))
+            .cloned()
+        else {
+            self.command_line
+                .borrow_mut()
+                .set_error("No hunk under the cursor".to_string());
+            return Ok(None);
+        };
+        let (from, to, lines, replaced) = if obtain {
+            (
+                &partner.buffer,
+                &self.buffer,
+                hunk.theirs,
+                hunk.ours.clone(),
+            )
+        } else {
+            (
+                &self.buffer,
+                &partner.buffer,
+                hunk.ours.clone(),
+                hunk.theirs,
+            )
+        };
+        let text: String = from
+            .borrow()
+            .lines()
+            .cloned(lines)
+            .iter()
+            .map(|line| format!("{line}\n"))
+            .collect();
+        let range = TextRange {
+            start: (replaced.start, 0),
+            end: (replaced.end, 0),
+        };
+        let mut to = to.borrow_mut();
+        let cursor = to.cursor;
+        to.replace_range(range, &text, cursor)?;
+        if obtain {
+            return Ok(Some(hunk.ours.start));
+        }
+        self.lsp.borrow_mut().sync(&mut to);
+        Ok(Some(line))
+    }
+}
+
+impl Editor {
+    /// The git hunk the cursor of the window with focus is on
+    fn hunk_at_cursor(&self) -> Result<(Rc<RefCell<Buffer>>, Hunk)> {
+        let buffer = self.buffer();
+        let line = self.window().borrow().cursor.0;
+        let hunk = buffer
+            .borrow()
+            .hunks()
+            .into_iter()
+            .find(|hunk| hunk.touches(line));
+        match hunk {
+            Some(hunk) => Ok((buffer, hunk)),
+            None => anyhow::bail!("No hunk under the cursor"),
+        }
+    }
+
+    pub(super) fn preview_hunk(&mut self, commands: &mut ComponentCommands) -> Result<()> {
+        let (buffer, hunk) = self.hunk_at_cursor()?;
+        let popup = {
+            let buffer = buffer.borrow();
+            let base = buffer
+                .vcs()
+                .and_then(|vcs| vcs.base.as_deref())
+                .unwrap_or_default();
+            HoverPopup::hunk(&base[hunk.old], &buffer.lines().as_slice()[hunk.new])
+        };
+        self.close_hover(commands);
+        self.hover_id =
+            Some(commands.add_overlay(popup, HoverPopup::overlay(self.win_border.get()))?);
+        Ok(())
+    }
+
+    /// Write the index's version of the file with the hunk under the cursor as it is in the buffer
+    /// git runs on a worker, the gutter catches up once it's done
+    pub(super) fn stage_hunk(&mut self) -> Result<()> {
+        let (buffer, hunk) = self.hunk_at_cursor()?;
+        let buffer = buffer.borrow();
+        let Some(path) = buffer.file_path().map(Path::to_path_buf) else {
+            anyhow::bail!("E32: No file name");
+        };
+        let Some(info) = buffer.vcs() else {
+            anyhow::bail!("Not in a git repository");
+        };
+        let staged = info.with_hunk(&hunk, buffer.lines().as_slice());
+        vcs::stage_in_background(&self.tasks, buffer.id(), path, staged);
+        Ok(())
+    }
+
+    /// Put back the index's lines in place of the hunk under the cursor
+    pub(super) fn reset_hunk(&mut self, commands: &mut ComponentCommands) -> Result<()> {
+        let (buffer, hunk) = self.hunk_at_cursor()?;
+        let line = {
+            let mut buffer = buffer.borrow_mut();
+            let base = buffer
+                .vcs()
+                .and_then(|vcs| vcs.base.clone())
+                .unwrap_or_default();
+            let text: String = base[hunk.old]
+                .iter()
+                .map(|line| format!("{line}\n"))
+                .collect();
+            let range = TextRange {
+                start: (hunk.new.start, 0),
+                end: (hunk.new.end, 0),
+            };
+            let cursor = self.window().borrow().cursor;
+            buffer.replace_range(range, &text, cursor)?;
+            self.lsp.borrow_mut().sync(&mut buffer);
+            hunk.new.start.min(buffer.line_count() - 1)
+        };
+        self.window().borrow_mut().cursor = (line, 0);
+        self.rebuild_tabs(commands)
+    }
+}
@@ -0,0 +1,206 @@
+use std::{path::Path, rc::Rc};
+
+use anyhow::Result;
+
+use super::Editor;
+use crate::{
+    buffer::Buffer,
+    lsp::{
+        self, Lsp, LspReply,
+        protocol::{Location, WorkspaceEdit, uri_to_path},
+    },
+    tui::{hover::HoverPopup, tree::ComponentCommands},
+};
+
+impl Editor {
+    /// Act on what a language server sent
+    pub(super) fn lsp_reply(
+        &mut self,
+        reply: LspReply,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        match reply {
+            LspReply::Diagnostics { path, diagnostics } => {
+                let buffer = self
+                    .buffers
+                    .borrow()
+                    .iter()
+                    .find(|buffer| buffer.borrow().file_path() == Some(path.as_path()))
+                    .cloned();
+                if let Some(buffer) = buffer {
+                    buffer.borrow_mut().set_diagnostics(diagnostics);
+                }
+            }
+            LspReply::Hover(text) => self.show_hover(&text, commands)?,
+            LspReply::Definition(locations) => {
+                let Some(location) = locations.first() else {
+                    anyhow::bail!("No definition found");
+                };
+                self.jump_to(location, commands)?;
+                if locations.len() > 1 {
+                    self.command_line
+                        .borrow_mut()
+                        .set_message(format!("Definition 1 of {}", locations.len()));
+                }
+            }
+            LspReply::References(locations) => {
+                if locations.is_empty() {
+                    anyhow::bail!("No references found");
+                }
+                // Each `gr` goes on to the reference after the one the cursor is on
+                let here = {
+                    let window = self.window();
+                    let window = window.borrow();
+                    let buffer = window.buffer.borrow();
+                    (buffer.file_path().map(Path::to_path_buf), window.cursor)
+                };
+                let index = locations
+                    .iter()
+                    .position(|location| {
+                        let buffer = self.buffer();
+                        let buffer = buffer.borrow();
+                        uri_to_path(&location.uri) == here.0
+                            && location.range.start.to_cursor(buffer.lines().as_slice()) == here.1
+                    })
+                    .map_or(0, |index| (index + 1) % locations.len());
+                self.jump_to(&locations[index], commands)?;
+                self.command_line.borrow_mut().set_message(format!(
+                    "Reference {} of {}",
+                    index + 1,
+                    locations.len()
+                ));
+            }
+            LspReply::Edit(edit) => self.apply_workspace_edit(edit, commands)?,
+            LspReply::Format { buffer, edits } => {
+                let Some(buffer) = self.buffers.borrow().get(buffer) else {
+                    return Ok(());
+                };
+                lsp::apply_edits(&mut buffer.borrow_mut(), edits)?;
+                self.lsp.borrow_mut().sync(&mut buffer.borrow_mut());
+                self.rebuild_tabs(commands)?;
+            }
+            LspReply::CodeActions(titles) => {
+                if titles.is_empty() {
+                    anyhow::bail!("No code actions available");
+                }
+                let mut listing: Vec<String> = titles
+                    .iter()
+                    .enumerate()
+                    .map(|(index, title)| format!("{:>3}: {title}", index + 1))
+                    .collect();
+                listing.push("Apply one with :codeaction N".to_string());
+                self.command_line
+                    .borrow_mut()
+                    .set_message(listing.join("\n"));
+            }
+            LspReply::Message { text, error: true } => {
+                self.command_line.borrow_mut().set_error(text);
+            }
+            LspReply::Message { text, error: false } => {
+                self.command_line.borrow_mut().set_message(text);
+            }
+        }
+        Ok(())
+    }
+
+    /// Float hover text below the cursor
+    fn show_hover(&mut self, text: &str, commands: &mut ComponentCommands) -> Result<()> {
+        self.close_hover(commands);
+        if text.trim().is_empty() {
+            anyhow::bail!("No information available");
+        }
+        self.hover_id = Some(commands.add_overlay(
+            HoverPopup::new(text),
+            HoverPopup::overlay(self.win_border.get()),
+        )?);
+        Ok(())
+    }
+
+    pub(super) fn close_hover(&mut self, commands: &mut ComponentCommands) {
+        if let Some(hover_id) = self.hover_id.take() {
+            commands.remove_component(hover_id);
+        }
+    }
+
+    /// Put the cursor of the window with focus on a location, showing its file if it isn't already
+    fn jump_to(&mut self, location: &Location, commands: &mut ComponentCommands) -> Result<()> {
+        let Some(path) = uri_to_path(&location.uri) else {
+            anyhow::bail!("Cannot open {}", location.uri);
+        };
+        let buffer = self.buffers.borrow_mut().open(&path)?;
+        let target = {
+            let buffer = buffer.borrow();
+            let (line, col) = location.range.start.to_cursor(buffer.lines().as_slice());
+            let line = line.min(buffer.line_count() - 1);
+            (line, col.min(buffer.line_len(line).saturating_sub(1)))
+        };
+        if Rc::ptr_eq(&buffer, &self.buffer()) {
+            self.window().borrow_mut().cursor = target;
+            self.focus_window(self.current.get(), commands);
+            return Ok(());
+        }
+        buffer.borrow_mut().cursor = target;
+        self.show_buffer(buffer, commands)
+    }
+
+    /// Make the edits of a rename or code action, loading the files not open yet
+    pub(super) fn apply_workspace_edit(
+        &mut self,
+        edit: WorkspaceEdit,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        let files = edit.len();
+        let mut count = 0;
+        for (path, edits) in edit {
+            let buffer = self.buffers.borrow_mut().open(&path)?;
+            count += edits.len();
+            lsp::apply_edits(&mut buffer.borrow_mut(), edits)?;
+            self.lsp.borrow_mut().sync(&mut buffer.borrow_mut());
+        }
+        self.rebuild_tabs(commands)?;
+        let files = if files == 1 {
+            "1 file".to_string()
+        } else {
+            format!("{files} files")
+        };
+        self.command_line
+            .borrow_mut()
+            .set_message(format!("{count} changes in {files}"));
+        Ok(())
+    }
+
+    /// The language server's diagnostics for the current buffer, for `:diagnostics`
+    pub(super) fn list_diagnostics(&self) -> String {
+        let buffer = self.buffer();
+        let buffer = buffer.borrow();
+        if buffer.diagnostics().is_empty() {
+            return "No diagnostics".to_string();
+        }
+        buffer
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| {
+                let (line, col) = diagnostic.range.start.to_cursor(buffer.lines().as_slice());
+                let message = diagnostic.message.lines().next().unwrap_or_default();
+                format!(
+                    "{}:{}: {} {message}",
+                    line + 1,
+                    col + 1,
+                    diagnostic.severity.sign()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Send a request about the cursor position to the current buffer's language server
+    pub(super) fn lsp_request(
+        &self,
+        request: impl FnOnce(&mut Lsp, &mut Buffer, (usize, usize)) -> Result<()>,
+    ) -> Result<()> {
+        let window = self.window();
+        let window = window.borrow();
+        let mut buffer = window.buffer.borrow_mut();
+        request(&mut self.lsp.borrow_mut(), &mut buffer, window.cursor)
+    }
+}
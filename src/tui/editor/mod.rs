@@ -0,0 +1,1801 @@
+use std::{
+    cell::{Cell, RefCell, RefMut},
+    rc::Rc,
+};
+
+use anyhow::Result;
+use crossterm::{
+    event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
+    terminal,
+};
+
+use crate::{
+    buffer::{Buffer, TextRange, byte_index, grapheme, list::BufferList},
+    completion::CompletionSources,
+    event::{HostRequest, ReovimEvent},
+    lsp::Lsp,
+    snippet::{Range, library::SnippetLibrary, session::SnippetSession},
+    syntax::{TextObject, theme::Theme},
+    task::Tasks,
+    tui::{
+        Component, CursorStyle, Formatting, LayoutMode, Measurement, Overflow,
+        command::{CommandComponent, CommandLine, ExCommand},
+        completion::{Completion, CompletionKey},
+        decoration::BorderStyle,
+        diff::{self, DiffHunk, LineDiff},
+        tab::{TabLineComponent, TabList, TabPage},
+        tree::{ComponentCommands, ComponentId, ComponentNode, Frame},
+        window::{
+            Layout, Row, Rows, SplitComponent, Window, WindowContext, WindowId, pair_diff_windows,
+        },
+        wrap::{self, WrapOptions},
+    },
+    vcs::{self, diff::Algorithm},
+};
+
+mod buffers;
+mod fold;
+mod hunks;
+mod language_server;
+mod mouse;
+mod options;
+mod rows;
+mod snippets;
+mod tabs;
+mod visual;
+mod windows;
+
+use mouse::{Click, SelectBy};
+use rows::TextRow;
+use tabs::show_tab_page;
+
+/// The editing mode, shared between the text, the command line and the editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    /// Selecting text from where `v` was pressed to the cursor
+    Visual,
+}
+
+impl Mode {
+    fn cursor_style(self) -> CursorStyle {
+        match self {
+            Mode::Normal | Mode::Visual => CursorStyle::Block,
+            Mode::Insert => CursorStyle::Line,
+        }
+    }
+}
+
+/// Row components a window keeps when the terminal's size can't be read
+const DEFAULT_HEIGHT: usize = 24;
+/// Screen rows the mouse wheel scrolls by until `:set mousescroll` says otherwise
+const DEFAULT_MOUSE_SCROLL: usize = 3;
+
+/// What a window draws over its text, shared by every row
+#[derive(Default)]
+struct Marks {
+    /// The snippet placeholder being filled in
+    placeholder: Cell<Option<Range>>,
+    /// The visual mode selection
+    selection: Cell<Option<TextRange>>,
+    /// How each line differs from the other window's in diff mode
+    diff: RefCell<Vec<Option<LineDiff>>>,
+    /// How long lines are shown, the window's options
+    wrap: RefCell<WrapOptions>,
+    /// The first char shown of every line when they don't wrap
+    left_col: Cell<usize>,
+    /// The line at the top of the window and how many of its screen rows are scrolled above it
+    skipped: Cell<Option<(usize, usize)>>,
+}
+
+impl Marks {
+    /// Screen rows of `line` scrolled out of view above the window
+    fn skipped(&self, line: usize) -> usize {
+        match self.skipped.get() {
+            Some((top, rows)) if top == line => rows,
+            _ => 0,
+        }
+    }
+}
+
+/// A window's view onto its buffer with one row per line, handling vi motions and edits
+/// Only the rows in view have components, they are bound to other rows as the window scrolls
+pub struct EditableText {
+    window: Rc<RefCell<Window>>,
+    /// The buffer the window showed when this component was built
+    buffer: Rc<RefCell<Buffer>>,
+    mode: Rc<Cell<Mode>>,
+    command_line: Rc<RefCell<CommandLine>>,
+    current: Rc<Cell<WindowId>>,
+    /// Screen column j/k try to return to after passing shorter lines
+    desired_col: usize,
+    /// The line auto-indent just indented, the indent goes again when nothing is typed on it
+    autoindented: Option<usize>,
+    /// First keys of a normal or visual mode command, like `d` in `dd` or `di` in `dif`
+    pending: String,
+    completion: Completion,
+    snippets: Rc<SnippetLibrary>,
+    /// The snippet being filled in
+    snippet: Option<SnippetSession>,
+    /// The placeholder and selection for the rows to highlight
+    marks: Rc<Marks>,
+    theme: Rc<Theme>,
+    /// Where the visual mode selection started, the cursor is its other end
+    anchor: (usize, usize),
+    /// Selections grown into syntax nodes, for shrinking back
+    expanded: Vec<TextRange>,
+    lsp: Rc<RefCell<Lsp>>,
+    /// For reading what git has for the buffer's file
+    tasks: Tasks,
+    diff_algorithm: Rc<Cell<Algorithm>>,
+    /// Where the buffer differs from the other window's in diff mode
+    diff_hunks: Vec<DiffHunk>,
+    /// Columns the window had when the last frame was drawn
+    width: u16,
+    /// Screen rows the window had when the last frame was drawn
+    height: usize,
+    /// What each row component shows, in order from the top of the window
+    slots: Vec<Rc<Cell<Row>>>,
+    /// Row components to keep, enough to fill the terminal
+    capacity: usize,
+    /// Screen rows the mouse wheel scrolls by, shared by every window
+    mouse_scroll: Rc<Cell<usize>>,
+    /// The text last selected in any window, for a middle click to paste
+    primary_selection: Rc<RefCell<String>>,
+    /// The last left click, for counting double and triple clicks
+    last_click: Option<Click>,
+    /// What the left button pressed on and what it selects by while it is held, a drag selects from there
+    grabbed: Option<(SelectBy, TextRange)>,
+}
+
+/// What an insert mode key does to the text, for following edits inside a snippet placeholder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InsertEdit {
+    /// Moves the cursor, the text stays the same
+    Move,
+    /// Adds text or breaks the line
+    Insert,
+    /// Removes text
+    Delete,
+}
+
+impl InsertEdit {
+    fn of(key: KeyEvent) -> InsertEdit {
+        match key.code {
+            KeyCode::Char(_) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                InsertEdit::Insert
+            }
+            KeyCode::Tab | KeyCode::Enter => InsertEdit::Insert,
+            KeyCode::Backspace | KeyCode::Delete => InsertEdit::Delete,
+            _ => InsertEdit::Move,
+        }
+    }
+}
+
+impl EditableText {
+    pub fn new(window: Rc<RefCell<Window>>, context: WindowContext) -> Self {
+        let (buffer, desired_col, wrap) = {
+            let window = window.borrow();
+            let (line, col) = window.cursor;
+            let buffer = window.buffer.borrow();
+            let text = buffer.line(line).unwrap_or_default();
+            // Tabs are drawn as wide as the buffer's `tabstop`, `:set` rebuilds the windows
+            let wrap = WrapOptions {
+                tabstop: buffer.options.tabstop,
+                ..window.wrap.clone()
+            };
+            (
+                window.buffer.clone(),
+                wrap::display_col(text, col, &wrap),
+                wrap,
+            )
+        };
+        Self {
+            window,
+            buffer,
+            mode: context.mode,
+            command_line: context.command_line,
+            current: context.current,
+            desired_col,
+            autoindented: None,
+            pending: String::new(),
+            completion: Completion::new(
+                context.completion,
+                context.tasks.clone(),
+                context.win_border,
+            ),
+            snippets: context.snippets,
+            theme: context.theme,
+            snippet: None,
+            marks: Rc::new(Marks {
+                wrap: RefCell::new(wrap),
+                ..Default::default()
+            }),
+            anchor: (0, 0),
+            expanded: Vec::new(),
+            lsp: context.lsp,
+            tasks: context.tasks,
+            diff_algorithm: context.diff_algorithm,
+            diff_hunks: Vec::new(),
+            width: 0,
+            height: 0,
+            slots: Vec::new(),
+            capacity: terminal::size().map_or(DEFAULT_HEIGHT, |(_, rows)| rows as usize),
+            mouse_scroll: context.mouse_scroll,
+            primary_selection: context.primary_selection,
+            last_click: None,
+            grabbed: None,
+        }
+    }
+
+    /// Furthest column the cursor may be on, insert mode may sit after the last cluster
+    fn max_col(&self, line: usize) -> usize {
+        let len = self.buffer.borrow().line_len(line);
+        match self.mode.get() {
+            Mode::Insert => len,
+            Mode::Normal | Mode::Visual => {
+                grapheme::cluster_start(&self.line_text(line), len.saturating_sub(1))
+            }
+        }
+    }
+
+    /// Keep the cursor in the buffer and on the first char of a grapheme cluster
+    fn clamp(&self, line: usize, col: usize) -> (usize, usize) {
+        let last_line = self.buffer.borrow().line_count().saturating_sub(1);
+        let line = line.min(last_line);
+        let col = col.min(self.max_col(line));
+        (line, grapheme::cluster_start(&self.line_text(line), col))
+    }
+
+    /// Remember the screen column of `target` for moving up and down
+    fn remember_col(&mut self, (line, col): (usize, usize)) {
+        let options = self.marks.wrap.borrow();
+        self.desired_col = wrap::display_col(&self.line_text(line), col, &options);
+    }
+
+    /// Move vertically by rows, keeping the desired column where the line is long enough
+    /// Filler rows are passed over and a fold counts as one row
+    fn vertical(&self, line: usize, delta: isize) -> (usize, usize) {
+        let window = self.window.borrow();
+        let mut row = window.row_of(line);
+        let mut remaining = delta.unsigned_abs();
+        while remaining > 0 {
+            let Some(next) = row
+                .checked_add_signed(delta.signum())
+                .filter(|&next| next < window.rows.len())
+            else {
+                break;
+            };
+            row = next;
+            if window.rows.get(row) != Some(Row::Filler) {
+                remaining -= 1;
+            }
+        }
+        let line = window.line_at(row);
+        let options = self.marks.wrap.borrow();
+        let col = wrap::col_at_display(&self.line_text(line), self.desired_col, &options);
+        self.clamp(line, col)
+    }
+
+    fn line_text(&self, line: usize) -> String {
+        self.buffer
+            .borrow()
+            .line(line)
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Columns the text of each row has, the window's width less the gutter
+    fn text_width(&self) -> usize {
+        let gutter = self.buffer.borrow().line_count().to_string().len() + 2;
+        (self.width as usize).saturating_sub(gutter)
+    }
+
+    /// The screen rows `text` is shown on
+    fn screen_lines(&self, text: &str) -> Vec<wrap::ScreenLine> {
+        let options = self.marks.wrap.borrow();
+        wrap::screen_lines(text, self.text_width(), self.marks.left_col.get(), &options)
+    }
+
+    /// Move a screen row down or up for `gj` and `gk`, over to the next line past the rows of this one
+    /// The cursor keeps its column on screen, counted from where the text of the row starts
+    fn screen_vertical(&self, (line, col): (usize, usize), down: bool) -> (usize, usize) {
+        let options = self.marks.wrap.borrow().clone();
+        let text = self.line_text(line);
+        let screen_lines = self.screen_lines(&text);
+        let row = wrap::row_of(&screen_lines, col);
+        let (_, screen_col) = wrap::screen_position(&text, &screen_lines, col, &options);
+        let offset = screen_col - screen_lines[row].prefix(&options);
+        let (line, text, screen_lines, row) = if down && row + 1 < screen_lines.len() {
+            (line, text, screen_lines, row + 1)
+        } else if !down && row > 0 {
+            (line, text, screen_lines, row - 1)
+        } else {
+            let (next, _) = self.vertical(line, if down { 1 } else { -1 });
+            if next == line {
+                return (line, col);
+            }
+            let text = self.line_text(next);
+            let screen_lines = self.screen_lines(&text);
+            let row = if down { 0 } else { screen_lines.len() - 1 };
+            (next, text, screen_lines, row)
+        };
+        let screen_col = screen_lines[row].prefix(&options) + offset;
+        (
+            line,
+            wrap::col_at(&text, &screen_lines, row, screen_col, &options),
+        )
+    }
+
+    fn enter_insert(&mut self, line: usize, col: usize) -> (usize, usize) {
+        self.mode.set(Mode::Insert);
+        self.buffer.borrow_mut().begin_change((line, col));
+        self.clamp(line, col)
+    }
+
+    /// Handle a key in normal mode, returning where the cursor moves to
+    fn normal_key(
+        &mut self,
+        key: KeyEvent,
+        line: usize,
+        col: usize,
+    ) -> Result<Option<(usize, usize)>> {
+        let pending = std::mem::take(&mut self.pending);
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let target = match (pending.as_str(), key.code) {
+            ("d", KeyCode::Char('d')) => {
+                self.buffer
+                    .borrow_mut()
+                    .remove_lines(line, 1, (line, col))?;
+                self.clamp(line, 0)
+            }
+            ("g", KeyCode::Char('g')) => self.clamp(0, 0),
+            ("g", KeyCode::Char('j')) => self.screen_vertical((line, col), true),
+            ("g", KeyCode::Char('k')) => self.screen_vertical((line, col), false),
+            ("g", KeyCode::Char(character @ ('0' | '$'))) => {
+                let text = self.line_text(line);
+                let screen_lines = self.screen_lines(&text);
+                let row = &screen_lines[wrap::row_of(&screen_lines, col)];
+                match character {
+                    '0' => (line, row.start),
+                    _ => (line, row.end.saturating_sub(1).max(row.start)),
+                }
+            }
+            // The language server answers later, the editor jumps once it does
+            ("g", KeyCode::Char('d')) => {
+                let mut lsp = self.lsp.borrow_mut();
+                lsp.definition(&mut self.buffer.borrow_mut(), (line, col))?;
+                return Ok(None);
+            }
+            ("g", KeyCode::Char('r')) => {
+                let mut lsp = self.lsp.borrow_mut();
+                lsp.references(&mut self.buffer.borrow_mut(), (line, col))?;
+                return Ok(None);
+            }
+            ("", KeyCode::Char('K')) => {
+                let mut lsp = self.lsp.borrow_mut();
+                lsp.hover(&mut self.buffer.borrow_mut(), (line, col))?;
+                return Ok(None);
+            }
+            ("]" | "[", KeyCode::Char(character @ 'c'))
+            | ("d", KeyCode::Char(character @ ('o' | 'p'))) => {
+                match self.diff_key(&pending, character, (line, col))? {
+                    Some(target) => target,
+                    None => return Ok(None),
+                }
+            }
+            ("z" | "zf", code) => match self.fold_key(&pending, code, (line, col))? {
+                Some(target) => target,
+                None => return Ok(None),
+            },
+            (">", KeyCode::Char('>')) | ("<", KeyCode::Char('<')) => {
+                self.buffer.borrow_mut().shift_lines(
+                    line..line + 1,
+                    pending == ">",
+                    (line, col),
+                )?;
+                self.first_non_blank(line)
+            }
+            ("", KeyCode::Char(character @ ('d' | 'c' | 'g' | 'z' | ']' | '[' | '>' | '<')))
+                if !control =>
+            {
+                self.pending.push(character);
+                return Ok(None);
+            }
+            ("d" | "c", KeyCode::Char(character @ ('a' | 'i'))) => {
+                self.pending = format!("{pending}{character}");
+                return Ok(None);
+            }
+            (command @ ("da" | "di" | "ca" | "ci"), KeyCode::Char(character @ ('f' | 'c'))) => {
+                let (operator, scope) = (command.as_bytes()[0], command.as_bytes()[1]);
+                let Some(range) = self.object_at((line, col), character, scope == b'i') else {
+                    return Ok(None);
+                };
+                return self.operate(operator == b'c', range, (line, col)).map(Some);
+            }
+            (_, KeyCode::Char('v')) if !control => {
+                self.start_visual((line, col), None);
+                (line, col)
+            }
+            (_, KeyCode::Char('o')) if alt => {
+                let cursor = TextRange {
+                    start: (line, col),
+                    end: (line, col),
+                };
+                let Some(node) = self.buffer.borrow().expand_node(cursor) else {
+                    return Ok(None);
+                };
+                self.start_visual((line, col), Some(node));
+                return Ok(Some(self.selection_cursor(node)));
+            }
+            (_, KeyCode::Char('r')) if control => match self.buffer.borrow_mut().redo() {
+                Some(cursor) => cursor,
+                None => return Ok(None),
+            },
+            (_, KeyCode::Char('h')) | (_, KeyCode::Left) | (_, KeyCode::Backspace) => {
+                self.clamp(line, grapheme::previous_cluster(&self.line_text(line), col))
+            }
+            (_, KeyCode::Char('l')) | (_, KeyCode::Right) | (_, KeyCode::Char(' ')) => {
+                self.clamp(line, grapheme::next_cluster(&self.line_text(line), col))
+            }
+            (_, KeyCode::Char('j')) | (_, KeyCode::Down) | (_, KeyCode::Enter) => {
+                return Ok(Some(self.vertical(line, 1)));
+            }
+            (_, KeyCode::Char('k')) | (_, KeyCode::Up) => {
+                return Ok(Some(self.vertical(line, -1)));
+            }
+            (_, KeyCode::Char('0')) | (_, KeyCode::Home) => self.clamp(line, 0),
+            (_, KeyCode::Char('$')) | (_, KeyCode::End) => {
+                let target = self.clamp(line, usize::MAX);
+                self.desired_col = usize::MAX;
+                return Ok(Some(target));
+            }
+            (_, KeyCode::Char('G')) => self.clamp(usize::MAX, col),
+            (_, KeyCode::Char('x')) | (_, KeyCode::Delete) => {
+                self.buffer.borrow_mut().remove_cluster(line, col)?;
+                self.clamp(line, col)
+            }
+            (_, KeyCode::Char('J')) => {
+                let join_col = self.buffer.borrow().line_len(line);
+                self.buffer.borrow_mut().join_line(line)?;
+                self.clamp(line, join_col)
+            }
+            (_, KeyCode::Char('u')) if !control => match self.buffer.borrow_mut().undo() {
+                Some(cursor) => cursor,
+                None => {
+                    self.command_line
+                        .borrow_mut()
+                        .set_message("Already at oldest change");
+                    return Ok(None);
+                }
+            },
+            (_, KeyCode::Char('i')) | (_, KeyCode::Insert) => self.enter_insert(line, col),
+            (_, KeyCode::Char('a')) => {
+                self.enter_insert(line, grapheme::next_cluster(&self.line_text(line), col))
+            }
+            (_, KeyCode::Char('I')) => self.enter_insert(line, 0),
+            (_, KeyCode::Char('A')) => self.enter_insert(line, usize::MAX),
+            (_, KeyCode::Char(character @ ('o' | 'O'))) => {
+                let at = if character == 'o' { line + 1 } else { line };
+                let mut buffer = self.buffer.borrow_mut();
+                let indent = match buffer.options.autoindent {
+                    true => buffer
+                        .options
+                        .indent_width(buffer.line(line).unwrap_or_default()),
+                    false => 0,
+                };
+                let blanks = buffer.options.blanks(0, indent);
+                buffer.insert_lines(at, vec![blanks], (line, col))?;
+                drop(buffer);
+                self.enter_insert(at, 0);
+                self.autoindent(at)
+            }
+            _ => return Ok(None),
+        };
+        let target = self.clamp(target.0, target.1);
+        self.remember_col(target);
+        Ok(Some(target))
+    }
+
+    /// The `af`/`if`/`ac`/`ic` text object at `cursor`, from the buffer's syntax tree
+    fn object_at(&self, cursor: (usize, usize), kind: char, inner: bool) -> Option<TextRange> {
+        let object = match kind {
+            'f' => TextObject::Function,
+            _ => TextObject::Class,
+        };
+        let range = self.buffer.borrow().text_object(cursor, object, inner);
+        if range.is_none() {
+            let name = if object == TextObject::Function {
+                "function"
+            } else {
+                "class"
+            };
+            self.command_line
+                .borrow_mut()
+                .set_error(format!("No {name} under the cursor"));
+        }
+        range
+    }
+
+    /// Delete a range, or change it when `change` is set, returning where the cursor goes
+    fn operate(
+        &mut self,
+        change: bool,
+        mut range: TextRange,
+        cursor: (usize, usize),
+    ) -> Result<(usize, usize)> {
+        if !change {
+            self.buffer.borrow_mut().remove_range(range, cursor)?;
+            return Ok(self.clamp(range.start.0, range.start.1));
+        }
+        // Changing whole lines leaves an empty one to type on
+        if range.end.1 == 0 && range.end.0 > range.start.0 && range.start.1 == 0 {
+            let last = range.end.0 - 1;
+            range.end = (last, self.buffer.borrow().line_len(last));
+        }
+        let mut buffer = self.buffer.borrow_mut();
+        buffer.begin_change(cursor);
+        buffer.remove_range(range, cursor)?;
+        drop(buffer);
+        Ok(self.enter_insert(range.start.0, range.start.1))
+    }
+
+    /// The first char of `line` that isn't a blank, where `>>` and `<<` leave the cursor
+    fn first_non_blank(&self, line: usize) -> (usize, usize) {
+        let text = self.line_text(line);
+        let indent = text.len() - text.trim_start_matches([' ', '\t']).len();
+        self.clamp(line, text[..indent].chars().count())
+    }
+
+    /// Put the cursor after the indent of a line auto-indent just made
+    fn autoindent(&mut self, line: usize) -> (usize, usize) {
+        let text = self.line_text(line);
+        let indent = text.len() - text.trim_start_matches([' ', '\t']).len();
+        if indent > 0 {
+            self.autoindented = Some(line);
+        }
+        (line, text[..indent].chars().count())
+    }
+
+    /// Make the blanks before the cursor reach the screen column `to` gives for the one the cursor is on
+    /// They are tabs where they fit unless `expandtab` is set, the cursor's new column is returned
+    fn fill_blanks(
+        &mut self,
+        (line, col): (usize, usize),
+        to: impl FnOnce(usize) -> usize,
+    ) -> Result<usize> {
+        let mut buffer = self.buffer.borrow_mut();
+        let options = buffer.options;
+        let text = buffer.line(line).unwrap_or_default();
+        let (before, after) = text.split_at(byte_index(text, col));
+        let kept = before.trim_end_matches([' ', '\t']);
+        let from = options.display_width(kept);
+        let blanks = options.blanks(from, to(options.display_width(before)).max(from));
+        let filled = format!("{kept}{blanks}{after}");
+        let end = kept.chars().count() + blanks.chars().count();
+        buffer.set_line(line, filled, (line, col))?;
+        Ok(end)
+    }
+
+    /// Handle a key in insert mode, returning where the cursor moves to
+    fn insert_key(
+        &mut self,
+        key: KeyEvent,
+        line: usize,
+        col: usize,
+    ) -> Result<Option<(usize, usize)>> {
+        // Auto-indent goes again from a line left without anything typed on it
+        let unused_indent = self.autoindented.take() == Some(line)
+            && self
+                .line_text(line)
+                .trim_start_matches([' ', '\t'])
+                .is_empty();
+        let target = match key.code {
+            KeyCode::Esc => {
+                let mut buffer = self.buffer.borrow_mut();
+                let col = if unused_indent {
+                    buffer.set_indent(line, 0, (line, col))?;
+                    0
+                } else {
+                    col
+                };
+                buffer.end_change();
+                drop(buffer);
+                self.mode.set(Mode::Normal);
+                self.clamp(line, col.saturating_sub(1))
+            }
+            KeyCode::Char(character) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.buffer.borrow_mut().insert_char(line, col, character)?;
+                (line, col + 1)
+            }
+            KeyCode::Tab => {
+                let options = self.buffer.borrow().options;
+                if options.softtabstop == 0 && !options.expandtab {
+                    self.buffer.borrow_mut().insert_char(line, col, '\t')?;
+                    (line, col + 1)
+                } else {
+                    let stop = match options.softtabstop {
+                        0 => options.tabstop.max(1),
+                        stop => stop,
+                    };
+                    let col =
+                        self.fill_blanks((line, col), |display| display + stop - display % stop)?;
+                    (line, col)
+                }
+            }
+            KeyCode::Enter => {
+                let mut buffer = self.buffer.borrow_mut();
+                buffer.split_line(line, col)?;
+                if buffer.options.autoindent {
+                    let indent = buffer
+                        .options
+                        .indent_width(buffer.line(line).unwrap_or_default());
+                    buffer.set_indent(line + 1, indent, (line, col))?;
+                    if unused_indent {
+                        buffer.set_indent(line, 0, (line, col))?;
+                    }
+                    drop(buffer);
+                    self.autoindent(line + 1)
+                } else {
+                    (line + 1, 0)
+                }
+            }
+            // With `softtabstop` blanks go back to the previous stop
+            KeyCode::Backspace
+                if col > 0
+                    && self.buffer.borrow().options.softtabstop > 0
+                    && self
+                        .line_text(line)
+                        .chars()
+                        .nth(col - 1)
+                        .is_some_and(|c| c == ' ' || c == '\t') =>
+            {
+                let stop = self.buffer.borrow().options.softtabstop;
+                let col = self.fill_blanks((line, col), |display| (display - 1) / stop * stop)?;
+                (line, col)
+            }
+            KeyCode::Backspace if col > 0 => {
+                let start = grapheme::cluster_start(&self.line_text(line), col - 1);
+                self.buffer.borrow_mut().remove_cluster(line, start)?;
+                (line, start)
+            }
+            KeyCode::Backspace if line > 0 => {
+                let join_col = self.buffer.borrow().line_len(line - 1);
+                self.buffer.borrow_mut().join_line(line - 1)?;
+                (line - 1, join_col)
+            }
+            KeyCode::Delete if col < self.buffer.borrow().line_len(line) => {
+                self.buffer.borrow_mut().remove_cluster(line, col)?;
+                (line, col)
+            }
+            KeyCode::Delete => {
+                self.buffer.borrow_mut().join_line(line)?;
+                (line, col)
+            }
+            KeyCode::Left => (line, grapheme::previous_cluster(&self.line_text(line), col)),
+            KeyCode::Right => (line, grapheme::next_cluster(&self.line_text(line), col)),
+            KeyCode::Up => return Ok(Some(self.vertical(line, -1))),
+            KeyCode::Down => return Ok(Some(self.vertical(line, 1))),
+            KeyCode::Home => (line, 0),
+            KeyCode::End => (line, usize::MAX),
+            _ => return Ok(None),
+        };
+        let target = self.clamp(target.0, target.1);
+        self.remember_col(target);
+        Ok(Some(target))
+    }
+
+    /// Replace the text from `start` to the cursor with an accepted completion
+    fn accept(
+        &mut self,
+        (line, col): (usize, usize),
+        start: usize,
+        text: &str,
+    ) -> Result<Option<(usize, usize)>> {
+        let mut buffer = self.buffer.borrow_mut();
+        let Some(current) = buffer.line(line) else {
+            return Ok(None);
+        };
+        let mut replaced = current.to_string();
+        replaced.replace_range(byte_index(current, start)..byte_index(current, col), text);
+        buffer.set_line(line, replaced, (line, col))?;
+        drop(buffer);
+        let target = (line, start + text.chars().count());
+        self.remember_col(target);
+        Ok(Some(target))
+    }
+
+    /// What each row shows, one per buffer line unless the window is in diff mode
+    fn layout_rows(&mut self) -> Rows {
+        let partner = self.window.borrow().diff_with.clone();
+        let Some(partner) = partner else {
+            self.marks.diff.borrow_mut().clear();
+            self.diff_hunks.clear();
+            // Closed folds take one row each, the rows between them are counted rather than listed
+            let mut buffer = self.buffer.borrow_mut();
+            let closed = buffer.folds().closed();
+            return Rows::lines(buffer.line_count(), &closed);
+        };
+        let layout = diff::layout(
+            self.buffer.borrow().lines().as_slice(),
+            partner.buffer.borrow().lines().as_slice(),
+            partner.first,
+            self.diff_algorithm.get(),
+        );
+        *self.marks.diff.borrow_mut() = layout.lines;
+        self.diff_hunks = layout.hunks;
+        Rows::Listed(layout.rows)
+    }
+
+    /// Add a row component showing `row`, the cursor goes on it once it exists when `cursor` is set
+    fn add_row(
+        &mut self,
+        commands: &mut ComponentCommands,
+        row: Row,
+        cursor: Option<usize>,
+    ) -> Result<ComponentId> {
+        let slot = Rc::new(Cell::new(row));
+        self.slots.push(slot.clone());
+        let mut text_row = TextRow::new(
+            self.buffer.clone(),
+            slot,
+            self.marks.clone(),
+            self.theme.clone(),
+        );
+        text_row.start_cursor = cursor.map(|col| (col as u16, self.mode.get().cursor_style()));
+        commands.add_component(text_row)
+    }
+
+    /// Lay the rows out again, returning whether any row changed
+    fn sync_rows(&mut self) -> bool {
+        let rows = self.layout_rows();
+        let old = std::mem::replace(&mut self.window.borrow_mut().rows, rows.clone());
+        old != rows
+    }
+
+    /// Bind the row components to the rows from the window's scroll position on
+    /// Only as many rows as fit on the terminal have a component, more are added when it grows
+    fn bind_rows(
+        &mut self,
+        commands: &mut ComponentCommands,
+        target: (usize, usize),
+    ) -> Result<()> {
+        let (top, shown, target_row) = {
+            let mut window = self.window.borrow_mut();
+            let top = window.scroll.min(window.rows.len().saturating_sub(1));
+            window.scroll = top;
+            let count = (window.rows.len() - top).min(self.capacity);
+            let shown: Vec<Row> = (top..top + count)
+                .filter_map(|row| window.rows.get(row))
+                .collect();
+            (top, shown, window.row_of(target.0))
+        };
+        // Only the line at the top can be scrolled partly out of view
+        if let Some((line, _)) = self.marks.skipped.get()
+            && shown.first() != Some(&Row::Line(line))
+        {
+            self.marks.skipped.set(None);
+        }
+        let children = commands.children().unwrap_or_default();
+        for &row_id in children.iter().skip(shown.len()) {
+            commands.remove_child(row_id);
+        }
+        self.slots.truncate(shown.len());
+        for (slot, &row) in self.slots.iter().zip(&shown) {
+            slot.set(row);
+        }
+        for (index, &row) in shown.iter().enumerate().skip(self.slots.len()) {
+            let cursor = (top + index == target_row).then_some(target.1);
+            self.add_row(commands, row, cursor)?;
+        }
+        // The window scrolls by binding other rows, the components themselves stay put
+        commands.set_scroll_y(0);
+        Ok(())
+    }
+
+    /// Screen rows `row` takes, less those scrolled above the window
+    fn row_height(&self, row: usize) -> usize {
+        match self.window.borrow().rows.get(row) {
+            Some(Row::Line(line)) => self
+                .screen_lines(&self.line_text(line))
+                .len()
+                .saturating_sub(self.marks.skipped(line))
+                .max(1),
+            _ => 1,
+        }
+    }
+
+    /// The first row to show so `row` is in view, scrolling as little as possible
+    fn scroll_to(&self, row: usize) -> usize {
+        let top = self.window.borrow().scroll;
+        if row < top {
+            return row;
+        }
+        // Walk back from the row while the rows above it still fit, at most back to the rows in view
+        let height = self.height.max(1);
+        let mut first = row;
+        let mut used = self.row_height(row);
+        while first > top {
+            let above = self.row_height(first - 1);
+            if used + above > height {
+                break;
+            }
+            used += above;
+            first -= 1;
+        }
+        first
+    }
+
+    /// The last row wholly in view when the window shows the rows from `top`
+    fn bottom_row(&self, top: usize) -> usize {
+        let len = self.window.borrow().rows.len();
+        let mut used = 0;
+        let mut row = top;
+        while row < len {
+            used += self.row_height(row);
+            if used > self.height && row > top {
+                break;
+            }
+            row += 1;
+        }
+        row.saturating_sub(1).max(top)
+    }
+
+    /// Focus the row for `line`, scrolling it into view, and put the cursor on `col`
+    fn place_cursor(
+        &mut self,
+        commands: &mut ComponentCommands,
+        (line, col): (usize, usize),
+    ) -> Result<()> {
+        let row = self.window.borrow().row_of(line);
+        let scroll = self.scroll_to(row);
+        self.window.borrow_mut().scroll = scroll;
+        // A cursor on the top line brings back its rows scrolled out of view up to its own
+        let skipped = self.marks.skipped(line);
+        if skipped > 0 && row == scroll {
+            let cursor_row = wrap::row_of(&self.screen_lines(&self.line_text(line)), col);
+            if cursor_row < skipped {
+                self.marks
+                    .skipped
+                    .set((cursor_row > 0).then_some((line, cursor_row)));
+            }
+        }
+        self.bind_rows(commands, (line, col))?;
+        self.follow_scroll(commands, row, scroll);
+        let rows = commands.children().unwrap_or_default();
+        let Some(&row_id) = rows.get(row - scroll) else {
+            return Ok(());
+        };
+        let style = self.mode.get().cursor_style();
+        match commands
+            .children_of(row_id)
+            .and_then(|cells| cells.get(1).copied())
+        {
+            Some(content_id) => {
+                commands.set_focus(content_id);
+                commands.set_cursor_for(content_id, col as u16, 0);
+                commands.set_cursor_style_for(content_id, style);
+            }
+            // A row added this update, it places its own cursor once its children exist
+            None => commands.set_focus(row_id),
+        }
+        let options = self.marks.wrap.borrow().clone();
+        if !options.wrap {
+            let text = self.line_text(line);
+            let left_col = wrap::left_col(
+                &text,
+                col,
+                self.marks.left_col.get(),
+                self.text_width(),
+                &options,
+            );
+            self.marks.left_col.set(left_col);
+        }
+        self.window.borrow_mut().cursor = (line, col);
+        let mut buffer = self.buffer.borrow_mut();
+        buffer.cursor = (line, col);
+        buffer.scroll = scroll;
+        Ok(())
+    }
+
+    /// In diff mode have the other window scroll along
+    fn follow_scroll(&self, commands: &mut ComponentCommands, row: usize, scroll: usize) {
+        let partner = self.window.borrow().diff_with.clone();
+        if let Some(partner) = partner {
+            commands.emit(ReovimEvent::DiffScroll(partner.window, row, scroll));
+        }
+    }
+
+    /// Scroll the window by `delta` screen rows for the mouse wheel, taking the cursor along when it would leave them
+    /// A long line at the top may be shown from one of the rows it wraps onto
+    fn scroll_view(&mut self, commands: &mut ComponentCommands, delta: isize) -> Result<()> {
+        let ((line, col), len, mut top) = {
+            let window = self.window.borrow();
+            (window.cursor, window.rows.len(), window.scroll)
+        };
+        let mut skipped = self.marks.skipped(self.window.borrow().line_at(top));
+        self.marks.skipped.set(None);
+        for _ in 0..delta.unsigned_abs() {
+            if delta > 0 && skipped + 1 < self.row_height(top) {
+                skipped += 1;
+            } else if delta > 0 && top + 1 < len {
+                top += 1;
+                skipped = 0;
+            } else if delta < 0 && skipped > 0 {
+                skipped -= 1;
+            } else if delta < 0 && top > 0 {
+                top -= 1;
+                skipped = self.row_height(top) - 1;
+            } else {
+                break;
+            }
+        }
+        self.window.borrow_mut().scroll = top;
+        if skipped > 0 {
+            let top_line = self.window.borrow().line_at(top);
+            self.marks.skipped.set(Some((top_line, skipped)));
+        }
+        let row = self.window.borrow().row_of(line);
+        let mut shown = row.clamp(top, self.bottom_row(top));
+        // Fillers have no line, the cursor goes on the nearest row that does
+        while shown < len - 1 && self.window.borrow().rows.get(shown) == Some(Row::Filler) {
+            shown += 1;
+        }
+        let mut target = if shown == row {
+            (line, col)
+        } else {
+            let line = self.window.borrow().line_at(shown);
+            let options = self.marks.wrap.borrow().clone();
+            let col = wrap::col_at_display(&self.line_text(line), self.desired_col, &options);
+            self.clamp(line, col)
+        };
+        // Nor can it stay on the rows of the top line scrolled out of view
+        if shown == top && skipped > 0 {
+            let options = self.marks.wrap.borrow().clone();
+            let text = self.line_text(target.0);
+            let screen_lines = self.screen_lines(&text);
+            let (cursor_row, screen_col) =
+                wrap::screen_position(&text, &screen_lines, target.1, &options);
+            if cursor_row < skipped {
+                let col = wrap::col_at(&text, &screen_lines, skipped, screen_col, &options);
+                target = self.clamp(target.0, col);
+            }
+        }
+        if commands.has_focus() {
+            return self.place_cursor(commands, target);
+        }
+        // A window without focus only keeps where its cursor is for when it gets it
+        self.window.borrow_mut().cursor = target;
+        self.bind_rows(commands, target)?;
+        self.follow_scroll(commands, self.window.borrow().row_of(target.0), top);
+        Ok(())
+    }
+
+    /// Put the cursor on `target` after a key or a paste, catching the language server, the rows and the
+    /// other windows up with any edit to the buffer, which had `line_count` lines before it
+    fn settle(
+        &mut self,
+        commands: &mut ComponentCommands,
+        target: (usize, usize),
+        line_count: usize,
+    ) -> Result<()> {
+        self.lsp.borrow_mut().sync(&mut self.buffer.borrow_mut());
+        self.buffer.borrow_mut().forget_unused_lines();
+        let rows_changed = self.sync_rows();
+        self.place_cursor(commands, target)?;
+        self.completion.refresh(&self.buffer, target, commands);
+        // Other windows on the buffer catch up with its lines and folds
+        if rows_changed || self.buffer.borrow().line_count() != line_count {
+            commands.emit(ReovimEvent::BufferChanged(self.buffer.borrow().id()));
+        }
+        // The other window of a diff lays out its side again, `dp` may have edited it
+        let partner = self.window.borrow().diff_with.clone();
+        if let Some(partner) = partner {
+            commands.emit(ReovimEvent::BufferChanged(partner.buffer.borrow().id()));
+        }
+        Ok(())
+    }
+}
+
+impl Component for EditableText {
+    fn children(&mut self, commands: &mut super::tree::ComponentCommands) -> anyhow::Result<()> {
+        let (start_line, start_col, scroll) = {
+            let window = self.window.borrow();
+            let start_line = window.cursor.0.min(self.buffer.borrow().line_count() - 1);
+            (start_line, window.cursor.1, window.scroll)
+        };
+        // Shown buffers are opened with their language server and compared with git's index
+        self.lsp.borrow_mut().sync(&mut self.buffer.borrow_mut());
+        let wants_vcs = self.buffer.borrow_mut().wants_vcs();
+        if let Some(path) = wants_vcs {
+            vcs::load_in_background(&self.tasks, self.buffer.borrow().id(), path);
+        }
+        // Only the current window takes focus, the others keep their cursor for when they get it
+        let is_current = self.window.borrow().id() == self.current.get();
+        self.sync_rows();
+        let start_row = self.window.borrow().row_of(start_line);
+        // Scroll no further than keeps the cursor's row among the row components
+        self.window.borrow_mut().scroll = scroll
+            .min(start_row)
+            .max((start_row + 1).saturating_sub(self.capacity));
+        self.bind_rows(commands, (start_line, start_col))?;
+        let top = self.window.borrow().scroll;
+        let rows = commands.children().unwrap_or_default();
+        if is_current && let Some(&row_id) = rows.get(start_row - top) {
+            commands.focus_child(row_id);
+        }
+        Ok(())
+    }
+    fn update(
+        &mut self,
+        event: crate::event::ReovimEvent,
+        commands: &mut super::tree::ComponentCommands,
+    ) -> Result<bool> {
+        if let Some(text_id) = self.window.borrow().text_id {
+            let rect = commands.rect_of(text_id);
+            self.width = rect.width;
+            self.height = rect.height as usize;
+        }
+        if let ReovimEvent::Resize(_, height) = event {
+            // A taller terminal shows more rows, each needs a component
+            self.capacity = self.capacity.max(height as usize);
+            let cursor = self.window.borrow().cursor;
+            self.bind_rows(commands, cursor)?;
+            return Ok(true);
+        }
+        if let ReovimEvent::Mouse(MouseEvent {
+            kind: kind @ (MouseEventKind::ScrollUp | MouseEventKind::ScrollDown),
+            column,
+            row,
+            ..
+        }) = event
+        {
+            // The wheel over the window scrolls its rows, the tree's scrolling would only move the components in view
+            let text_id = self.window.borrow().text_id;
+            let Some(text_id) = text_id else {
+                return Ok(false);
+            };
+            let rect = commands.screen_rect_of(text_id);
+            if !(rect.x..rect.x + rect.width).contains(&column)
+                || !(rect.y..rect.y + rect.height).contains(&row)
+            {
+                return Ok(false);
+            }
+            commands.consume_event();
+            let rows = self.mouse_scroll.get() as isize;
+            let delta = if kind == MouseEventKind::ScrollUp {
+                -rows
+            } else {
+                rows
+            };
+            self.scroll_view(commands, delta)?;
+            return Ok(true);
+        }
+        if let ReovimEvent::Mouse(mouse) = event {
+            return match self.mouse(mouse, commands) {
+                Ok(changed) => Ok(changed),
+                Err(err) => {
+                    self.command_line.borrow_mut().set_error(err.to_string());
+                    Ok(true)
+                }
+            };
+        }
+        if let ReovimEvent::BufferChanged(id) = event {
+            // Another window edited the buffer, or the one this window is compared with, catch up with its lines
+            let compared = self
+                .window
+                .borrow()
+                .diff_with
+                .as_ref()
+                .is_some_and(|partner| partner.buffer.borrow().id() == id);
+            if (id != self.buffer.borrow().id() && !compared) || commands.has_focus() {
+                return Ok(false);
+            }
+            let last_line = self.buffer.borrow().line_count() - 1;
+            let cursor = {
+                let mut window = self.window.borrow_mut();
+                window.cursor.0 = window.cursor.0.min(last_line);
+                window.cursor
+            };
+            self.sync_rows();
+            self.bind_rows(commands, cursor)?;
+            return Ok(true);
+        }
+        if let ReovimEvent::DiffScroll(window_id, row, scroll) = event {
+            // The other window of the diff moved, keep the same rows in view
+            if window_id != self.window.borrow().id() {
+                return Ok(false);
+            }
+            let line = self.window.borrow().line_at(row);
+            let target = self.clamp(line, self.window.borrow().cursor.1);
+            {
+                let mut window = self.window.borrow_mut();
+                window.cursor = target;
+                window.scroll = scroll;
+            }
+            self.bind_rows(commands, target)?;
+            return Ok(true);
+        }
+        if let ReovimEvent::Completion(response) = event {
+            let cursor = self.window.borrow().cursor;
+            self.completion.receive(response, &self.buffer, cursor);
+            return Ok(self.completion.is_open());
+        }
+        if !commands.has_focus() {
+            return Ok(false);
+        }
+        let ReovimEvent::Key(key) = event else {
+            return Ok(false);
+        };
+        // `gt` and `gT` switch tabs, which the editor does
+        if self.mode.get() == Mode::Normal
+            && self.pending == "g"
+            && let KeyCode::Char(character @ ('t' | 'T')) = key.code
+        {
+            self.pending.clear();
+            let command = if character == 't' {
+                "tabnext"
+            } else {
+                "tabprevious"
+            };
+            commands.emit(ReovimEvent::Command(command.to_string()));
+            return Ok(true);
+        }
+        let line_count = self.buffer.borrow().line_count();
+        let line = {
+            let window = self.window.borrow();
+            commands
+                .focused_child_index()
+                .map_or(window.cursor.0, |index| {
+                    window.line_at(window.scroll + index)
+                })
+        };
+        let col = commands.focused_cursor().col as usize;
+        // Rendering scrolls the row components when the cursor's row doesn't fit below them, move the window instead
+        let offset = commands.get_scroll_y();
+        let rebound = offset > 0;
+        if rebound {
+            self.window.borrow_mut().scroll += offset;
+            self.place_cursor(commands, (line, col))?;
+        }
+        let result = match self.mode.get() {
+            Mode::Normal => self.normal_key(key, line, col),
+            Mode::Visual => self.visual_key(key, line, col),
+            Mode::Insert => match self
+                .completion
+                .key(key, &self.buffer, (line, col), commands)
+            {
+                Ok(CompletionKey::Handled) => return Ok(true),
+                Ok(CompletionKey::Accept { start, text }) => {
+                    self.snippet_edit(InsertEdit::Insert, (line, col), |editable, col| {
+                        editable.accept((line, col), start.min(col), &text)
+                    })
+                }
+                Ok(CompletionKey::Ignored) => self.snippet_key(key, (line, col), commands),
+                Err(err) => Err(err),
+            },
+        };
+        match result {
+            Ok(Some(target)) => {
+                self.settle(commands, target, line_count)?;
+                Ok(true)
+            }
+            Ok(None) => Ok(rebound),
+            Err(err) => {
+                self.command_line.borrow_mut().set_error(err.to_string());
+                Ok(true)
+            }
+        }
+    }
+
+    fn default_formatting(&self) -> Formatting {
+        Formatting {
+            preferred_width: Measurement::Fill(1),
+            preferred_height: Measurement::Fill(1),
+            overflow_x: Overflow::Wrap,
+            overflow_y: Overflow::Scroll,
+            request_focus: false,
+            layout_mode: LayoutMode::VerticalSplit,
+            ..Default::default()
+        }
+    }
+}
+
+pub struct Editor {
+    buffers: Rc<RefCell<BufferList>>,
+    /// Every tab page with its windows, shared with the tab line
+    tabs: Rc<RefCell<TabList>>,
+    next_window_id: WindowId,
+    mode: Rc<Cell<Mode>>,
+    command_line: Rc<RefCell<CommandLine>>,
+    /// The window with focus, window ids are unique across tabs
+    current: Rc<Cell<WindowId>>,
+    tab_line_id: Option<ComponentId>,
+    /// The component holding one child per tab page, only the current one gets any room
+    tab_pages_id: Option<ComponentId>,
+    command_id: Option<ComponentId>,
+    /// Component to give focus back to when the command line closes
+    return_focus: Option<ComponentId>,
+    /// `Ctrl-W` was pressed, the next key is a window command
+    window_pending: bool,
+    tasks: Tasks,
+    completion: CompletionSources,
+    snippets: Rc<SnippetLibrary>,
+    theme: Rc<Theme>,
+    lsp: Rc<RefCell<Lsp>>,
+    /// The hover popup `K` opened, closed by the next key
+    hover_id: Option<ComponentId>,
+    diff_algorithm: Rc<Cell<Algorithm>>,
+    /// Screen rows the mouse wheel scrolls by
+    mouse_scroll: Rc<Cell<usize>>,
+    /// The border floating windows are drawn with, set by `:set winborder`
+    win_border: Rc<Cell<Option<BorderStyle>>>,
+    /// The text last selected in any window, which a middle click pastes
+    primary_selection: Rc<RefCell<String>>,
+}
+
+impl Editor {
+    pub fn new(
+        buffers: Rc<RefCell<BufferList>>,
+        buffer: Rc<RefCell<Buffer>>,
+        tasks: Tasks,
+        completion: CompletionSources,
+        snippets: SnippetLibrary,
+        lsp: Lsp,
+    ) -> Self {
+        let window = Window::new(1, buffer);
+        Self {
+            buffers,
+            tabs: Rc::new(RefCell::new(TabList::new(TabPage::new(window)))),
+            next_window_id: 2,
+            mode: Rc::new(Cell::new(Mode::Normal)),
+            command_line: Rc::new(RefCell::new(CommandLine::default())),
+            current: Rc::new(Cell::new(1)),
+            tab_line_id: None,
+            tab_pages_id: None,
+            command_id: None,
+            return_focus: None,
+            window_pending: false,
+            tasks,
+            completion,
+            snippets: Rc::new(snippets),
+            theme: Rc::new(Theme::default()),
+            lsp: Rc::new(RefCell::new(lsp)),
+            hover_id: None,
+            diff_algorithm: Rc::default(),
+            mouse_scroll: Rc::new(Cell::new(DEFAULT_MOUSE_SCROLL)),
+            win_border: Rc::new(Cell::new(Some(BorderStyle::Single))),
+            primary_selection: Rc::default(),
+        }
+    }
+
+    /// Compare `buffer` to the left with the buffer shown, for `reovim -d`
+    pub fn diff_with(&mut self, buffer: Rc<RefCell<Buffer>>) {
+        self.add_window(LayoutMode::HorizontalSplit, Some(buffer));
+        for window in self.windows() {
+            window.borrow_mut().diff = true;
+        }
+    }
+}
+
+impl Editor {
+    /// Close the command line and give focus back to the text, returning what was typed
+    fn close_command_line(&mut self, commands: &mut ComponentCommands) -> Option<String> {
+        if let Some(return_focus) = self.return_focus.take() {
+            commands.set_focus(return_focus);
+        }
+        self.command_line.borrow_mut().close()
+    }
+
+    fn context(&self) -> WindowContext {
+        WindowContext {
+            buffers: self.buffers.clone(),
+            mode: self.mode.clone(),
+            command_line: self.command_line.clone(),
+            current: self.current.clone(),
+            tasks: self.tasks.clone(),
+            completion: self.completion.clone(),
+            snippets: self.snippets.clone(),
+            theme: self.theme.clone(),
+            lsp: self.lsp.clone(),
+            diff_algorithm: self.diff_algorithm.clone(),
+            mouse_scroll: self.mouse_scroll.clone(),
+            win_border: self.win_border.clone(),
+            primary_selection: self.primary_selection.clone(),
+        }
+    }
+
+    fn get_window(&self, id: WindowId) -> Option<Rc<RefCell<Window>>> {
+        self.tabs.borrow().current().get_window(id)
+    }
+
+    /// The windows in the current tab
+    fn windows(&self) -> Vec<Rc<RefCell<Window>>> {
+        self.tabs.borrow().current().windows.clone()
+    }
+
+    /// The windows in every tab
+    fn all_windows(&self) -> Vec<Rc<RefCell<Window>>> {
+        self.tabs
+            .borrow()
+            .iter()
+            .flat_map(|page| page.windows.iter().cloned())
+            .collect()
+    }
+
+    /// The window layout of the current tab
+    fn layout(&self) -> RefMut<'_, Layout> {
+        RefMut::map(self.tabs.borrow_mut(), |tabs| {
+            &mut tabs.current_mut().layout
+        })
+    }
+
+    /// The window with focus
+    fn window(&self) -> Rc<RefCell<Window>> {
+        self.get_window(self.current.get())
+            .expect("the current window is always open")
+    }
+
+    /// The buffer shown in the window with focus
+    fn buffer(&self) -> Rc<RefCell<Buffer>> {
+        self.window().borrow().buffer.clone()
+    }
+
+    /// Build the window components of the current tab again from its layout
+    fn rebuild_windows(&mut self, commands: &mut ComponentCommands) -> Result<()> {
+        let index = self.tabs.borrow().current_index();
+        self.rebuild_tab(index, commands)
+    }
+
+    /// Build the window components of a tab again from its layout, windows keep their cursor and scroll
+    fn rebuild_tab(&mut self, index: usize, commands: &mut ComponentCommands) -> Result<()> {
+        let (layout, windows, split_id) = match self.tabs.borrow().get(index) {
+            Some(page) => (page.layout.clone(), page.windows.clone(), page.split_id),
+            None => return Ok(()),
+        };
+        pair_diff_windows(&layout, &windows);
+        // Rendering may have scrolled the row components past the window's first row, take that along
+        for window in &windows {
+            let mut window = window.borrow_mut();
+            if let Some(text_id) = window.text_id {
+                window.scroll += commands.scroll_y_of(text_id);
+            }
+        }
+        if let Some(split_id) = split_id {
+            let component = SplitComponent::new(layout, windows, self.context());
+            let split_id = commands.replace_component(split_id, component)?;
+            let shown = index == self.tabs.borrow().current_index();
+            show_tab_page(commands, split_id, shown);
+            if let Some(page) = self.tabs.borrow_mut().get_mut(index) {
+                page.split_id = Some(split_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the window components of every tab again, after a buffer shown in any of them changed
+    fn rebuild_tabs(&mut self, commands: &mut ComponentCommands) -> Result<()> {
+        let len = self.tabs.borrow().len();
+        for index in 0..len {
+            self.rebuild_tab(index, commands)?;
+        }
+        Ok(())
+    }
+
+    /// Refuse to quit while a buffer has unsaved changes, checking the current buffer first
+    fn check_quit(&self) -> Result<()> {
+        if self.buffer().borrow().is_modified() {
+            anyhow::bail!("E37: No write since last change (add ! to override)");
+        }
+        for buffer in self.buffers.borrow().iter() {
+            let buffer = buffer.borrow();
+            if buffer.is_modified() {
+                anyhow::bail!(
+                    "E162: No write since last change for buffer \"{}\"",
+                    buffer.display_name()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// `:q` closes the window with focus, and quits once it is the last one in the last tab
+    fn quit(&mut self, force: bool, commands: &mut ComponentCommands) -> Result<()> {
+        if self.windows().len() > 1 || self.tabs.borrow().len() > 1 {
+            return self.close_window(self.current.get(), commands);
+        }
+        if !force {
+            self.check_quit()?;
+        }
+        commands.request(HostRequest::Quit(0));
+        Ok(())
+    }
+
+    /// The syntax nodes under the cursor and how many folds the tree offers, for `:syntax`
+    fn describe_syntax(&self) -> String {
+        let window = self.window();
+        let window = window.borrow();
+        let buffer = window.buffer.borrow();
+        let Some((language, nodes)) = buffer.syntax_nodes(window.cursor) else {
+            return "No syntax tree for this buffer".to_string();
+        };
+        let folds = buffer.syntax_folds().len();
+        format!(
+            "{language}: {}\n{folds} foldable regions",
+            nodes.join(" < ")
+        )
+    }
+
+    fn execute(&mut self, input: &str, commands: &mut ComponentCommands) {
+        let result = ExCommand::parse(input).and_then(|command| match command {
+            ExCommand::Write(path) => self.write(path, commands),
+            ExCommand::Quit { force } => self.quit(force, commands),
+            ExCommand::WriteQuit(path) => {
+                self.write(path, commands)?;
+                self.quit(false, commands)
+            }
+            ExCommand::QuitWithError => {
+                commands.request(HostRequest::Quit(1));
+                Ok(())
+            }
+            ExCommand::Suspend => {
+                commands.request(HostRequest::Suspend);
+                Ok(())
+            }
+            ExCommand::Edit { path, force } => self.edit(path, force, commands),
+            ExCommand::BufferNext(count) => {
+                let next = self
+                    .buffers
+                    .borrow()
+                    .next(self.buffer().borrow().id(), count);
+                match next {
+                    Some(next) => self.show_buffer(next, commands),
+                    None => Ok(()),
+                }
+            }
+            ExCommand::BufferPrev(count) => {
+                let prev = self
+                    .buffers
+                    .borrow()
+                    .prev(self.buffer().borrow().id(), count);
+                match prev {
+                    Some(prev) => self.show_buffer(prev, commands),
+                    None => Ok(()),
+                }
+            }
+            ExCommand::Buffer(id) => self.show_buffer_id(id, commands),
+            ExCommand::BufferDelete { buffer, force } => {
+                self.delete_buffer(buffer, force, commands)
+            }
+            ExCommand::ListBuffers => {
+                let listing = self.list_buffers();
+                self.command_line.borrow_mut().set_message(listing);
+                Ok(())
+            }
+            ExCommand::ListSnippets => {
+                let listing = self.list_snippets();
+                self.command_line.borrow_mut().set_message(listing);
+                Ok(())
+            }
+            ExCommand::Syntax => {
+                let description = self.describe_syntax();
+                self.command_line.borrow_mut().set_message(description);
+                Ok(())
+            }
+            ExCommand::Rename(name) => {
+                self.lsp_request(|lsp, buffer, cursor| lsp.rename(buffer, cursor, &name))
+            }
+            ExCommand::CodeAction(None) => self.lsp_request(|lsp, buffer, (line, _)| {
+                let range = TextRange {
+                    start: (line, 0),
+                    end: (line, buffer.line_len(line)),
+                };
+                lsp.code_actions(buffer, range)
+            }),
+            ExCommand::CodeAction(Some(number)) => {
+                let edit = self.lsp.borrow_mut().code_action(number)?;
+                match edit {
+                    Some(edit) => self.apply_workspace_edit(edit, commands),
+                    None => Ok(()),
+                }
+            }
+            ExCommand::Format => self.lsp_request(|lsp, buffer, _| lsp.format(buffer)),
+            ExCommand::Diagnostics => {
+                let listing = self.list_diagnostics();
+                self.command_line.borrow_mut().set_message(listing);
+                Ok(())
+            }
+            ExCommand::LspInfo => {
+                let description = self.lsp.borrow().describe();
+                self.command_line.borrow_mut().set_message(description);
+                Ok(())
+            }
+            ExCommand::PreviewHunk => self.preview_hunk(commands),
+            ExCommand::StageHunk => self.stage_hunk(),
+            ExCommand::ResetHunk => self.reset_hunk(commands),
+            ExCommand::Set { option, value } => self.set_option(&option, value, commands),
+            ExCommand::DiffThis => self.set_diff(&[self.window()], true, commands),
+            ExCommand::DiffOff { all } => {
+                let windows = if all {
+                    self.windows()
+                } else {
+                    vec![self.window()]
+                };
+                self.set_diff(&windows, false, commands)
+            }
+            ExCommand::DiffSplit(path) => self.diff_split(path, commands),
+            ExCommand::DiffAlgorithm(None) => {
+                let name = self.diff_algorithm.get().name();
+                self.command_line
+                    .borrow_mut()
+                    .set_message(format!("  diffalgorithm={name}"));
+                Ok(())
+            }
+            ExCommand::DiffAlgorithm(Some(algorithm)) => {
+                self.diff_algorithm.set(algorithm);
+                self.rebuild_tabs(commands)
+            }
+            ExCommand::Split { path, mode } => {
+                let buffer = self.open_path(path)?;
+                self.split(mode, buffer, commands)
+            }
+            ExCommand::New { mode } => {
+                let buffer = self.buffers.borrow_mut().add(Buffer::default());
+                self.split(mode, Some(buffer), commands)
+            }
+            ExCommand::Close => self.close_window(self.current.get(), commands),
+            ExCommand::Only => self.only_window(commands),
+            ExCommand::TabNew(path) => self.new_tab(path, commands),
+            ExCommand::TabNext(number) => {
+                self.next_tab(number, commands);
+                Ok(())
+            }
+            ExCommand::TabPrev(count) => {
+                self.prev_tab(count, commands);
+                Ok(())
+            }
+            ExCommand::TabClose => self.close_tab(commands),
+            ExCommand::TabMove(position) => {
+                self.move_tab(position);
+                Ok(())
+            }
+        });
+        if let Err(err) = result {
+            self.command_line.borrow_mut().set_error(err.to_string());
+        }
+    }
+}
+
+impl Component for Editor {
+    fn children(&mut self, commands: &mut super::tree::ComponentCommands) -> anyhow::Result<()> {
+        self.tab_line_id = Some(commands.add_component(TabLineComponent::new(
+            self.tabs.clone(),
+            self.current.clone(),
+        ))?);
+        let tab_pages_id = commands.add_child_with_formatting(
+            ComponentNode::Frame(Frame::new(LayoutMode::VerticalSplit)),
+            Formatting {
+                preferred_width: Measurement::Fill(1),
+                preferred_height: Measurement::Fill(1),
+                overflow_x: Overflow::Hide,
+                overflow_y: Overflow::Hide,
+                request_focus: false,
+                layout_mode: LayoutMode::VerticalSplit,
+                ..Default::default()
+            },
+        )?;
+        self.tab_pages_id = Some(tab_pages_id);
+        let split_id = self.add_tab_page(tab_pages_id, commands)?;
+        self.tabs.borrow_mut().current_mut().split_id = Some(split_id);
+        self.command_id = Some(commands.add_component(CommandComponent::new(
+            self.command_line.clone(),
+            self.mode.clone(),
+        ))?);
+        Ok(())
+    }
+
+    fn default_formatting(&self) -> Formatting {
+        Formatting {
+            preferred_width: Measurement::Fill(1),
+            preferred_height: Measurement::Fill(1),
+            overflow_x: Overflow::Wrap,
+            overflow_y: Overflow::Scroll,
+            request_focus: true,
+            layout_mode: LayoutMode::VerticalSplit,
+            ..Default::default()
+        }
+    }
+
+    fn update(
+        &mut self,
+        event: crate::event::ReovimEvent,
+        commands: &mut super::tree::ComponentCommands,
+    ) -> Result<bool> {
+        let key = match event {
+            ReovimEvent::Key(key) => {
+                self.close_hover(commands);
+                key
+            }
+            ReovimEvent::Lsp(event) => {
+                let reply = self.lsp.borrow_mut().handle(event);
+                let Some(reply) = reply else {
+                    return Ok(false);
+                };
+                if let Err(err) = self.lsp_reply(reply, commands) {
+                    self.command_line.borrow_mut().set_error(err.to_string());
+                }
+                return Ok(true);
+            }
+            ReovimEvent::Command(input) => {
+                self.execute(&input, commands);
+                return Ok(true);
+            }
+            ReovimEvent::Vcs(id, info) => {
+                let buffer = self.buffers.borrow().get(id);
+                let Some(buffer) = buffer else {
+                    return Ok(false);
+                };
+                buffer.borrow_mut().set_vcs(info);
+                return Ok(true);
+            }
+            ReovimEvent::Staged(id, staged) => {
+                let buffer = self.buffers.borrow().get(id);
+                let Some(buffer) = buffer else {
+                    return Ok(false);
+                };
+                match staged {
+                    Ok(info) => {
+                        buffer.borrow_mut().set_vcs(info);
+                        self.command_line.borrow_mut().set_message("Hunk staged");
+                    }
+                    Err(err) => self.command_line.borrow_mut().set_error(err),
+                }
+                return Ok(true);
+            }
+            ReovimEvent::Idle => {
+                self.sync_swap_files();
+                return Ok(false);
+            }
+            ReovimEvent::Swap(id, Err(err)) => {
+                let buffer = self.buffers.borrow().get(id);
+                let Some(buffer) = buffer else {
+                    return Ok(false);
+                };
+                self.command_line.borrow_mut().set_error(format!(
+                    "E303: Unable to write swap file for \"{}\", recovery impossible: {err}",
+                    buffer.borrow().display_name()
+                ));
+                return Ok(true);
+            }
+            ReovimEvent::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                ..
+            }) => {
+                let Some(tab_line_id) = self.tab_line_id else {
+                    return Ok(false);
+                };
+                if !commands.rect_of(tab_line_id).contains(column, row) {
+                    return Ok(false);
+                }
+                let tab = self.tabs.borrow().tab_at(column, self.current.get());
+                if let Some(tab) = tab {
+                    self.select_tab(tab, commands);
+                }
+                commands.consume_event();
+                return Ok(true);
+            }
+            _ => return Ok(false),
+        };
+
+        if self.command_line.borrow().is_open() {
+            match key.code {
+                KeyCode::Esc => {
+                    self.close_command_line(commands);
+                }
+                KeyCode::Enter => {
+                    if let Some(input) = self.close_command_line(commands) {
+                        self.execute(&input, commands);
+                    }
+                }
+                KeyCode::Backspace => {
+                    let empty = self
+                        .command_line
+                        .borrow_mut()
+                        .input_mut()
+                        .map(|input| input.pop().is_none())
+                        .unwrap_or(true);
+                    if empty {
+                        self.close_command_line(commands);
+                    }
+                }
+                KeyCode::Char(character) => {
+                    if let Some(input) = self.command_line.borrow_mut().input_mut() {
+                        input.push(character);
+                    }
+                }
+                _ => {}
+            }
+            // Keys that closed the command line must not reach the text
+            if !self.command_line.borrow().is_open() {
+                commands.consume_event();
+            }
+            return Ok(true);
+        }
+
+        self.command_line.borrow_mut().clear_multiline_message();
+        if self.mode.get() != Mode::Normal {
+            return Ok(false);
+        }
+
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        if self.window_pending {
+            self.window_pending = false;
+            commands.consume_event();
+            if let Err(err) = self.window_command(key, commands) {
+                self.command_line.borrow_mut().set_error(err.to_string());
+            }
+            return Ok(true);
+        }
+        match key.code {
+            KeyCode::Char('w') if control => {
+                self.window_pending = true;
+                commands.consume_event();
+                return Ok(true);
+            }
+            KeyCode::Char('z') if control => {
+                commands.consume_event();
+                commands.request(HostRequest::Suspend);
+                return Ok(true);
+            }
+            // Leaves every window at once, but like `:q` not while a buffer has unsaved changes
+            KeyCode::Char('u') if control => {
+                commands.consume_event();
+                match self.check_quit() {
+                    Ok(()) => commands.request(HostRequest::Quit(0)),
+                    Err(err) => self.command_line.borrow_mut().set_error(err.to_string()),
+                }
+                return Ok(true);
+            }
+            KeyCode::Char(':') => {
+                if let Some(command_id) = self.command_id {
+                    self.return_focus = Some(commands.focused());
+                    self.command_line.borrow_mut().open();
+                    commands.set_focus(command_id);
+                    return Ok(true);
+                }
+            }
+            // Terminals send Ctrl-^ as Ctrl-6
+            KeyCode::Char('^' | '6') if control => {
+                commands.consume_event();
+                let alternate = self.window().borrow().alternate;
+                match alternate {
+                    Some(alternate) => {
+                        if let Err(err) = self.show_buffer_id(alternate, commands) {
+                            self.command_line.borrow_mut().set_error(err.to_string());
+                        }
+                    }
+                    None => self
+                        .command_line
+                        .borrow_mut()
+                        .set_error("E23: No alternate file"),
+                }
+                return Ok(true);
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    fn render(
+        &self,
+        _buffer: &mut super::terminal_buffer::TerminalBuffer,
+        _query: crate::tui::ComponentQuery,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
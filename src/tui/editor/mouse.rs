@@ -0,0 +1,367 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+
+use super::{EditableText, Mode};
+use crate::{
+    buffer::TextRange,
+    completion::is_keyword,
+    tui::{Rect, tree::ComponentCommands, window::Row, wrap},
+};
+
+/// Clicks closer together than this in the same place count as a double or triple click
+const MULTI_CLICK: Duration = Duration::from_millis(500);
+
+/// What a click selects, and a drag after it selects by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SelectBy {
+    Char,
+    Word,
+    Line,
+}
+
+/// A left click on the text, counted with the ones just before it in the same place
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Click {
+    at: Instant,
+    column: u16,
+    row: u16,
+    count: usize,
+}
+
+impl Click {
+    /// The click at `column` and `row`, a double or triple click when it follows `last` closely enough
+    fn after(last: Option<Click>, column: u16, row: u16) -> Click {
+        let at = Instant::now();
+        let count = match last {
+            Some(last)
+                if (last.column, last.row) == (column, row)
+                    && at.duration_since(last.at) < MULTI_CLICK =>
+            {
+                // A fourth click starts counting again
+                last.count % 3 + 1
+            }
+            _ => 1,
+        };
+        Click {
+            at,
+            column,
+            row,
+            count,
+        }
+    }
+
+    fn selects_by(&self) -> SelectBy {
+        match self.count {
+            1 => SelectBy::Char,
+            2 => SelectBy::Word,
+            _ => SelectBy::Line,
+        }
+    }
+}
+
+impl EditableText {
+    /// The line and col drawn at screen `column` and `row`, or past the top or bottom of the window the
+    /// nearest one out of view, none before the rows have been drawn
+    fn position_at(
+        &self,
+        commands: &ComponentCommands,
+        column: u16,
+        row: u16,
+    ) -> Option<(usize, usize)> {
+        let (top, len) = {
+            let window = self.window.borrow();
+            (window.scroll, window.rows.len())
+        };
+        // Where the text of each row was drawn on the last frame
+        let drawn: Vec<(usize, Rect)> = commands
+            .children()
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, row_id)| {
+                let content_id = commands.children_of(row_id)?.get(1).copied()?;
+                let rect = commands.screen_rect_of(content_id);
+                (rect.height > 0).then_some((top + index, rect))
+            })
+            .collect();
+        let (&(_, first), &(last_row, _)) = (drawn.first()?, drawn.last()?);
+        let skipped = self.marks.skipped(self.window.borrow().line_at(top));
+        let (window_row, screen_row) = match drawn
+            .iter()
+            .find(|(_, rect)| (rect.y..rect.y + rect.height).contains(&row))
+        {
+            Some(&(window_row, rect)) if window_row == top => {
+                (top, (row - rect.y) as usize + skipped)
+            }
+            Some(&(window_row, rect)) => (window_row, (row - rect.y) as usize),
+            None if row < first.y && skipped > 0 => (top, skipped - 1),
+            None if row < first.y => (top.saturating_sub(1), usize::MAX),
+            None if last_row + 1 < len => (last_row + 1, 0),
+            None => (last_row, usize::MAX),
+        };
+        let shown = self.window.borrow().rows.get(window_row);
+        let Some(Row::Line(line)) = shown else {
+            return Some(self.clamp(self.window.borrow().line_at(window_row), 0));
+        };
+        let options = self.marks.wrap.borrow().clone();
+        let text = self.line_text(line);
+        let screen_lines = self.screen_lines(&text);
+        let screen_row = screen_row.min(screen_lines.len() - 1);
+        let screen_col = column.saturating_sub(first.x) as usize;
+        let len = text.chars().count();
+        // Past the end of the line is after its last char, where insert mode can put the cursor
+        let col = if (screen_row, screen_col)
+            >= wrap::screen_position(&text, &screen_lines, len, &options)
+        {
+            len
+        } else {
+            wrap::col_at(&text, &screen_lines, screen_row, screen_col, &options)
+        };
+        Some(self.clamp(line, col))
+    }
+
+    /// The word, run of blanks or run of other chars at `(line, col)`, what a double click selects
+    fn word_at(&self, (line, col): (usize, usize)) -> TextRange {
+        let chars: Vec<char> = self.line_text(line).chars().collect();
+        let class = |character: char| {
+            if character.is_whitespace() {
+                0
+            } else if is_keyword(character) {
+                1
+            } else {
+                2
+            }
+        };
+        let Some(&under) = chars.get(col) else {
+            return TextRange {
+                start: (line, col),
+                end: (line, col),
+            };
+        };
+        let start = chars[..col]
+            .iter()
+            .rposition(|&character| class(character) != class(under))
+            .map_or(0, |index| index + 1);
+        let end = chars[col..]
+            .iter()
+            .position(|&character| class(character) != class(under))
+            .map_or(chars.len(), |index| col + index);
+        TextRange {
+            start: (line, start),
+            end: (line, end),
+        }
+    }
+
+    /// What a click at `(line, col)` takes in when it selects by `by`
+    fn unit_at(&self, by: SelectBy, (line, col): (usize, usize)) -> TextRange {
+        match by {
+            SelectBy::Char => TextRange {
+                start: (line, col),
+                end: (line, col + 1),
+            },
+            SelectBy::Word => self.word_at((line, col)),
+            SelectBy::Line => TextRange {
+                start: (line, 0),
+                end: (line + 1, 0),
+            },
+        }
+    }
+
+    /// Select from what the left button pressed on to what it takes in at `target`, returning where the
+    /// cursor goes, at the end of the selection the mouse is at
+    fn select_from(
+        &mut self,
+        (by, grabbed): (SelectBy, TextRange),
+        target: (usize, usize),
+    ) -> (usize, usize) {
+        let reached = self.unit_at(by, target);
+        let (range, cursor, anchor) = if reached.start < grabbed.start {
+            let range = TextRange {
+                start: reached.start,
+                end: grabbed.end,
+            };
+            (range, reached.start, self.selection_cursor(grabbed))
+        } else {
+            let range = TextRange {
+                start: grabbed.start,
+                end: reached.end.max(grabbed.end),
+            };
+            (range, self.selection_cursor(range), grabbed.start)
+        };
+        self.leave_insert();
+        self.mode.set(Mode::Visual);
+        self.anchor = anchor;
+        self.expanded.clear();
+        self.select(range);
+        cursor
+    }
+
+    /// Leave insert mode for a selection made with the mouse, what was typed is one change to undo
+    fn leave_insert(&mut self) {
+        if self.mode.get() != Mode::Insert {
+            return;
+        }
+        self.buffer.borrow_mut().end_change();
+        self.autoindented = None;
+        self.end_snippet();
+        self.mode.set(Mode::Normal);
+    }
+
+    /// The text in `range`, lines joined by line breaks
+    fn text_in(&self, range: TextRange) -> String {
+        let buffer = self.buffer.borrow();
+        (range.start.0..=range.end.0)
+            .map(|line| {
+                let text = buffer.line(line).unwrap_or_default();
+                range
+                    .cols_on(line, text.chars().count())
+                    .map_or(String::new(), |(start, end)| {
+                        text.chars().skip(start).take(end - start).collect()
+                    })
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Keep the selected text for a middle click to paste, in this window or another
+    pub(super) fn keep_selection(&self) {
+        if let Some(range) = self.marks.selection.get() {
+            *self.primary_selection.borrow_mut() = self.text_in(range);
+        }
+    }
+
+    /// Paste the text last selected before `at`, leaving the cursor at its end
+    fn paste_selection(
+        &mut self,
+        commands: &mut ComponentCommands,
+        at: (usize, usize),
+    ) -> Result<()> {
+        let text = self.primary_selection.borrow().clone();
+        if text.is_empty() {
+            return Ok(());
+        }
+        if self.mode.get() == Mode::Visual {
+            self.end_visual();
+        }
+        let line_count = self.buffer.borrow().line_count();
+        let cursor = self.window.borrow().cursor;
+        self.buffer
+            .borrow_mut()
+            .replace_range(TextRange { start: at, end: at }, &text, cursor)?;
+        let breaks = text.matches('\n').count();
+        let last = text.rsplit('\n').next().unwrap_or_default().chars().count();
+        let end = if breaks == 0 {
+            (at.0, at.1 + last)
+        } else {
+            (at.0 + breaks, last)
+        };
+        let target = match self.mode.get() {
+            Mode::Insert => end,
+            _ => self.clamp(end.0, end.1.saturating_sub(1)),
+        };
+        self.remember_col(target);
+        self.settle(commands, target, line_count)
+    }
+
+    /// Handle the mouse over the window's text, returning whether the window changed
+    /// A left click places the cursor, a double or triple click selects a word or a line and dragging
+    /// selects from where the button went down. Shift or Alt with a click extends the selection.
+    pub(super) fn mouse(
+        &mut self,
+        mouse: MouseEvent,
+        commands: &mut ComponentCommands,
+    ) -> Result<bool> {
+        let text_id = self.window.borrow().text_id;
+        let Some(text_id) = text_id else {
+            return Ok(false);
+        };
+        let inside = commands
+            .screen_rect_of(text_id)
+            .contains(mouse.column, mouse.row);
+        let extend = mouse
+            .modifiers
+            .intersects(KeyModifiers::SHIFT | KeyModifiers::ALT);
+        let id = self.window.borrow().id();
+        let target = match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) if !inside => {
+                // A click anywhere else leaves the selection here behind
+                self.grabbed = None;
+                if self.marks.selection.get().is_none() {
+                    return Ok(false);
+                }
+                if self.current.get() == id && self.mode.get() == Mode::Visual {
+                    self.end_visual();
+                } else {
+                    self.marks.selection.set(None);
+                    self.expanded.clear();
+                }
+                return Ok(true);
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some(target) = self.position_at(commands, mouse.column, mouse.row) else {
+                    return Ok(false);
+                };
+                self.pending.clear();
+                self.current.set(id);
+                let click = Click::after(self.last_click, mouse.column, mouse.row);
+                self.last_click = Some(click);
+                if extend {
+                    let cursor = self.window.borrow().cursor;
+                    if self.mode.get() != Mode::Visual {
+                        self.leave_insert();
+                        self.start_visual(cursor, None);
+                    }
+                    self.grabbed =
+                        Some((SelectBy::Char, self.unit_at(SelectBy::Char, self.anchor)));
+                    self.select_to(target);
+                    target
+                } else {
+                    let grabbed = (click.selects_by(), self.unit_at(click.selects_by(), target));
+                    self.grabbed = Some(grabbed);
+                    if grabbed.0 == SelectBy::Char {
+                        if self.mode.get() == Mode::Visual {
+                            self.end_visual();
+                        }
+                        self.clamp(target.0, target.1)
+                    } else {
+                        self.select_from(grabbed, target)
+                    }
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let Some(grabbed) = self.grabbed else {
+                    return Ok(false);
+                };
+                let Some(target) = self.position_at(commands, mouse.column, mouse.row) else {
+                    return Ok(false);
+                };
+                // Nothing is selected until the mouse leaves the char it went down on
+                if self.mode.get() != Mode::Visual && target == grabbed.1.start {
+                    return Ok(false);
+                }
+                self.select_from(grabbed, target)
+            }
+            MouseEventKind::Up(MouseButton::Left) => {
+                if self.grabbed.take().is_some() {
+                    self.keep_selection();
+                }
+                return Ok(false);
+            }
+            MouseEventKind::Down(MouseButton::Middle) if inside => {
+                let Some(at) = self.position_at(commands, mouse.column, mouse.row) else {
+                    return Ok(false);
+                };
+                self.pending.clear();
+                self.current.set(id);
+                self.paste_selection(commands, at)?;
+                return Ok(true);
+            }
+            _ => return Ok(false),
+        };
+        self.remember_col(target);
+        self.place_cursor(commands, target)?;
+        Ok(true)
+    }
+}
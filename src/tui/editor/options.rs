@@ -0,0 +1,162 @@
+use anyhow::Result;
+
+use super::Editor;
+use crate::{
+    buffer::{
+        encoding::{FileEncoding, FileFormat},
+        fold::FoldMethod,
+    },
+    tui::{decoration::BorderStyle, listchars::ListChars, tree::ComponentCommands},
+};
+
+impl Editor {
+    /// `:set`, change an option or show its value when none is given
+    pub(super) fn set_option(
+        &mut self,
+        option: &str,
+        value: Option<String>,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        let (option, show) = match option.strip_suffix('?') {
+            Some(option) => (option, true),
+            None => (option, false),
+        };
+        let window = self.window();
+        let buffer = self.buffer();
+        let mut wrap = window.borrow().wrap.clone();
+        let mut options = buffer.borrow().options;
+        // Switches are turned on by name and off with `no` in front
+        let (name, on) = match option.strip_prefix("no") {
+            Some(name) if wrap.switch(name).is_some() || options.switch(name).is_some() => {
+                (name, false)
+            }
+            _ => (option, true),
+        };
+        if let Some(switch) = wrap.switch(name).or_else(|| options.switch(name)) {
+            if value.is_some() {
+                anyhow::bail!("E474: Invalid argument: {option}");
+            }
+            if show {
+                let prefix = if *switch { "" } else { "no" };
+                self.command_line
+                    .borrow_mut()
+                    .set_message(format!("  {prefix}{name}"));
+                return Ok(());
+            }
+            *switch = on;
+            window.borrow_mut().wrap = wrap;
+            buffer.borrow_mut().options = options;
+            return self.rebuild_tabs(commands);
+        }
+        if let Some(number) = options.number(option) {
+            let Some(value) = value else {
+                self.command_line
+                    .borrow_mut()
+                    .set_message(format!("  {option}={number}"));
+                return Ok(());
+            };
+            *number = value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("E521: Number required after =: {option}={value}"))?;
+            if options.tabstop == 0 {
+                anyhow::bail!("E487: Argument must be positive: {option}={value}");
+            }
+            buffer.borrow_mut().options = options;
+            return self.rebuild_tabs(commands);
+        }
+        match (option, value) {
+            ("fileformat" | "ff", None) => {
+                self.command_line
+                    .borrow_mut()
+                    .set_message(format!("  fileformat={}", options.file.fileformat.name()));
+                Ok(())
+            }
+            ("fileformat" | "ff", Some(value)) => {
+                options.file.fileformat = FileFormat::parse(&value)
+                    .ok_or_else(|| anyhow::anyhow!("E474: Invalid argument: {option}={value}"))?;
+                buffer.borrow_mut().options = options;
+                Ok(())
+            }
+            ("fileencoding" | "fenc", None) => {
+                self.command_line.borrow_mut().set_message(format!(
+                    "  fileencoding={}",
+                    options.file.fileencoding.name()
+                ));
+                Ok(())
+            }
+            ("fileencoding" | "fenc", Some(value)) => {
+                options.file.fileencoding = FileEncoding::parse(&value)
+                    .ok_or_else(|| anyhow::anyhow!("E474: Invalid argument: {option}={value}"))?;
+                buffer.borrow_mut().options = options;
+                Ok(())
+            }
+            ("listchars" | "lcs", None) => {
+                self.command_line
+                    .borrow_mut()
+                    .set_message(format!("  listchars={}", wrap.listchars));
+                Ok(())
+            }
+            ("listchars" | "lcs", Some(value)) => {
+                wrap.listchars = ListChars::parse(&value)?;
+                window.borrow_mut().wrap = wrap;
+                self.rebuild_windows(commands)
+            }
+            ("showbreak" | "sbr", None) => {
+                self.command_line
+                    .borrow_mut()
+                    .set_message(format!("  showbreak={}", wrap.showbreak));
+                Ok(())
+            }
+            ("showbreak" | "sbr", Some(value)) => {
+                wrap.showbreak = value;
+                window.borrow_mut().wrap = wrap;
+                self.rebuild_windows(commands)
+            }
+            ("foldmethod" | "fdm", None) => {
+                let method = self.buffer().borrow_mut().folds().method();
+                self.command_line
+                    .borrow_mut()
+                    .set_message(format!("  foldmethod={}", method.name()));
+                Ok(())
+            }
+            ("foldmethod" | "fdm", Some(value)) => {
+                let method = FoldMethod::parse(&value)
+                    .ok_or_else(|| anyhow::anyhow!("E474: Invalid argument: {option}={value}"))?;
+                self.buffer().borrow_mut().folds().set_method(method)?;
+                self.rebuild_tabs(commands)
+            }
+            ("mousescroll", None) => {
+                self.command_line
+                    .borrow_mut()
+                    .set_message(format!("  mousescroll=ver:{}", self.mouse_scroll.get()));
+                Ok(())
+            }
+            ("mousescroll", Some(value)) => {
+                let rows = value
+                    .strip_prefix("ver:")
+                    .and_then(|rows| rows.parse().ok())
+                    .ok_or_else(|| anyhow::anyhow!("E474: Invalid argument: {option}={value}"))?;
+                self.mouse_scroll.set(rows);
+                Ok(())
+            }
+            ("winborder", None) => {
+                let name = self.win_border.get().map_or("none", BorderStyle::name);
+                self.command_line
+                    .borrow_mut()
+                    .set_message(format!("  winborder={name}"));
+                Ok(())
+            }
+            ("winborder", Some(value)) => {
+                let border = match value.as_str() {
+                    "none" => None,
+                    name => Some(BorderStyle::parse(name).ok_or_else(|| {
+                        anyhow::anyhow!("E474: Invalid argument: {option}={value}")
+                    })?),
+                };
+                self.win_border.set(border);
+                Ok(())
+            }
+            _ => anyhow::bail!("E518: Unknown option: {option}"),
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use anyhow::Result;
+use crossterm::style::Color;
+
+use super::Marks;
+use crate::{
+    buffer::{Buffer, byte_index, grapheme},
+    lsp::protocol::Severity,
+    syntax::theme::Theme,
+    tui::{
+        Component, Cursor, CursorStyle, Formatting, LayoutMode, Measurement, Overflow, window::Row,
+        wrap,
+    },
+    vcs::VcsStatus,
+};
+
+/// The color of a diagnostic's gutter sign and underline
+fn severity_color(severity: Severity) -> Color {
+    match severity {
+        Severity::Error => Color::Red,
+        Severity::Warning => Color::Yellow,
+        Severity::Information => Color::Blue,
+        Severity::Hint => Color::Cyan,
+    }
+}
+
+/// Shown under the line number on the rows a long line wraps onto
+const CONTINUATION: &str = "↳";
+
+struct TextGutter {
+    /// What the row shows, shared with its content
+    row: Rc<Cell<Row>>,
+    buffer: Rc<RefCell<Buffer>>,
+    /// Screen rows the line takes, counted by its content
+    rows: Rc<Cell<usize>>,
+}
+
+impl TextGutter {
+    pub(super) fn new(
+        row: Rc<Cell<Row>>,
+        buffer: Rc<RefCell<Buffer>>,
+        rows: Rc<Cell<usize>>,
+    ) -> Self {
+        Self { row, buffer, rows }
+    }
+}
+
+impl Component for TextGutter {
+    fn render(
+        &self,
+        buffer: &mut crate::tui::terminal_buffer::TerminalBuffer,
+        _query: crate::tui::ComponentQuery,
+    ) -> anyhow::Result<()> {
+        // Wide enough for the last line number, so the gutter grows with the buffer
+        let text_buffer = self.buffer.borrow();
+        let width = text_buffer.line_count().to_string().len();
+        let line = match self.row.get() {
+            Row::Line(line) | Row::Fold { start: line, .. } => line,
+            // Blank under a filler, so the fill starts where the text does
+            Row::Filler => {
+                buffer.write(&" ".repeat(width + 2));
+                return Ok(());
+            }
+        };
+        // How the line differs from git's index colours the bar
+        buffer.set_background(text_buffer.line_vcs_status(line).color());
+        // The line's worst diagnostic takes the place of the bar
+        match text_buffer.line_severity(line) {
+            Some(severity) => buffer
+                .set_foreground(severity_color(severity))
+                .write(&severity.sign().to_string())
+                .set_foreground(Color::Reset),
+            None => buffer.write("│"),
+        };
+        buffer
+            .set_background(Color::Reset)
+            .write(&format!("{:>width$} ", line + 1));
+        let status_color = text_buffer.line_vcs_status(line).color();
+        for _ in 1..self.rows.get().min(buffer.height() as usize) {
+            buffer
+                .newline()
+                .set_background(status_color)
+                .write("│")
+                .set_background(Color::Reset)
+                .write(&format!("{CONTINUATION:>width$} "));
+        }
+        Ok(())
+    }
+    fn default_formatting(&self) -> Formatting {
+        Formatting {
+            preferred_width: Measurement::Content,
+            preferred_height: Measurement::Fill(1),
+            overflow_x: Overflow::Hide,
+            overflow_y: Overflow::Hide,
+            request_focus: false,
+            focusable: false,
+            ..Default::default()
+        }
+    }
+}
+
+struct TextContent {
+    buffer: Rc<RefCell<Buffer>>,
+    /// The line, fold or filler this row shows, changed as the window scrolls
+    row: Rc<Cell<Row>>,
+    marks: Rc<Marks>,
+    theme: Rc<Theme>,
+    /// Screen rows the line took when last drawn, for the gutter to mark
+    rows: Rc<Cell<usize>>,
+}
+
+impl TextContent {
+    pub(super) fn new(
+        buffer: Rc<RefCell<Buffer>>,
+        row: Rc<Cell<Row>>,
+        marks: Rc<Marks>,
+        theme: Rc<Theme>,
+        rows: Rc<Cell<usize>>,
+    ) -> Self {
+        Self {
+            buffer,
+            row,
+            marks,
+            theme,
+            rows,
+        }
+    }
+
+    /// The rows the line is broken into at `width`
+    fn screen_lines(&self, text: &str, width: u16) -> Vec<wrap::ScreenLine> {
+        let options = self.marks.wrap.borrow();
+        wrap::screen_lines(text, width as usize, self.marks.left_col.get(), &options)
+    }
+
+    /// The fold's line count and first line in place of its text
+    fn render_fold(
+        &self,
+        buffer: &mut crate::tui::terminal_buffer::TerminalBuffer,
+        (start, end): (usize, usize),
+        background: Color,
+    ) {
+        let text_buffer = self.buffer.borrow();
+        let text = text_buffer.line(start).unwrap_or_default();
+        let count = end - start;
+        let lines = if count == 1 { "line" } else { "lines" };
+        let summary = format!("+--{count:>3} {lines}: {}", text.trim());
+        let width = buffer.width() as usize;
+        buffer
+            .set_foreground(Color::Cyan)
+            .set_background(background)
+            .write(&summary.chars().take(width).collect::<String>())
+            .set_foreground(Color::Reset);
+    }
+
+    /// Room left for lines only the other side of a diff has
+    fn render_filler(&self, buffer: &mut crate::tui::terminal_buffer::TerminalBuffer) {
+        let width = buffer.width() as usize;
+        buffer
+            .set_foreground(VcsStatus::Deleted.color())
+            .write(&"-".repeat(width))
+            .set_foreground(Color::Reset);
+    }
+}
+
+impl Component for TextContent {
+    fn render(
+        &self,
+        buffer: &mut crate::tui::terminal_buffer::TerminalBuffer,
+        query: crate::tui::ComponentQuery,
+    ) -> anyhow::Result<()> {
+        let (line, fold_end) = match self.row.get() {
+            Row::Line(line) => (line, None),
+            Row::Fold { start, end } => (start, Some(end)),
+            Row::Filler => {
+                self.rows.set(1);
+                self.render_filler(buffer);
+                return Ok(());
+            }
+        };
+        // Lines that differ from the other window in diff mode take the colour of their change
+        let line_diff = self.marks.diff.borrow().get(line).copied().flatten();
+        let background = if query.has_focus() {
+            Color::DarkGrey
+        } else {
+            line_diff.map_or(Color::Reset, |line_diff| line_diff.status.color())
+        };
+        if let Some(end) = fold_end {
+            self.rows.set(1);
+            self.render_fold(buffer, (line, end), background);
+            return Ok(());
+        }
+        let text_buffer = self.buffer.borrow();
+        let text = text_buffer.line(line).unwrap_or_default();
+        let mut screen_lines = self.screen_lines(text, buffer.width());
+        // The top line of the window may have scrolled partly out of view
+        let skipped = self
+            .marks
+            .skipped(line)
+            .min(screen_lines.len().saturating_sub(1));
+        screen_lines.drain(..skipped);
+        self.rows.set(screen_lines.len());
+        let shown = screen_lines.first().map_or(0, |line| line.start)
+            ..screen_lines.last().map_or(0, |line| line.end);
+        let shown = byte_index(text, shown.start)..byte_index(text, shown.end);
+        // Where each row after the first starts
+        let mut breaks = screen_lines
+            .iter()
+            .skip(1)
+            .map(|line| (byte_index(text, line.start), line))
+            .peekable();
+        let changed = line_diff
+            .and_then(|line_diff| line_diff.changed)
+            .map(|(start, end)| byte_index(text, start)..byte_index(text, end));
+        let spans = text_buffer.highlights(line);
+        let placeholder = self
+            .marks
+            .placeholder
+            .get()
+            .filter(|placeholder| placeholder.line == line)
+            .map(|placeholder| {
+                byte_index(text, placeholder.start)..byte_index(text, placeholder.end)
+            });
+        let selection = self
+            .marks
+            .selection
+            .get()
+            .and_then(|selection| selection.cols_on(line, text.chars().count()))
+            .map(|(start, end)| byte_index(text, start)..byte_index(text, end));
+        let underlines: Vec<_> = text_buffer
+            .diagnostics()
+            .iter()
+            .filter_map(|diagnostic| {
+                let mut range = diagnostic
+                    .range
+                    .to_text_range(text_buffer.lines().as_slice());
+                // Empty ranges mark the char they start at
+                if range.start == range.end {
+                    range.end.1 += 1;
+                }
+                let (start, end) = range.cols_on(line, text.chars().count())?;
+                Some((
+                    byte_index(text, start)..byte_index(text, end),
+                    diagnostic.severity,
+                ))
+            })
+            .collect();
+        // Split the line wherever its color changes or it breaks onto the next row
+        let mut bounds = vec![shown.start, shown.end];
+        bounds.extend(breaks.clone().map(|(start, _)| start));
+        bounds.extend(spans.iter().flat_map(|span| [span.start, span.end]));
+        bounds.extend(
+            placeholder
+                .iter()
+                .chain(&selection)
+                .chain(&changed)
+                .flat_map(|range| [range.start, range.end]),
+        );
+        bounds.extend(
+            underlines
+                .iter()
+                .flat_map(|(range, _)| [range.start, range.end]),
+        );
+        // Colours change only between grapheme clusters, which are drawn whole
+        for bound in &mut bounds {
+            *bound = grapheme::ceil_boundary(text, *bound);
+        }
+        bounds.sort_unstable();
+        bounds.dedup();
+        let options = self.marks.wrap.borrow();
+        let drawn = wrap::drawn(text, &options);
+        if skipped > 0 {
+            buffer.write(&" ".repeat(screen_lines[0].indent));
+            if screen_lines[0].showbreak {
+                buffer.set_foreground(Color::Blue).write(&options.showbreak);
+            }
+        }
+        for segment in bounds.windows(2) {
+            let (start, end) = (segment[0], segment[1]);
+            if !shown.contains(&start) {
+                continue;
+            }
+            if let Some((_, line)) = breaks.next_if(|&(at, _)| at == start) {
+                buffer.newline().write(&" ".repeat(line.indent));
+                if line.showbreak {
+                    buffer.set_foreground(Color::Blue).write(&options.showbreak);
+                }
+            }
+            let foreground = spans
+                .iter()
+                .find(|span| span.start <= start && start < span.end)
+                .and_then(|span| self.theme.color(span.group))
+                .unwrap_or(Color::Reset);
+            let segment_background = match (&placeholder, &selection, &changed) {
+                (Some(range), _, _) if range.contains(&start) => Color::DarkCyan,
+                (_, Some(range), _) if range.contains(&start) => Color::DarkBlue,
+                (_, _, Some(range)) if range.contains(&start) => Color::DarkRed,
+                _ => background,
+            };
+            let underline = underlines
+                .iter()
+                .filter(|(range, _)| range.contains(&start))
+                .map(|&(_, severity)| severity)
+                .min();
+            buffer
+                .set_background(segment_background)
+                .set_underline(underline.map(severity_color));
+            let first = drawn.partition_point(|cluster| cluster.bytes.start < start);
+            for cluster in drawn[first..]
+                .iter()
+                .take_while(|cluster| cluster.bytes.start < end)
+            {
+                let foreground = if cluster.special {
+                    Color::Blue
+                } else {
+                    foreground
+                };
+                buffer.set_foreground(foreground).write(&cluster.text);
+            }
+        }
+        // The end of the line is marked when it is in view and there is room after it
+        let end = wrap::screen_position(text, &screen_lines, text.chars().count(), &options);
+        let eol = options
+            .listchars
+            .eol
+            .filter(|_| options.list && shown.end == text.len() && end.1 < buffer.width() as usize);
+        if let Some(eol) = eol {
+            buffer
+                .set_foreground(Color::Blue)
+                .set_background(background)
+                .set_underline(None)
+                .write(eol.encode_utf8(&mut [0; 4]));
+        }
+        buffer
+            .set_foreground(Color::Reset)
+            .set_background(background)
+            .set_underline(None);
+        Ok(())
+    }
+    fn default_formatting(&self) -> Formatting {
+        Formatting {
+            preferred_width: Measurement::Fill(1),
+            preferred_height: Measurement::Content,
+            // Long lines are broken into rows here, by the window's wrap options
+            overflow_x: Overflow::Hide,
+            overflow_y: Overflow::Hide,
+            request_focus: false,
+            layout_mode: LayoutMode::VerticalSplit,
+            focusable: true,
+            ..Default::default()
+        }
+    }
+
+    /// The cursor is kept as the char column, drawn on the row the char wrapped onto
+    fn screen_cursor(&self, cursor: Cursor, width: u16) -> Cursor {
+        let Row::Line(line) = self.row.get() else {
+            return cursor;
+        };
+        let text_buffer = self.buffer.borrow();
+        let text = text_buffer.line(line).unwrap_or_default();
+        let screen_lines = self.screen_lines(text, width);
+        let options = self.marks.wrap.borrow();
+        let (row, col) = wrap::screen_position(text, &screen_lines, cursor.col as usize, &options);
+        let row = row.saturating_sub(self.marks.skipped(line));
+        Cursor::from_xy(row as u16, col as u16)
+    }
+}
+
+/// One of a window's rows, bound to whichever line, fold or filler is shown there as the window scrolls
+pub(super) struct TextRow {
+    row: Rc<Cell<Row>>,
+    buffer: Rc<RefCell<Buffer>>,
+    marks: Rc<Marks>,
+    theme: Rc<Theme>,
+    /// Cursor column and style to give the content once it exists, for rows focused before initialization
+    pub(super) start_cursor: Option<(u16, CursorStyle)>,
+}
+
+impl TextRow {
+    pub(super) fn new(
+        buffer: Rc<RefCell<Buffer>>,
+        row: Rc<Cell<Row>>,
+        marks: Rc<Marks>,
+        theme: Rc<Theme>,
+    ) -> Self {
+        Self {
+            row,
+            buffer,
+            marks,
+            theme,
+            start_cursor: None,
+        }
+    }
+}
+
+impl Component for TextRow {
+    fn children(&mut self, commands: &mut crate::tui::tree::ComponentCommands) -> Result<()> {
+        let rows = Rc::new(Cell::new(1));
+        commands.add_component(TextGutter::new(
+            self.row.clone(),
+            self.buffer.clone(),
+            rows.clone(),
+        ))?;
+        let content = TextContent::new(
+            self.buffer.clone(),
+            self.row.clone(),
+            self.marks.clone(),
+            self.theme.clone(),
+            rows,
+        );
+        let content_id = commands.add_component(content)?;
+        if let Some((col, style)) = self.start_cursor.take() {
+            commands.set_cursor_for(content_id, col, 0);
+            commands.set_cursor_style_for(content_id, style);
+        }
+        Ok(())
+    }
+    fn default_formatting(&self) -> Formatting {
+        Formatting {
+            preferred_width: Measurement::Fill(1),
+            preferred_height: Measurement::Content,
+            overflow_x: Overflow::Hide,
+            overflow_y: Overflow::Hide,
+            request_focus: false,
+            layout_mode: LayoutMode::HorizontalSplit,
+            focusable: true,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+
+use super::{EditableText, Editor, InsertEdit};
+use crate::{
+    completion::CompletionKind,
+    snippet::{Snippet, Variables, session::SnippetSession},
+    tui::tree::ComponentCommands,
+};
+
+impl EditableText {
+    /// Handle an insert mode key the completion menu didn't take, with snippets in mind
+    pub(super) fn snippet_key(
+        &mut self,
+        key: KeyEvent,
+        (line, col): (usize, usize),
+        commands: &mut ComponentCommands,
+    ) -> Result<Option<(usize, usize)>> {
+        match key.code {
+            KeyCode::Tab | KeyCode::BackTab if self.snippet.is_some() => {
+                return self.jump_snippet(key.code == KeyCode::Tab, commands);
+            }
+            KeyCode::Tab => {
+                if let Some(target) = self.expand_snippet((line, col), commands)? {
+                    return Ok(Some(target));
+                }
+            }
+            KeyCode::Esc => self.end_snippet(),
+            _ => {}
+        }
+        self.snippet_edit(InsertEdit::of(key), (line, col), |editable, col| {
+            editable.insert_key(key, line, col)
+        })
+    }
+
+    /// Make an insert mode edit, keeping the snippet's placeholders and mirrors in step
+    pub(super) fn snippet_edit(
+        &mut self,
+        edit: InsertEdit,
+        (line, col): (usize, usize),
+        apply: impl FnOnce(&mut Self, usize) -> Result<Option<(usize, usize)>>,
+    ) -> Result<Option<(usize, usize)>> {
+        let Some(mut session) = self.snippet.take() else {
+            return apply(self, col);
+        };
+        if edit == InsertEdit::Move {
+            session.deselect();
+            self.snippet = Some(session);
+            return apply(self, col);
+        }
+
+        // Typing over a placeholder that was just jumped to replaces it
+        let cleared = session.clear_selection(&mut self.buffer.borrow_mut())?;
+        let col = if cleared { session.cursor().1 } else { col };
+        let (length, line_count) = {
+            let buffer = self.buffer.borrow();
+            (buffer.line_len(line), buffer.line_count())
+        };
+        let target = if cleared && edit == InsertEdit::Delete {
+            Some((line, col))
+        } else {
+            apply(self, col)?
+        };
+
+        let (new_length, new_line_count) = {
+            let buffer = self.buffer.borrow();
+            (buffer.line_len(line), buffer.line_count())
+        };
+        let edited_at = target.map_or(col, |target| target.1.min(col));
+        let delta = new_length as isize - length as isize;
+        let follows = new_line_count == line_count
+            && session.edited(&mut self.buffer.borrow_mut(), line, edited_at, delta)?;
+        if follows {
+            self.snippet = Some(session);
+        }
+        self.marks
+            .placeholder
+            .set(self.snippet.as_ref().and_then(SnippetSession::highlight));
+        Ok(target)
+    }
+
+    /// Expand the snippet whose prefix is before the cursor, if there is one
+    fn expand_snippet(
+        &mut self,
+        (line, col): (usize, usize),
+        commands: &mut ComponentCommands,
+    ) -> Result<Option<(usize, usize)>> {
+        let (definition, start, variables) = {
+            let buffer = self.buffer.borrow();
+            let text = buffer.line(line).unwrap_or_default();
+            let before: Vec<char> = text.chars().take(col).collect();
+            // Prefixes can hold punctuation like `#inc`, the whole word before the cursor is tried first
+            let starts = [
+                before
+                    .iter()
+                    .rposition(|character| character.is_whitespace())
+                    .map_or(0, |index| index + 1),
+                CompletionKind::Keyword.start(text, col),
+            ];
+            let found = starts.into_iter().find_map(|start| {
+                let prefix: String = before[start..].iter().collect();
+                let definition = self.snippets.find(buffer.filetype(), &prefix)?;
+                Some((definition, start, prefix))
+            });
+            let Some((definition, start, prefix)) = found else {
+                return Ok(None);
+            };
+            let variables = Variables {
+                path: buffer.file_path().map(Path::to_path_buf),
+                line: text.to_string(),
+                line_index: line,
+                word: prefix,
+            };
+            (definition, start, variables)
+        };
+        let snippet = Snippet::parse(&definition.body)
+            .map_err(|err| anyhow::anyhow!("snippet {}: {err}", definition.name))?;
+        let session = SnippetSession::insert(
+            &mut self.buffer.borrow_mut(),
+            snippet.expand(&variables),
+            (line, start),
+            col,
+        )?;
+        self.snippet = Some(session);
+        self.enter_stop(commands)
+    }
+
+    /// Move to the next or previous tab stop of the snippet
+    fn jump_snippet(
+        &mut self,
+        forward: bool,
+        commands: &mut ComponentCommands,
+    ) -> Result<Option<(usize, usize)>> {
+        let Some(session) = &mut self.snippet else {
+            return Ok(None);
+        };
+        if !session.jump(forward) {
+            return Ok(None);
+        }
+        self.enter_stop(commands)
+    }
+
+    /// Put the cursor on the snippet's current tab stop, ending the snippet at `$0`
+    fn enter_stop(&mut self, commands: &mut ComponentCommands) -> Result<Option<(usize, usize)>> {
+        let Some(session) = &self.snippet else {
+            return Ok(None);
+        };
+        let target = session.cursor();
+        let choices = session.current().choices.clone();
+        if session.is_finished() {
+            self.end_snippet();
+        } else {
+            self.marks.placeholder.set(session.highlight());
+        }
+        // Leaving a choice closes its options, reaching one opens them
+        if choices.is_empty() {
+            self.completion.close(commands);
+        } else {
+            self.completion.offer(&choices, target, commands)?;
+        }
+        self.remember_col(target);
+        Ok(Some(target))
+    }
+
+    pub(super) fn end_snippet(&mut self) {
+        self.snippet = None;
+        self.marks.placeholder.set(None);
+    }
+}
+
+impl Editor {
+    /// The snippets for the current buffer's filetype, for `:snippets`
+    pub(super) fn list_snippets(&self) -> String {
+        let filetype = self.window().borrow().buffer.borrow().filetype();
+        let available = self.snippets.available(filetype);
+        if available.is_empty() {
+            return format!("No snippets for {}", filetype.unwrap_or("this buffer"));
+        }
+        available
+            .iter()
+            .map(|definition| {
+                let description = definition.description.as_deref().unwrap_or_default();
+                format!(
+                    "{:<12} {:<24} {description}",
+                    definition.prefix, definition.name
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use super::Editor;
+use crate::{
+    buffer::Buffer,
+    tui::{
+        Component, Measurement,
+        command::TabPosition,
+        tab::TabPage,
+        tree::{ComponentCommands, ComponentId},
+        window::{SplitComponent, Window, pair_diff_windows},
+    },
+};
+
+impl Editor {
+    /// The tab pages, for tests to see which one is shown and what windows it has
+    #[cfg(test)]
+    pub fn tabs(&self) -> std::rc::Rc<std::cell::RefCell<crate::tui::tab::TabList>> {
+        self.tabs.clone()
+    }
+
+    /// Show another tab, focus goes to the window that had it when the tab was left
+    pub(super) fn select_tab(&mut self, index: usize, commands: &mut ComponentCommands) {
+        let (hidden, shown, window) = {
+            let mut tabs = self.tabs.borrow_mut();
+            tabs.current_mut().current = self.current.get();
+            let hidden = tabs.current().split_id;
+            tabs.select(index);
+            (hidden, tabs.current().split_id, tabs.current().current)
+        };
+        if hidden == shown {
+            return;
+        }
+        if let Some(hidden) = hidden {
+            show_tab_page(commands, hidden, false);
+        }
+        if let Some(shown) = shown {
+            show_tab_page(commands, shown, true);
+        }
+        self.focus_window(window, commands);
+    }
+
+    /// `:tabnew [file]` opens a tab after the current one, with an empty buffer without a file
+    pub(super) fn new_tab(
+        &mut self,
+        path: Option<PathBuf>,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        let buffer = match self.open_path(path)? {
+            Some(buffer) => buffer,
+            None => self.buffers.borrow_mut().add(Buffer::default()),
+        };
+        let Some(tab_pages_id) = self.tab_pages_id else {
+            return Ok(());
+        };
+        let id = self.next_window_id;
+        self.next_window_id += 1;
+        let hidden = {
+            let mut tabs = self.tabs.borrow_mut();
+            tabs.current_mut().current = self.current.get();
+            let hidden = tabs.current().split_id;
+            tabs.insert(TabPage::new(Window::new(id, buffer)));
+            hidden
+        };
+        if let Some(hidden) = hidden {
+            show_tab_page(commands, hidden, false);
+        }
+        // The new window takes focus as soon as its text is built
+        self.current.set(id);
+        let split_id = self.add_tab_page(tab_pages_id, commands)?;
+        self.tabs.borrow_mut().current_mut().split_id = Some(split_id);
+        Ok(())
+    }
+
+    /// Add the components for the current tab's windows
+    pub(super) fn add_tab_page(
+        &self,
+        tab_pages_id: ComponentId,
+        commands: &mut ComponentCommands,
+    ) -> Result<ComponentId> {
+        let (layout, windows) = {
+            let tabs = self.tabs.borrow();
+            (
+                tabs.current().layout.clone(),
+                tabs.current().windows.clone(),
+            )
+        };
+        pair_diff_windows(&layout, &windows);
+        let component = SplitComponent::new(layout, windows, self.context());
+        let formatting = component.default_formatting();
+        commands.add_component_to(tab_pages_id, component, formatting)
+    }
+
+    /// Close the current tab, the tab after it is shown instead
+    pub(super) fn close_tab(&mut self, commands: &mut ComponentCommands) -> Result<()> {
+        if self.tabs.borrow().len() < 2 {
+            anyhow::bail!("E784: Cannot close last tab page");
+        }
+        let page = {
+            let mut tabs = self.tabs.borrow_mut();
+            let index = tabs.current_index();
+            tabs.remove(index)
+        };
+        // The buffers left behind remember where the cursor of the last window showing them was
+        for window in &page.windows {
+            let window = window.borrow();
+            let mut buffer = window.buffer.borrow_mut();
+            buffer.cursor = window.cursor;
+            buffer.scroll = window.scroll;
+        }
+        if let Some(split_id) = page.split_id {
+            commands.remove_component(split_id);
+        }
+        let (shown, window) = {
+            let tabs = self.tabs.borrow();
+            (tabs.current().split_id, tabs.current().current)
+        };
+        if let Some(shown) = shown {
+            show_tab_page(commands, shown, true);
+        }
+        self.focus_window(window, commands);
+        Ok(())
+    }
+
+    /// `:tabmove`, the tab line order changes but every tab keeps its windows
+    pub(super) fn move_tab(&mut self, position: TabPosition) {
+        let mut tabs = self.tabs.borrow_mut();
+        let index = match position {
+            // Tab numbers count from 1 and include the tab being moved
+            TabPosition::After(tab) if tab > tabs.current_index() => tab - 1,
+            TabPosition::After(tab) => tab,
+            TabPosition::Relative(offset) => tabs.current_index().saturating_add_signed(offset),
+            TabPosition::Last => usize::MAX,
+        };
+        tabs.move_current(index);
+    }
+
+    /// `:tabnext` and `gt` go to a tab by number, or the next one wrapping around
+    pub(super) fn next_tab(&mut self, number: Option<usize>, commands: &mut ComponentCommands) {
+        let len = self.tabs.borrow().len();
+        let index = match number {
+            Some(number) => number.saturating_sub(1),
+            None => (self.tabs.borrow().current_index() + 1) % len,
+        };
+        self.select_tab(index, commands);
+    }
+
+    /// `:tabprevious` and `gT` go back some tabs, wrapping around
+    pub(super) fn prev_tab(&mut self, count: usize, commands: &mut ComponentCommands) {
+        let len = self.tabs.borrow().len();
+        let index = (self.tabs.borrow().current_index() + len - count % len) % len;
+        self.select_tab(index, commands);
+    }
+}
+
+/// Give a tab page's windows the whole area, or no room at all while another tab is shown
+pub(super) fn show_tab_page(commands: &mut ComponentCommands, split_id: ComponentId, shown: bool) {
+    let mut formatting = commands.formatting_of(split_id);
+    formatting.preferred_height = if shown {
+        Measurement::Fill(1)
+    } else {
+        Measurement::Cell(0)
+    };
+    commands.set_formatting_for(split_id, formatting);
+}
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use super::{EditableText, Mode};
+use crate::buffer::TextRange;
+
+impl EditableText {
+    /// Enter visual mode selecting from `anchor` to the cursor, or `range` when given
+    pub(super) fn start_visual(&mut self, anchor: (usize, usize), range: Option<TextRange>) {
+        self.mode.set(Mode::Visual);
+        self.anchor = anchor;
+        self.expanded.clear();
+        self.select(range.unwrap_or(TextRange {
+            start: anchor,
+            end: (anchor.0, anchor.1 + 1),
+        }));
+    }
+
+    pub(super) fn select(&mut self, range: TextRange) {
+        self.marks.selection.set(Some(range));
+    }
+
+    /// Select from the anchor to the cursor, both ends included
+    pub(super) fn select_to(&mut self, cursor: (usize, usize)) {
+        let (start, end) = if cursor < self.anchor {
+            (cursor, self.anchor)
+        } else {
+            (self.anchor, cursor)
+        };
+        self.select(TextRange {
+            start,
+            end: (end.0, end.1 + 1),
+        });
+    }
+
+    /// The last char of a selection, where the cursor sits
+    pub(super) fn selection_cursor(&self, range: TextRange) -> (usize, usize) {
+        let (line, col) = range.end;
+        if col > 0 || line == range.start.0 {
+            return self.clamp(line, col.saturating_sub(1));
+        }
+        // Ends with a line break, the cursor goes on the last line it covers
+        self.clamp(line - 1, usize::MAX)
+    }
+
+    pub(super) fn end_visual(&mut self) {
+        self.keep_selection();
+        self.mode.set(Mode::Normal);
+        self.marks.selection.set(None);
+        self.expanded.clear();
+    }
+
+    /// Keys that move the cursor the same way in normal and visual mode
+    fn is_motion(key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char(character) => {
+                !key.modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+                    && "hjkl0$Gg ".contains(character)
+            }
+            KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::Up
+            | KeyCode::Down
+            | KeyCode::Home
+            | KeyCode::End
+            | KeyCode::Backspace
+            | KeyCode::Enter => true,
+            _ => false,
+        }
+    }
+
+    /// Handle a key in visual mode, returning where the cursor moves to
+    pub(super) fn visual_key(
+        &mut self,
+        key: KeyEvent,
+        line: usize,
+        col: usize,
+    ) -> Result<Option<(usize, usize)>> {
+        if (self.pending.is_empty() || self.pending == "g") && Self::is_motion(key) {
+            let target = self.normal_key(key, line, col)?;
+            if let Some(target) = target {
+                self.expanded.clear();
+                self.select_to(target);
+            }
+            return Ok(target);
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let Some(selection) = self.marks.selection.get() else {
+            self.end_visual();
+            return Ok(None);
+        };
+        let target = match (pending.as_str(), key.code) {
+            (_, KeyCode::Esc) | (_, KeyCode::Char('v')) => {
+                self.end_visual();
+                self.clamp(line, col)
+            }
+            (_, KeyCode::Char('o')) if alt => {
+                let Some(node) = self.buffer.borrow().expand_node(selection) else {
+                    return Ok(None);
+                };
+                self.expanded.push(selection);
+                self.select(node);
+                self.selection_cursor(node)
+            }
+            (_, KeyCode::Char('i')) if alt => {
+                let Some(previous) = self.expanded.pop() else {
+                    return Ok(None);
+                };
+                self.select(previous);
+                self.selection_cursor(previous)
+            }
+            ("", KeyCode::Char('o')) => {
+                let cursor = std::mem::replace(&mut self.anchor, (line, col));
+                self.select_to(cursor);
+                cursor
+            }
+            ("", KeyCode::Char('d' | 'x' | 'c') | KeyCode::Delete) => {
+                self.end_visual();
+                return self
+                    .operate(key.code == KeyCode::Char('c'), selection, (line, col))
+                    .map(Some);
+            }
+            ("", KeyCode::Char(character @ ('>' | '<'))) => {
+                self.end_visual();
+                let last = self.selection_cursor(selection).0;
+                self.buffer.borrow_mut().shift_lines(
+                    selection.start.0..last + 1,
+                    character == '>',
+                    (line, col),
+                )?;
+                self.first_non_blank(selection.start.0)
+            }
+            ("", KeyCode::Char(character @ ('a' | 'i' | 'z'))) => {
+                self.pending.push(character);
+                return Ok(None);
+            }
+            ("z", KeyCode::Char('f')) => {
+                self.end_visual();
+                let last = self.selection_cursor(selection).0;
+                self.buffer
+                    .borrow_mut()
+                    .folds()
+                    .create(selection.start.0, last + 1)?;
+                self.clamp(selection.start.0, selection.start.1)
+            }
+            ("a" | "i", KeyCode::Char(character @ ('f' | 'c'))) => {
+                let Some(range) = self.object_at((line, col), character, pending == "i") else {
+                    return Ok(None);
+                };
+                self.anchor = range.start;
+                self.expanded.clear();
+                self.select(range);
+                self.selection_cursor(range)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(target))
+    }
+}
@@ -0,0 +1,255 @@
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+
+use super::Editor;
+use crate::{
+    buffer::Buffer,
+    tui::{
+        LayoutMode,
+        tree::ComponentCommands,
+        window::{Layout, Resize, Window, WindowId},
+    },
+};
+
+impl Editor {
+    /// Move focus to another window, onto the line its cursor was on
+    pub(super) fn focus_window(&mut self, id: WindowId, commands: &mut ComponentCommands) {
+        let Some(window) = self.get_window(id) else {
+            return;
+        };
+        self.current.set(id);
+        let window = window.borrow();
+        let Some(text_id) = window.text_id else {
+            return;
+        };
+        let (line, col) = window.cursor;
+        // The row components start at the window's first row in view
+        let content_id = commands
+            .children_of(text_id)
+            .zip(window.row_of(line).checked_sub(window.scroll))
+            .and_then(|(rows, index)| rows.get(index).copied())
+            .and_then(|row_id| commands.children_of(row_id))
+            .and_then(|cells| cells.get(1).copied());
+        match content_id {
+            Some(content_id) => {
+                commands.set_focus(content_id);
+                commands.set_cursor_for(content_id, col as u16, 0);
+                commands.set_cursor_style_for(content_id, self.mode.get().cursor_style());
+            }
+            None => commands.set_focus(text_id),
+        }
+    }
+
+    /// Split the window with focus, the new window shows `buffer` and takes focus
+    pub(super) fn split(
+        &mut self,
+        mode: LayoutMode,
+        buffer: Option<Rc<RefCell<Buffer>>>,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        self.add_window(mode, buffer);
+        self.rebuild_windows(commands)
+    }
+
+    /// Add a window next to the one with focus and focus it, its components are built by the next rebuild
+    pub(super) fn add_window(
+        &mut self,
+        mode: LayoutMode,
+        buffer: Option<Rc<RefCell<Buffer>>>,
+    ) -> Rc<RefCell<Window>> {
+        let current = self.window();
+        let id = self.next_window_id;
+        self.next_window_id += 1;
+        let mut window = {
+            let current = current.borrow();
+            let mut window = Window::new(id, current.buffer.clone());
+            window.cursor = current.cursor;
+            window.scroll = current.scroll;
+            window.alternate = current.alternate;
+            window.wrap = current.wrap.clone();
+            window
+        };
+        if let Some(buffer) = buffer {
+            window.show(buffer);
+        }
+        let window = Rc::new(RefCell::new(window));
+        {
+            let mut tabs = self.tabs.borrow_mut();
+            let page = tabs.current_mut();
+            page.windows.push(window.clone());
+            page.layout.split(self.current.get(), id, mode);
+        }
+        self.current.set(id);
+        window
+    }
+
+    /// Put windows into diff mode, or out of it with `diffoff`
+    pub(super) fn set_diff(
+        &mut self,
+        windows: &[Rc<RefCell<Window>>],
+        diff: bool,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        for window in windows {
+            window.borrow_mut().diff = diff;
+        }
+        self.rebuild_windows(commands)
+    }
+
+    /// Open `path` to the left of the window with focus and compare the two
+    pub(super) fn diff_split(
+        &mut self,
+        path: PathBuf,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        let buffer = self.open_path(Some(path))?;
+        let current = self.window();
+        let window = self.add_window(LayoutMode::HorizontalSplit, buffer);
+        self.set_diff(&[current, window], true, commands)
+    }
+
+    /// Close a window, focus moves to the window that took its place
+    /// Closing the last window of a tab closes the tab
+    pub(super) fn close_window(
+        &mut self,
+        id: WindowId,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        let order = self.layout().windows();
+        if order.len() < 2 {
+            if self.tabs.borrow().len() > 1 {
+                return self.close_tab(commands);
+            }
+            anyhow::bail!("E444: Cannot close last window");
+        }
+        let index = order.iter().position(|&window| window == id).unwrap_or(0);
+        if let Some(window) = self.get_window(id) {
+            // The buffer left behind remembers where this window's cursor was
+            let window = window.borrow();
+            let mut buffer = window.buffer.borrow_mut();
+            buffer.cursor = window.cursor;
+            buffer.scroll = window.scroll;
+        }
+        {
+            let mut tabs = self.tabs.borrow_mut();
+            let page = tabs.current_mut();
+            page.layout.remove(id);
+            page.windows.retain(|window| window.borrow().id() != id);
+        }
+        if self.current.get() == id {
+            let order = self.layout().windows();
+            self.current.set(order[index.min(order.len() - 1)]);
+        }
+        self.rebuild_windows(commands)
+    }
+
+    /// Close every window but the one with focus
+    pub(super) fn only_window(&mut self, commands: &mut ComponentCommands) -> Result<()> {
+        let current = self.current.get();
+        {
+            let mut tabs = self.tabs.borrow_mut();
+            let page = tabs.current_mut();
+            page.layout = Layout::Window(current);
+            page.windows
+                .retain(|window| window.borrow().id() == current);
+        }
+        self.rebuild_windows(commands)
+    }
+
+    fn resize_window(
+        &mut self,
+        mode: LayoutMode,
+        resize: Resize,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        let Some(tab_pages_id) = self.tab_pages_id else {
+            return Ok(());
+        };
+        let area = commands.rect_of(tab_pages_id);
+        let resized =
+            self.layout()
+                .resize(self.current.get(), mode, resize, area.width, area.height);
+        if resized {
+            self.rebuild_windows(commands)?;
+        }
+        Ok(())
+    }
+
+    /// Handle the key after `Ctrl-W`
+    pub(super) fn window_command(
+        &mut self,
+        key: KeyEvent,
+        commands: &mut ComponentCommands,
+    ) -> Result<()> {
+        let KeyCode::Char(character) = key.code else {
+            let direction = match key.code {
+                KeyCode::Left => Some((LayoutMode::HorizontalSplit, false)),
+                KeyCode::Right => Some((LayoutMode::HorizontalSplit, true)),
+                KeyCode::Up => Some((LayoutMode::VerticalSplit, false)),
+                KeyCode::Down => Some((LayoutMode::VerticalSplit, true)),
+                _ => None,
+            };
+            let neighbor = direction.and_then(|(mode, forward)| {
+                self.layout().neighbor(self.current.get(), mode, forward)
+            });
+            if let Some(neighbor) = neighbor {
+                self.focus_window(neighbor, commands);
+            }
+            return Ok(());
+        };
+        // Window commands also work with Ctrl held, like `Ctrl-W Ctrl-W`
+        match character {
+            's' | 'S' => self.split(LayoutMode::VerticalSplit, None, commands)?,
+            'v' => self.split(LayoutMode::HorizontalSplit, None, commands)?,
+            'n' => {
+                let buffer = self.buffers.borrow_mut().add(Buffer::default());
+                self.split(LayoutMode::VerticalSplit, Some(buffer), commands)?
+            }
+            'c' => self.close_window(self.current.get(), commands)?,
+            'q' => self.quit(false, commands)?,
+            'o' => self.only_window(commands)?,
+            'w' => {
+                let order = self.layout().windows();
+                let index = order.iter().position(|&id| id == self.current.get());
+                let next = index.map(|index| order[(index + 1) % order.len()]);
+                if let Some(next) = next {
+                    self.focus_window(next, commands);
+                }
+            }
+            'W' => {
+                let order = self.layout().windows();
+                let index = order.iter().position(|&id| id == self.current.get());
+                let prev = index.map(|index| order[(index + order.len() - 1) % order.len()]);
+                if let Some(prev) = prev {
+                    self.focus_window(prev, commands);
+                }
+            }
+            'h' | 'j' | 'k' | 'l' => {
+                let (mode, forward) = match character {
+                    'h' => (LayoutMode::HorizontalSplit, false),
+                    'l' => (LayoutMode::HorizontalSplit, true),
+                    'k' => (LayoutMode::VerticalSplit, false),
+                    _ => (LayoutMode::VerticalSplit, true),
+                };
+                let neighbor = self.layout().neighbor(self.current.get(), mode, forward);
+                if let Some(neighbor) = neighbor {
+                    self.focus_window(neighbor, commands);
+                }
+            }
+            '=' => {
+                self.layout().equalize();
+                self.rebuild_windows(commands)?;
+            }
+            '+' => self.resize_window(LayoutMode::VerticalSplit, Resize::By(1), commands)?,
+            '-' => self.resize_window(LayoutMode::VerticalSplit, Resize::By(-1), commands)?,
+            '>' => self.resize_window(LayoutMode::HorizontalSplit, Resize::By(1), commands)?,
+            '<' => self.resize_window(LayoutMode::HorizontalSplit, Resize::By(-1), commands)?,
+            '_' => self.resize_window(LayoutMode::VerticalSplit, Resize::Max, commands)?,
+            '|' => self.resize_window(LayoutMode::HorizontalSplit, Resize::Max, commands)?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
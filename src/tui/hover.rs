@@ -0,0 +1,113 @@
+use anyhow::Result;
+use crossterm::style::Color;
+use unicode_width::UnicodeWidthChar;
+
+use crate::tui::{
+    Component, Formatting, LayoutMode, Measurement, Overflow,
+    decoration::BorderStyle,
+    overlay::{Anchor, Overlay},
+    terminal_buffer::TerminalBuffer,
+};
+
+/// Columns hover text is wrapped at
+const MAX_WIDTH: usize = 80;
+/// Lines shown before the rest is cut off
+const MAX_LINES: usize = 20;
+
+/// Documentation a language server returned, or a git hunk, floating below the cursor until the next key
+pub struct HoverPopup {
+    lines: Vec<(String, Color)>,
+}
+
+impl HoverPopup {
+    pub fn new(text: &str) -> Self {
+        let mut lines = Vec::new();
+        for line in text.lines() {
+            // Markdown code fences only say what language the code is in
+            if line.trim_start().starts_with("```") {
+                continue;
+            }
+            wrap(line.trim_end(), Color::Reset, &mut lines);
+        }
+        while lines.last().is_some_and(|(line, _)| line.is_empty()) {
+            lines.pop();
+        }
+        Self::limited(lines)
+    }
+
+    /// The lines a hunk removes and the ones it adds, marked and coloured like a diff
+    pub fn hunk(removed: &[String], added: &[String]) -> Self {
+        let mut lines = Vec::new();
+        for line in removed {
+            wrap(&format!("-{line}"), Color::Red, &mut lines);
+        }
+        for line in added {
+            wrap(&format!("+{line}"), Color::Green, &mut lines);
+        }
+        Self::limited(lines)
+    }
+
+    fn limited(mut lines: Vec<(String, Color)>) -> Self {
+        if lines.len() > MAX_LINES {
+            lines.truncate(MAX_LINES);
+            lines.push(("...".to_string(), Color::Reset));
+        }
+        Self { lines }
+    }
+
+    /// Below the cursor, in the border `'winborder'` gives
+    pub fn overlay(border: Option<BorderStyle>) -> Overlay {
+        Overlay {
+            anchor: Anchor::Cursor { col: 0, row: 0 },
+            z_index: 1,
+            border,
+            padding: 0,
+            ..Default::default()
+        }
+    }
+}
+
+/// Split a line into pieces no wider than `MAX_WIDTH`
+fn wrap(line: &str, color: Color, lines: &mut Vec<(String, Color)>) {
+    let mut current = String::new();
+    let mut width = 0;
+    for character in line.chars() {
+        let character_width = character.width().unwrap_or(0);
+        if width + character_width > MAX_WIDTH {
+            lines.push((std::mem::take(&mut current), color));
+            width = 0;
+        }
+        current.push(character);
+        width += character_width;
+    }
+    lines.push((current, color));
+}
+
+impl Component for HoverPopup {
+    fn render(
+        &self,
+        buffer: &mut TerminalBuffer,
+        _query: crate::tui::ComponentQuery,
+    ) -> Result<()> {
+        for (index, (line, color)) in self.lines.iter().enumerate() {
+            if index > 0 {
+                buffer.newline();
+            }
+            buffer.set_foreground(*color).write(line);
+        }
+        Ok(())
+    }
+
+    fn default_formatting(&self) -> Formatting {
+        Formatting {
+            preferred_width: Measurement::Content,
+            preferred_height: Measurement::Content,
+            overflow_x: Overflow::Hide,
+            overflow_y: Overflow::Hide,
+            request_focus: false,
+            layout_mode: LayoutMode::VerticalSplit,
+            focusable: false,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! How a container shares its room between its children
+//!
+//! Children are laid out along the container's axis, left to right in a `HorizontalSplit` and top to bottom
+//! in a `VerticalSplit`, inside the container's rect less its padding. Each child is resolved in steps:
+//!
+//! 1. Along the axis every child claims a base size and, when it is flexible, a weight. `Cell`, `Percent`
+//!    and `Ratio` are their size of the room, `Content` is what the child measures, `Min(n)` claims `n` cells
+//!    with a weight of 1, `Max(n)` nothing with a weight of 1 and `Fill(w)` nothing with a weight of `w`.
+//!    A child's margins along the axis are claimed with it.
+//! 2. The room the claims leave is shared between the flexible children in proportion to their weights, the
+//!    first ones taking a cell each of what doesn't divide evenly. A `Max` child that would pass its bound
+//!    stops there and the rest is shared again between the others. When the claims are more than the room
+//!    nothing is shared and the children past its end are clipped.
+//! 3. Room still left, when no child was flexible, goes before, between or after the children as the
+//!    container's `MainAlign` says.
+//! 4. Across the axis a child takes its preferred size of the room less its margins, `Fill` and `Min` taking
+//!    all of it and `Max(n)` up to `n` cells. `CrossAlign` places it in that room, `Stretch` giving it all
+//!    of it whatever it prefers.
+//! 5. `preferred_x` and `preferred_y` then move the child from there by their size of the room.
+//!
+//! A child whose overflow along the axis is `Wrap` and that doesn't fit in what is left of the room starts a
+//! new row or column after the largest child of the one before, which is aligned on its own.
+
+use crate::tui::{CrossAlign, Edges, LayoutMode, MainAlign, Measurement};
+
+/// What a child asks for along the axis, with its margins there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Claim {
+    /// Cells the child gets before any room is shared
+    pub base: u16,
+    /// The child's part of the room left, none when it isn't flexible
+    pub weight: u16,
+    /// The most cells the child grows to
+    pub max: Option<u16>,
+    /// Margins before and after the child
+    pub margin: (u16, u16),
+}
+
+impl Claim {
+    /// The claim of a child sized by `measurement`, `content` being what it measures when it is sized by that
+    pub fn new(measurement: Measurement, room: u16, content: u16, margin: (u16, u16)) -> Claim {
+        let (base, weight, max) = match measurement {
+            Measurement::Content => (content.min(room), 0, None),
+            Measurement::Min(cells) => (clamp(cells, room), 1, None),
+            Measurement::Max(cells) => (0, 1, Some(clamp(cells, room))),
+            Measurement::Fill(weight) => (0, weight, None),
+            fixed => (size(fixed, room), 0, None),
+        };
+        Claim {
+            base,
+            weight,
+            max,
+            margin,
+        }
+    }
+
+    fn outer(&self, size: u16) -> u16 {
+        size.saturating_add(self.margin.0)
+            .saturating_add(self.margin.1)
+    }
+}
+
+/// The size of a measurement in `room` cells, the whole room for ones sized by what is left or measured
+pub fn size(measurement: Measurement, room: u16) -> u16 {
+    match measurement {
+        Measurement::Cell(cells) => clamp(cells, room),
+        Measurement::Percent(percent) => fraction(room, percent.min(100) as u16, 100),
+        Measurement::Ratio(numerator, denominator) => fraction(room, numerator, denominator),
+        Measurement::Max(cells) => clamp(cells, room),
+        Measurement::Content | Measurement::Min(_) | Measurement::Fill(_) => room,
+    }
+}
+
+/// How far `preferred_x` or `preferred_y` move a child in `room` cells, only fixed measurements do
+pub fn offset(measurement: Measurement, room: u16) -> u16 {
+    match measurement {
+        Measurement::Cell(_) | Measurement::Percent(_) | Measurement::Ratio(_, _) => {
+            size(measurement, room)
+        }
+        _ => 0,
+    }
+}
+
+fn clamp(cells: usize, room: u16) -> u16 {
+    cells.min(room as usize) as u16
+}
+
+fn fraction(room: u16, numerator: u16, denominator: u16) -> u16 {
+    if denominator == 0 {
+        return 0;
+    }
+    (room as u32 * numerator as u32 / denominator as u32).min(room as u32) as u16
+}
+
+/// The sizes of the children along the axis once the room their claims leave is shared, steps 1 and 2
+pub fn share(claims: &[Claim], room: u16) -> Vec<u16> {
+    let mut sizes: Vec<u16> = claims.iter().map(|claim| claim.base).collect();
+    let claimed: u32 = claims
+        .iter()
+        .map(|claim| claim.outer(claim.base) as u32)
+        .sum();
+    let mut free = (room as u32).saturating_sub(claimed);
+    let mut capped = vec![false; claims.len()];
+    while free > 0 {
+        let growing: Vec<usize> = (0..claims.len())
+            .filter(|&index| claims[index].weight > 0 && !capped[index])
+            .collect();
+        let weights: u32 = growing
+            .iter()
+            .map(|&index| claims[index].weight as u32)
+            .sum();
+        if weights == 0 {
+            break;
+        }
+        let mut shares: Vec<u32> = growing
+            .iter()
+            .map(|&index| free * claims[index].weight as u32 / weights)
+            .collect();
+        let mut extra = free - shares.iter().sum::<u32>();
+        for share in &mut shares {
+            if extra == 0 {
+                break;
+            }
+            *share += 1;
+            extra -= 1;
+        }
+        // Children reaching their bound take what they can, the others share what is left again
+        let mut reached = false;
+        for (&index, &share) in growing.iter().zip(&shares) {
+            if let Some(max) = claims[index].max
+                && sizes[index] as u32 + share >= max as u32
+            {
+                free -= (max - sizes[index]) as u32;
+                sizes[index] = max;
+                capped[index] = true;
+                reached = true;
+            }
+        }
+        if reached {
+            continue;
+        }
+        for (&index, &share) in growing.iter().zip(&shares) {
+            sizes[index] += share as u16;
+        }
+        break;
+    }
+    sizes
+}
+
+/// Where each child starts along the axis, after its margin, with the room left placed by `align`, step 3
+pub fn place(claims: &[Claim], sizes: &[u16], room: u16, align: MainAlign) -> Vec<u16> {
+    let used: u32 = claims
+        .iter()
+        .zip(sizes)
+        .map(|(claim, &size)| claim.outer(size) as u32)
+        .sum();
+    let free = (room as u32).saturating_sub(used);
+    let count = claims.len() as u32;
+    let (lead, gap, mut extra) = match align {
+        MainAlign::Start => (0, 0, 0),
+        MainAlign::Center => (free / 2, 0, 0),
+        MainAlign::End => (free, 0, 0),
+        MainAlign::SpaceBetween if count > 1 => (0, free / (count - 1), free % (count - 1)),
+        MainAlign::SpaceBetween => (0, 0, 0),
+        MainAlign::SpaceAround => (free / count.max(1) / 2, free / count.max(1), 0),
+    };
+    let mut at = lead;
+    claims
+        .iter()
+        .zip(sizes)
+        .map(|(claim, &size)| {
+            let start = at + claim.margin.0 as u32;
+            at = start + size as u32 + claim.margin.1 as u32 + gap;
+            if extra > 0 {
+                at += 1;
+                extra -= 1;
+            }
+            start.min(u16::MAX as u32) as u16
+        })
+        .collect()
+}
+
+/// Where a child starts across the axis and its size there, step 4
+pub fn align_across(
+    measurement: Measurement,
+    content: u16,
+    margin: (u16, u16),
+    room: u16,
+    align: CrossAlign,
+) -> (u16, u16) {
+    let room = room.saturating_sub(margin.0).saturating_sub(margin.1);
+    let size = match (align, measurement) {
+        (CrossAlign::Stretch, _) => room,
+        (_, Measurement::Content) => content.min(room),
+        (_, measurement) => size(measurement, room),
+    };
+    let start = match align {
+        CrossAlign::Start | CrossAlign::Stretch => 0,
+        CrossAlign::Center => (room - size) / 2,
+        CrossAlign::End => room - size,
+    };
+    (margin.0 + start, size)
+}
+
+/// A width and height as the length along the layout's axis and across it
+pub fn along(mode: LayoutMode, width: u16, height: u16) -> (u16, u16) {
+    match mode {
+        LayoutMode::HorizontalSplit => (width, height),
+        LayoutMode::VerticalSplit => (height, width),
+    }
+}
+
+/// Lengths along and across the layout's axis as a width and height
+pub fn back(mode: LayoutMode, main: u16, cross: u16) -> (u16, u16) {
+    along(mode, main, cross)
+}
+
+/// Edges before and after along the layout's axis, then before and after across it
+pub fn edges(mode: LayoutMode, edges: Edges) -> ((u16, u16), (u16, u16)) {
+    match mode {
+        LayoutMode::HorizontalSplit => ((edges.left, edges.right), (edges.top, edges.bottom)),
+        LayoutMode::VerticalSplit => ((edges.top, edges.bottom), (edges.left, edges.right)),
+    }
+}
+
+/// Measurements that grow into the room the others leave
+pub fn flexible(measurement: Measurement) -> bool {
+    matches!(
+        measurement,
+        Measurement::Min(_) | Measurement::Max(_) | Measurement::Fill(_)
+    )
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+
+use anyhow::{Result, bail};
+
+use crate::buffer::grapheme;
+
+/// What `list` mode draws for blanks and line ends, set with `:set listchars`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListChars {
+    /// First and fill char of a tab and its last if given, without them tabs are drawn as `^I`
+    pub tab: Option<(char, char, Option<char>)>,
+    /// Spaces at the end of a line
+    pub trail: Option<char>,
+    /// No-break spaces
+    pub nbsp: Option<char>,
+    /// After the last char of a line
+    pub eol: Option<char>,
+}
+
+impl Default for ListChars {
+    fn default() -> Self {
+        Self {
+            tab: Some(('>', ' ', None)),
+            trail: Some('-'),
+            nbsp: Some('+'),
+            eol: None,
+        }
+    }
+}
+
+impl ListChars {
+    /// Parse a value like `tab:> ,trail:-,eol:$`, settings left out are not drawn
+    pub fn parse(value: &str) -> Result<ListChars> {
+        let mut list = ListChars {
+            tab: None,
+            trail: None,
+            nbsp: None,
+            eol: None,
+        };
+        for setting in value.split(',').filter(|setting| !setting.is_empty()) {
+            let Some((name, chars)) = setting.split_once(':') else {
+                bail!("E474: Invalid argument: listchars={value}");
+            };
+            let chars: Vec<char> = chars.chars().collect();
+            // Each char takes one column, like the blank it stands for
+            if chars
+                .iter()
+                .any(|&c| grapheme::width(c.encode_utf8(&mut [0; 4])) != 1 || c.is_control())
+            {
+                bail!("E474: Invalid argument: listchars={value}");
+            }
+            match (name, chars.as_slice()) {
+                ("tab", &[first, fill]) => list.tab = Some((first, fill, None)),
+                ("tab", &[first, fill, last]) => list.tab = Some((first, fill, Some(last))),
+                ("trail", &[c]) => list.trail = Some(c),
+                ("nbsp", &[c]) => list.nbsp = Some(c),
+                ("eol", &[c]) => list.eol = Some(c),
+                _ => bail!("E474: Invalid argument: listchars={value}"),
+            }
+        }
+        Ok(list)
+    }
+
+    /// What a tab `width` columns wide is drawn as
+    pub fn tab(&self, width: usize) -> String {
+        let Some((first, fill, last)) = self.tab else {
+            return "^I".to_string();
+        };
+        let mut drawn = String::new();
+        match last {
+            Some(last) => {
+                if width > 1 {
+                    drawn.push(first);
+                }
+                drawn.extend(std::iter::repeat_n(fill, width.saturating_sub(2)));
+                drawn.push(last);
+            }
+            None => {
+                drawn.push(first);
+                drawn.extend(std::iter::repeat_n(fill, width.saturating_sub(1)));
+            }
+        }
+        drawn
+    }
+}
+
+impl fmt::Display for ListChars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut settings = Vec::new();
+        if let Some((first, fill, last)) = self.tab {
+            let last = last.map(String::from).unwrap_or_default();
+            settings.push(format!("tab:{first}{fill}{last}"));
+        }
+        for (name, c) in [
+            ("trail", self.trail),
+            ("nbsp", self.nbsp),
+            ("eol", self.eol),
+        ] {
+            if let Some(c) = c {
+                settings.push(format!("{name}:{c}"));
+            }
+        }
+        write!(f, "{}", settings.join(","))
+    }
+}
@@ -1,15 +1,29 @@
 use anyhow::Result;
 use crossterm::cursor::SetCursorStyle;
 
-use crate::event::ReovimEvent;
+use crate::{event::ReovimEvent, tui::decoration::Decoration};
 
+#[cfg(test)]
+mod benches;
 pub mod command;
+pub mod completion;
 pub mod debug;
+pub mod decoration;
+pub mod diff;
 pub mod editor;
+pub mod hover;
+pub mod layout;
+pub mod listchars;
+pub mod overlay;
 pub mod status;
+pub mod tab;
 pub mod terminal_buffer;
+#[cfg(test)]
+mod tests;
 pub mod text;
 pub mod tree;
+pub mod window;
+pub mod wrap;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LayoutMode {
@@ -88,6 +102,14 @@ impl Rect {
     pub fn contains(&self, col: u16, row: u16) -> bool {
         col >= self.x && col < self.x + self.width && row >= self.y && row < self.y + self.height
     }
+
+    /// Check if every cell of `other` is within this rect, an empty one is within any rect
+    pub fn contains_rect(&self, other: Rect) -> bool {
+        other.width == 0
+            || other.height == 0
+            || (self.contains(other.x, other.y)
+                && self.contains(other.x + other.width - 1, other.y + other.height - 1))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -100,15 +122,83 @@ pub enum Overflow {
     Scroll,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Measurement {
     /// Exact number of cells
     Cell(usize),
     /// Percentage of available space
     Percent(u8),
+    /// Fraction of available space, `Ratio(1, 3)` is a third
+    Ratio(u16, u16),
     /// Size based on rendered content
     Content,
-    Fill,
+    /// At least this many cells, growing into the space left like `Fill(1)`
+    Min(usize),
+    /// A share of the space left like `Fill(1)`, up to this many cells
+    Max(usize),
+    /// A share of the space the other children leave, in proportion to the weight
+    Fill(u16),
+}
+
+/// Space on each side of a component, in cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Edges {
+    pub top: u16,
+    pub right: u16,
+    pub bottom: u16,
+    pub left: u16,
+}
+
+impl Edges {
+    pub const fn all(cells: u16) -> Self {
+        Self {
+            top: cells,
+            right: cells,
+            bottom: cells,
+            left: cells,
+        }
+    }
+
+    pub const fn symmetric(vertical: u16, horizontal: u16) -> Self {
+        Self {
+            top: vertical,
+            right: horizontal,
+            bottom: vertical,
+            left: horizontal,
+        }
+    }
+
+    pub fn horizontal(&self) -> u16 {
+        self.left.saturating_add(self.right)
+    }
+
+    pub fn vertical(&self) -> u16 {
+        self.top.saturating_add(self.bottom)
+    }
+}
+
+/// Where children go along a container's axis when they leave space
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MainAlign {
+    #[default]
+    Start,
+    Center,
+    End,
+    /// The space goes between the children, none before the first or after the last
+    SpaceBetween,
+    /// Each child gets the same space on both sides
+    SpaceAround,
+}
+
+/// Where children go across a container's axis, in the row or column they are in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrossAlign {
+    #[default]
+    Start,
+    Center,
+    End,
+    /// Children take the whole row or column whatever their preferred size
+    Stretch,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -119,6 +209,7 @@ pub struct Size {
 
 #[derive(Debug, Clone, Copy)]
 pub struct Formatting {
+    /// Offset from where the parent's layout puts the component, or from an overlay's anchor
     pub preferred_x: Measurement,
     pub preferred_y: Measurement,
     pub preferred_width: Measurement,
@@ -128,6 +219,29 @@ pub struct Formatting {
     pub request_focus: bool,
     pub layout_mode: LayoutMode,
     pub focusable: bool,
+    /// Space between the component's edges and its children
+    pub padding: Edges,
+    /// Space kept around the component in its parent's layout, outside its rect
+    pub margin: Edges,
+    /// Where the children go along the layout's axis
+    pub main_align: MainAlign,
+    /// Where the children go across the layout's axis
+    pub cross_align: CrossAlign,
+    /// Border, title and scrollbar drawn inside the component's rect
+    pub decoration: Decoration,
+}
+
+impl Formatting {
+    /// Cells between the component's edges and its children, the decoration's and the padding
+    pub fn inset(&self) -> Edges {
+        let decoration = self.decoration.edges();
+        Edges {
+            top: self.padding.top.saturating_add(decoration.top),
+            right: self.padding.right.saturating_add(decoration.right),
+            bottom: self.padding.bottom.saturating_add(decoration.bottom),
+            left: self.padding.left.saturating_add(decoration.left),
+        }
+    }
 }
 
 impl Default for Formatting {
@@ -142,6 +256,11 @@ impl Default for Formatting {
             request_focus: false,
             layout_mode: LayoutMode::VerticalSplit,
             focusable: true,
+            padding: Edges::default(),
+            margin: Edges::default(),
+            main_align: MainAlign::Start,
+            cross_align: CrossAlign::Start,
+            decoration: Decoration::default(),
         }
     }
 }
@@ -164,6 +283,7 @@ pub trait Component {
     ///
     /// # Returns
     /// A bool indicating whether the component changed and needs to be re-rendered
+    /// Its layout and that of its descendants is measured again too, the tree keeps it otherwise
     ///
     /// # Example
     /// ```ignore
@@ -187,6 +307,21 @@ pub trait Component {
         Formatting::default()
     }
 
+    /// Shown in the top border when the formatting gives the component one
+    fn title(&self) -> Option<String> {
+        None
+    }
+
+    /// Shown in the bottom border when the formatting gives the component one
+    fn footer(&self) -> Option<String> {
+        None
+    }
+
+    /// Where the cursor stored at `cursor` is drawn, for components that break their own text into rows
+    fn screen_cursor(&self, cursor: Cursor, _width: u16) -> Cursor {
+        cursor
+    }
+
     /// Initialize child components for this component
     /// Called after the component is added to the tree, allows the component to add children
     /// through the provided ComponentCommands
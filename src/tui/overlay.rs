@@ -0,0 +1,147 @@
+use std::io::Write;
+
+use anyhow::Result;
+use crossterm::{
+    ExecutableCommand,
+    cursor::MoveTo,
+    style::{Color, Print, ResetColor, SetBackgroundColor},
+};
+
+use crate::tui::{
+    Rect,
+    decoration::{BorderStyle, Decoration},
+    tree::ComponentId,
+};
+
+/// What a floating component is positioned against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Anchor {
+    /// The whole screen, `preferred_x` and `preferred_y` are offsets from its top left
+    #[default]
+    Editor,
+    /// Another component, like a window or an overlay, `preferred_x` and `preferred_y` are offsets from its top left
+    /// The overlay is hidden while the component isn't on screen
+    Component(ComponentId),
+    /// The terminal cursor, the overlay opens on the line below it or above when there isn't room
+    /// The offsets move it from the cursor's column and line and can be negative
+    Cursor { col: i16, row: i16 },
+}
+
+/// How a floating component sits above the tiled layout
+/// Its size comes from the component's `preferred_width` and `preferred_height`, not counting the border and padding
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Overlay {
+    pub anchor: Anchor,
+    /// Higher overlays are drawn above lower ones, equal ones in the order they were added
+    pub z_index: i32,
+    pub border: Option<BorderStyle>,
+    /// Shown in the top border
+    pub title: Option<String>,
+    /// Blank cells between the border and the content
+    pub padding: u16,
+    /// Darken a strip right of and below the overlay
+    pub shadow: bool,
+}
+
+impl Overlay {
+    /// Cells the border and padding take on each side of the content
+    pub fn inset(&self) -> u16 {
+        self.padding + u16::from(self.border.is_some())
+    }
+
+    /// Outer size of the overlay around content of the given size
+    pub fn outer_size(&self, width: u16, height: u16) -> (u16, u16) {
+        let inset = self.inset() * 2;
+        (width.saturating_add(inset), height.saturating_add(inset))
+    }
+
+    /// Where the overlay goes, given where its anchor puts the top left corner
+    /// `cursor_line` is the screen row a cursor anchored overlay must not cover
+    pub fn place(
+        &self,
+        (width, height): (u16, u16),
+        (x, y): (i32, i32),
+        cursor_line: Option<u16>,
+        screen: Rect,
+    ) -> Rect {
+        let width = width.min(screen.width);
+        let height = height.min(screen.height);
+        let mut y = y;
+        if let Some(line) = cursor_line {
+            // Open above the cursor when the overlay doesn't fit below it but does above
+            let below = y + height as i32 > screen.height as i32;
+            if below && line as i32 >= height as i32 {
+                y = line as i32 - height as i32;
+            }
+        }
+        // Shift the overlay back onto the screen rather than cut it off
+        let x = x.clamp(0, (screen.width - width) as i32) as u16;
+        let y = y.clamp(0, (screen.height - height) as i32) as u16;
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Where the component draws inside the border and padding
+    pub fn content_rect(&self, outer: Rect) -> Rect {
+        let inset = self.inset();
+        Rect {
+            x: outer.x + inset,
+            y: outer.y + inset,
+            width: outer.width.saturating_sub(inset * 2),
+            height: outer.height.saturating_sub(inset * 2),
+        }
+    }
+
+    /// Every cell the overlay draws on, the shadow included
+    pub fn covered(&self, outer: Rect, screen: Rect) -> Rect {
+        if !self.shadow {
+            return outer;
+        }
+        Rect {
+            width: (outer.width + 1).min(screen.width - outer.x),
+            height: (outer.height + 1).min(screen.height - outer.y),
+            ..outer
+        }
+    }
+
+    /// Blank the overlay's area and draw the border, title and shadow around where the content goes
+    pub fn draw_frame<W: Write + ?Sized>(
+        &self,
+        stdout: &mut W,
+        outer: Rect,
+        screen: Rect,
+    ) -> Result<()> {
+        stdout.execute(ResetColor)?;
+        for y in outer.y..outer.y + outer.height {
+            stdout.execute(MoveTo(outer.x, y))?;
+            stdout.execute(Print(" ".repeat(outer.width as usize)))?;
+        }
+
+        let decoration = Decoration {
+            border: self.border,
+            ..Decoration::default()
+        };
+        decoration.draw(stdout, outer, self.title.as_deref(), None, None)?;
+
+        if self.shadow {
+            let covered = self.covered(outer, screen);
+            stdout.execute(SetBackgroundColor(Color::Black))?;
+            if covered.width > outer.width {
+                for y in outer.y + 1..covered.y + covered.height {
+                    stdout.execute(MoveTo(outer.x + outer.width, y))?;
+                    stdout.execute(Print(' '))?;
+                }
+            }
+            if covered.height > outer.height {
+                stdout.execute(MoveTo(outer.x + 1, outer.y + outer.height))?;
+                stdout.execute(Print(" ".repeat(covered.width.saturating_sub(1) as usize)))?;
+            }
+            stdout.execute(ResetColor)?;
+        }
+        Ok(())
+    }
+}
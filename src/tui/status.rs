@@ -1,18 +1,96 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use crate::buffer::{
+    encoding::{FileEncoding, FileFormat},
+    list::BufferList,
+};
 use crate::tui::{
-    Component, Formatting, LayoutMode, Measurement, Overflow, terminal_buffer::TerminalBuffer,
+    Component, Formatting, LayoutMode, Measurement, Overflow,
+    terminal_buffer::TerminalBuffer,
+    window::{Window, WindowId},
 };
 
 use anyhow::Result;
 use crossterm::style::Color;
 
 pub struct StatusComponent {
-    /// The file name for the bottom left.
-    file_name: String,
+    /// The window whose buffer and cursor are shown
+    window: Rc<RefCell<Window>>,
+    /// Every loaded buffer, for the position of this one in the list
+    buffers: Rc<RefCell<BufferList>>,
+    /// The window with focus, its status line stands out from the others
+    current: Rc<Cell<WindowId>>,
 }
 
 impl StatusComponent {
-    pub fn new(file_name: String) -> Self {
-        StatusComponent { file_name }
+    pub fn new(
+        window: Rc<RefCell<Window>>,
+        buffers: Rc<RefCell<BufferList>>,
+        current: Rc<Cell<WindowId>>,
+    ) -> Self {
+        StatusComponent {
+            window,
+            buffers,
+            current,
+        }
+    }
+
+    fn label(&self) -> String {
+        let window = self.window.borrow();
+        let buffer = window.buffer.borrow();
+        let mut label = buffer.file_name().to_string();
+        if buffer.is_modified() {
+            label.push_str(" [+]");
+        }
+        if buffer.is_new() {
+            label.push_str(" [New]");
+        }
+        if buffer.options.read_only {
+            label.push_str(" [RO]");
+        }
+        // Highlighting, git, the language server and completion are off for it
+        if buffer.is_large() {
+            label.push_str(" [large]");
+        }
+        // How the file is laid out on disk, when it isn't plain UTF-8 with unix line breaks
+        let file = buffer.options.file;
+        if buffer.is_binary() {
+            label.push_str(" [binary]");
+        } else {
+            if file.fileformat != FileFormat::Unix {
+                label.push_str(&format!(" [{}]", file.fileformat.name()));
+            }
+            if file.fileencoding != FileEncoding::Utf8 {
+                label.push_str(&format!(" [{}]", file.fileencoding.name()));
+            }
+            if file.bomb {
+                label.push_str(" [BOM]");
+            }
+            if !file.endofline {
+                label.push_str(" [noeol]");
+            }
+        }
+        let buffers = self.buffers.borrow();
+        if let Some(index) = buffers.index_of(buffer.id())
+            && buffers.len() > 1
+        {
+            label.push_str(&format!(" [{index}/{}]", buffers.len()));
+        }
+        label
+    }
+
+    /// The cursor position, after the git branch when the file is in a repository
+    fn position(&self) -> String {
+        let window = self.window.borrow();
+        let (line, col) = window.cursor;
+        let buffer = window.buffer.borrow();
+        match buffer.vcs().and_then(|vcs| vcs.branch.as_deref()) {
+            Some(branch) => format!("{branch}  {},{} ", line + 1, col + 1),
+            None => format!("{},{} ", line + 1, col + 1),
+        }
     }
 }
 
@@ -25,11 +103,22 @@ fn pad_or_truncate(s: &str, width: u16) -> String {
 }
 
 impl Component for StatusComponent {
-    fn render(&self, buffer: &mut TerminalBuffer, _query: crate::tui::ComponentQuery) -> Result<()> {
-        let status_line_str = pad_or_truncate(&self.file_name, buffer.width());
+    fn render(
+        &self,
+        buffer: &mut TerminalBuffer,
+        _query: crate::tui::ComponentQuery,
+    ) -> Result<()> {
+        let position = self.position();
+        let label_width = buffer.width().saturating_sub(position.len() as u16);
+        let status_line_str = pad_or_truncate(&self.label(), label_width) + &position;
+        let foreground = if self.window.borrow().id() == self.current.get() {
+            Color::Yellow
+        } else {
+            Color::DarkGrey
+        };
         buffer
             .set_background(Color::Black)
-            .set_foreground(Color::Yellow)
+            .set_foreground(foreground)
             .write(&status_line_str);
         Ok(())
     }
@@ -38,13 +127,14 @@ impl Component for StatusComponent {
         Formatting {
             preferred_x: Measurement::Cell(0),
             preferred_y: Measurement::Cell(0),
-            preferred_width: Measurement::Fill,
+            preferred_width: Measurement::Fill(1),
             preferred_height: Measurement::Cell(1),
             overflow_x: Overflow::Hide,
             overflow_y: Overflow::Hide,
             request_focus: false,
             layout_mode: LayoutMode::VerticalSplit,
             focusable: false,
+            ..Default::default()
         }
     }
 }
@@ -0,0 +1,223 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use anyhow::Result;
+use crossterm::style::Color;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::tui::{
+    Component, Formatting, LayoutMode, Measurement, Overflow,
+    terminal_buffer::TerminalBuffer,
+    tree::ComponentId,
+    window::{Layout, Window, WindowId},
+};
+
+/// A tab page holds its own windows and how they are arranged
+pub struct TabPage {
+    /// How the windows are arranged
+    pub layout: Layout,
+    /// Every window in this tab, in the order they were created
+    pub windows: Vec<Rc<RefCell<Window>>>,
+    /// The window with focus while this tab is shown, or the one that had it when the tab was left
+    pub current: WindowId,
+    /// The component holding the windows, rebuilt whenever the layout changes
+    pub split_id: Option<ComponentId>,
+}
+
+impl TabPage {
+    pub fn new(window: Window) -> Self {
+        let id = window.id();
+        Self {
+            layout: Layout::Window(id),
+            windows: vec![Rc::new(RefCell::new(window))],
+            current: id,
+            split_id: None,
+        }
+    }
+
+    pub fn get_window(&self, id: WindowId) -> Option<Rc<RefCell<Window>>> {
+        self.windows
+            .iter()
+            .find(|window| window.borrow().id() == id)
+            .cloned()
+    }
+
+    /// Tab line text: the number, the buffer in the current window and `+` when any buffer in the tab is modified
+    /// `focused` is the window with focus, it's only found in the tab being shown
+    fn label(&self, number: usize, focused: WindowId) -> String {
+        let name = self
+            .get_window(focused)
+            .or_else(|| self.get_window(self.current))
+            .map(|window| window.borrow().buffer.borrow().file_name().to_string())
+            .unwrap_or_default();
+        let modified = self
+            .windows
+            .iter()
+            .any(|window| window.borrow().buffer.borrow().is_modified());
+        if modified {
+            format!(" {number} {name} + ")
+        } else {
+            format!(" {number} {name} ")
+        }
+    }
+}
+
+/// Every tab page in the order shown on the tab line
+pub struct TabList {
+    pages: Vec<TabPage>,
+    current: usize,
+}
+
+impl TabList {
+    pub fn new(page: TabPage) -> Self {
+        Self {
+            pages: vec![page],
+            current: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Index of the tab being shown
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn current(&self) -> &TabPage {
+        &self.pages[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut TabPage {
+        &mut self.pages[self.current]
+    }
+
+    pub fn get(&self, index: usize) -> Option<&TabPage> {
+        self.pages.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut TabPage> {
+        self.pages.get_mut(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TabPage> {
+        self.pages.iter()
+    }
+
+    /// Show another tab, the index is clamped to the last tab
+    pub fn select(&mut self, index: usize) {
+        self.current = index.min(self.pages.len() - 1);
+    }
+
+    /// Add a tab after the current one and show it
+    pub fn insert(&mut self, page: TabPage) {
+        self.current += 1;
+        self.pages.insert(self.current, page);
+    }
+
+    /// Remove a tab, the next tab is shown in its place or the previous one when it was the last
+    pub fn remove(&mut self, index: usize) -> TabPage {
+        let page = self.pages.remove(index);
+        if self.current > index || self.current == self.pages.len() {
+            self.current = self.current.saturating_sub(1);
+        }
+        page
+    }
+
+    /// Move the current tab to another position, clamped to the last one
+    pub fn move_current(&mut self, index: usize) {
+        let index = index.min(self.pages.len() - 1);
+        let page = self.pages.remove(self.current);
+        self.pages.insert(index, page);
+        self.current = index;
+    }
+
+    fn labels(&self, focused: WindowId) -> Vec<String> {
+        self.pages
+            .iter()
+            .enumerate()
+            .map(|(index, page)| page.label(index + 1, focused))
+            .collect()
+    }
+
+    /// The tab whose label is at a column of the tab line
+    pub fn tab_at(&self, col: u16, focused: WindowId) -> Option<usize> {
+        let mut end = 0;
+        for (index, label) in self.labels(focused).iter().enumerate() {
+            end += label.width();
+            if (col as usize) < end {
+                return Some(index);
+            }
+        }
+        None
+    }
+}
+
+/// The line at the top listing every tab, only shown when there is more than one
+pub struct TabLineComponent {
+    tabs: Rc<RefCell<TabList>>,
+    /// The window with focus, the current tab is labelled with its buffer
+    current: Rc<Cell<WindowId>>,
+}
+
+impl TabLineComponent {
+    pub fn new(tabs: Rc<RefCell<TabList>>, current: Rc<Cell<WindowId>>) -> Self {
+        Self { tabs, current }
+    }
+}
+
+impl Component for TabLineComponent {
+    fn render(
+        &self,
+        buffer: &mut TerminalBuffer,
+        _query: crate::tui::ComponentQuery,
+    ) -> Result<()> {
+        let tabs = self.tabs.borrow();
+        if tabs.len() < 2 {
+            return Ok(());
+        }
+        let width = buffer.width() as usize;
+        let mut used = 0;
+        for (index, label) in tabs.labels(self.current.get()).iter().enumerate() {
+            if index == tabs.current_index() {
+                buffer
+                    .set_background(Color::Black)
+                    .set_foreground(Color::Yellow);
+            } else {
+                buffer
+                    .set_background(Color::DarkGrey)
+                    .set_foreground(Color::White);
+            }
+            let mut text = String::new();
+            for character in label.chars() {
+                let character_width = character.width().unwrap_or(0);
+                if used + character_width > width {
+                    break;
+                }
+                text.push(character);
+                used += character_width;
+            }
+            buffer.write(&text);
+        }
+        buffer
+            .set_background(Color::DarkGrey)
+            .write(&" ".repeat(width.saturating_sub(used)));
+        Ok(())
+    }
+
+    fn default_formatting(&self) -> Formatting {
+        Formatting {
+            preferred_width: Measurement::Fill(1),
+            preferred_height: Measurement::Content,
+            overflow_x: Overflow::Hide,
+            overflow_y: Overflow::Hide,
+            request_focus: false,
+            layout_mode: LayoutMode::VerticalSplit,
+            focusable: false,
+            ..Default::default()
+        }
+    }
+}
@@ -1,11 +1,21 @@
 use crossterm::style::Color;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::buffer::grapheme::cluster_width;
+
+/// Columns between the tab stops of text written with tabs in it
+const TAB_WIDTH: usize = 8;
 
 #[derive(Clone, Debug)]
 pub enum TerminalCommand {
-    Print(char),
+    /// One grapheme cluster, taking `cluster_width` cells
+    Print(String),
     Newline,
     SetForeground(Color),
     SetBackground(Color),
+    /// Underline in the color until turned off with `None`
+    SetUnderline(Option<Color>),
     Clear,
 }
 
@@ -21,6 +31,8 @@ pub struct TerminalBuffer {
     scroll_y: usize,
     cursor_col: u16,
     cursor_row: u16,
+    /// Columns written since the last newline, where the next tab stop is counted from
+    column: usize,
 }
 
 impl TerminalBuffer {
@@ -35,6 +47,7 @@ impl TerminalBuffer {
             scroll_y: 0,
             cursor_col: 0,
             cursor_row: 0,
+            column: 0,
         }
     }
 
@@ -44,13 +57,35 @@ impl TerminalBuffer {
     }
 
     pub fn write(&mut self, text: &str) -> &mut Self {
-        for ch in text.chars() {
-            self.buffer.push(TerminalCommand::Print(ch));
+        for cluster in text.graphemes(true) {
+            // Tabs become blanks up to the next tab stop, the terminal would move its cursor by an unknown distance
+            if cluster == "\t" {
+                let blanks = TAB_WIDTH - self.column % TAB_WIDTH;
+                for _ in 0..blanks {
+                    self.buffer.push(TerminalCommand::Print(" ".to_string()));
+                }
+                self.column += blanks;
+                continue;
+            }
+            // Other control chars would move it too
+            let cluster: String = cluster
+                .chars()
+                .map(|c| if c.is_control() { '\u{fffd}' } else { c })
+                .collect();
+            // Marks with nothing to combine with go on a blank, like vim draws them
+            let cluster = if cluster.width() == 0 {
+                format!(" {cluster}")
+            } else {
+                cluster
+            };
+            self.column += cluster_width(&cluster);
+            self.buffer.push(TerminalCommand::Print(cluster));
         }
         self
     }
 
     pub fn newline(&mut self) -> &mut Self {
+        self.column = 0;
         self.buffer.push(TerminalCommand::Newline);
         self
     }
@@ -65,7 +100,13 @@ impl TerminalBuffer {
         self
     }
 
+    pub fn set_underline(&mut self, color: Option<Color>) -> &mut Self {
+        self.buffer.push(TerminalCommand::SetUnderline(color));
+        self
+    }
+
     pub fn clear(&mut self) -> &mut Self {
+        self.column = 0;
         self.buffer.push(TerminalCommand::Clear);
         self
     }
@@ -134,13 +175,14 @@ impl TerminalBuffer {
 
         for cmd in &self.buffer {
             match cmd {
-                TerminalCommand::Print(_) => {
-                    current_line_width += 1;
+                TerminalCommand::Print(cluster) => {
+                    let cluster_width = cluster_width(cluster) as u16;
+                    current_line_width += cluster_width;
 
                     // Account for wrapping at buffer width
                     if current_line_width > self.width {
                         // Wrapped to next line
-                        current_line_width = 1;
+                        current_line_width = cluster_width;
                         height += 1;
                     }
 
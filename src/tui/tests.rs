@@ -0,0 +1,972 @@
+use std::{cell::RefCell, fs, path::PathBuf, rc::Rc, time::Duration};
+
+use anyhow::Result;
+use crossbeam::channel::Receiver;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use proptest::prelude::*;
+
+use super::{
+    Component, ComponentQuery, CrossAlign, Edges, Formatting, LayoutMode, MainAlign, Measurement,
+    Overflow, Rect,
+    decoration::{self, BorderStyle, Decoration, ScrollPosition, TitleAlign},
+    layout::{self, Claim},
+    tab::TabList,
+    terminal_buffer::TerminalBuffer,
+    tree::{ComponentNode, ComponentTree, Frame},
+    window::WindowId,
+};
+use crate::{
+    buffer::{Buffer, list::BufferList},
+    completion::{CompletionResponse, CompletionSources},
+    event::{HostRequest, ReovimEvent},
+    lsp::{Lsp, LspConfig},
+    snippet::library::SnippetLibrary,
+    task::Tasks,
+    terminal,
+    tui::editor::Editor,
+    vcs::VcsInfo,
+};
+
+fn claim(measurement: Measurement, room: u16) -> Claim {
+    Claim::new(measurement, room, 0, (0, 0))
+}
+
+/// A frame `width` by `height` laid out as `container` says, with a child frame for each formatting
+fn lay_out(container: Formatting, children: &[Formatting], width: u16, height: u16) -> Vec<Rect> {
+    let mut tree = ComponentTree::new(ComponentNode::Frame(Frame::new(LayoutMode::VerticalSplit)));
+    let container = Formatting {
+        preferred_width: Measurement::Cell(width as usize),
+        preferred_height: Measurement::Cell(height as usize),
+        ..container
+    };
+    let frame = ComponentNode::Frame(Frame::new(container.layout_mode));
+    let parent = tree.add_child_with_formatting(0, frame, container).unwrap();
+    let ids: Vec<_> = children
+        .iter()
+        .map(|&formatting| {
+            let frame = ComponentNode::Frame(Frame::new(LayoutMode::VerticalSplit));
+            tree.add_child_with_formatting(parent, frame, formatting)
+                .unwrap()
+        })
+        .collect();
+    tree.layout(200, 100);
+    ids.into_iter().map(|id| tree.rect(id).unwrap()).collect()
+}
+
+fn sized(width: Measurement, height: Measurement) -> Formatting {
+    Formatting {
+        preferred_width: width,
+        preferred_height: height,
+        ..Formatting::default()
+    }
+}
+
+fn row() -> Formatting {
+    Formatting {
+        layout_mode: LayoutMode::HorizontalSplit,
+        ..Formatting::default()
+    }
+}
+
+fn widths(rects: &[Rect]) -> Vec<u16> {
+    rects.iter().map(|rect| rect.width).collect()
+}
+
+#[test]
+fn fill_shares_the_room_by_weight() {
+    let claims = [
+        claim(Measurement::Cell(10), 100),
+        claim(Measurement::Fill(1), 100),
+        claim(Measurement::Fill(3), 100),
+    ];
+    assert_eq!(layout::share(&claims, 100), [10, 23, 67]);
+}
+
+#[test]
+fn max_stops_growing_at_its_bound() {
+    let claims = [
+        claim(Measurement::Max(2), 40),
+        claim(Measurement::Fill(1), 40),
+        claim(Measurement::Min(30), 40),
+    ];
+    // Max takes its 2 of the 10 cells left, the other two share the rest on top of what Min claims
+    assert_eq!(layout::share(&claims, 40), [2, 4, 34]);
+}
+
+#[test]
+fn fixed_sizes_are_their_part_of_the_room() {
+    assert_eq!(layout::size(Measurement::Ratio(1, 3), 90), 30);
+    assert_eq!(layout::size(Measurement::Ratio(1, 0), 90), 0);
+    assert_eq!(layout::size(Measurement::Percent(50), 9), 4);
+    assert_eq!(layout::size(Measurement::Cell(200), 9), 9);
+}
+
+#[test]
+fn children_are_placed_in_the_room_they_leave() {
+    let children = [
+        sized(Measurement::Cell(10), Measurement::Cell(4)),
+        sized(Measurement::Cell(20), Measurement::Cell(2)),
+    ];
+    let starts = |main_align| {
+        let container = Formatting {
+            main_align,
+            ..row()
+        };
+        lay_out(container, &children, 100, 10)
+            .iter()
+            .map(|rect| rect.x)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(starts(MainAlign::Start), [0, 10]);
+    assert_eq!(starts(MainAlign::Center), [35, 45]);
+    assert_eq!(starts(MainAlign::End), [70, 80]);
+    assert_eq!(starts(MainAlign::SpaceBetween), [0, 80]);
+    assert_eq!(starts(MainAlign::SpaceAround), [17, 62]);
+}
+
+#[test]
+fn children_are_aligned_across_the_axis() {
+    let children = [sized(Measurement::Cell(10), Measurement::Cell(4))];
+    let across = |cross_align| {
+        let container = Formatting {
+            cross_align,
+            ..row()
+        };
+        let rect = lay_out(container, &children, 100, 10)[0];
+        (rect.y, rect.height)
+    };
+    assert_eq!(across(CrossAlign::Start), (0, 4));
+    assert_eq!(across(CrossAlign::Center), (3, 4));
+    assert_eq!(across(CrossAlign::End), (6, 4));
+    assert_eq!(across(CrossAlign::Stretch), (0, 10));
+}
+
+#[test]
+fn padding_and_margins_are_left_around_children() {
+    let container = Formatting {
+        padding: Edges::symmetric(1, 2),
+        ..row()
+    };
+    let children = [
+        Formatting {
+            margin: Edges::all(1),
+            ..sized(Measurement::Cell(10), Measurement::Fill(1))
+        },
+        sized(Measurement::Fill(1), Measurement::Fill(1)),
+    ];
+    let rects = lay_out(container, &children, 40, 10);
+    assert_eq!(
+        rects[0],
+        Rect {
+            x: 3,
+            y: 2,
+            width: 10,
+            height: 6
+        }
+    );
+    // The rest of the 36 columns inside the padding, after the first child and its margins
+    assert_eq!(
+        rects[1],
+        Rect {
+            x: 14,
+            y: 1,
+            width: 24,
+            height: 8
+        }
+    );
+}
+
+#[test]
+fn preferred_position_moves_a_child() {
+    let children = [Formatting {
+        preferred_x: Measurement::Cell(3),
+        preferred_y: Measurement::Percent(50),
+        ..sized(Measurement::Cell(10), Measurement::Cell(2))
+    }];
+    let rect = lay_out(row(), &children, 100, 10)[0];
+    assert_eq!((rect.x, rect.y), (3, 5));
+}
+
+#[test]
+fn vertical_layouts_resolve_heights_the_same_way() {
+    let container = Formatting {
+        layout_mode: LayoutMode::VerticalSplit,
+        main_align: MainAlign::End,
+        ..Formatting::default()
+    };
+    let children = [
+        sized(Measurement::Percent(100), Measurement::Ratio(1, 4)),
+        sized(Measurement::Max(30), Measurement::Cell(3)),
+    ];
+    let rects = lay_out(container, &children, 50, 20);
+    assert_eq!(
+        rects
+            .iter()
+            .map(|rect| (rect.y, rect.height))
+            .collect::<Vec<_>>(),
+        [(12, 5), (17, 3)]
+    );
+    assert_eq!(widths(&rects), [50, 30]);
+}
+
+/// A line of text
+struct Label(String);
+
+impl Component for Label {
+    fn render(&self, buffer: &mut TerminalBuffer, _query: ComponentQuery) -> Result<()> {
+        buffer.write(&self.0);
+        Ok(())
+    }
+
+    fn default_formatting(&self) -> Formatting {
+        sized(Measurement::Fill(1), Measurement::Cell(1))
+    }
+}
+
+/// A bordered list of `lines` labels that scrolls, in a screen `height` rows high
+fn scrolling_list(lines: usize, height: u16) -> (ComponentTree<'static>, usize) {
+    let mut tree = ComponentTree::new(ComponentNode::Frame(Frame::new(LayoutMode::VerticalSplit)));
+    let list = Formatting {
+        overflow_y: Overflow::Scroll,
+        decoration: Decoration {
+            border: Some(BorderStyle::Rounded),
+            title_align: TitleAlign::Center,
+            scrollbar: true,
+            ..Decoration::default()
+        },
+        ..Formatting::default()
+    };
+    let frame = Frame::new(LayoutMode::VerticalSplit).with_title("Files");
+    let list_id = tree
+        .add_child_with_formatting(0, ComponentNode::Frame(frame), list)
+        .unwrap();
+    for line in 0..lines {
+        let label = ComponentNode::Component(Box::new(Label(format!("line {line}"))));
+        tree.add_child(list_id, label).unwrap();
+    }
+    tree.layout(20, height);
+    tree.mark_all_dirty();
+    tree.render(&mut std::io::sink()).unwrap();
+    (tree, list_id)
+}
+
+fn mouse(kind: MouseEventKind, column: u16, row: u16) -> ReovimEvent {
+    ReovimEvent::Mouse(MouseEvent {
+        kind,
+        column,
+        row,
+        modifiers: KeyModifiers::NONE,
+    })
+}
+
+#[test]
+fn borders_are_drawn_around_the_children() {
+    let (mut tree, list_id) = scrolling_list(3, 5);
+    let first = tree.children(list_id).unwrap()[0];
+    assert_eq!(
+        tree.rect(first).unwrap(),
+        Rect {
+            x: 1,
+            y: 1,
+            width: 18,
+            height: 1
+        }
+    );
+    let mut output = Vec::new();
+    tree.mark_all_dirty();
+    tree.render(&mut output).unwrap();
+    let output = String::from_utf8_lossy(&output);
+    assert!(output.contains("╭───── Files ──────╮"), "{output}");
+    assert!(output.contains("╰──────────────────╯"), "{output}");
+}
+
+#[test]
+fn the_thumb_shows_how_far_the_content_is_scrolled() {
+    let scroll = |offset| ScrollPosition {
+        offset,
+        visible: 10,
+        content: 40,
+    };
+    assert_eq!(decoration::thumb_span(10, scroll(0)), (0, 2));
+    assert_eq!(decoration::thumb_span(10, scroll(15)), (4, 2));
+    assert_eq!(decoration::thumb_span(10, scroll(30)), (8, 2));
+    // All of the content is shown
+    let all = ScrollPosition {
+        offset: 0,
+        visible: 10,
+        content: 4,
+    };
+    assert_eq!(decoration::thumb_span(10, all), (0, 10));
+    assert_eq!(decoration::offset_at(10, 9, scroll(0)), 30);
+}
+
+#[test]
+fn dragging_the_scrollbar_scrolls() {
+    let (mut tree, list_id) = scrolling_list(30, 12);
+    let scroll_y = |tree: &ComponentTree| tree.global_to_local(list_id, 0, 0).1;
+    // The track is the right border between the corners
+    tree.update(mouse(MouseEventKind::Down(MouseButton::Left), 19, 10))
+        .unwrap();
+    assert_eq!(scroll_y(&tree), 20);
+    tree.update(mouse(MouseEventKind::Drag(MouseButton::Left), 3, 1))
+        .unwrap();
+    assert_eq!(scroll_y(&tree), 0);
+    tree.update(mouse(MouseEventKind::Up(MouseButton::Left), 3, 1))
+        .unwrap();
+    // Presses elsewhere are the components'
+    tree.update(mouse(MouseEventKind::Drag(MouseButton::Left), 19, 10))
+        .unwrap();
+    assert_eq!(scroll_y(&tree), 0);
+}
+
+#[test]
+fn titles_and_footers_sit_where_aligned() {
+    let mut tree = ComponentTree::new(ComponentNode::Frame(Frame::new(LayoutMode::VerticalSplit)));
+    let formatting = Formatting {
+        decoration: Decoration {
+            border: Some(BorderStyle::Thick),
+            title_align: TitleAlign::Right,
+            footer_align: TitleAlign::Center,
+            ..Decoration::default()
+        },
+        ..Formatting::default()
+    };
+    let frame = Frame::new(LayoutMode::VerticalSplit)
+        .with_title("Files")
+        .with_footer("3 lines");
+    tree.add_child_with_formatting(0, ComponentNode::Frame(frame), formatting)
+        .unwrap();
+    tree.layout(20, 4);
+    tree.mark_all_dirty();
+    let mut output = Vec::new();
+    tree.render(&mut output).unwrap();
+    let mut screen = Screen::new(20, 4);
+    screen.write(&output);
+    assert_eq!(screen.row(0), "┏━━━━━━━━━━ Files ━┓");
+    assert_eq!(screen.row(1), "┃                  ┃");
+    assert_eq!(screen.row(3), "┗━━━━ 3 lines ━━━━━┛");
+}
+
+#[test]
+fn overlays_are_framed_in_their_border_style() {
+    let framed = |border| {
+        let mut tree =
+            ComponentTree::new(ComponentNode::Frame(Frame::new(LayoutMode::VerticalSplit)));
+        let overlay = super::overlay::Overlay {
+            border,
+            title: Some("Hi".to_string()),
+            ..Default::default()
+        };
+        let label = ComponentNode::Component(Box::new(Label("label".to_string())));
+        let formatting = sized(Measurement::Cell(6), Measurement::Cell(1));
+        tree.add_overlay(0, label, formatting, overlay).unwrap();
+        tree.layout(20, 5);
+        tree.mark_all_dirty();
+        let mut output = Vec::new();
+        tree.render(&mut output).unwrap();
+        let mut screen = Screen::new(20, 5);
+        screen.write(&output);
+        (0..3).map(|y| screen.row(y)).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        framed(Some(BorderStyle::Single)),
+        ["┌─ Hi ─┐", "│label │", "└──────┘"]
+    );
+    assert_eq!(
+        framed(Some(BorderStyle::Double)),
+        ["╔═ Hi ═╗", "║label ║", "╚══════╝"]
+    );
+    assert_eq!(
+        framed(Some(BorderStyle::Ascii)),
+        ["+- Hi -+", "|label |", "+------+"]
+    );
+    // Without a border there is nowhere for the title
+    assert_eq!(framed(None), ["label", "", ""]);
+}
+
+#[test]
+fn closing_a_popup_shows_what_was_under_it() {
+    for (y, height) in [(1, 1), (1, 3)] {
+        let mut tree =
+            ComponentTree::new(ComponentNode::Frame(Frame::new(LayoutMode::VerticalSplit)));
+        for line in 0..5 {
+            let label = ComponentNode::Component(Box::new(Label(format!("line {line} of text"))));
+            tree.add_child(0, label).unwrap();
+        }
+        let popup = ComponentNode::Component(Box::new(Label("popup".to_string())));
+        let formatting = Formatting {
+            preferred_x: Measurement::Cell(3),
+            preferred_y: Measurement::Cell(y),
+            ..sized(Measurement::Cell(8), Measurement::Cell(height))
+        };
+        let popup_id = tree
+            .add_overlay(0, popup, formatting, Default::default())
+            .unwrap();
+        let mut screen = Screen::new(20, 5);
+        let mut draw = |tree: &mut ComponentTree| {
+            let mut output = Vec::new();
+            tree.layout(20, 5);
+            tree.render(&mut output).unwrap();
+            screen.write(&output);
+            (0..5).map(|y| screen.row(y)).collect::<Vec<_>>()
+        };
+        tree.mark_all_dirty();
+        assert_eq!(draw(&mut tree)[1], "linpopup   ext");
+        tree.remove(popup_id);
+        assert_eq!(
+            draw(&mut tree),
+            (0..5)
+                .map(|line| format!("line {line} of text"))
+                .collect::<Vec<_>>()
+        );
+    }
+}
+
+/// What a terminal shows after the output written to it, enough of one to read frames back
+struct Screen {
+    rows: Vec<Vec<char>>,
+    cursor: (u16, u16),
+}
+
+impl Screen {
+    fn new(width: u16, height: u16) -> Self {
+        Self {
+            rows: vec![vec![' '; width as usize]; height as usize],
+            cursor: (0, 0),
+        }
+    }
+
+    /// Print the output, following the cursor moves in it and skipping other escape sequences
+    fn write(&mut self, output: &[u8]) {
+        let output = String::from_utf8_lossy(output);
+        let mut chars = output.chars();
+        while let Some(character) = chars.next() {
+            if character == '\x1b' {
+                let mut sequence = String::new();
+                for character in chars.by_ref() {
+                    sequence.push(character);
+                    if sequence.len() > 1 && ('@'..='~').contains(&character) {
+                        break;
+                    }
+                }
+                let Some(position) = sequence
+                    .strip_prefix('[')
+                    .and_then(|rest| rest.strip_suffix('H'))
+                else {
+                    continue;
+                };
+                let (row, col) = position.split_once(';').unwrap_or((position, "1"));
+                let one_based = |number: &str| number.parse::<u16>().unwrap_or(1).saturating_sub(1);
+                self.cursor = (one_based(col), one_based(row));
+            } else if !character.is_control() {
+                let (x, y) = self.cursor;
+                if let Some(cell) = self
+                    .rows
+                    .get_mut(y as usize)
+                    .and_then(|row| row.get_mut(x as usize))
+                {
+                    *cell = character;
+                }
+                self.cursor.0 += 1;
+            }
+        }
+    }
+
+    /// Row `y` without the blanks at its end
+    fn row(&self, y: u16) -> String {
+        let row: String = self.rows[y as usize].iter().collect();
+        row.trim_end().to_string()
+    }
+}
+
+/// An editor on a buffer of `text`, drawn on a screen after every event as the event loop does
+struct Editing {
+    tree: ComponentTree<'static>,
+    buffer: Rc<RefCell<Buffer>>,
+    tabs: Rc<RefCell<TabList>>,
+    /// What the workers send back, kept from the editor until a test hands it over
+    events: Receiver<ReovimEvent>,
+    screen: Screen,
+    size: (u16, u16),
+}
+
+impl Editing {
+    fn new(text: &str, width: u16, height: u16) -> Self {
+        Self::with_snippets(text, width, height, None)
+    }
+
+    /// An editor with the snippet files in `snippets`
+    fn with_snippets(text: &str, width: u16, height: u16, snippets: Option<PathBuf>) -> Self {
+        Self::with_buffer(Buffer::from_text(text), width, height, snippets)
+    }
+
+    /// An editor on `text` piped in on stdin
+    fn piped(text: &str, width: u16, height: u16) -> Self {
+        let buffer = Buffer::from_reader(text.as_bytes()).unwrap();
+        Self::with_buffer(buffer, width, height, None)
+    }
+
+    fn with_buffer(buffer: Buffer, width: u16, height: u16, snippets: Option<PathBuf>) -> Self {
+        let mut buffers = BufferList::default();
+        let buffer = buffers.add(buffer);
+        let (tasks, events) = Tasks::start();
+        let lsp = Lsp::new(LspConfig::default(), tasks.sender());
+        let editor = Editor::new(
+            Rc::new(RefCell::new(buffers)),
+            buffer.clone(),
+            tasks,
+            CompletionSources::default(),
+            SnippetLibrary::new(snippets),
+            lsp,
+        );
+        let tabs = editor.tabs();
+        let mut editing = Self {
+            tree: ComponentTree::new(ComponentNode::Component(Box::new(editor))),
+            buffer,
+            tabs,
+            events,
+            screen: Screen::new(width, height),
+            size: (width, height),
+        };
+        editing.tree.initialize_pending_components().unwrap();
+        editing.tree.layout(width, height);
+        editing.tree.mark_all_dirty();
+        editing.draw();
+        editing
+    }
+
+    fn draw(&mut self) {
+        let mut output = Vec::new();
+        self.tree.layout(self.size.0, self.size.1);
+        self.tree.render(&mut output).unwrap();
+        self.screen.write(&output);
+    }
+
+    fn update(&mut self, event: ReovimEvent) {
+        self.tree.update(event).unwrap();
+        self.draw();
+    }
+
+    /// Type `keys`, one key per char, with `\r`, `\t` and `\x1b` for Enter, Tab and Escape
+    fn keys(&mut self, keys: &str) {
+        for character in keys.chars() {
+            let code = match character {
+                '\r' => KeyCode::Enter,
+                '\t' => KeyCode::Tab,
+                '\x1b' => KeyCode::Esc,
+                _ => KeyCode::Char(character),
+            };
+            self.key(code, KeyModifiers::NONE);
+        }
+    }
+
+    fn key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        self.update(ReovimEvent::Key(KeyEvent::new(code, modifiers)));
+    }
+
+    /// Press and let go of the left button over `column` and `row`
+    fn click(&mut self, column: u16, row: u16) {
+        self.update(mouse(MouseEventKind::Down(MouseButton::Left), column, row));
+        self.update(mouse(MouseEventKind::Up(MouseButton::Left), column, row));
+    }
+
+    /// Wait for a worker to answer a completion request, without giving the answer to the editor yet
+    fn completion_response(&self) -> CompletionResponse {
+        loop {
+            let event = self.events.recv_timeout(Duration::from_secs(5)).unwrap();
+            if let ReovimEvent::Completion(response) = event {
+                return response;
+            }
+        }
+    }
+
+    fn cursor(&self) -> (usize, usize) {
+        self.buffer.borrow().cursor
+    }
+
+    fn text(&self) -> Vec<String> {
+        self.buffer.borrow().lines().as_slice().to_vec()
+    }
+
+    /// The tab being shown, counting from 1, and the windows in it
+    fn tab(&self) -> (usize, Vec<WindowId>) {
+        let tabs = self.tabs.borrow();
+        let windows = tabs.current().windows.iter();
+        let windows = windows.map(|window| window.borrow().id()).collect();
+        (tabs.current_index() + 1, windows)
+    }
+
+    /// The last row, where messages and errors are shown
+    fn message(&self) -> String {
+        self.screen.row(self.size.1 - 1)
+    }
+}
+
+#[test]
+fn do_and_dp_need_diff_mode() {
+    let mut editing = Editing::new("one\ntwo\n", 40, 10);
+    editing.keys("do");
+    assert_eq!(editing.text(), ["one", "two"]);
+    assert!(editing.message().contains("E99"), "{}", editing.message());
+    editing.keys("jdp");
+    assert_eq!(editing.text(), ["one", "two"]);
+    // On the second line, no line was opened above or below
+    assert_eq!(editing.screen.cursor.1, 1);
+}
+
+#[test]
+fn keys_after_z_that_are_not_fold_commands_do_nothing() {
+    let mut editing = Editing::new("one\ntwo\n", 40, 10);
+    editing.keys("zxzfx");
+    assert_eq!(editing.text(), ["one", "two"]);
+    editing.keys("zfj");
+    assert!(editing.screen.row(0).ends_with("2 lines: one"));
+}
+
+const WRAPPED: &str = "alpha beta gamma delta epsilon zeta eta theta\nsecond\nthird\n";
+
+#[test]
+fn gj_and_gk_move_by_screen_row() {
+    let mut editing = Editing::new(WRAPPED, 20, 8);
+    editing.keys(":set lbr\r");
+    assert_eq!(editing.screen.row(0), "│1 alpha beta gamma");
+    assert_eq!(editing.screen.row(1), "│↳ delta epsilon");
+    editing.keys("gj");
+    assert_eq!((editing.cursor(), editing.screen.cursor), ((0, 17), (3, 1)));
+    editing.keys("gj");
+    assert_eq!((editing.cursor(), editing.screen.cursor), ((0, 31), (3, 2)));
+    editing.keys("gj");
+    assert_eq!((editing.cursor(), editing.screen.cursor), ((1, 0), (3, 3)));
+    editing.keys("gkgk");
+    assert_eq!(editing.cursor(), (0, 17));
+    // `g$` and `g0` stay on the screen row
+    editing.keys("g$");
+    assert_eq!(editing.cursor(), (0, 30));
+    editing.keys("g0");
+    assert_eq!(editing.cursor(), (0, 17));
+    // Without wrapping a screen row is a line
+    editing.keys(":set nowrap\rgg0gj");
+    assert_eq!(editing.cursor(), (1, 0));
+}
+
+#[test]
+fn clicks_select_a_char_a_word_or_a_line() {
+    let mut editing = Editing::new("one two three\nfour five\n", 30, 8);
+    // Columns 0 to 2 are the border and line number
+    editing.click(7, 0);
+    assert_eq!(editing.cursor(), (0, 4));
+    editing.click(7, 0);
+    editing.keys("d");
+    assert_eq!(editing.text(), ["one  three", "four five"]);
+    editing.keys("u");
+    for _ in 0..3 {
+        editing.click(4, 1);
+    }
+    editing.keys("d");
+    assert_eq!(editing.text(), ["one two three"]);
+}
+
+#[test]
+fn dragging_selects_and_shift_extends() {
+    let mut editing = Editing::new("one two three\nfour five\n", 30, 8);
+    editing.update(mouse(MouseEventKind::Down(MouseButton::Left), 3, 0));
+    editing.update(mouse(MouseEventKind::Drag(MouseButton::Left), 6, 0));
+    editing.update(mouse(MouseEventKind::Up(MouseButton::Left), 6, 0));
+    editing.keys("d");
+    assert_eq!(editing.text(), ["two three", "four five"]);
+    editing.keys("u");
+    editing.click(3, 0);
+    editing.update(ReovimEvent::Mouse(MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column: 4,
+        row: 1,
+        modifiers: KeyModifiers::SHIFT,
+    }));
+    editing.keys("d");
+    assert_eq!(editing.text(), ["ur five"]);
+}
+
+#[test]
+fn the_wheel_scrolls_by_screen_rows() {
+    let numbered: String = (4..20).map(|line| format!("line {line}\n")).collect();
+    let mut editing = Editing::new(&format!("{WRAPPED}{numbered}"), 20, 8);
+    editing.keys(":set lbr\r");
+    assert_eq!(editing.screen.row(3), "│ ↳ eta theta");
+    // Three rows down the last row of the first line is on top, the cursor is moved onto it
+    editing.update(mouse(MouseEventKind::ScrollDown, 5, 2));
+    assert_eq!(editing.screen.row(0), "│ 1 eta theta");
+    assert_eq!(editing.cursor(), (0, 36));
+    editing.keys(":set mousescroll=ver:1\r");
+    editing.update(mouse(MouseEventKind::ScrollDown, 5, 2));
+    assert_eq!(editing.screen.row(0), "│ 2 second");
+    assert_eq!(editing.cursor(), (1, 0));
+    editing.update(mouse(MouseEventKind::ScrollUp, 5, 2));
+    editing.update(mouse(MouseEventKind::ScrollUp, 5, 2));
+    assert_eq!(editing.screen.row(0), "│ 1 epsilon zeta");
+}
+
+#[test]
+fn floating_windows_take_the_winborder() {
+    let mut editing = Editing::new("uno\ntwo\n", 40, 8);
+    editing.buffer.borrow_mut().set_vcs(Some(VcsInfo {
+        base: Some(vec!["one".to_string(), "two".to_string()]),
+        newline_at_end: true,
+        branch: None,
+    }));
+    // The popup opens below the cursor, over the text after the gutter
+    let corners = |editing: &Editing| {
+        let corner = |y| editing.screen.row(y).chars().nth(3);
+        (corner(1), corner(4))
+    };
+    editing.keys(":previewhunk\r");
+    assert_eq!(corners(&editing), (Some('┌'), Some('└')));
+    editing.keys("\x1b:set winborder=double\r:previewhunk\r");
+    assert_eq!(corners(&editing), (Some('╔'), Some('╚')));
+    editing.keys("\x1b:set winborder?\r");
+    assert_eq!(editing.message(), "  winborder=double");
+    editing.keys(":set winborder=none\r:previewhunk\r");
+    assert_eq!(editing.screen.row(1), "│2 -one");
+    editing.keys("\x1b:set winborder=wavy\r");
+    assert!(editing.message().contains("E474"), "{}", editing.message());
+}
+
+#[test]
+fn ctrl_z_asks_the_host_to_suspend() {
+    let mut editing = Editing::new("one\n", 30, 8);
+    editing.tree.take_requests();
+    editing.key(KeyCode::Char('z'), KeyModifiers::CONTROL);
+    assert_eq!(editing.tree.take_requests(), [HostRequest::Suspend]);
+    editing.keys(":suspend\r");
+    assert_eq!(editing.tree.take_requests(), [HostRequest::Suspend]);
+    // Nothing was typed or changed on the way
+    assert_eq!(editing.text(), ["one"]);
+}
+
+#[test]
+fn writing_piped_text_sends_it_to_stdout_on_exit() {
+    let mut editing = Editing::piped("one\ntwo\n", 60, 8);
+    editing.tree.take_requests();
+    editing.keys("x:w\r");
+    assert_eq!(
+        editing.tree.take_requests(),
+        [HostRequest::Output(b"ne\ntwo\n".to_vec())]
+    );
+    assert_eq!(editing.message(), "written to stdout on exit");
+    editing.keys("jdd:wq\r");
+    assert_eq!(
+        editing.tree.take_requests(),
+        [HostRequest::Output(b"ne\n".to_vec()), HostRequest::Quit(0)]
+    );
+}
+
+#[test]
+fn cq_quits_with_an_error_code_even_with_unsaved_changes() {
+    let mut editing = Editing::new("one\n", 60, 8);
+    editing.tree.take_requests();
+    editing.keys("x:cq\r");
+    assert_eq!(editing.tree.take_requests(), [HostRequest::Quit(1)]);
+}
+
+#[test]
+fn k_after_an_operator_cancels_it() {
+    let mut editing = Editing::new("one\n", 60, 8);
+    editing.keys("K");
+    let hover = editing.message();
+    assert!(!hover.is_empty());
+    for keys in ["dK", "gK", "cK"] {
+        editing.keys(":\x1b");
+        editing.keys(keys);
+        assert_eq!(editing.message(), "", "{keys}");
+        assert_eq!(editing.text(), ["one"]);
+    }
+}
+
+#[test]
+fn ctrl_u_quits_only_without_unsaved_changes() {
+    let mut editing = Editing::new("one\n", 60, 8);
+    editing.tree.take_requests();
+    editing.keys("x");
+    editing.key(KeyCode::Char('u'), KeyModifiers::CONTROL);
+    assert_eq!(editing.tree.take_requests(), []);
+    assert!(editing.message().contains("E37"), "{}", editing.message());
+    // Not taken as `u`, the change is still there
+    assert_eq!(editing.text(), ["ne"]);
+    editing.keys("u");
+    editing.key(KeyCode::Char('u'), KeyModifiers::CONTROL);
+    assert_eq!(editing.tree.take_requests(), [HostRequest::Quit(0)]);
+}
+
+#[test]
+fn tabs_open_switch_move_and_close() {
+    let mut editing = Editing::new("one\n", 40, 10);
+    editing.keys(":split\r");
+    assert_eq!(editing.tab(), (1, vec![1, 2]));
+    editing.keys(":tabnew\r");
+    assert_eq!(editing.tab(), (2, vec![3]));
+    // `gt` wraps around to the first tab, which still has both its windows
+    editing.keys("gt");
+    assert_eq!(editing.tab(), (1, vec![1, 2]));
+    editing.keys("gT");
+    assert_eq!(editing.tab(), (2, vec![3]));
+    editing.keys(":tabnew\r");
+    assert_eq!(editing.tab(), (3, vec![4]));
+    editing.keys(":tabnext 1\r");
+    assert_eq!(editing.tab(), (1, vec![1, 2]));
+    editing.keys("gTgT");
+    assert_eq!(editing.tab(), (2, vec![3]));
+
+    editing.keys(":tabmove 0\r");
+    assert_eq!(editing.tab(), (1, vec![3]));
+    editing.keys(":tabmove +1\r");
+    assert_eq!(editing.tab(), (2, vec![3]));
+    editing.keys(":tabmove\r");
+    assert_eq!(editing.tab(), (3, vec![3]));
+    editing.keys(":tabmove -2\r");
+    assert_eq!(editing.tab(), (1, vec![3]));
+    // The tabs after it kept their order: the split one, then the last opened
+    editing.keys("gt");
+    assert_eq!(editing.tab(), (2, vec![1, 2]));
+    editing.keys("gt");
+    assert_eq!(editing.tab(), (3, vec![4]));
+
+    // Closing the last tab shows the one before it
+    editing.keys(":tabclose\r");
+    assert_eq!(editing.tab(), (2, vec![1, 2]));
+    editing.keys("gT:tabclose\r");
+    assert_eq!(editing.tab(), (1, vec![1, 2]));
+    editing.keys(":tabclose\r");
+    assert!(editing.message().contains("E784"), "{}", editing.message());
+    assert_eq!(editing.tab(), (1, vec![1, 2]));
+}
+
+#[test]
+fn clicking_a_tab_label_shows_that_tab() {
+    let mut editing = Editing::new("one\n", 40, 10);
+    editing.keys(":tabnew\r:vsplit\r");
+    assert_eq!(editing.tab(), (2, vec![2, 3]));
+    let labels = editing.screen.row(0);
+    let first = labels.find(" 1 ").unwrap() as u16;
+    let second = labels.find(" 2 ").unwrap() as u16;
+    editing.click(first + 1, 0);
+    assert_eq!(editing.tab(), (1, vec![1]));
+    editing.click(second + 1, 0);
+    assert_eq!(editing.tab(), (2, vec![2, 3]));
+    // Past the labels there is no tab to go to
+    editing.click(39, 0);
+    assert_eq!(editing.tab(), (2, vec![2, 3]));
+}
+
+#[test]
+fn ctrl_p_starts_on_the_last_candidate() {
+    let mut editing = Editing::new("alpha beta\ngamma\n", 60, 10);
+    editing.keys("Go");
+    editing.key(KeyCode::Char('p'), KeyModifiers::CONTROL);
+    let response = editing.completion_response();
+    editing.update(ReovimEvent::Completion(response));
+    // The word nearest above the cursor, as `Ctrl-P` searches backwards
+    editing.keys("\t");
+    assert_eq!(editing.text(), ["alpha beta", "gamma", "gamma"]);
+
+    editing.keys("\x1bo");
+    editing.key(KeyCode::Char('n'), KeyModifiers::CONTROL);
+    let response = editing.completion_response();
+    editing.update(ReovimEvent::Completion(response));
+    editing.keys("\t");
+    assert_eq!(editing.text()[3], "alpha");
+}
+
+#[test]
+fn answers_to_a_replaced_request_are_dropped() {
+    let mut editing = Editing::new("one two\n", 60, 10);
+    let menu_shown = |editing: &Editing| (0..10).any(|y| editing.screen.row(y).contains("[buf]"));
+    editing.keys("o");
+    editing.key(KeyCode::Char('n'), KeyModifiers::CONTROL);
+    let stale = editing.completion_response();
+    editing.key(KeyCode::Char('e'), KeyModifiers::CONTROL);
+    editing.key(KeyCode::Char('n'), KeyModifiers::CONTROL);
+    let current = editing.completion_response();
+    assert!(stale.id < current.id);
+
+    editing.update(ReovimEvent::Completion(stale.clone()));
+    assert!(!menu_shown(&editing));
+    editing.update(ReovimEvent::Completion(current));
+    assert!(menu_shown(&editing));
+    // An answer arriving late doesn't add to the menu either
+    editing.update(ReovimEvent::Completion(stale));
+    editing.keys("\t");
+    assert_eq!(editing.text(), ["one two", "one"]);
+}
+
+#[test]
+fn a_panic_on_any_thread_restores_the_terminal() {
+    static RESTORED: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    terminal::restore_on_panic(|| {
+        RESTORED.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    });
+    for _ in 0..2 {
+        let worker = std::thread::Builder::new()
+            .name("reovim-worker-0".to_string())
+            .spawn(|| panic!("a worker panicked"))
+            .unwrap();
+        assert!(worker.join().is_err());
+    }
+    // Once, the second panic finds it restored
+    assert_eq!(RESTORED.load(std::sync::atomic::Ordering::SeqCst), 1);
+    assert!(terminal::panicked());
+}
+
+#[test]
+fn snippets_expand_at_the_indent_of_their_line() {
+    let dir = std::env::temp_dir().join(format!("reovim-snippets-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("all.toml"),
+        "[if]\nprefix = \"iff\"\nbody = [\"if ${1:cond} {\", \"\\t$0\", \"}\"]\n",
+    )
+    .unwrap();
+    let mut editing = Editing::with_snippets("fn f() {\n    \n}\n", 40, 10, Some(dir.clone()));
+    editing.keys(":set et\r:set sw=2\rjAiff\ta\tb\x1b");
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        editing.text(),
+        ["fn f() {", "    if a {", "      b", "    }", "}"]
+    );
+    // The snippet was typed as one change
+    editing.keys("u");
+    assert_eq!(editing.text(), ["fn f() {", "    ", "}"]);
+}
+
+proptest! {
+    #[test]
+    fn shares_never_pass_the_room(
+        measurements in prop::collection::vec(
+            prop_oneof![
+                (0usize..50).prop_map(Measurement::Cell),
+                (0u8..=100).prop_map(Measurement::Percent),
+                (0u16..5, 1u16..5).prop_map(|(a, b)| Measurement::Ratio(a, b)),
+                (0usize..50).prop_map(Measurement::Min),
+                (0usize..50).prop_map(Measurement::Max),
+                (0u16..5).prop_map(Measurement::Fill),
+            ],
+            1..8,
+        ),
+        room in 0u16..200,
+    ) {
+        let claims: Vec<_> = measurements.iter().map(|&measurement| claim(measurement, room)).collect();
+        let sizes = layout::share(&claims, room);
+        let claimed: u32 = claims.iter().map(|claim| claim.base as u32).sum();
+        let total: u32 = sizes.iter().map(|&size| size as u32).sum();
+        // Flexible children use all the room there is, unless every one of them stopped at its bound
+        let flexible = claims.iter().any(|claim| claim.weight > 0 && claim.max.is_none());
+        if claimed <= room as u32 {
+            prop_assert!(total <= room as u32);
+            prop_assert!(!flexible || total == room as u32);
+        } else {
+            prop_assert_eq!(total, claimed);
+        }
+        for (claim, &size) in claims.iter().zip(&sizes) {
+            prop_assert!(size >= claim.base);
+            prop_assert!(claim.max.is_none_or(|max| size <= max));
+        }
+    }
+}
@@ -1,11 +1,13 @@
 use std::cell::Cell;
 
 use crate::{
+    buffer::{byte_index, grapheme},
     event::ReovimEvent,
     tui::{
         Component, CursorStyle, Formatting, LayoutMode, Measurement, Overflow,
-        terminal_buffer::TerminalBuffer,
+        terminal_buffer::TerminalBuffer, wrap::split_by_width,
     },
+    vcs::VcsStatus,
 };
 
 use anyhow::Result;
@@ -13,31 +15,13 @@ use crossterm::{
     event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind},
     style::Color,
 };
-use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use unicode_width::UnicodeWidthStr;
 
 enum TextMode {
     Insert,
     Normal,
 }
 
-enum VcsStatus {
-    Add,
-    None,
-    Deleted,
-    Modified,
-}
-
-impl VcsStatus {
-    fn color(&self) -> Color {
-        match self {
-            VcsStatus::Add => Color::Green,
-            VcsStatus::None => Color::Reset,
-            VcsStatus::Deleted => Color::Red,
-            VcsStatus::Modified => Color::DarkYellow,
-        }
-    }
-}
-
 pub enum Content {
     Multi(Vec<String>),
     Single(String),
@@ -234,33 +218,6 @@ fn pad_or_truncate(s: &str, width: u16) -> String {
     }
 }
 
-/// Split text into chunks of max width, respecting unicode character widths
-fn split_by_width(text: &str, max_width: u16) -> Vec<&str> {
-    let max_width = max_width as usize;
-    let mut chunks = Vec::new();
-    let mut current_width = 0;
-    let mut start_byte = 0;
-
-    for (byte_pos, ch) in text.char_indices() {
-        let ch_width = ch.width().unwrap_or(0);
-        if current_width + ch_width > max_width && start_byte < byte_pos {
-            // Exceeded width, slice from start_byte to byte_pos
-            chunks.push(&text[start_byte..byte_pos]);
-            start_byte = byte_pos;
-            current_width = ch_width;
-        } else {
-            current_width += ch_width;
-        }
-    }
-
-    // Add remaining text
-    if start_byte < text.len() {
-        chunks.push(&text[start_byte..]);
-    }
-
-    chunks
-}
-
 impl<'a> TextComponent<'a> {
     /// Calculate gutter width (line number + divider + space)
     fn gutter_width(&self) -> u16 {
@@ -329,7 +286,11 @@ impl<'a> TextComponent<'a> {
 }
 
 impl<'a> Component for TextComponent<'a> {
-    fn render(&self, buffer: &mut TerminalBuffer, _query: crate::tui::ComponentQuery) -> Result<()> {
+    fn render(
+        &self,
+        buffer: &mut TerminalBuffer,
+        _query: crate::tui::ComponentQuery,
+    ) -> Result<()> {
         // Get scroll offset from buffer
         let (_, scroll_y) = buffer.scroll();
         let start_at = scroll_y;
@@ -419,12 +380,19 @@ impl<'a> Component for TextComponent<'a> {
                         let cursor = commands.get_cursor();
                         let gutter_width = self.gutter_width();
                         if cursor.col != gutter_width {
+                            let mut width = 1;
                             if let Some(contents) =
                                 self.get_line_mut(cursor.row + commands.get_scroll_y() as u16)
                             {
-                                contents.remove((cursor.col - gutter_width) as usize);
+                                // The cursor is on a screen column, the cluster before it goes
+                                let display = (cursor.col - gutter_width) as usize;
+                                let col = grapheme::col_at_display(contents, display);
+                                let start = grapheme::previous_cluster(contents, col);
+                                let bytes = byte_index(contents, start)..byte_index(contents, col);
+                                width = display - grapheme::display_col(contents, start);
+                                contents.replace_range(bytes, "");
                             }
-                            commands.move_cursor(-1, 0);
+                            commands.move_cursor(-(width as i32), 0);
                             return Ok(true);
                         }
                     }
@@ -434,9 +402,12 @@ impl<'a> Component for TextComponent<'a> {
                         if let Some(contents) =
                             self.get_line_mut(cursor.row + commands.get_scroll_y() as u16)
                         {
-                            contents.insert((cursor.col - gutter_width) as usize, character);
+                            let display = (cursor.col - gutter_width) as usize;
+                            let col = grapheme::col_at_display(contents, display);
+                            contents.insert(byte_index(contents, col), character);
                         }
-                        commands.move_cursor(1, 0);
+                        let width = grapheme::cluster_width(character.encode_utf8(&mut [0; 4]));
+                        commands.move_cursor(width as i32, 0);
                         return Ok(true);
                     }
                     _ => {}
@@ -505,13 +476,14 @@ impl<'a> Component for TextComponent<'a> {
         Formatting {
             preferred_x: Measurement::Cell(0),
             preferred_y: Measurement::Cell(0),
-            preferred_width: Measurement::Fill, // Leave room for gutter
-            preferred_height: Measurement::Fill,
+            preferred_width: Measurement::Fill(1), // Leave room for gutter
+            preferred_height: Measurement::Fill(1),
             overflow_x: Overflow::Hide,
             overflow_y: Overflow::Scroll,
             request_focus: true,
             layout_mode: LayoutMode::VerticalSplit,
             focusable: true,
+            ..Default::default()
         }
     }
 }
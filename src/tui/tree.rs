@@ -1,47 +1,32 @@
-use crate::event::ReovimEvent;
+use crate::buffer::grapheme::cluster_width;
+use crate::event::{HostRequest, ReovimEvent};
 use crate::tui::debug::DebugComponent;
+use crate::tui::decoration::{self, Decoration, ScrollPosition};
+use crate::tui::overlay::{Anchor, Overlay};
 use crate::tui::status::StatusComponent;
 use crate::tui::terminal_buffer::{TerminalBuffer, TerminalCommand};
 use crate::tui::text::TextComponent;
+use crate::tui::wrap::split_by_width;
 use crate::tui::{
-    Component, ComponentQuery, Cursor, CursorStyle, Formatting, LayoutMode, Measurement, Overflow, Rect,
+    Component, ComponentQuery, CrossAlign, Cursor, CursorStyle, Edges, Formatting, LayoutMode,
+    MainAlign, Measurement, Overflow, Rect, layout,
 };
 use anyhow::Result;
-use crossterm::ExecutableCommand;
 use crossterm::cursor::{Hide, MoveTo, Show};
-use crossterm::event::{MouseButton, MouseEventKind};
-use crossterm::style::{Print, ResetColor, SetBackgroundColor, SetForegroundColor};
-use std::io::Stdout;
-use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use crossterm::style::{
+    Attribute, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor,
+    SetUnderlineColor,
+};
+use crossterm::{ExecutableCommand, queue};
+use std::cell::RefCell;
+use std::io::Write;
+use unicode_width::UnicodeWidthStr;
 
 pub type ComponentId = usize;
 
-/// Split text into chunks of max width, respecting unicode character widths
-fn split_by_width(text: &str, max_width: u16) -> Vec<&str> {
-    let max_width = max_width as usize;
-    let mut chunks = Vec::new();
-    let mut current_width = 0;
-    let mut start_byte = 0;
-
-    for (byte_pos, ch) in text.char_indices() {
-        let ch_width = ch.width().unwrap_or(0);
-        if current_width + ch_width > max_width && start_byte < byte_pos {
-            // Exceeded width, slice from start_byte to byte_pos
-            chunks.push(&text[start_byte..byte_pos]);
-            start_byte = byte_pos;
-            current_width = ch_width;
-        } else {
-            current_width += ch_width;
-        }
-    }
-
-    // Add remaining text
-    if start_byte < text.len() {
-        chunks.push(&text[start_byte..]);
-    }
-
-    chunks
-}
+/// The room a component was offered and the size it measured in it
+type MeasuredSize = ((u16, u16), (u16, u16));
 
 /// Commands that a component can perform on the tree
 /// This provides a limited interface to prevent arbitrary tree mutations
@@ -60,6 +45,49 @@ impl<'a> ComponentCommands<'a> {
         self.tree.focus_path.contains(&self.self_id)
     }
 
+    /// Ask the application hosting the tree to act, handled after the current event
+    pub fn request(&mut self, request: HostRequest) {
+        self.tree.requests.push(request);
+    }
+
+    /// Stop the current event from reaching any further components
+    pub fn consume_event(&mut self) {
+        self.tree.event_consumed = true;
+    }
+
+    /// Dispatch another event to the whole tree once the current one has been handled
+    pub fn emit(&mut self, event: ReovimEvent) {
+        self.tree.queued_events.push(event);
+    }
+
+    /// The component that currently has focus
+    pub fn focused(&self) -> ComponentId {
+        self.tree.focus
+    }
+
+    /// Move focus to any component in the tree
+    /// Each ancestor's cursor_row is pointed at the child on the path so sibling navigation resumes from it
+    pub fn set_focus(&mut self, id: ComponentId) {
+        if id >= self.tree.components.len() {
+            return;
+        }
+        let path = self.tree.build_focus_path(id);
+        for pair in path.windows(2) {
+            let (parent, child) = (pair[0], pair[1]);
+            let index = self
+                .tree
+                .children
+                .get(parent)
+                .and_then(|children| children.iter().position(|&c| c == child));
+            if let (Some(index), Some(row_slot)) = (index, self.tree.cursor_row.get_mut(parent)) {
+                *row_slot = index as u16;
+            }
+        }
+        self.tree.focus = id;
+        self.tree.focus_path = path;
+        self.tree.mark_dirty(id);
+    }
+
     /// Get the IDs of this component's children
     pub fn children(&self) -> Option<Vec<ComponentId>> {
         self.tree.children(self.self_id)
@@ -298,6 +326,25 @@ impl<'a> ComponentCommands<'a> {
         self.tree.mark_dirty(current_id);
     }
 
+    /// Move focus to one of this component's children
+    /// Focus descends to the child's first focusable leaf once its children are initialized
+    pub fn focus_child(&mut self, child_id: ComponentId) {
+        let index = self
+            .tree
+            .children
+            .get(self.self_id)
+            .and_then(|children| children.iter().position(|&id| id == child_id));
+        if let Some(index) = index {
+            // Parent's cursor_row tracks which child is focused
+            if let Some(row_slot) = self.tree.cursor_row.get_mut(self.self_id) {
+                *row_slot = index as u16;
+            }
+            self.tree.focus = child_id;
+            self.tree.focus_path = self.tree.build_focus_path(child_id);
+            self.tree.mark_dirty(child_id);
+        }
+    }
+
     /// Set focus to a component and automatically descend to the first focusable child leaf
     fn set_focus_with_descent(&mut self, component_id: ComponentId) {
         // Find the deepest focusable descendant (automatically descends into first focusable child)
@@ -340,7 +387,11 @@ impl<'a> ComponentCommands<'a> {
                 let delta = if row_delta > 0 { row_delta } else { col_delta } as usize;
                 (current_pos + delta).min(siblings.len() - 1)
             } else if row_delta < 0 || col_delta < 0 {
-                let delta = if row_delta < 0 { (-row_delta) as usize } else { (-col_delta) as usize };
+                let delta = if row_delta < 0 {
+                    (-row_delta) as usize
+                } else {
+                    (-col_delta) as usize
+                };
                 current_pos.saturating_sub(delta)
             } else {
                 return;
@@ -554,16 +605,192 @@ impl<'a> ComponentCommands<'a> {
         let boxed: Box<dyn Component> = Box::new(component);
         self.add_child(ComponentNode::Component(boxed))
     }
+
+    /// Add a component under another component with custom formatting
+    /// For containers a component created and keeps filling itself
+    pub fn add_component_to<C: Component + 'static>(
+        &mut self,
+        parent_id: ComponentId,
+        component: C,
+        formatting: Formatting,
+    ) -> Result<ComponentId> {
+        let boxed: Box<dyn Component> = Box::new(component);
+        self.tree
+            .add_child_with_formatting(parent_id, ComponentNode::Component(boxed), formatting)
+    }
+
+    /// Add a floating component owned by this component, drawn above the tiled layout
+    pub fn add_overlay<C: Component + 'static>(
+        &mut self,
+        component: C,
+        overlay: Overlay,
+    ) -> Result<ComponentId> {
+        let formatting = component.default_formatting();
+        let boxed: Box<dyn Component> = Box::new(component);
+        self.tree.add_overlay(
+            self.self_id,
+            ComponentNode::Component(boxed),
+            formatting,
+            overlay,
+        )
+    }
+
+    /// Move a floating component or change its decoration
+    pub fn set_overlay(&mut self, id: ComponentId, overlay: Overlay) {
+        let slot = self
+            .tree
+            .overlays
+            .iter_mut()
+            .find(|(overlay_id, _)| *overlay_id == id);
+        if let Some((_, slot)) = slot {
+            *slot = overlay;
+        }
+    }
+
+    /// Replace a component, keeping its position among its siblings
+    /// The old component and its descendants are removed from the tree
+    pub fn replace_component<C: Component + 'static>(
+        &mut self,
+        child_id: ComponentId,
+        component: C,
+    ) -> Result<ComponentId> {
+        let parent_id = self
+            .tree
+            .parent(child_id)
+            .flatten()
+            .ok_or_else(|| anyhow::anyhow!("Child component not found"))?;
+        let index = self
+            .tree
+            .children
+            .get(parent_id)
+            .and_then(|children| children.iter().position(|&id| id == child_id))
+            .ok_or_else(|| anyhow::anyhow!("Child component not found"))?;
+        let formatting = component.default_formatting();
+        let new_id = self.add_component_to(parent_id, component, formatting)?;
+        if let Some(children) = self.tree.children.get_mut(parent_id) {
+            children.retain(|&id| id != new_id);
+            children.insert(index, new_id);
+        }
+        self.tree.remove(child_id);
+        Ok(new_id)
+    }
+
+    /// Remove one of this component's children and its descendants
+    pub fn remove_child(&mut self, child_id: ComponentId) {
+        if self.tree.parent(child_id) == Some(Some(self.self_id)) {
+            self.tree.remove(child_id);
+        }
+    }
+
+    /// Remove any component and its descendants
+    pub fn remove_component(&mut self, id: ComponentId) {
+        self.tree.remove(id);
+    }
+
+    /// Get the IDs of any component's children
+    pub fn children_of(&self, id: ComponentId) -> Option<Vec<ComponentId>> {
+        self.tree.children(id)
+    }
+
+    /// Index of this component's child that contains the focus, if focus is inside this component
+    pub fn focused_child_index(&self) -> Option<usize> {
+        let position = self
+            .tree
+            .focus_path
+            .iter()
+            .position(|&id| id == self.self_id)?;
+        let child = *self.tree.focus_path.get(position + 1)?;
+        self.tree
+            .children
+            .get(self.self_id)?
+            .iter()
+            .position(|&id| id == child)
+    }
+
+    /// Cursor of the focused component in its own content coordinates, ignoring scroll
+    pub fn focused_cursor(&self) -> Cursor {
+        let focus = self.tree.focus;
+        let col = self.tree.cursor_col.get(focus).copied().unwrap_or(0);
+        let row = self.tree.cursor_row.get(focus).copied().unwrap_or(0);
+        Cursor::from_xy(row, col)
+    }
+
+    /// Place the cursor of any component, the caller is responsible for keeping it in bounds
+    pub fn set_cursor_for(&mut self, id: ComponentId, col: u16, row: u16) {
+        if let Some(col_slot) = self.tree.cursor_col.get_mut(id) {
+            *col_slot = col;
+        }
+        if let Some(row_slot) = self.tree.cursor_row.get_mut(id) {
+            *row_slot = row;
+        }
+        self.tree.mark_dirty(id);
+    }
+
+    /// Vertical scroll offset of any component
+    pub fn scroll_y_of(&self, id: ComponentId) -> usize {
+        self.tree.scroll_y.get(id).copied().unwrap_or(0)
+    }
+
+    /// Position and size of any component from the last layout, relative to its parent
+    pub fn rect_of(&self, id: ComponentId) -> Rect {
+        self.tree.rect(id).unwrap_or_default()
+    }
+
+    /// Where any component was drawn on the last frame, in screen coordinates, empty when it wasn't
+    pub fn screen_rect_of(&self, id: ComponentId) -> Rect {
+        self.tree.screen_rects.get(id).copied().unwrap_or_default()
+    }
+
+    /// Formatting of any component
+    pub fn formatting_of(&self, id: ComponentId) -> Formatting {
+        self.tree.formatting.get(id).copied().unwrap_or_default()
+    }
+
+    /// Change the formatting of any component, it takes effect on the next layout
+    pub fn set_formatting_for(&mut self, id: ComponentId, formatting: Formatting) {
+        if let Some(slot) = self.tree.formatting.get_mut(id) {
+            *slot = formatting;
+        }
+        // The parent hands out its room by its children's formatting
+        self.tree.forget_layout(id);
+        let parent = self.tree.parent(id).flatten();
+        self.tree.relayout_from(parent.unwrap_or(id));
+        self.tree.mark_dirty(id);
+    }
+
+    /// Set the cursor display style of any component
+    pub fn set_cursor_style_for(&mut self, id: ComponentId, style: CursorStyle) {
+        if let Some(style_slot) = self.tree.cursor_style.get_mut(id) {
+            *style_slot = style;
+        }
+    }
 }
 
 /// A frame is a layout container that holds children
 pub struct Frame {
     pub layout_mode: LayoutMode,
+    /// Shown in the top and bottom border when the frame's formatting gives it one
+    pub title: Option<String>,
+    pub footer: Option<String>,
 }
 
 impl Frame {
     pub fn new(layout_mode: LayoutMode) -> Self {
-        Self { layout_mode }
+        Self {
+            layout_mode,
+            title: None,
+            footer: None,
+        }
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn with_footer(mut self, footer: impl Into<String>) -> Self {
+        self.footer = Some(footer.into());
+        self
     }
 }
 
@@ -613,6 +840,29 @@ impl<'a> ComponentNode<'a> {
             ComponentNode::Component(component) => component.children(commands),
         }
     }
+
+    pub fn screen_cursor(&self, cursor: Cursor, width: u16) -> Cursor {
+        match self {
+            ComponentNode::Component(component) => component.screen_cursor(cursor, width),
+            _ => cursor,
+        }
+    }
+
+    pub fn title(&self) -> Option<String> {
+        match self {
+            ComponentNode::Frame(frame) => frame.title.clone(),
+            ComponentNode::Component(component) => component.title(),
+            _ => None,
+        }
+    }
+
+    pub fn footer(&self) -> Option<String> {
+        match self {
+            ComponentNode::Frame(frame) => frame.footer.clone(),
+            ComponentNode::Component(component) => component.footer(),
+            _ => None,
+        }
+    }
 }
 
 /// Arena-based component tree
@@ -651,6 +901,26 @@ pub struct ComponentTree<'a> {
     cursor_style: Vec<CursorStyle>,
     /// Path of component IDs from root to currently focused component
     focus_path: Vec<ComponentId>,
+    /// Requests for the host application, drained after each event
+    requests: Vec<HostRequest>,
+    /// A component consumed the event being dispatched, stop propagating it
+    event_consumed: bool,
+    /// Events components emitted while handling an event, dispatched after it
+    queued_events: Vec<ReovimEvent>,
+    /// screen_rects[i] is where component i was drawn on the last frame, empty when it wasn't
+    screen_rects: Vec<Rect>,
+    /// Floating components in the order they were added, they aren't in their parent's children
+    overlays: Vec<(ComponentId, Overlay)>,
+    /// Cells the overlays covered on the last frame
+    overlay_areas: Vec<Rect>,
+    /// measured[i] holds the last two sizes component i measured, emptied once it changes
+    measured: RefCell<Vec<Vec<MeasuredSize>>>,
+    /// laid_out[i] is the room component i's children were laid out in, `None` once something inside changed
+    laid_out: Vec<Option<(u16, u16)>>,
+    /// Components to lay out again in the room they have, the nearest ones to a change that don't grow with it
+    relayout: Vec<ComponentId>,
+    /// The container whose scrollbar the mouse is dragging
+    scrollbar_drag: Option<ComponentId>,
 }
 
 impl<'a> ComponentTree<'a> {
@@ -678,6 +948,16 @@ impl<'a> ComponentTree<'a> {
             cursor_initialized: vec![false],
             cursor_style: vec![CursorStyle::default()],
             focus_path: vec![0], // Start with root in focus path
+            requests: Vec::new(),
+            event_consumed: false,
+            queued_events: Vec::new(),
+            screen_rects: vec![Rect::empty()],
+            overlays: Vec::new(),
+            overlay_areas: Vec::new(),
+            measured: RefCell::new(vec![Vec::new()]),
+            laid_out: vec![None],
+            relayout: Vec::new(),
+            scrollbar_drag: None,
         }
     }
 
@@ -722,6 +1002,9 @@ impl<'a> ComponentTree<'a> {
             height: 0,
         });
         self.formatting.push(formatting);
+        self.screen_rects.push(Rect::empty());
+        self.measured.borrow_mut().push(Vec::new());
+        self.laid_out.push(None);
         self.scroll_x.push(0);
         self.scroll_y.push(0);
         self.children.push(Vec::new()); // Initialize empty children list for this component
@@ -745,6 +1028,7 @@ impl<'a> ComponentTree<'a> {
         if let Some(children) = self.children.get_mut(parent_id) {
             children.push(child_id);
         }
+        self.relayout_from(parent_id);
 
         // Mark component for initialization - will call children() method later
         if !is_frame {
@@ -754,6 +1038,78 @@ impl<'a> ComponentTree<'a> {
         Ok(child_id)
     }
 
+    /// Add a floating component above the tiled layout
+    /// The parent owns it, focus and removal work as for a child, but it takes no room in the parent's layout
+    pub fn add_overlay(
+        &mut self,
+        parent_id: ComponentId,
+        child: ComponentNode<'a>,
+        formatting: Formatting,
+        overlay: Overlay,
+    ) -> Result<ComponentId> {
+        let child_id = self.add_child_with_formatting(parent_id, child, formatting)?;
+        if let Some(children) = self.children.get_mut(parent_id) {
+            children.retain(|&id| id != child_id);
+        }
+        self.overlays.push((child_id, overlay));
+        Ok(child_id)
+    }
+
+    /// Remove a component and all of its descendants
+    /// Slots in the arena are kept so IDs stay stable, removed components are dropped
+    pub fn remove(&mut self, id: ComponentId) {
+        if id == self.root || id >= self.components.len() {
+            return;
+        }
+        let parent = self.parent.get(id).copied().flatten();
+        if let Some(children) = parent.and_then(|parent| self.children.get_mut(parent)) {
+            children.retain(|&child| child != id);
+        }
+        self.remove_subtree(id);
+        if let Some(parent) = parent {
+            self.relayout_from(parent);
+        }
+
+        // Focus can't stay inside a removed component, fall back to its parent
+        if self.focus_path.contains(&id) {
+            self.focus = parent.unwrap_or(self.root);
+            self.focus_path = self.build_focus_path(self.focus);
+        }
+    }
+
+    fn remove_subtree(&mut self, id: ComponentId) {
+        let child_ids = self
+            .children
+            .get_mut(id)
+            .map(std::mem::take)
+            .unwrap_or_default();
+        for child_id in child_ids {
+            self.remove_subtree(child_id);
+        }
+        // Overlays go with the component that owns them
+        let owned: Vec<ComponentId> = self
+            .overlays
+            .iter()
+            .map(|&(overlay_id, _)| overlay_id)
+            .filter(|&overlay_id| self.parent.get(overlay_id) == Some(&Some(id)))
+            .collect();
+        for overlay_id in owned {
+            self.remove_subtree(overlay_id);
+        }
+        self.overlays.retain(|&(overlay_id, _)| overlay_id != id);
+        if let Some(component) = self.components.get_mut(id) {
+            *component = ComponentNode::Frame(Frame::new(LayoutMode::VerticalSplit));
+        }
+        if let Some(parent) = self.parent.get_mut(id) {
+            *parent = None;
+        }
+        if let Some(rect) = self.rects.get_mut(id) {
+            *rect = Rect::empty();
+        }
+        self.dirty.retain(|&dirty| dirty != id);
+        self.pending_initialization.retain(|&pending| pending != id);
+    }
+
     /// Get a component by ID (immutable)
     pub fn get(&self, id: ComponentId) -> Option<&ComponentNode<'a>> {
         self.components.get(id)
@@ -817,38 +1173,67 @@ impl<'a> ComponentTree<'a> {
     }
 
     /// Calculate actual width/height from a Measurement given available space
-    /// Note: Fill returns the full available space; caller should handle distributing Fill space
+    /// Note: Content and the flexible measurements return the full available space, the layout shares it
     fn calculate_size(&self, measurement: Measurement, available: u16) -> u16 {
-        match measurement {
-            Measurement::Cell(cells) => cells.min(available as usize) as u16,
-            Measurement::Percent(percent) => {
-                let percent = percent.min(100) as u16;
-                (available * percent) / 100
+        layout::size(measurement, available)
+    }
+
+    /// Measure the size of a component, rendering it only when it changed since it was last measured at this size
+    fn measure_component(&self, id: ComponentId, max_width: u16, max_height: u16) -> (u16, u16) {
+        let cached = self.measured.borrow().get(id).and_then(|sizes| {
+            sizes
+                .iter()
+                .find(|(max, _)| *max == (max_width, max_height))
+                .map(|&(_, size)| size)
+        });
+        if let Some(size) = cached {
+            return size;
+        }
+        let size = self.measure_uncached(id, max_width, max_height);
+        if let Some(sizes) = self.measured.borrow_mut().get_mut(id) {
+            // Rows are measured at the room they were offered and then at the width they got
+            if sizes.len() == 2 {
+                sizes.remove(0);
             }
-            Measurement::Content => available, // Will be handled specially in layout_children
-            Measurement::Fill => available,
+            sizes.push(((max_width, max_height), size));
         }
+        size
     }
 
     /// Measure the size of a component by rendering it to a temporary buffer
     /// For containers with children, measures the combined child dimensions instead
-    fn measure_component(&self, id: ComponentId, max_width: u16, max_height: u16) -> (u16, u16) {
+    fn measure_uncached(&self, id: ComponentId, max_width: u16, max_height: u16) -> (u16, u16) {
         // If this component has children, measure their combined size instead
         if let Some(child_ids) = self.children(id) {
             if !child_ids.is_empty() {
                 let formatting = self.formatting.get(id).copied().unwrap_or_default();
-
-                return match formatting.layout_mode {
+                // Children go inside the decoration and padding, which the container measures with them
+                let padding = formatting.inset();
+                let max_width = max_width.saturating_sub(padding.horizontal());
+                let max_height = max_height.saturating_sub(padding.vertical());
+                let (width, height) = match formatting.layout_mode {
                     LayoutMode::VerticalSplit => {
                         // Children are stacked vertically: width is max, height is sum
                         let mut total_height = 0u16;
                         let mut max_width_child = 0u16;
 
                         for &child_id in &child_ids {
-                            let (child_width, child_height) =
-                                self.measure_component(child_id, max_width, max_height);
-                            max_width_child = max_width_child.max(child_width);
-                            total_height = total_height.saturating_add(child_height);
+                            let margin = self
+                                .formatting
+                                .get(child_id)
+                                .copied()
+                                .unwrap_or_default()
+                                .margin;
+                            let (child_width, child_height) = self.measure_component(
+                                child_id,
+                                max_width.saturating_sub(margin.horizontal()),
+                                max_height.saturating_sub(margin.vertical()),
+                            );
+                            max_width_child = max_width_child
+                                .max(child_width.saturating_add(margin.horizontal()));
+                            total_height = total_height
+                                .saturating_add(child_height)
+                                .saturating_add(margin.vertical());
                         }
 
                         (max_width_child, total_height)
@@ -857,29 +1242,97 @@ impl<'a> ComponentTree<'a> {
                         // Children are laid out horizontally: width is sum, height is max
                         let mut total_width = 0u16;
                         let mut max_height_child = 0u16;
+                        // Children filling the height take the row's rather than set it, unless they all do
+                        let mut max_fill_height = 0u16;
+                        let mut flexible = Vec::new();
+                        let mut measure = |child_id: ComponentId, width: u16| {
+                            let child_formatting =
+                                self.formatting.get(child_id).copied().unwrap_or_default();
+                            let margin = child_formatting.margin;
+                            let (child_width, child_height) = self.measure_component(
+                                child_id,
+                                width,
+                                max_height.saturating_sub(margin.vertical()),
+                            );
+                            let outer_height = child_height.saturating_add(margin.vertical());
+                            if layout::flexible(child_formatting.preferred_height) {
+                                max_fill_height = max_fill_height.max(outer_height);
+                            } else {
+                                max_height_child = max_height_child.max(outer_height);
+                            }
+                            child_width.saturating_add(margin.horizontal())
+                        };
 
                         for &child_id in &child_ids {
-                            let (child_width, child_height) =
-                                self.measure_component(child_id, max_width, max_height);
-                            total_width = total_width.saturating_add(child_width);
-                            max_height_child = max_height_child.max(child_height);
+                            let child_formatting =
+                                self.formatting.get(child_id).copied().unwrap_or_default();
+                            if layout::flexible(child_formatting.preferred_width) {
+                                flexible.push(child_id);
+                                continue;
+                            }
+                            let width =
+                                max_width.saturating_sub(child_formatting.margin.horizontal());
+                            total_width = total_width.saturating_add(measure(child_id, width));
+                        }
+
+                        // Flexible children share the width the others leave, as they do when laid out
+                        let left = max_width.saturating_sub(total_width);
+                        let claims: Vec<_> = flexible
+                            .iter()
+                            .map(|&child_id| {
+                                let child_formatting =
+                                    self.formatting.get(child_id).copied().unwrap_or_default();
+                                let margin = child_formatting.margin;
+                                let margin = (margin.left, margin.right);
+                                layout::Claim::new(
+                                    child_formatting.preferred_width,
+                                    left,
+                                    0,
+                                    margin,
+                                )
+                            })
+                            .collect();
+                        for (&child_id, width) in flexible.iter().zip(layout::share(&claims, left))
+                        {
+                            total_width = total_width.saturating_add(measure(child_id, width));
                         }
 
+                        if max_height_child == 0 {
+                            max_height_child = max_fill_height;
+                        }
                         (total_width, max_height_child)
                     }
                 };
+                return (
+                    width.saturating_add(padding.horizontal()),
+                    height.saturating_add(padding.vertical()),
+                );
             }
         }
 
-        // Leaf component: render it to measure
-        let mut buffer = TerminalBuffer::new(max_width, max_height);
+        // Leaf component: render it to measure, inside its decoration
+        let edges = self
+            .formatting
+            .get(id)
+            .copied()
+            .unwrap_or_default()
+            .decoration
+            .edges();
+        let mut buffer = TerminalBuffer::new(
+            max_width.saturating_sub(edges.horizontal()),
+            max_height.saturating_sub(edges.vertical()),
+        );
 
         if let Some(component) = self.components.get(id) {
             let query = ComponentQuery {
                 focus: self.focus_path.contains(&id),
             };
             let _ = component.render(&mut buffer, query);
-            buffer.measure_content()
+            let (width, height) = buffer.measure_content();
+            (
+                width.saturating_add(edges.horizontal()),
+                height.saturating_add(edges.vertical()),
+            )
         } else {
             (0, 0)
         }
@@ -955,8 +1408,8 @@ impl<'a> ComponentTree<'a> {
 
             for cmd in buffer.commands() {
                 match cmd {
-                    TerminalCommand::Print(_ch) => {
-                        current_x += 1;
+                    TerminalCommand::Print(cluster) => {
+                        current_x += cluster_width(cluster) as u16;
                         if current_x >= render_width {
                             visual_height += 1;
                             current_x = 0;
@@ -992,7 +1445,7 @@ impl<'a> ComponentTree<'a> {
             let mut text = String::new();
             for cmd in buffer.commands() {
                 match cmd {
-                    TerminalCommand::Print(ch) => text.push(*ch),
+                    TerminalCommand::Print(cluster) => text.push_str(cluster),
                     TerminalCommand::Newline => text.push('\n'),
                     _ => {}
                 }
@@ -1058,8 +1511,73 @@ impl<'a> ComponentTree<'a> {
         (rect.width, rect.height)
     }
 
+    /// Lay out what changed since the last layout
+    /// Components whose room and content are the same keep their layout, the measurements of ones sized by
+    /// their content are kept until it changes
     pub fn layout(&mut self, width: u16, height: u16) {
         self.layout_node(self.root, width, height);
+        for id in std::mem::take(&mut self.relayout) {
+            // Removed since, or laid out again with its ancestors
+            let removed = id != self.root && self.parent.get(id).copied().flatten().is_none();
+            if removed || self.laid_out.get(id).copied().flatten().is_some() {
+                continue;
+            }
+            let rect = self.rects.get(id).copied().unwrap_or_default();
+            self.layout_node(id, rect.width, rect.height);
+        }
+    }
+
+    /// A component's content changed, forget the layout of it and everything inside it
+    fn invalidate_layout(&mut self, id: ComponentId) {
+        self.forget_layout(id);
+        self.relayout_from(id);
+    }
+
+    /// Forget what was measured and laid out for a component and its descendants
+    fn forget_layout(&mut self, id: ComponentId) {
+        if let Some(sizes) = self.measured.get_mut().get_mut(id) {
+            sizes.clear();
+        }
+        if let Some(laid_out) = self.laid_out.get_mut(id) {
+            *laid_out = None;
+        }
+        for child_id in self.children.get(id).cloned().unwrap_or_default() {
+            self.forget_layout(child_id);
+        }
+    }
+
+    /// Have the layout reach `id` again, starting from the nearest ancestor whose size doesn't depend on it
+    /// That is the parent of the highest ancestor sized by its content, or `id` itself when there is none
+    fn relayout_from(&mut self, id: ComponentId) {
+        let mut start = id;
+        let mut path = Vec::new();
+        let mut current = Some(id);
+        while let Some(node) = current {
+            // Containers measure as their children do, whatever their own formatting
+            if let Some(sizes) = self.measured.get_mut().get_mut(node) {
+                sizes.clear();
+            }
+            path.push(node);
+            let formatting = self.formatting.get(node).copied().unwrap_or_default();
+            current = self.parent.get(node).copied().flatten();
+            if matches!(formatting.preferred_width, Measurement::Content)
+                || matches!(formatting.preferred_height, Measurement::Content)
+            {
+                start = current.unwrap_or(node);
+            }
+        }
+        for node in path
+            .into_iter()
+            .take_while(|&node| node != start)
+            .chain([start])
+        {
+            if let Some(laid_out) = self.laid_out.get_mut(node) {
+                *laid_out = None;
+            }
+        }
+        if !self.relayout.contains(&start) {
+            self.relayout.push(start);
+        }
     }
 
     /// Mark all components as dirty (need re-render)
@@ -1071,6 +1589,14 @@ impl<'a> ComponentTree<'a> {
     }
 
     fn layout_node(&mut self, id: ComponentId, available_width: u16, available_height: u16) {
+        // Nothing inside changed and the room is the same, the children are where they were
+        if self.laid_out.get(id) == Some(&Some((available_width, available_height))) {
+            return;
+        }
+        if let Some(laid_out) = self.laid_out.get_mut(id) {
+            *laid_out = Some((available_width, available_height));
+        }
+
         // Calculate this component's size based on preferences
         let formatting = self.formatting.get(id).copied().unwrap_or_default();
 
@@ -1092,264 +1618,314 @@ impl<'a> ComponentTree<'a> {
 
         // Layout children
         if let Some(child_ids) = self.children(id) {
-            // Get layout mode from formatting
-            let layout_mode = self
-                .formatting
-                .get(id)
-                .copied()
-                .unwrap_or_default()
-                .layout_mode;
-            self.layout_children(child_ids, component_width, component_height, layout_mode);
+            self.layout_children(child_ids, component_width, component_height, formatting);
         }
     }
 
+    /// Lay out a container's children in its room, as `layout` describes
     fn layout_children(
         &mut self,
         child_ids: Vec<ComponentId>,
         available_width: u16,
         available_height: u16,
-        layout_mode: LayoutMode,
+        container: Formatting,
     ) {
         if child_ids.is_empty() {
             return;
         }
-
-        match layout_mode {
-            LayoutMode::HorizontalSplit => {
-                self.layout_children_horizontal(child_ids, available_width, available_height)
-            }
-            LayoutMode::VerticalSplit => {
-                self.layout_children_vertical(child_ids, available_width, available_height)
-            }
-        }
-    }
-
-    fn layout_children_horizontal(
-        &mut self,
-        child_ids: Vec<ComponentId>,
-        available_width: u16,
-        available_height: u16,
-    ) {
-        // First pass: calculate space used by non-Fill components and count Fill components
-        let mut used_width = 0u16;
-        let mut fill_count = 0;
-        let mut widths: Vec<u16> = Vec::new();
-
-        for child_id in child_ids.iter() {
-            let formatting = self.formatting.get(*child_id).copied().unwrap_or_default();
-            match formatting.preferred_width {
-                Measurement::Fill => {
-                    fill_count += 1;
-                    widths.push(0); // Placeholder, will be calculated
-                }
-                Measurement::Content => {
-                    // Measure the component's rendered content
-                    let (measured_width, _) =
-                        self.measure_component(*child_id, available_width, available_height);
-                    used_width = used_width.saturating_add(measured_width);
-                    widths.push(measured_width);
-                }
-                _ => {
-                    let width = self.calculate_size(formatting.preferred_width, available_width);
-                    used_width = used_width.saturating_add(width);
-                    widths.push(width);
-                }
+        let mode = container.layout_mode;
+        let inset = container.inset();
+        let (padding_main, padding_cross) = layout::edges(mode, inset);
+        let (room, cross_room) = layout::along(
+            mode,
+            available_width.saturating_sub(inset.horizontal()),
+            available_height.saturating_sub(inset.vertical()),
+        );
+        let formattings: Vec<Formatting> = child_ids
+            .iter()
+            .map(|&child_id| self.formatting.get(child_id).copied().unwrap_or_default())
+            .collect();
+        let claims = self.claims(&child_ids, container, room, cross_room);
+        let sizes = layout::share(&claims, room);
+
+        // Children that wrap start a new row or column when they don't fit in what is left of this one
+        let mut lines: Vec<Vec<usize>> = vec![Vec::new()];
+        let mut used = 0u16;
+        for (index, claim) in claims.iter().enumerate() {
+            let outer = sizes[index]
+                .saturating_add(claim.margin.0)
+                .saturating_add(claim.margin.1);
+            let overflow = match mode {
+                LayoutMode::HorizontalSplit => formattings[index].overflow_x,
+                LayoutMode::VerticalSplit => formattings[index].overflow_y,
+            };
+            let line = lines.last_mut().expect("there is always a line");
+            if !line.is_empty() && used.saturating_add(outer) > room && overflow == Overflow::Wrap {
+                lines.push(vec![index]);
+                used = outer;
+            } else {
+                line.push(index);
+                used = used.saturating_add(outer);
             }
         }
 
-        // Calculate width for Fill components
-        let remaining_width = available_width.saturating_sub(used_width);
-        let fill_width = if fill_count > 0 {
-            remaining_width / fill_count as u16
-        } else {
-            0
-        };
-
-        // Update widths for Fill components
-        for (i, width) in widths.iter_mut().enumerate() {
-            if *width == 0 && i < child_ids.len() {
-                let formatting = self
-                    .formatting
-                    .get(child_ids[i])
-                    .copied()
-                    .unwrap_or_default();
-                if matches!(formatting.preferred_width, Measurement::Fill) {
-                    *width = fill_width;
-                }
+        let single_line = lines.len() == 1;
+        let mut line_start = 0u16;
+        for line in lines {
+            // Past the end of the room, the children left aren't shown
+            if line_start >= cross_room {
+                break;
             }
-        }
-
-        // Layout children with calculated widths
-        let mut current_x = 0u16;
-        let mut current_y = 0u16;
-        let mut max_height_in_row = 1u16;
-
-        for (i, child_id) in child_ids.iter().enumerate() {
-            let formatting = self.formatting.get(*child_id).copied().unwrap_or_default();
-            let component_width = widths[i];
-            let component_height = if matches!(formatting.preferred_height, Measurement::Content) {
-                // Measure the component's rendered content with the actual component width
-                // This is critical for components with overflow_x: Wrap to calculate correct height
-                let (_, measured_height) =
-                    self.measure_component(*child_id, component_width, available_height);
-                measured_height
+            let line_claims: Vec<_> = line.iter().map(|&index| claims[index]).collect();
+            let line_sizes: Vec<_> = line.iter().map(|&index| sizes[index]).collect();
+            let starts = layout::place(&line_claims, &line_sizes, room, container.main_align);
+            let line_room = cross_room - line_start;
+            let across: Vec<(Measurement, u16, (u16, u16))> = line
+                .iter()
+                .map(|&index| {
+                    let (_, margin) = layout::edges(mode, formattings[index].margin);
+                    let measurement = match mode {
+                        LayoutMode::HorizontalSplit => formattings[index].preferred_height,
+                        LayoutMode::VerticalSplit => formattings[index].preferred_width,
+                    };
+                    let content = if measurement == Measurement::Content {
+                        let (width, height) = match mode {
+                            // Across a row the height depends on the width the child got, as text wraps
+                            LayoutMode::HorizontalSplit => self.measure_component(
+                                child_ids[index],
+                                sizes[index],
+                                line_room.saturating_sub(margin.0 + margin.1),
+                            ),
+                            LayoutMode::VerticalSplit => {
+                                self.measure_room(child_ids[index], room, line_room)
+                            }
+                        };
+                        layout::along(mode, width, height).1
+                    } else {
+                        0
+                    };
+                    (measurement, content, margin)
+                })
+                .collect();
+            // A row or column of several is as large as its largest child, a single one has all the room
+            let line_size = if single_line {
+                line_room
             } else {
-                self.calculate_size(formatting.preferred_height, available_height)
+                across
+                    .iter()
+                    .map(|&(measurement, content, margin)| {
+                        let (start, size) = layout::align_across(
+                            measurement,
+                            content,
+                            margin,
+                            line_room,
+                            CrossAlign::Start,
+                        );
+                        start.saturating_add(size).saturating_add(margin.1)
+                    })
+                    .max()
+                    .unwrap_or(0)
+                    .max(1)
             };
-
-            // Check if component fits on current line
-            let fits_on_line = current_x + component_width <= available_width;
-
-            // Determine behavior based on overflow and fit
-            if !fits_on_line && formatting.overflow_x == Overflow::Wrap {
-                // Move to next line
-                current_x = 0;
-                current_y += max_height_in_row;
-                max_height_in_row = 1;
-            }
-
-            // Check if we've exceeded available height
-            if current_y >= available_height {
-                break;
+            let placed: Vec<(u16, u16)> = across
+                .iter()
+                .map(|&(measurement, content, margin)| {
+                    layout::align_across(
+                        measurement,
+                        content,
+                        margin,
+                        line_size,
+                        container.cross_align,
+                    )
+                })
+                .collect();
+
+            for ((&index, &main_start), &(cross_start, cross_size)) in
+                line.iter().zip(&starts).zip(&placed)
+            {
+                let child_id = child_ids[index];
+                let formatting = formattings[index];
+                let (main, cross) = layout::along(
+                    mode,
+                    layout::offset(formatting.preferred_x, available_width),
+                    layout::offset(formatting.preferred_y, available_height),
+                );
+                let (x, y) = layout::back(
+                    mode,
+                    padding_main
+                        .0
+                        .saturating_add(main_start)
+                        .saturating_add(main),
+                    padding_cross
+                        .0
+                        .saturating_add(line_start)
+                        .saturating_add(cross_start)
+                        .saturating_add(cross),
+                );
+                let (width, height) = layout::back(mode, sizes[index], cross_size);
+                if let Some(rect_slot) = self.rects.get_mut(child_id) {
+                    *rect_slot = Rect {
+                        x,
+                        y,
+                        width,
+                        height,
+                    };
+                }
+                self.layout_node(child_id, width, height);
             }
+            line_start = line_start.saturating_add(line_size);
+        }
+    }
 
-            let rect = Rect {
-                x: current_x,
-                y: current_y,
-                width: component_width,
-                height: component_height,
-            };
+    /// What each child of a container claims along its axis, measuring the ones sized by their content
+    fn claims(
+        &self,
+        child_ids: &[ComponentId],
+        container: Formatting,
+        room: u16,
+        cross_room: u16,
+    ) -> Vec<layout::Claim> {
+        let mode = container.layout_mode;
+        child_ids
+            .iter()
+            .map(|&child_id| {
+                let formatting = self.formatting.get(child_id).copied().unwrap_or_default();
+                let (margin, _) = layout::edges(mode, formatting.margin);
+                let measurement = match mode {
+                    LayoutMode::HorizontalSplit => formatting.preferred_width,
+                    LayoutMode::VerticalSplit => formatting.preferred_height,
+                };
+                let content = if measurement == Measurement::Content {
+                    let (width, height) = self.measure_room(child_id, room, cross_room);
+                    layout::along(mode, width, height).0
+                } else {
+                    0
+                };
+                layout::Claim::new(measurement, room, content, margin)
+            })
+            .collect()
+    }
+
+    /// Measure a child of a container in the room the container has inside, less the child's margins
+    fn measure_room(&self, child_id: ComponentId, room: u16, cross_room: u16) -> (u16, u16) {
+        let parent = self.parent.get(child_id).copied().flatten();
+        let mode = parent
+            .and_then(|parent| self.formatting.get(parent))
+            .map(|formatting| formatting.layout_mode)
+            .unwrap_or(LayoutMode::VerticalSplit);
+        let margin = self
+            .formatting
+            .get(child_id)
+            .copied()
+            .unwrap_or_default()
+            .margin;
+        let (width, height) = layout::back(mode, room, cross_room);
+        self.measure_component(
+            child_id,
+            width.saturating_sub(margin.horizontal()),
+            height.saturating_sub(margin.vertical()),
+        )
+    }
 
-            // Store rect in tree's rects Vec
-            if let Some(rect_slot) = self.rects.get_mut(*child_id) {
-                *rect_slot = rect;
-            }
+    /// Render the entire tree to the terminal output (stdout, or the tty in pipe mode)
+    pub fn render<W: Write + ?Sized>(&mut self, stdout: &mut W) -> Result<()> {
+        // Hide cursor during rendering
+        stdout.execute(Hide)?;
 
-            // Update position for next child
-            current_x += component_width;
-            max_height_in_row = max_height_in_row.max(component_height);
+        // They show what their owner has for them, which changes without them knowing, so an overlay
+        // is measured afresh when it or its owner changed, before drawing marks everything shown
+        let changed: Vec<ComponentId> = self
+            .overlays
+            .iter()
+            .map(|&(id, _)| id)
+            .filter(|&id| {
+                self.is_dirty(id)
+                    || self
+                        .parent
+                        .get(id)
+                        .copied()
+                        .flatten()
+                        .is_some_and(|owner| self.is_dirty(owner))
+            })
+            .collect();
 
-            // Recursively layout this child
-            self.layout_node(*child_id, component_width, component_height);
+        // Render all nodes
+        for rect in self.screen_rects.iter_mut() {
+            *rect = Rect::empty();
         }
-    }
+        self.render_node(self.root, stdout)?;
 
-    fn layout_children_vertical(
-        &mut self,
-        child_ids: Vec<ComponentId>,
-        available_width: u16,
-        available_height: u16,
-    ) {
-        // First pass: calculate space used by non-Fill components and count Fill components
-        let mut used_height = 0u16;
-        let mut fill_count = 0;
-        let mut heights: Vec<u16> = Vec::new();
-
-        for child_id in child_ids.iter() {
-            let formatting = self.formatting.get(*child_id).copied().unwrap_or_default();
-            match formatting.preferred_height {
-                Measurement::Fill => {
-                    fill_count += 1;
-                    heights.push(0); // Placeholder, will be calculated
-                }
-                Measurement::Content => {
-                    // Measure the component's rendered content
-                    let (_, measured_height) =
-                        self.measure_component(*child_id, available_width, available_height);
-                    used_height = used_height.saturating_add(measured_height);
-                    heights.push(measured_height);
-                }
-                _ => {
-                    let height = self.calculate_size(formatting.preferred_height, available_height);
-                    used_height = used_height.saturating_add(height);
-                    heights.push(height);
+        // Overlays are placed once the tiled layout is drawn, anchors need to know where things ended up
+        for id in changed {
+            self.forget_layout(id);
+        }
+        let placed = self.place_overlays();
+        let screen = self.rects.get(self.root).copied().unwrap_or_default();
+        let areas: Vec<Rect> = placed
+            .iter()
+            .map(|(_, overlay, outer)| overlay.covered(*outer, screen))
+            .collect();
+
+        // Cells an overlay covered on the last frame and no longer does show what's underneath again
+        let mut redrawn: Vec<ComponentId> = Vec::new();
+        // Queued through a reference, `queue!` needs a sized writer
+        let mut output = &mut *stdout;
+        for area in std::mem::take(&mut self.overlay_areas) {
+            let mut uncovered = false;
+            for y in area.y..area.y + area.height {
+                for x in area.x..area.x + area.width {
+                    if areas.iter().any(|covered| covered.contains(x, y)) {
+                        continue;
+                    }
+                    queue!(&mut output, MoveTo(x, y), ResetColor, Print(' '))?;
+                    uncovered = true;
                 }
             }
-        }
-
-        // Calculate height for Fill components
-        let remaining_height = available_height.saturating_sub(used_height);
-        let fill_height = if fill_count > 0 {
-            remaining_height / fill_count as u16
-        } else {
-            0
-        };
-
-        // Update heights for Fill components
-        for (i, height) in heights.iter_mut().enumerate() {
-            if *height == 0 && i < child_ids.len() {
-                let formatting = self
-                    .formatting
-                    .get(child_ids[i])
-                    .copied()
-                    .unwrap_or_default();
-                if matches!(formatting.preferred_height, Measurement::Fill) {
-                    *height = fill_height;
-                }
+            let id = self.drawn_under(area);
+            if uncovered && !redrawn.contains(&id) {
+                redrawn.push(id);
             }
         }
-
-        // Layout children with calculated heights
-        let mut current_x = 0u16;
-        let mut current_y = 0u16;
-        let mut max_width_in_column = 1u16;
-
-        for (i, child_id) in child_ids.iter().enumerate() {
-            let formatting = self.formatting.get(*child_id).copied().unwrap_or_default();
-            let component_width = if matches!(formatting.preferred_width, Measurement::Content) {
-                // Measure the component's rendered content
-                let (measured_width, _) =
-                    self.measure_component(*child_id, available_width, available_height);
-                measured_width
-            } else {
-                self.calculate_size(formatting.preferred_width, available_width)
-            };
-            let component_height = heights[i];
-
-            // Check if component fits on current column
-            let fits_in_column = current_y + component_height <= available_height;
-
-            // Determine behavior based on overflow and fit
-            if !fits_in_column && formatting.overflow_y == Overflow::Wrap {
-                // Move to next column
-                current_y = 0;
-                current_x += max_width_in_column;
-                max_width_in_column = 1;
+        output.flush()?;
+        // Only what was under the uncovered cells is drawn again, a component inside another one
+        // being drawn is drawn with it
+        let nested: Vec<ComponentId> = redrawn
+            .iter()
+            .copied()
+            .filter(|&id| {
+                redrawn
+                    .iter()
+                    .any(|&other| self.is_descendant_of(other, id))
+            })
+            .collect();
+        let cursor_pos = self.cursor_pos;
+        for id in redrawn.into_iter().filter(|id| !nested.contains(id)) {
+            // Rendering a subtree takes its rect on screen, as its parent gives it when drawing
+            let screen_rect = self.screen_rects.get(id).copied().unwrap_or_default();
+            let rect = self.rects.get(id).copied().unwrap_or_default();
+            if let Some(slot) = self.rects.get_mut(id) {
+                *slot = screen_rect;
             }
-
-            // Check if we've exceeded available width
-            if current_x >= available_width {
-                break;
+            self.mark_dirty(id);
+            self.render_node(id, stdout)?;
+            if let Some(slot) = self.rects.get_mut(id) {
+                *slot = rect;
             }
+        }
+        self.cursor_pos = cursor_pos;
 
-            let rect = Rect {
-                x: current_x,
-                y: current_y,
-                width: component_width,
-                height: component_height,
-            };
-
-            // Store rect in tree's rects Vec
-            if let Some(rect_slot) = self.rects.get_mut(*child_id) {
-                *rect_slot = rect;
+        // Lowest first so higher overlays end up on top
+        for (id, overlay, outer) in placed {
+            overlay.draw_frame(stdout, outer, screen)?;
+            let content = overlay.content_rect(outer);
+            if let Some(rect) = self.rects.get_mut(id) {
+                *rect = content;
             }
-
-            // Update position for next child
-            current_y += component_height;
-            max_width_in_column = max_width_in_column.max(component_width);
-
-            // Recursively layout this child
-            self.layout_node(*child_id, component_width, component_height);
+            self.layout_node(id, content.width, content.height);
+            self.mark_dirty(id);
+            self.render_node(id, stdout)?;
         }
-    }
-
-    /// Render the entire tree to stdout
-    pub fn render(&mut self, stdout: &mut Stdout) -> Result<()> {
-        // Hide cursor during rendering
-        stdout.execute(Hide)?;
-
-        // Render all nodes
-        self.render_node(self.root, stdout)?;
+        self.overlay_areas = areas;
 
         // Show cursor if we found one
         if let Some((x, y)) = self.cursor_pos {
@@ -1363,9 +1939,111 @@ impl<'a> ComponentTree<'a> {
         Ok(())
     }
 
-    fn render_node(&mut self, id: ComponentId, stdout: &mut Stdout) -> Result<()> {
+    /// The innermost component drawn this frame whose rect on screen holds all of `area`
+    fn drawn_under(&self, area: Rect) -> ComponentId {
+        let mut id = self.root;
+        while let Some(child_id) = self.children.get(id).and_then(|child_ids| {
+            child_ids.iter().copied().find(|&child_id| {
+                self.screen_rects
+                    .get(child_id)
+                    .is_some_and(|rect| rect.width > 0 && rect.contains_rect(area))
+            })
+        }) {
+            id = child_id;
+        }
+        id
+    }
+
+    /// Where each overlay goes this frame, lowest z-index first
+    /// Overlays whose anchor isn't on screen are left out
+    fn place_overlays(&self) -> Vec<(ComponentId, Overlay, Rect)> {
+        let screen = self.rects.get(self.root).copied().unwrap_or_default();
+        let mut overlays = self.overlays.clone();
+        overlays.sort_by_key(|(_, overlay)| overlay.z_index);
+
+        let mut placed: Vec<(ComponentId, Overlay, Rect)> = Vec::new();
+        for (id, overlay) in overlays {
+            let formatting = self.formatting.get(id).copied().unwrap_or_default();
+            let inset = overlay.inset() * 2;
+            let max_width = screen.width.saturating_sub(inset);
+            let max_height = screen.height.saturating_sub(inset);
+            let measured = matches!(formatting.preferred_width, Measurement::Content)
+                || matches!(formatting.preferred_height, Measurement::Content);
+            let (content_width, content_height) = if measured {
+                self.measure_component(id, max_width, max_height)
+            } else {
+                (0, 0)
+            };
+            let width = match formatting.preferred_width {
+                Measurement::Content => content_width,
+                measurement => self.calculate_size(measurement, max_width),
+            };
+            let height = match formatting.preferred_height {
+                Measurement::Content => content_height,
+                measurement => self.calculate_size(measurement, max_height),
+            };
+            // Nothing to show, like a menu without any entries yet
+            if measured && content_width == 0 && content_height == 0 {
+                continue;
+            }
+            let size = overlay.outer_size(width, height);
+
+            let (origin, cursor_line) = match overlay.anchor {
+                Anchor::Editor => (screen, None),
+                Anchor::Component(target) => {
+                    // Overlays anchored to one placed earlier this frame follow where it went
+                    let area = placed
+                        .iter()
+                        .find(|(placed_id, _, _)| *placed_id == target)
+                        .map(|(_, placed, outer)| placed.content_rect(*outer))
+                        .or_else(|| self.screen_rects.get(target).copied())
+                        .unwrap_or_default();
+                    if area.width == 0 || area.height == 0 {
+                        continue;
+                    }
+                    (area, None)
+                }
+                Anchor::Cursor { .. } => {
+                    let Some((col, row)) = self.cursor_pos else {
+                        continue;
+                    };
+                    let cursor = Rect {
+                        x: col,
+                        y: row,
+                        width: 1,
+                        height: 1,
+                    };
+                    (cursor, Some(row))
+                }
+            };
+            let (x, y) = match overlay.anchor {
+                Anchor::Cursor { col, row } => (
+                    origin.x as i32 + col as i32,
+                    origin.y as i32 + 1 + row as i32,
+                ),
+                _ => {
+                    let x = self.calculate_size(formatting.preferred_x, origin.width);
+                    let y = self.calculate_size(formatting.preferred_y, origin.height);
+                    (origin.x as i32 + x as i32, origin.y as i32 + y as i32)
+                }
+            };
+            let outer = overlay.place(size, (x, y), cursor_line, screen);
+            placed.push((id, overlay, outer));
+        }
+        placed
+    }
+
+    fn render_node<W: Write + ?Sized>(&mut self, id: ComponentId, stdout: &mut W) -> Result<()> {
         let rect = self.rects.get(id).copied().unwrap_or_default();
         let formatting = self.formatting.get(id).copied().unwrap_or_default();
+        if let Some(screen_rect) = self.screen_rects.get_mut(id) {
+            *screen_rect = rect;
+        }
+
+        // Nothing to draw for components with no room, like hidden tab pages
+        if rect.width == 0 || rect.height == 0 {
+            return Ok(());
+        }
 
         // Auto-scroll to keep focused child in view
         // This MUST happen before we render anything, so scroll is correct for rendering
@@ -1396,7 +2074,8 @@ impl<'a> ComponentTree<'a> {
                 // If we found a focused child of this container, auto-scroll to bring it into view
                 if let Some(focused_child) = focused_child_of_container {
                     // Find the index of the focused child
-                    let focused_child_index = child_ids_vec.iter().position(|&c| c == focused_child);
+                    let focused_child_index =
+                        child_ids_vec.iter().position(|&c| c == focused_child);
 
                     if let Some(child_idx) = focused_child_index {
                         // Calculate cumulative visual height to check if child is in view
@@ -1405,7 +2084,8 @@ impl<'a> ComponentTree<'a> {
                         let mut child_end_row = None;
 
                         for (idx, child_id) in child_ids_vec.iter().enumerate() {
-                            let child_visual_height = self.rects
+                            let child_visual_height = self
+                                .rects
                                 .get(*child_id)
                                 .map(|r| r.height as usize)
                                 .unwrap_or(0);
@@ -1424,7 +2104,8 @@ impl<'a> ComponentTree<'a> {
                             let mut visible_start_row = 0usize;
                             for idx in 0..parent_scroll_y {
                                 if let Some(child_id) = child_ids_vec.get(idx) {
-                                    visible_start_row += self.rects
+                                    visible_start_row += self
+                                        .rects
                                         .get(*child_id)
                                         .map(|r| r.height as usize)
                                         .unwrap_or(0);
@@ -1452,12 +2133,15 @@ impl<'a> ComponentTree<'a> {
                                 // Calculate how much space we need to leave from the top to fit this child at the bottom
                                 for idx in (0..child_idx).rev() {
                                     if let Some(check_child_id) = child_ids_vec.get(idx) {
-                                        let check_height_val = self.rects
+                                        let check_height_val = self
+                                            .rects
                                             .get(*check_child_id)
                                             .map(|r| r.height as usize)
                                             .unwrap_or(0);
 
-                                        if check_height + check_height_val + (end_row - start_row) <= visible_height {
+                                        if check_height + check_height_val + (end_row - start_row)
+                                            <= visible_height
+                                        {
                                             check_height += check_height_val;
                                             new_scroll = idx;
                                         } else {
@@ -1495,8 +2179,16 @@ impl<'a> ComponentTree<'a> {
         // Handle cursor position and scrolling for focused component (before rendering)
         let mut final_scroll_y = scroll_y;
 
+        // The container draws over its gaps when it draws again, then the children over the rest
+        if self.is_dirty(id) {
+            self.blank_gaps(stdout, id, rect)?;
+        }
+
         // Now get the component after all mutable operations are done
         if let Some(component) = self.components.get(id) {
+            // The component draws inside its decoration
+            let rect = formatting.decoration.inner(rect);
+
             // Create a virtual buffer for this component
             let mut buffer = TerminalBuffer::new(rect.width, rect.height);
 
@@ -1509,8 +2201,16 @@ impl<'a> ComponentTree<'a> {
             buffer.set_scroll(scroll_x, final_scroll_y);
 
             // Convert absolute cursor position to relative position within visible area
-            let mut relative_row = (cursor_tree_row as usize).saturating_sub(final_scroll_y);
-            let mut relative_col = (cursor_tree_col as usize).saturating_sub(scroll_x) as u16;
+            let relative_row = (cursor_tree_row as usize).saturating_sub(final_scroll_y);
+            let relative_col = (cursor_tree_col as usize).saturating_sub(scroll_x) as u16;
+
+            // Components that break their own text into rows say where the cursor lands on them
+            let placed = component.screen_cursor(
+                Cursor::from_xy(relative_row as u16, relative_col),
+                rect.width,
+            );
+            let mut relative_row = placed.row as usize;
+            let mut relative_col = placed.col;
 
             // Set cursor position in buffer as component-relative
             buffer.set_cursor_position(relative_col, relative_row as u16);
@@ -1585,6 +2285,19 @@ impl<'a> ComponentTree<'a> {
             }
         }
 
+        if formatting.decoration != Decoration::default() && self.is_dirty(id) {
+            let title = self.components.get(id).and_then(ComponentNode::title);
+            let footer = self.components.get(id).and_then(ComponentNode::footer);
+            let scroll = self.scroll_position(id);
+            formatting.decoration.draw(
+                stdout,
+                rect,
+                title.as_deref(),
+                footer.as_deref(),
+                scroll,
+            )?;
+        }
+
         // Render children
         if let Some(child_ids_vec) = self.children(id) {
             // Get this component's scroll offset and bounds
@@ -1623,7 +2336,6 @@ impl<'a> ComponentTree<'a> {
                 }
             }
 
-
             // Limit scrolling: prevent scrolling past the last child
             if formatting.overflow_y == Overflow::Scroll && !child_ids_vec.is_empty() {
                 // Maximum scroll index is the number of children
@@ -1639,7 +2351,10 @@ impl<'a> ComponentTree<'a> {
 
             // Track the lowest point rendered by children
             let mut lowest_rendered_y: Option<u16> = None;
-            let mut current_screen_y = parent_rect.y;
+            let scroll_offset = self.scroll_offset(id, final_scroll_y);
+            // Children stop short of the bottom border
+            let inner = formatting.decoration.inner(parent_rect);
+            let inner_bottom = inner.y + inner.height;
 
             for (child_index, child_id) in child_ids_vec.iter().enumerate() {
                 // For vertical layouts, skip children before the scroll position
@@ -1657,17 +2372,19 @@ impl<'a> ComponentTree<'a> {
                         continue;
                     }
                 };
+                // Children scrolled past are above the parent, the first one shown is at its top
+                let current_screen_y = parent_rect.y + original_y.saturating_sub(scroll_offset);
 
                 // Stop if this child would render completely below the parent (vertical layouts only)
                 if formatting.layout_mode == LayoutMode::VerticalSplit
-                    && current_screen_y >= parent_rect.y + parent_rect.height
+                    && current_screen_y >= inner_bottom
                 {
                     break;
                 }
 
                 // Stop if child doesn't fit completely in visible range (atomic rendering, vertical only)
                 if formatting.layout_mode == LayoutMode::VerticalSplit
-                    && current_screen_y + child_height as u16 > parent_rect.y + parent_rect.height
+                    && current_screen_y + child_height as u16 > inner_bottom
                 {
                     break;
                 }
@@ -1682,9 +2399,11 @@ impl<'a> ComponentTree<'a> {
                         Some(current_lowest) => current_lowest.max(child_screen_bottom),
                     });
 
-                    // Temporarily adjust child's y position for rendering
+                    // Temporarily adjust child's position for rendering, x is relative to a parent that may be offset
+                    let original_x = self.rects.get(*child_id).map(|r| r.x).unwrap_or(0);
                     {
                         if let Some(child_rect_mut) = self.rects.get_mut(*child_id) {
+                            child_rect_mut.x = parent_rect.x + original_x;
                             child_rect_mut.y = current_screen_y;
                         }
                     } // Drop the mutable borrow
@@ -1692,15 +2411,13 @@ impl<'a> ComponentTree<'a> {
                     // Render child
                     let _ = self.render_node(*child_id, stdout);
 
-                    // Restore original y position for next frame
+                    // Restore original position for next frame
                     {
                         if let Some(child_rect_mut) = self.rects.get_mut(*child_id) {
+                            child_rect_mut.x = original_x;
                             child_rect_mut.y = original_y;
                         }
                     }
-
-                    // Advance screen position for next child
-                    current_screen_y += child_height as u16;
                 } else {
                     // For horizontal layouts, convert relative rect positions to absolute screen positions
                     let (original_x, child_width) = {
@@ -1740,12 +2457,13 @@ impl<'a> ComponentTree<'a> {
                 }
             }
 
-            // Fill remaining space below children with whitespace
+            // Fill remaining space below children with whitespace, inside the decoration
             if let Some(lowest_y) = lowest_rendered_y {
-                let parent_bottom = parent_rect.y + parent_rect.height;
+                let inner = formatting.decoration.inner(parent_rect);
+                let parent_bottom = inner.y + inner.height;
                 if lowest_y < parent_bottom {
                     for y in lowest_y..parent_bottom {
-                        for x in parent_rect.x..(parent_rect.x + parent_rect.width) {
+                        for x in inner.x..(inner.x + inner.width) {
                             stdout.execute(MoveTo(x, y))?;
                             stdout.execute(Print(' '))?;
                         }
@@ -1757,9 +2475,126 @@ impl<'a> ComponentTree<'a> {
         Ok(())
     }
 
-    fn composite_buffer_hide(
+    /// Rows of a vertical layout above the first child shown, `scroll` children down
+    fn scroll_offset(&self, id: ComponentId, scroll: usize) -> u16 {
+        if scroll == 0 {
+            return 0;
+        }
+        let Some(&first) = self
+            .children
+            .get(id)
+            .and_then(|children| children.get(scroll))
+        else {
+            return 0;
+        };
+        let padding = self.formatting.get(id).copied().unwrap_or_default().inset();
+        let margin = self
+            .formatting
+            .get(first)
+            .copied()
+            .unwrap_or_default()
+            .margin;
+        let y = self.rects.get(first).map(|rect| rect.y).unwrap_or(0);
+        y.saturating_sub(margin.top).saturating_sub(padding.top)
+    }
+
+    /// How far a vertical container with `Overflow::Scroll` is scrolled through its children, in rows
+    fn scroll_position(&self, id: ComponentId) -> Option<ScrollPosition> {
+        let formatting = self.formatting.get(id).copied().unwrap_or_default();
+        let child_ids = self.children.get(id)?;
+        if formatting.overflow_y != Overflow::Scroll
+            || formatting.layout_mode != LayoutMode::VerticalSplit
+            || child_ids.is_empty()
+        {
+            return None;
+        }
+        let inset = formatting.inset();
+        let bottom = child_ids
+            .iter()
+            .map(|&child_id| {
+                let rect = self.rects.get(child_id).copied().unwrap_or_default();
+                let margin = self
+                    .formatting
+                    .get(child_id)
+                    .copied()
+                    .unwrap_or_default()
+                    .margin;
+                rect.y as usize + rect.height as usize + margin.bottom as usize
+            })
+            .max()
+            .unwrap_or(0);
+        let rect = self.rects.get(id).copied().unwrap_or_default();
+        let scroll = self.scroll_y.get(id).copied().unwrap_or(0);
+        Some(ScrollPosition {
+            offset: self.scroll_offset(id, scroll) as usize,
+            visible: rect.height.saturating_sub(inset.vertical()) as usize,
+            content: bottom.saturating_sub(inset.top as usize),
+        })
+    }
+
+    /// Blank what padding, margins and alignment leave of a container's rect, the children draw the rest
+    fn blank_gaps<W: Write + ?Sized>(
+        &self,
+        stdout: &mut W,
+        id: ComponentId,
+        rect: Rect,
+    ) -> Result<()> {
+        let formatting = self.formatting.get(id).copied().unwrap_or_default();
+        let child_ids = self.children.get(id).cloned().unwrap_or_default();
+        let spaced = formatting.inset() != Edges::default()
+            || formatting.main_align != MainAlign::Start
+            || formatting.cross_align != CrossAlign::Start
+            || child_ids.iter().any(|&child_id| {
+                self.formatting
+                    .get(child_id)
+                    .is_some_and(|child| child.margin != Edges::default())
+            });
+        if child_ids.is_empty() || !spaced {
+            return Ok(());
+        }
+        let vertical = formatting.layout_mode == LayoutMode::VerticalSplit;
+        let scroll = if vertical {
+            self.scroll_y.get(id).copied().unwrap_or(0)
+        } else {
+            0
+        };
+        let scroll_offset = self.scroll_offset(id, scroll);
+        let covered: Vec<Rect> = child_ids
+            .iter()
+            .skip(scroll)
+            .filter_map(|&child_id| self.rects.get(child_id))
+            .map(|child| Rect {
+                x: rect.x + child.x,
+                y: rect.y + child.y.saturating_sub(scroll_offset),
+                ..*child
+            })
+            .collect();
+        for y in rect.y..rect.y + rect.height {
+            let mut spans: Vec<(u16, u16)> = covered
+                .iter()
+                .filter(|child| child.y <= y && y < child.y + child.height)
+                .map(|child| (child.x, child.x + child.width))
+                .collect();
+            spans.sort_unstable();
+            let mut x = rect.x;
+            for (start, end) in spans
+                .into_iter()
+                .chain([(rect.x + rect.width, rect.x + rect.width)])
+            {
+                let start = start.min(rect.x + rect.width);
+                if start > x {
+                    stdout.execute(MoveTo(x, y))?;
+                    stdout.execute(Print(" ".repeat((start - x) as usize)))?;
+                }
+                x = x.max(end);
+            }
+        }
+        Ok(())
+    }
+
+    fn composite_buffer_hide<W: Write + ?Sized>(
         &self,
-        stdout: &mut Stdout,
+        stdout: &mut W,
         buffer: &TerminalBuffer,
         start_x: u16,
         start_y: u16,
@@ -1779,8 +2614,8 @@ impl<'a> ComponentTree<'a> {
                         skipped_newlines += 1;
                         x = 0;
                     }
-                    TerminalCommand::Print(_) => {
-                        x += 1;
+                    TerminalCommand::Print(cluster) => {
+                        x += cluster_width(cluster) as u16;
                         if x >= buffer.width() {
                             skipped_newlines += 1;
                             x = 0;
@@ -1797,16 +2632,22 @@ impl<'a> ComponentTree<'a> {
             }
 
             match cmd {
-                TerminalCommand::Print(ch) => {
-                    if x >= buffer.width() {
-                        // Skip this character (hide overflow)
+                TerminalCommand::Print(cluster) => {
+                    let cluster_width = cluster_width(cluster) as u16;
+                    if x + cluster_width > buffer.width() {
+                        // Skip this cluster (hide overflow), blanking the cell a wide one doesn't fit in
+                        while x < buffer.width() {
+                            stdout.execute(MoveTo(start_x + x, start_y + screen_y))?;
+                            stdout.execute(Print(' '))?;
+                            x += 1;
+                        }
                         continue;
                     }
 
                     // Move to position and print
                     stdout.execute(MoveTo(start_x + x, start_y + screen_y))?;
-                    stdout.execute(Print(ch))?;
-                    x += 1;
+                    stdout.execute(Print(cluster))?;
+                    x += cluster_width;
                 }
                 TerminalCommand::Newline => {
                     // Reset colors before newline
@@ -1826,14 +2667,21 @@ impl<'a> ComponentTree<'a> {
                 TerminalCommand::SetBackground(color) => {
                     stdout.execute(SetBackgroundColor(*color))?;
                 }
+                TerminalCommand::SetUnderline(Some(color)) => {
+                    stdout.execute(SetUnderlineColor(*color))?;
+                    stdout.execute(SetAttribute(Attribute::Underlined))?;
+                }
+                TerminalCommand::SetUnderline(None) => {
+                    stdout.execute(SetAttribute(Attribute::NoUnderline))?;
+                }
                 TerminalCommand::Clear => {
                     // Skip for now
                 }
             }
         }
 
-        // Always pad the current line if we have content, even if we hit max_lines
-        if x > 0 || screen_y > 0 {
+        // Always pad the current line, a line with nothing printed on it is blanked
+        if screen_y < max_lines {
             stdout.execute(ResetColor)?;
             while x < buffer.width() {
                 stdout.execute(MoveTo(start_x + x, start_y + screen_y))?;
@@ -1855,9 +2703,9 @@ impl<'a> ComponentTree<'a> {
         Ok(())
     }
 
-    fn composite_buffer_wrap(
+    fn composite_buffer_wrap<W: Write + ?Sized>(
         &self,
-        stdout: &mut Stdout,
+        stdout: &mut W,
         buffer: &TerminalBuffer,
         start_x: u16,
         start_y: u16,
@@ -1877,8 +2725,8 @@ impl<'a> ComponentTree<'a> {
                         skipped_newlines += 1;
                         x = 0;
                     }
-                    TerminalCommand::Print(_ch) => {
-                        x += 1;
+                    TerminalCommand::Print(cluster) => {
+                        x += cluster_width(cluster) as u16;
                         if x >= buffer.width() {
                             skipped_newlines += 1;
                             x = 0;
@@ -1895,9 +2743,10 @@ impl<'a> ComponentTree<'a> {
             }
 
             match cmd {
-                TerminalCommand::Print(ch) => {
-                    // Wrap to next line if we overflow width
-                    if x >= buffer.width() {
+                TerminalCommand::Print(cluster) => {
+                    let cluster_width = cluster_width(cluster) as u16;
+                    // Wrap to next line if we overflow width, wide clusters don't split across lines
+                    if x + cluster_width > buffer.width() {
                         // Pad the line we're leaving
                         while x < buffer.width() {
                             stdout.execute(MoveTo(start_x + x, start_y + screen_y))?;
@@ -1915,8 +2764,8 @@ impl<'a> ComponentTree<'a> {
 
                     // Move to position and print
                     stdout.execute(MoveTo(start_x + x, start_y + screen_y))?;
-                    stdout.execute(Print(ch))?;
-                    x += 1;
+                    stdout.execute(Print(cluster))?;
+                    x += cluster_width;
                 }
                 TerminalCommand::Newline => {
                     // Pad the rest of the line with spaces
@@ -1935,6 +2784,13 @@ impl<'a> ComponentTree<'a> {
                 TerminalCommand::SetBackground(color) => {
                     stdout.execute(SetBackgroundColor(*color))?;
                 }
+                TerminalCommand::SetUnderline(Some(color)) => {
+                    stdout.execute(SetUnderlineColor(*color))?;
+                    stdout.execute(SetAttribute(Attribute::Underlined))?;
+                }
+                TerminalCommand::SetUnderline(None) => {
+                    stdout.execute(SetAttribute(Attribute::NoUnderline))?;
+                }
                 TerminalCommand::Clear => {
                     // Skip for now
                 }
@@ -2058,12 +2914,12 @@ impl<'a> ComponentTree<'a> {
             0
         };
 
-        let new_scroll = ((current_scroll as isize + amount)
-            .max(0) as usize)
-            .min(max_scroll);
+        let new_scroll = ((current_scroll as isize + amount).max(0) as usize).min(max_scroll);
 
         if new_scroll != current_scroll {
-            self.scroll_y.insert(id, new_scroll);
+            if let Some(scroll) = self.scroll_y.get_mut(id) {
+                *scroll = new_scroll;
+            }
             self.mark_dirty(id);
 
             // Get the viewport height to determine visible range
@@ -2085,9 +2941,7 @@ impl<'a> ComponentTree<'a> {
                             break;
                         }
                     }
-                    focused_child.and_then(|child| {
-                        child_ids_vec.iter().position(|&c| c == child)
-                    })
+                    focused_child.and_then(|child| child_ids_vec.iter().position(|&c| c == child))
                 } else {
                     None
                 };
@@ -2111,7 +2965,8 @@ impl<'a> ComponentTree<'a> {
                         if idx < new_scroll {
                             // Focused child was scrolled above, clamp to first visible child
                             if let Some(first_visible_child) = child_ids_vec.get(new_scroll) {
-                                let deepest = self.find_last_focusable_descendant(*first_visible_child);
+                                let deepest =
+                                    self.find_last_focusable_descendant(*first_visible_child);
                                 self.focus = deepest;
                                 self.update_focus_path();
                                 // Update container's cursor to point to this child
@@ -2121,9 +2976,12 @@ impl<'a> ComponentTree<'a> {
                             }
                         } else {
                             // Focused child was scrolled below, clamp to last visible child
-                            let last_visible_idx = (new_scroll + visible_height).saturating_sub(1).min(child_ids_vec.len() - 1);
+                            let last_visible_idx = (new_scroll + visible_height)
+                                .saturating_sub(1)
+                                .min(child_ids_vec.len() - 1);
                             if let Some(last_visible_child) = child_ids_vec.get(last_visible_idx) {
-                                let deepest = self.find_last_focusable_descendant(*last_visible_child);
+                                let deepest =
+                                    self.find_last_focusable_descendant(*last_visible_child);
                                 self.focus = deepest;
                                 self.update_focus_path();
                                 // Update container's cursor to point to this child
@@ -2147,7 +3005,6 @@ impl<'a> ComponentTree<'a> {
                 }
             }
 
-
             // Clamp cursor to visible range after scrolling
             let cursor_tree_row = self.cursor_row.get(id).copied().unwrap_or(0);
 
@@ -2168,44 +3025,123 @@ impl<'a> ComponentTree<'a> {
 
     /// Handle an event for the entire tree
     pub fn update(&mut self, event: ReovimEvent) -> Result<()> {
-        // Handle scroll events at tree level
-        if let ReovimEvent::Mouse(mouse_event) = &event {
-            match mouse_event.kind {
-                MouseEventKind::Down(MouseButton::Left) => {
-                    let rect = self.rect(self.focus).unwrap_or_default();
-                    if rect.contains(mouse_event.column, mouse_event.row) {
-                        self.update_node(self.root, &event)?;
-                    }
-                    return Ok(());
-                }
-                MouseEventKind::ScrollUp => {
-                    if let Some(container_id) =
-                        self.find_scrollable_container_at(mouse_event.column, mouse_event.row)
-                    {
-                        self.scroll_by(container_id, -1);
-                    }
-                    return Ok(());
-                }
-                MouseEventKind::ScrollDown => {
-                    if let Some(container_id) =
-                        self.find_scrollable_container_at(mouse_event.column, mouse_event.row)
-                    {
-                        self.scroll_by(container_id, 1);
-                    }
-                    return Ok(());
-                }
-                _ => {}
-            }
+        // Pressing and dragging on a scrollbar scrolls its container, nothing else sees the mouse
+        if let ReovimEvent::Mouse(mouse_event) = &event
+            && self.drag_scrollbar(*mouse_event)
+        {
+            return Ok(());
         }
 
-        // For other events, pass to root
-        self.update_node(self.root, &event)?;
+        self.dispatch(&event)?;
+
+        // Scroll events no component consumed scroll the container under the mouse
+        if let ReovimEvent::Mouse(mouse_event) = &event
+            && !self.event_consumed
+        {
+            let amount = match mouse_event.kind {
+                MouseEventKind::ScrollUp => -1,
+                MouseEventKind::ScrollDown => 1,
+                _ => 0,
+            };
+            if amount != 0
+                && let Some(container_id) =
+                    self.find_scrollable_container_at(mouse_event.column, mouse_event.row)
+            {
+                self.scroll_by(container_id, amount);
+            }
+        }
 
         // After handling events, initialize any pending components
         self.initialize_pending_components()?;
+
+        // Then dispatch anything components emitted, in the order it was emitted
+        while !self.queued_events.is_empty() {
+            for queued in std::mem::take(&mut self.queued_events) {
+                self.dispatch(&queued)?;
+                self.initialize_pending_components()?;
+            }
+        }
         Ok(())
     }
 
+    /// Scroll the container whose scrollbar the mouse pressed or drags to where it is, false for other events
+    fn drag_scrollbar(&mut self, mouse_event: MouseEvent) -> bool {
+        let (col, row) = (mouse_event.column, mouse_event.row);
+        let id = match (mouse_event.kind, self.scrollbar_drag) {
+            (MouseEventKind::Down(MouseButton::Left), _) => {
+                // The innermost container drawn there, the last one laid out
+                let Some(id) = (0..self.components.len()).rev().find(|&id| {
+                    let formatting = self.formatting.get(id).copied().unwrap_or_default();
+                    let rect = self.screen_rects.get(id).copied().unwrap_or_default();
+                    self.scroll_position(id).is_some()
+                        && formatting
+                            .decoration
+                            .track(rect)
+                            .is_some_and(|track| track.contains(col, row))
+                }) else {
+                    return false;
+                };
+                self.scrollbar_drag = Some(id);
+                id
+            }
+            (MouseEventKind::Drag(MouseButton::Left), Some(id)) => id,
+            (MouseEventKind::Up(MouseButton::Left), Some(_)) => {
+                self.scrollbar_drag = None;
+                return true;
+            }
+            _ => return false,
+        };
+        let formatting = self.formatting.get(id).copied().unwrap_or_default();
+        let rect = self.screen_rects.get(id).copied().unwrap_or_default();
+        let (Some(track), Some(scroll)) =
+            (formatting.decoration.track(rect), self.scroll_position(id))
+        else {
+            return true;
+        };
+        // The first child at or past the row the mouse points at goes to the top
+        let offset = decoration::offset_at(track.height, row.saturating_sub(track.y), scroll);
+        let top = formatting.inset().top as usize + offset;
+        let child_ids = self.children.get(id).cloned().unwrap_or_default();
+        let target = child_ids
+            .iter()
+            .position(|&child_id| {
+                let child = self.rects.get(child_id).copied().unwrap_or_default();
+                child.y as usize + child.height as usize > top
+            })
+            .unwrap_or(child_ids.len().saturating_sub(1));
+        let current = self.scroll_y.get(id).copied().unwrap_or(0);
+        self.scroll_by(id, target as isize - current as isize);
+        true
+    }
+
+    /// Pass an event through the tiled tree, then the overlays from the top down until one consumes it
+    fn dispatch(&mut self, event: &ReovimEvent) -> Result<()> {
+        self.event_consumed = false;
+        self.update_node(self.root, event)?;
+        let mut overlays = self.overlays.clone();
+        overlays.sort_by_key(|(_, overlay)| overlay.z_index);
+        overlays.reverse();
+        for (id, _) in overlays {
+            if self.event_consumed {
+                break;
+            }
+            // An earlier overlay may have removed this one
+            if self
+                .overlays
+                .iter()
+                .any(|&(overlay_id, _)| overlay_id == id)
+            {
+                self.update_node(id, event)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Take the requests components made for the host application
+    pub fn take_requests(&mut self) -> Vec<HostRequest> {
+        std::mem::take(&mut self.requests)
+    }
+
     /// Initialize children for all pending components
     pub fn initialize_pending_components(&mut self) -> Result<()> {
         // Keep processing until all pending components are initialized
@@ -2335,12 +3271,17 @@ impl<'a> ComponentTree<'a> {
                 let dirty = component.update(event.clone(), &mut commands)?;
                 if dirty {
                     self.mark_dirty(id);
+                    // What it draws may have changed size, as may what its descendants draw of its state
+                    self.invalidate_layout(id);
                 }
             }
         }
 
         if let Some(child_ids) = self.children(id) {
             for child_id in child_ids {
+                if self.event_consumed {
+                    break;
+                }
                 self.update_node(child_id, event)?;
             }
         }
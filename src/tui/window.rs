@@ -0,0 +1,768 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use anyhow::Result;
+use crossterm::style::Color;
+
+use crate::{
+    buffer::{Buffer, BufferId, list::BufferList},
+    completion::CompletionSources,
+    lsp::Lsp,
+    snippet::library::SnippetLibrary,
+    syntax::theme::Theme,
+    task::Tasks,
+    tui::{
+        Component, Formatting, LayoutMode, Measurement, Overflow,
+        command::CommandLine,
+        decoration::BorderStyle,
+        editor::{EditableText, Mode},
+        status::StatusComponent,
+        terminal_buffer::TerminalBuffer,
+        tree::{ComponentCommands, ComponentId, ComponentNode},
+        wrap::WrapOptions,
+    },
+    vcs::diff::Algorithm,
+};
+
+pub type WindowId = usize;
+
+/// Rows a window needs besides its text, for the status line
+const STATUS_HEIGHT: u16 = 1;
+/// Columns drawn between side by side windows
+const SEPARATOR_WIDTH: u16 = 1;
+
+/// A view onto a buffer, every window has its own cursor and scroll position
+pub struct Window {
+    id: WindowId,
+    pub buffer: Rc<RefCell<Buffer>>,
+    /// (line, col) of the cursor
+    pub cursor: (usize, usize),
+    /// First visible row, the row the text component's first row component shows
+    pub scroll: usize,
+    /// The buffer this window showed before the current one, for `Ctrl-^`
+    pub alternate: Option<BufferId>,
+    /// The text component showing this window, set each time the window tree is built
+    pub text_id: Option<ComponentId>,
+    /// What each row of the text component shows, set each time it lays out the buffer
+    pub rows: Rows,
+    /// Compared with another window of the tab, set by `:diffthis`
+    pub diff: bool,
+    /// The window this one is compared with, paired up when the tab's windows are built
+    pub diff_with: Option<DiffPartner>,
+    /// How lines too long for the window are shown
+    pub wrap: WrapOptions,
+}
+
+/// What one row of a window's text shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Row {
+    Line(usize),
+    /// Room for lines only the other window in diff mode has
+    Filler,
+    /// Lines `start` up to `end` folded into one
+    Fold {
+        start: usize,
+        end: usize,
+    },
+}
+
+/// What each row of a window shows, worked out for the rows asked about rather than listed for the whole buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rows {
+    /// A row per line, but for closed folds which take one each
+    Lines {
+        count: usize,
+        /// Closed folds in order as `(start, end, row)`, the row being where the fold is shown
+        folds: Vec<(usize, usize, usize)>,
+    },
+    /// Every row listed, for diff mode where fillers line the windows up
+    Listed(Vec<Row>),
+}
+
+impl Default for Rows {
+    fn default() -> Self {
+        Rows::Listed(Vec::new())
+    }
+}
+
+impl Rows {
+    /// Rows for `count` lines with the closed folds `(start, end)` in order, none inside another
+    pub fn lines(count: usize, closed: &[(usize, usize)]) -> Rows {
+        let mut hidden = 0;
+        let folds = closed
+            .iter()
+            .map(|&(start, end)| {
+                let row = start - hidden;
+                hidden += end - start - 1;
+                (start, end, row)
+            })
+            .collect();
+        Rows::Lines { count, folds }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Rows::Lines { count, folds } => match folds.last() {
+                Some(&(_, end, row)) => row + 1 + count.saturating_sub(end),
+                None => *count,
+            },
+            Rows::Listed(rows) => rows.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, row: usize) -> Option<Row> {
+        match self {
+            Rows::Lines { folds, .. } => {
+                if row >= self.len() {
+                    return None;
+                }
+                let before = folds.partition_point(|&(_, _, fold_row)| fold_row <= row);
+                Some(match before.checked_sub(1).map(|index| folds[index]) {
+                    Some((start, end, fold_row)) if fold_row == row => Row::Fold { start, end },
+                    Some((_, end, fold_row)) => Row::Line(end + row - fold_row - 1),
+                    None => Row::Line(row),
+                })
+            }
+            Rows::Listed(rows) => rows.get(row).copied(),
+        }
+    }
+
+    /// The row showing `line`, the fold holding it when folded away
+    pub fn row_of(&self, line: usize) -> usize {
+        if self.is_empty() {
+            return line;
+        }
+        match self {
+            Rows::Lines { folds, .. } => {
+                let before = folds.partition_point(|&(start, _, _)| start <= line);
+                let row = match before.checked_sub(1).map(|index| folds[index]) {
+                    Some((_, end, fold_row)) if line < end => fold_row,
+                    Some((_, end, fold_row)) => fold_row + 1 + line - end,
+                    None => line,
+                };
+                row.min(self.len() - 1)
+            }
+            Rows::Listed(rows) => rows
+                .iter()
+                .position(|row| match *row {
+                    Row::Line(shown) => shown == line,
+                    Row::Fold { start, end } => (start..end).contains(&line),
+                    Row::Filler => false,
+                })
+                .unwrap_or(rows.len() - 1),
+        }
+    }
+
+    /// The line the cursor is on at `row`, for a filler the next line shown or else the one before
+    pub fn line_at(&self, row: usize) -> usize {
+        let line = |row: Row| match row {
+            Row::Line(line) | Row::Fold { start: line, .. } => Some(line),
+            Row::Filler => None,
+        };
+        if self.is_empty() {
+            return row;
+        }
+        let row = row.min(self.len() - 1);
+        match self {
+            Rows::Lines { .. } => self.get(row).and_then(line).unwrap_or(0),
+            Rows::Listed(rows) => rows[row..]
+                .iter()
+                .copied()
+                .find_map(line)
+                .or_else(|| rows[..row].iter().rev().copied().find_map(line))
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// The other window of a diff
+#[derive(Clone)]
+pub struct DiffPartner {
+    pub window: WindowId,
+    pub buffer: Rc<RefCell<Buffer>>,
+    /// This window comes first in the layout, its buffer is the old side
+    pub first: bool,
+}
+
+impl Window {
+    pub fn new(id: WindowId, buffer: Rc<RefCell<Buffer>>) -> Self {
+        let (cursor, scroll) = {
+            let buffer = buffer.borrow();
+            (buffer.cursor, buffer.scroll)
+        };
+        Self {
+            id,
+            buffer,
+            cursor,
+            scroll,
+            alternate: None,
+            text_id: None,
+            rows: Rows::default(),
+            diff: false,
+            diff_with: None,
+            wrap: WrapOptions::default(),
+        }
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.id
+    }
+
+    /// The row showing `line`, the fold holding it when folded away
+    pub fn row_of(&self, line: usize) -> usize {
+        self.rows.row_of(line)
+    }
+
+    /// The line the cursor is on at `row`, for a filler the next line shown or else the one before
+    pub fn line_at(&self, row: usize) -> usize {
+        self.rows.line_at(row)
+    }
+
+    /// Show another buffer, the buffer being left remembers where the cursor was
+    pub fn show(&mut self, buffer: Rc<RefCell<Buffer>>) {
+        if Rc::ptr_eq(&buffer, &self.buffer) {
+            return;
+        }
+        {
+            let mut previous = self.buffer.borrow_mut();
+            previous.cursor = self.cursor;
+            previous.scroll = self.scroll;
+            self.alternate = Some(previous.id());
+        }
+        {
+            let next = buffer.borrow();
+            self.cursor = next.cursor;
+            self.scroll = next.scroll;
+        }
+        self.buffer = buffer;
+    }
+}
+
+/// How windows are arranged on screen
+/// `LayoutMode::VerticalSplit` stacks windows like `:split`, `LayoutMode::HorizontalSplit` puts them side by side like `:vsplit`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layout {
+    Window(WindowId),
+    /// Children with their size along the split, `None` shares whatever is left equally
+    Split {
+        mode: LayoutMode,
+        children: Vec<(Layout, Option<u16>)>,
+    },
+}
+
+/// How `Ctrl-W` resizing changes a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resize {
+    /// Grow or shrink by a number of cells
+    By(i32),
+    /// As large as the other windows allow
+    Max,
+}
+
+/// Cells taken by separators between `count` windows
+fn separators(mode: LayoutMode, count: usize) -> u16 {
+    match mode {
+        LayoutMode::HorizontalSplit => count.saturating_sub(1) as u16 * SEPARATOR_WIDTH,
+        LayoutMode::VerticalSplit => 0,
+    }
+}
+
+/// Sizes of a split's children along the split, matching how the component tree lays them out
+fn child_sizes(children: &[(Layout, Option<u16>)], mode: LayoutMode, available: u16) -> Vec<u16> {
+    let fixed: u16 = children
+        .iter()
+        .filter_map(|(_, size)| size.map(|size| size.min(available)))
+        .fold(0, u16::saturating_add);
+    let fill_count = children.iter().filter(|(_, size)| size.is_none()).count() as u16;
+    let remaining = available
+        .saturating_sub(separators(mode, children.len()))
+        .saturating_sub(fixed);
+    let (fill, mut extra) = match fill_count {
+        0 => (0, 0),
+        count => (remaining / count, remaining % count),
+    };
+    children
+        .iter()
+        .map(|(_, size)| match size {
+            Some(size) => (*size).min(available),
+            None => {
+                let share = fill + u16::from(extra > 0);
+                extra = extra.saturating_sub(1);
+                share
+            }
+        })
+        .collect()
+}
+
+impl Layout {
+    /// Every window in screen order, left to right and top to bottom
+    pub fn windows(&self) -> Vec<WindowId> {
+        match self {
+            Layout::Window(id) => vec![*id],
+            Layout::Split { children, .. } => children
+                .iter()
+                .flat_map(|(child, _)| child.windows())
+                .collect(),
+        }
+    }
+
+    fn contains(&self, target: WindowId) -> bool {
+        match self {
+            Layout::Window(id) => *id == target,
+            Layout::Split { children, .. } => {
+                children.iter().any(|(child, _)| child.contains(target))
+            }
+        }
+    }
+
+    /// Split `target`, putting the `new` window above or to the left of it
+    pub fn split(&mut self, target: WindowId, new: WindowId, mode: LayoutMode) {
+        match self {
+            Layout::Window(id) if *id == target => {
+                *self = Layout::Split {
+                    mode,
+                    children: vec![(Layout::Window(new), None), (Layout::Window(target), None)],
+                };
+            }
+            Layout::Window(_) => {}
+            Layout::Split {
+                mode: split_mode,
+                children,
+            } => {
+                let position = children
+                    .iter()
+                    .position(|(child, _)| *child == Layout::Window(target));
+                match position {
+                    // Splitting along the same direction adds a sibling, the two share the old window's space
+                    Some(index) if *split_mode == mode => {
+                        children[index].1 = None;
+                        children.insert(index, (Layout::Window(new), None));
+                    }
+                    _ => {
+                        for (child, _) in children.iter_mut() {
+                            child.split(target, new, mode);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remove a window, a split left with a single child is replaced by it
+    pub fn remove(&mut self, target: WindowId) {
+        let Layout::Split { children, .. } = self else {
+            return;
+        };
+        let count = children.len();
+        children.retain(|(child, _)| *child != Layout::Window(target));
+        for (child, _) in children.iter_mut() {
+            child.remove(target);
+        }
+        // The last child takes up the space the removed window leaves
+        if children.len() != count
+            && let Some((_, size)) = children.last_mut()
+        {
+            *size = None;
+        }
+        if children.len() == 1 {
+            let (only, _) = children.remove(0);
+            *self = only;
+        }
+    }
+
+    /// Give every window an equal share of its split
+    pub fn equalize(&mut self) {
+        if let Layout::Split { children, .. } = self {
+            for (child, size) in children.iter_mut() {
+                *size = None;
+                child.equalize();
+            }
+        }
+    }
+
+    /// The window next to `target` when moving along `mode`, forward is down or right
+    pub fn neighbor(&self, target: WindowId, mode: LayoutMode, forward: bool) -> Option<WindowId> {
+        let Layout::Split {
+            mode: split_mode,
+            children,
+        } = self
+        else {
+            return None;
+        };
+        let index = children
+            .iter()
+            .position(|(child, _)| child.contains(target))?;
+        if let Some(found) = children[index].0.neighbor(target, mode, forward) {
+            return Some(found);
+        }
+        if *split_mode != mode {
+            return None;
+        }
+        let next = if forward {
+            index + 1
+        } else {
+            index.checked_sub(1)?
+        };
+        let (sibling, _) = children.get(next)?;
+        Some(sibling.edge_window(mode, forward))
+    }
+
+    /// The window entered first when moving into this layout along `mode`
+    fn edge_window(&self, mode: LayoutMode, forward: bool) -> WindowId {
+        match self {
+            Layout::Window(id) => *id,
+            Layout::Split {
+                mode: split_mode,
+                children,
+            } => {
+                let (child, _) = if *split_mode == mode && !forward {
+                    &children[children.len() - 1]
+                } else {
+                    &children[0]
+                };
+                child.edge_window(mode, forward)
+            }
+        }
+    }
+
+    /// Smallest size along `mode` that keeps every window usable
+    fn min_size(&self, mode: LayoutMode) -> u16 {
+        match self {
+            Layout::Window(_) => match mode {
+                LayoutMode::VerticalSplit => 1 + STATUS_HEIGHT,
+                LayoutMode::HorizontalSplit => 1,
+            },
+            Layout::Split {
+                mode: split_mode,
+                children,
+            } => {
+                let mins = children.iter().map(|(child, _)| child.min_size(mode));
+                if *split_mode == mode {
+                    mins.sum::<u16>() + separators(mode, children.len())
+                } else {
+                    mins.max().unwrap_or(0)
+                }
+            }
+        }
+    }
+
+    /// Resize the window along `mode` inside a layout of `width` by `height` cells
+    /// Space is taken from, or given to, the windows after it first and then those before it
+    /// Returns false when no split along `mode` contains the window
+    pub fn resize(
+        &mut self,
+        target: WindowId,
+        mode: LayoutMode,
+        resize: Resize,
+        width: u16,
+        height: u16,
+    ) -> bool {
+        let Layout::Split {
+            mode: split_mode,
+            children,
+        } = self
+        else {
+            return false;
+        };
+        let split_mode = *split_mode;
+        let length = match split_mode {
+            LayoutMode::HorizontalSplit => width,
+            LayoutMode::VerticalSplit => height,
+        };
+        let mut sizes = child_sizes(children, split_mode, length);
+        let Some(index) = children
+            .iter()
+            .position(|(child, _)| child.contains(target))
+        else {
+            return false;
+        };
+        let (child_width, child_height) = match split_mode {
+            LayoutMode::HorizontalSplit => (sizes[index], height),
+            LayoutMode::VerticalSplit => (width, sizes[index]),
+        };
+        if children[index]
+            .0
+            .resize(target, mode, resize, child_width, child_height)
+        {
+            return true;
+        }
+        if split_mode != mode || children.len() < 2 {
+            return false;
+        }
+
+        let available = length.saturating_sub(separators(mode, children.len()));
+        let mins: Vec<u16> = children
+            .iter()
+            .map(|(child, _)| child.min_size(mode))
+            .collect();
+        let others_min: u16 = mins
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .map(|(_, min)| *min)
+            .sum();
+        let max = available.saturating_sub(others_min).max(mins[index]);
+        let current = sizes[index];
+        let wanted = match resize {
+            Resize::By(delta) => {
+                (current as i32 + delta).clamp(mins[index] as i32, max as i32) as u16
+            }
+            Resize::Max => max,
+        };
+
+        let mut delta = wanted as i32 - current as i32;
+        sizes[index] = wanted;
+        let order = (index + 1..children.len()).chain((0..index).rev());
+        for other in order {
+            if delta == 0 {
+                break;
+            }
+            if delta > 0 {
+                let take = (sizes[other].saturating_sub(mins[other]) as i32).min(delta);
+                sizes[other] -= take as u16;
+                delta -= take;
+            } else {
+                sizes[other] += (-delta) as u16;
+                delta = 0;
+            }
+        }
+        sizes[index] = (sizes[index] as i32 - delta.max(0)) as u16;
+
+        // Fix every size but the last, which takes whatever the terminal has left
+        let last = children.len() - 1;
+        for (position, (_, size)) in children.iter_mut().enumerate() {
+            *size = (position != last).then_some(sizes[position]);
+        }
+        true
+    }
+}
+
+/// Compare the first two windows in diff mode, in layout order, the others show their buffer as usual
+pub fn pair_diff_windows(layout: &Layout, windows: &[Rc<RefCell<Window>>]) {
+    let diffed: Vec<_> = layout
+        .windows()
+        .into_iter()
+        .filter_map(|id| windows.iter().find(|window| window.borrow().id() == id))
+        .filter(|window| window.borrow().diff)
+        .take(2)
+        .collect();
+    for window in windows {
+        window.borrow_mut().diff_with = None;
+    }
+    if let [first, second] = diffed[..] {
+        let partner = |window: &Rc<RefCell<Window>>, first: bool| {
+            let window = window.borrow();
+            DiffPartner {
+                window: window.id(),
+                buffer: window.buffer.clone(),
+                first,
+            }
+        };
+        first.borrow_mut().diff_with = Some(partner(second, true));
+        second.borrow_mut().diff_with = Some(partner(first, false));
+    }
+}
+
+/// State shared by every window component
+#[derive(Clone)]
+pub struct WindowContext {
+    pub buffers: Rc<RefCell<BufferList>>,
+    pub mode: Rc<Cell<Mode>>,
+    pub command_line: Rc<RefCell<CommandLine>>,
+    /// The window with focus
+    pub current: Rc<Cell<WindowId>>,
+    /// Runs completion sources off the main thread
+    pub tasks: Tasks,
+    pub completion: CompletionSources,
+    pub snippets: Rc<SnippetLibrary>,
+    /// Colors for syntax highlighting
+    pub theme: Rc<Theme>,
+    /// Language servers, kept up with each buffer's edits
+    pub lsp: Rc<RefCell<Lsp>>,
+    /// How windows in diff mode match up their lines, set by `:diffalgorithm`
+    pub diff_algorithm: Rc<Cell<Algorithm>>,
+    /// Screen rows the mouse wheel scrolls by, set by `:set mousescroll`
+    pub mouse_scroll: Rc<Cell<usize>>,
+    /// The border of floating windows, like the completion preview, set by `:set winborder`
+    pub win_border: Rc<Cell<Option<BorderStyle>>>,
+    /// The text last selected, which a middle click pastes
+    pub primary_selection: Rc<RefCell<String>>,
+}
+
+/// Add a component sized along its parent's split
+fn add_sized<C: Component + 'static>(
+    commands: &mut ComponentCommands,
+    component: C,
+    mode: LayoutMode,
+    size: Option<u16>,
+) -> Result<ComponentId> {
+    let mut formatting = component.default_formatting();
+    let measurement = match size {
+        Some(size) => Measurement::Cell(size as usize),
+        None => Measurement::Fill(1),
+    };
+    match mode {
+        LayoutMode::VerticalSplit => formatting.preferred_height = measurement,
+        LayoutMode::HorizontalSplit => formatting.preferred_width = measurement,
+    }
+    commands.add_child_with_formatting(ComponentNode::Component(Box::new(component)), formatting)
+}
+
+/// Lays out a `Layout`, one component per split and per window
+pub struct SplitComponent {
+    layout: Layout,
+    windows: Vec<Rc<RefCell<Window>>>,
+    context: WindowContext,
+}
+
+impl SplitComponent {
+    pub fn new(layout: Layout, windows: Vec<Rc<RefCell<Window>>>, context: WindowContext) -> Self {
+        Self {
+            layout,
+            windows,
+            context,
+        }
+    }
+
+    fn layout_mode(&self) -> LayoutMode {
+        match &self.layout {
+            Layout::Split { mode, .. } => *mode,
+            Layout::Window(_) => LayoutMode::VerticalSplit,
+        }
+    }
+
+    fn add_child(
+        &self,
+        commands: &mut ComponentCommands,
+        child: &Layout,
+        mode: LayoutMode,
+        size: Option<u16>,
+    ) -> Result<()> {
+        match child {
+            Layout::Window(id) => {
+                let window = self
+                    .windows
+                    .iter()
+                    .find(|window| window.borrow().id() == *id);
+                if let Some(window) = window {
+                    let component = WindowComponent::new(window.clone(), self.context.clone());
+                    add_sized(commands, component, mode, size)?;
+                }
+            }
+            Layout::Split { .. } => {
+                let component =
+                    SplitComponent::new(child.clone(), self.windows.clone(), self.context.clone());
+                add_sized(commands, component, mode, size)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Component for SplitComponent {
+    fn children(&mut self, commands: &mut ComponentCommands) -> Result<()> {
+        match &self.layout {
+            Layout::Window(_) => {
+                self.add_child(commands, &self.layout, LayoutMode::VerticalSplit, None)?;
+            }
+            Layout::Split { mode, children } => {
+                for (index, (child, size)) in children.iter().enumerate() {
+                    if index > 0 && *mode == LayoutMode::HorizontalSplit {
+                        commands.add_component(SeparatorComponent)?;
+                    }
+                    self.add_child(commands, child, *mode, *size)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn default_formatting(&self) -> Formatting {
+        Formatting {
+            preferred_width: Measurement::Fill(1),
+            preferred_height: Measurement::Fill(1),
+            overflow_x: Overflow::Hide,
+            overflow_y: Overflow::Hide,
+            request_focus: false,
+            layout_mode: self.layout_mode(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A window's text with its status line below
+pub struct WindowComponent {
+    window: Rc<RefCell<Window>>,
+    context: WindowContext,
+}
+
+impl WindowComponent {
+    pub fn new(window: Rc<RefCell<Window>>, context: WindowContext) -> Self {
+        Self { window, context }
+    }
+}
+
+impl Component for WindowComponent {
+    fn children(&mut self, commands: &mut ComponentCommands) -> Result<()> {
+        let text_id =
+            commands.add_component(EditableText::new(self.window.clone(), self.context.clone()))?;
+        self.window.borrow_mut().text_id = Some(text_id);
+        commands.add_component(StatusComponent::new(
+            self.window.clone(),
+            self.context.buffers.clone(),
+            self.context.current.clone(),
+        ))?;
+        Ok(())
+    }
+
+    fn default_formatting(&self) -> Formatting {
+        Formatting {
+            preferred_width: Measurement::Fill(1),
+            preferred_height: Measurement::Fill(1),
+            overflow_x: Overflow::Hide,
+            overflow_y: Overflow::Hide,
+            request_focus: false,
+            layout_mode: LayoutMode::VerticalSplit,
+            ..Default::default()
+        }
+    }
+}
+
+/// The line drawn between side by side windows
+pub struct SeparatorComponent;
+
+impl Component for SeparatorComponent {
+    fn render(
+        &self,
+        buffer: &mut TerminalBuffer,
+        _query: crate::tui::ComponentQuery,
+    ) -> Result<()> {
+        buffer.set_foreground(Color::DarkGrey);
+        for row in 0..buffer.height() {
+            if row > 0 {
+                buffer.newline();
+            }
+            buffer.write("│");
+        }
+        Ok(())
+    }
+
+    fn default_formatting(&self) -> Formatting {
+        Formatting {
+            preferred_width: Measurement::Cell(SEPARATOR_WIDTH as usize),
+            preferred_height: Measurement::Fill(1),
+            overflow_x: Overflow::Hide,
+            overflow_y: Overflow::Hide,
+            request_focus: false,
+            layout_mode: LayoutMode::VerticalSplit,
+            focusable: false,
+            ..Default::default()
+        }
+    }
+}
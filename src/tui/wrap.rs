@@ -0,0 +1,402 @@
+use std::{borrow::Cow, ops::Range};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    buffer::grapheme::{self, cluster_width},
+    tui::listchars::ListChars,
+};
+
+/// Chars a line may break after with `linebreak`, like vim's default `breakat`
+const BREAK_AT: &str = " \t!@*-+;:,./?";
+/// Columns continuation rows keep for text however deep the indent is
+const MIN_TEXT_WIDTH: usize = 20;
+
+/// How a window shows its lines, set with `:set`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrapOptions {
+    /// Long lines go on over the rows below, otherwise the view scrolls sideways
+    pub wrap: bool,
+    /// Break after a blank or punctuation that fits rather than at the last column
+    pub linebreak: bool,
+    /// Continuation rows start as far in as the line's indent
+    pub breakindent: bool,
+    /// Drawn at the start of continuation rows
+    pub showbreak: String,
+    /// Draw tabs, trailing spaces, no-break spaces and line ends with `listchars`
+    pub list: bool,
+    pub listchars: ListChars,
+    /// Columns between tab stops, the buffer's `tabstop` once the window's text is built
+    pub tabstop: usize,
+}
+
+impl Default for WrapOptions {
+    fn default() -> Self {
+        Self {
+            wrap: true,
+            linebreak: false,
+            breakindent: false,
+            showbreak: String::new(),
+            list: false,
+            listchars: ListChars::default(),
+            tabstop: 8,
+        }
+    }
+}
+
+impl WrapOptions {
+    /// The on/off option called `name`, by its full or short name
+    pub fn switch(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "wrap" => Some(&mut self.wrap),
+            "linebreak" | "lbr" => Some(&mut self.linebreak),
+            "breakindent" | "bri" => Some(&mut self.breakindent),
+            "list" => Some(&mut self.list),
+            _ => None,
+        }
+    }
+
+    /// Columns a tab starting at screen column `display` of its line takes, up to the next tab stop
+    /// `list` mode without a `tab` setting draws every tab as `^I`
+    fn tab_width(&self, display: usize) -> usize {
+        if self.list && self.listchars.tab.is_none() {
+            return 2;
+        }
+        let tabstop = self.tabstop.max(1);
+        tabstop - display % tabstop
+    }
+}
+
+/// The part of a line shown on one screen row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenLine {
+    /// Chars `start` up to `end` of the line
+    pub start: usize,
+    pub end: usize,
+    /// Blank columns before the text, from `breakindent`
+    pub indent: usize,
+    /// The row starts with the `showbreak` marker
+    pub showbreak: bool,
+}
+
+impl ScreenLine {
+    /// Columns taken before the text starts
+    pub fn prefix(&self, options: &WrapOptions) -> usize {
+        self.indent
+            + if self.showbreak {
+                width(&options.showbreak)
+            } else {
+                0
+            }
+    }
+}
+
+/// A grapheme cluster of a line, by the chars it spans
+struct Cluster {
+    start: usize,
+    end: usize,
+    bytes: Range<usize>,
+    /// Screen column it starts at, counted from the start of the line
+    display: usize,
+    width: usize,
+    /// Its last char, where `linebreak` looks for a place to break
+    last: char,
+}
+
+/// The clusters of a line, tabs as wide as it takes to reach the next tab stop
+fn line_clusters(text: &str, options: &WrapOptions) -> Vec<Cluster> {
+    let mut display = 0;
+    let mut col = 0;
+    text.grapheme_indices(true)
+        .map(|(byte, cluster)| {
+            let width = if cluster == "\t" {
+                options.tab_width(display)
+            } else {
+                cluster_width(cluster)
+            };
+            let chars = cluster.chars().count();
+            let line = Cluster {
+                start: col,
+                end: col + chars,
+                bytes: byte..byte + cluster.len(),
+                display,
+                width,
+                last: cluster.chars().last().unwrap_or(' '),
+            };
+            display += width;
+            col += chars;
+            line
+        })
+        .collect()
+}
+
+/// Screen columns before char `col` of `text`, chars past the end of the text take one each
+pub fn display_col(text: &str, col: usize, options: &WrapOptions) -> usize {
+    let clusters = line_clusters(text, options);
+    match clusters.iter().find(|cluster| cluster.end > col) {
+        Some(cluster) => cluster.display,
+        None => {
+            let end = clusters.last().map_or(0, |cluster| cluster.end);
+            let width = clusters
+                .last()
+                .map_or(0, |cluster| cluster.display + cluster.width);
+            width + col.saturating_sub(end)
+        }
+    }
+}
+
+/// The char column of the cluster drawn over screen column `display`, the end of the text when it is narrower
+pub fn col_at_display(text: &str, display: usize, options: &WrapOptions) -> usize {
+    let clusters = line_clusters(text, options);
+    match clusters
+        .iter()
+        .find(|cluster| cluster.display + cluster.width > display)
+    {
+        Some(cluster) => cluster.start,
+        None => clusters.last().map_or(0, |cluster| cluster.end),
+    }
+}
+
+/// How one cluster of a line is drawn
+pub struct Drawn<'a> {
+    /// Where the cluster is in the line's bytes
+    pub bytes: Range<usize>,
+    pub text: Cow<'a, str>,
+    /// Drawn from `listchars` in place of the text
+    pub special: bool,
+}
+
+/// What is drawn for each cluster of `text`, tabs as blanks up to the next tab stop or as `listchars` in list mode
+pub fn drawn<'a>(text: &'a str, options: &WrapOptions) -> Vec<Drawn<'a>> {
+    // Spaces from here on are trailing
+    let trail = text.trim_end_matches(' ').len();
+    line_clusters(text, options)
+        .into_iter()
+        .map(|cluster| {
+            let shown = &text[cluster.bytes.clone()];
+            let listchars = &options.listchars;
+            let special = match shown {
+                _ if !options.list => None,
+                "\t" => Some(listchars.tab(cluster.width)),
+                " " if cluster.bytes.start >= trail => listchars.trail.map(String::from),
+                "\u{a0}" | "\u{202f}" => listchars.nbsp.map(String::from),
+                _ => None,
+            };
+            let (text, special) = match special {
+                Some(special) => (Cow::Owned(special), true),
+                None if shown == "\t" => (Cow::Owned(" ".repeat(cluster.width)), false),
+                None => (Cow::Borrowed(shown), false),
+            };
+            Drawn {
+                bytes: cluster.bytes,
+                text,
+                special,
+            }
+        })
+        .collect()
+}
+
+/// Columns `text` takes on screen
+pub fn width(text: &str) -> usize {
+    grapheme::width(text)
+}
+
+/// Split text into chunks of max width, never inside a grapheme cluster
+pub fn split_by_width(text: &str, max_width: u16) -> Vec<&str> {
+    let max_width = max_width as usize;
+    let mut chunks = Vec::new();
+    let mut current_width = 0;
+    let mut start_byte = 0;
+
+    for (byte_pos, cluster) in text.grapheme_indices(true) {
+        let width = cluster_width(cluster);
+        if current_width + width > max_width && start_byte < byte_pos {
+            // Exceeded width, slice from start_byte to byte_pos
+            chunks.push(&text[start_byte..byte_pos]);
+            start_byte = byte_pos;
+            current_width = width;
+        } else {
+            current_width += width;
+        }
+    }
+
+    // Add remaining text
+    if start_byte < text.len() {
+        chunks.push(&text[start_byte..]);
+    }
+
+    chunks
+}
+
+/// Where a row starting at cluster `start` ends when it has `room` columns, it takes at least one cluster
+fn fill(clusters: &[Cluster], start: usize, room: usize) -> usize {
+    let mut end = start;
+    let mut used = 0;
+    while end < clusters.len() && (end == start || used + clusters[end].width <= room) {
+        used += clusters[end].width;
+        end += 1;
+    }
+    end
+}
+
+/// Break `text` into the rows it takes in a window `width` columns wide
+/// Without wrapping that is the one row of what fits from char `left_col` on
+pub fn screen_lines(
+    text: &str,
+    width: usize,
+    left_col: usize,
+    options: &WrapOptions,
+) -> Vec<ScreenLine> {
+    let clusters = line_clusters(text, options);
+    let chars = clusters.last().map_or(0, |cluster| cluster.end);
+    // Char column where cluster `index` starts, the end of the line past the last
+    let col = |index: usize| clusters.get(index).map_or(chars, |cluster| cluster.start);
+    let first = ScreenLine {
+        start: 0,
+        end: chars,
+        indent: 0,
+        showbreak: false,
+    };
+    if width == 0 {
+        return vec![first];
+    }
+    if !options.wrap {
+        let start = clusters
+            .iter()
+            .position(|cluster| cluster.end > left_col)
+            .unwrap_or(clusters.len());
+        let end = fill(&clusters, start, width);
+        return vec![ScreenLine {
+            start: col(start),
+            end: col(end),
+            ..first
+        }];
+    }
+    let showbreak_width = self::width(&options.showbreak);
+    let showbreak = showbreak_width > 0 && showbreak_width < width;
+    let indent = if options.breakindent {
+        let indent: usize = clusters
+            .iter()
+            .take_while(|cluster| cluster.last.is_whitespace())
+            .map(|cluster| cluster.width)
+            .sum();
+        let room =
+            width.saturating_sub(MIN_TEXT_WIDTH + if showbreak { showbreak_width } else { 0 });
+        indent.min(room)
+    } else {
+        0
+    };
+
+    let mut lines = Vec::new();
+    let mut line = ScreenLine { end: 0, ..first };
+    let mut start = 0;
+    loop {
+        let room = width.saturating_sub(line.prefix(options)).max(1);
+        let mut end = fill(&clusters, start, room);
+        if end < clusters.len() && options.linebreak {
+            let breaks = (start + 1..=end)
+                .rev()
+                .find(|&at| BREAK_AT.contains(clusters[at - 1].last));
+            if let Some(at) = breaks {
+                end = at;
+            }
+        }
+        line.end = col(end);
+        lines.push(line);
+        if end >= clusters.len() {
+            return lines;
+        }
+        start = end;
+        line = ScreenLine {
+            start: col(end),
+            end: col(end),
+            indent,
+            showbreak,
+        };
+    }
+}
+
+/// The row of `lines` char `col` is on, past the end of the line is on the last row
+pub fn row_of(lines: &[ScreenLine], col: usize) -> usize {
+    lines
+        .iter()
+        .position(|line| col < line.end)
+        .unwrap_or(lines.len().saturating_sub(1))
+}
+
+/// The row and screen column char `col` of `text` is drawn at
+pub fn screen_position(
+    text: &str,
+    lines: &[ScreenLine],
+    col: usize,
+    options: &WrapOptions,
+) -> (usize, usize) {
+    let row = row_of(lines, col);
+    let Some(line) = lines.get(row) else {
+        return (0, 0);
+    };
+    let before =
+        display_col(text, col, options).saturating_sub(display_col(text, line.start, options));
+    (row, line.prefix(options) + before)
+}
+
+/// The char of `text` drawn at screen column `screen_col` of row `row`, the last on the row when it is shorter
+pub fn col_at(
+    text: &str,
+    lines: &[ScreenLine],
+    row: usize,
+    screen_col: usize,
+    options: &WrapOptions,
+) -> usize {
+    let Some(line) = lines.get(row) else {
+        return 0;
+    };
+    let mut x = line.prefix(options);
+    let mut last = line.start;
+    for cluster in line_clusters(text, options)
+        .iter()
+        .filter(|cluster| cluster.start >= line.start && cluster.end <= line.end)
+    {
+        x += cluster.width;
+        if x > screen_col {
+            return cluster.start;
+        }
+        last = cluster.start;
+    }
+    last
+}
+
+/// The first char to show when lines don't wrap, moved only as far as it takes to keep char `col` of `text` in view
+pub fn left_col(
+    text: &str,
+    col: usize,
+    left_col: usize,
+    width: usize,
+    options: &WrapOptions,
+) -> usize {
+    if col <= left_col || width == 0 {
+        return col.min(left_col);
+    }
+    let clusters = line_clusters(text, options);
+    let cursor_width = clusters
+        .iter()
+        .find(|cluster| cluster.end > col)
+        .map_or(1, |cluster| cluster.width);
+    let mut shown = clusters
+        .iter()
+        .filter(|cluster| cluster.end > left_col && cluster.start < col)
+        .peekable();
+    // Columns from the first cluster shown up to and including the cursor
+    let mut used: usize = shown.clone().map(|cluster| cluster.width).sum::<usize>() + cursor_width;
+    let mut left_col = shown.peek().map_or(col, |cluster| cluster.start);
+    while used > width {
+        match shown.next() {
+            Some(cluster) => {
+                used -= cluster.width;
+                left_col = cluster.end;
+            }
+            None => break,
+        }
+    }
+    left_col
+}
@@ -0,0 +1,294 @@
+use std::{collections::HashMap, ops::Range};
+
+/// Lines that differ between two versions of a text, as ranges of line indices in each
+/// An empty `old` range is an addition before that line, an empty `new` range a deletion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+impl Hunk {
+    /// Whether the cursor on `line` of the new text is on this hunk
+    /// A deletion is on the lines either side of where the old lines were
+    pub fn touches(&self, line: usize) -> bool {
+        if self.new.is_empty() {
+            line + 1 == self.new.start || line == self.new.start
+        } else {
+            self.new.contains(&line)
+        }
+    }
+}
+
+/// How lines are matched up between two texts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// The shortest edit script, what git uses by default
+    #[default]
+    Myers,
+    /// Lines that appear once in each text anchor the match first, which keeps moved blocks apart
+    Patience,
+}
+
+impl Algorithm {
+    pub fn parse(name: &str) -> Option<Algorithm> {
+        match name {
+            "myers" => Some(Algorithm::Myers),
+            "patience" => Some(Algorithm::Patience),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Myers => "myers",
+            Algorithm::Patience => "patience",
+        }
+    }
+}
+
+/// The hunks that turn `old` into `new`, in order, using Myers' algorithm in linear space
+pub fn diff(old: &[String], new: &[String]) -> Vec<Hunk> {
+    diff_with(Algorithm::Myers, old, new)
+}
+
+/// The hunks that turn `old` into `new` using `algorithm`
+pub fn diff_with(algorithm: Algorithm, old: &[String], new: &[String]) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    match algorithm {
+        Algorithm::Myers => compare(old, new, 0, 0, &mut hunks),
+        Algorithm::Patience => patience(old, new, 0, 0, &mut hunks),
+    }
+    // Splitting can leave a deletion right against an insertion, which reads as one change
+    let mut merged: Vec<Hunk> = Vec::with_capacity(hunks.len());
+    for hunk in hunks {
+        match merged.last_mut() {
+            Some(last) if last.old.end == hunk.old.start && last.new.end == hunk.new.start => {
+                last.old.end = hunk.old.end;
+                last.new.end = hunk.new.end;
+            }
+            _ => merged.push(hunk),
+        }
+    }
+    merged
+}
+
+/// Drop the lines `old` and `new` start and end with in common, moving the starts past them
+fn trim(old: &mut &[String], new: &mut &[String], old_start: &mut usize, new_start: &mut usize) {
+    let prefix = old.iter().zip(*new).take_while(|(a, b)| a == b).count();
+    *old = &old[prefix..];
+    *new = &new[prefix..];
+    *old_start += prefix;
+    *new_start += prefix;
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    *old = &old[..old.len() - suffix];
+    *new = &new[..new.len() - suffix];
+}
+
+/// Diff `old` against `new`, which start at the given lines of the whole texts
+fn compare(
+    mut old: &[String],
+    mut new: &[String],
+    mut old_start: usize,
+    mut new_start: usize,
+    hunks: &mut Vec<Hunk>,
+) {
+    trim(&mut old, &mut new, &mut old_start, &mut new_start);
+    if old.is_empty() && new.is_empty() {
+        return;
+    }
+    let whole = Hunk {
+        old: old_start..old_start + old.len(),
+        new: new_start..new_start + new.len(),
+    };
+    if old.is_empty() || new.is_empty() {
+        hunks.push(whole);
+        return;
+    }
+    match middle_snake(old, new) {
+        Some((x, y)) => {
+            compare(&old[..x], &new[..y], old_start, new_start, hunks);
+            compare(&old[x..], &new[y..], old_start + x, new_start + y, hunks);
+        }
+        None => hunks.push(whole),
+    }
+}
+
+/// Where the shortest edit script crosses its middle, searching from both ends at once
+/// `None` when nothing is shared and the texts are one replacement
+fn middle_snake(old: &[String], new: &[String]) -> Option<(usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d;
+    let length = 2 * max_d + 2;
+    let mut forward = vec![-1isize; length as usize];
+    let mut backward = vec![-1isize; length as usize];
+    forward[(offset + 1) as usize] = 0;
+    backward[(offset + 1) as usize] = 0;
+    let delta = n - m;
+    // With an odd delta the forward search is the one to meet the backward one
+    let front = delta % 2 != 0;
+    // Diagonals that ran off the edges, skipped from then on
+    let (mut k1_start, mut k1_end, mut k2_start, mut k2_end) = (0, 0, 0, 0);
+    for d in 0..max_d {
+        let mut k1 = -d + k1_start;
+        while k1 <= d - k1_end {
+            let index = (offset + k1) as usize;
+            let mut x1 = if k1 == -d || (k1 != d && forward[index - 1] < forward[index + 1]) {
+                forward[index + 1]
+            } else {
+                forward[index - 1] + 1
+            };
+            let mut y1 = x1 - k1;
+            while x1 < n && y1 < m && old[x1 as usize] == new[y1 as usize] {
+                x1 += 1;
+                y1 += 1;
+            }
+            forward[index] = x1;
+            if x1 > n {
+                k1_end += 2;
+            } else if y1 > m {
+                k1_start += 2;
+            } else if front {
+                let other = offset + delta - k1;
+                if other >= 0 && other < length && backward[other as usize] != -1 {
+                    let x2 = n - backward[other as usize];
+                    if x1 >= x2 {
+                        return Some((x1 as usize, y1 as usize));
+                    }
+                }
+            }
+            k1 += 2;
+        }
+        let mut k2 = -d + k2_start;
+        while k2 <= d - k2_end {
+            let index = (offset + k2) as usize;
+            let mut x2 = if k2 == -d || (k2 != d && backward[index - 1] < backward[index + 1]) {
+                backward[index + 1]
+            } else {
+                backward[index - 1] + 1
+            };
+            let mut y2 = x2 - k2;
+            while x2 < n && y2 < m && old[(n - x2 - 1) as usize] == new[(m - y2 - 1) as usize] {
+                x2 += 1;
+                y2 += 1;
+            }
+            backward[index] = x2;
+            if x2 > n {
+                k2_end += 2;
+            } else if y2 > m {
+                k2_start += 2;
+            } else if !front {
+                let other = offset + delta - k2;
+                if other >= 0 && other < length && forward[other as usize] != -1 {
+                    let x1 = forward[other as usize];
+                    let y1 = offset + x1 - other;
+                    if x1 >= n - x2 {
+                        return Some((x1 as usize, y1 as usize));
+                    }
+                }
+            }
+            k2 += 2;
+        }
+    }
+    None
+}
+
+/// Patience diff: match the lines unique to both sides in order, then diff between them
+/// Regions without unique lines fall back to Myers
+fn patience(
+    mut old: &[String],
+    mut new: &[String],
+    mut old_start: usize,
+    mut new_start: usize,
+    hunks: &mut Vec<Hunk>,
+) {
+    trim(&mut old, &mut new, &mut old_start, &mut new_start);
+    if old.is_empty() || new.is_empty() {
+        compare(old, new, old_start, new_start, hunks);
+        return;
+    }
+    let anchors = unique_matches(old, new);
+    if anchors.is_empty() {
+        compare(old, new, old_start, new_start, hunks);
+        return;
+    }
+    let (mut x, mut y) = (0, 0);
+    for (anchor_old, anchor_new) in anchors {
+        patience(
+            &old[x..anchor_old],
+            &new[y..anchor_new],
+            old_start + x,
+            new_start + y,
+            hunks,
+        );
+        (x, y) = (anchor_old + 1, anchor_new + 1);
+    }
+    patience(&old[x..], &new[y..], old_start + x, new_start + y, hunks);
+}
+
+/// Pairs of indices of lines appearing exactly once in each text, the longest run in the same order
+fn unique_matches(old: &[String], new: &[String]) -> Vec<(usize, usize)> {
+    // For each line: times seen in old, where in old, times seen in new, where in new
+    let mut counts: HashMap<&str, (usize, usize, usize, usize)> = HashMap::new();
+    for (index, line) in old.iter().enumerate() {
+        let entry = counts.entry(line).or_default();
+        entry.0 += 1;
+        entry.1 = index;
+    }
+    for (index, line) in new.iter().enumerate() {
+        if let Some(entry) = counts.get_mut(line.as_str()) {
+            entry.2 += 1;
+            entry.3 = index;
+        }
+    }
+    let mut pairs: Vec<(usize, usize)> = counts
+        .into_values()
+        .filter(|&(in_old, _, in_new, _)| in_old == 1 && in_new == 1)
+        .map(|(_, old_index, _, new_index)| (old_index, new_index))
+        .collect();
+    pairs.sort_unstable();
+
+    // Patience sorting finds the longest increasing run of new indices
+    // `tops[i]` ends the best run of length i + 1, `previous` links each pair to the one before it
+    let mut tops: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (index, &(_, new_index)) in pairs.iter().enumerate() {
+        let pile = tops.partition_point(|&top| pairs[top].1 < new_index);
+        if pile > 0 {
+            previous[index] = Some(tops[pile - 1]);
+        }
+        if pile == tops.len() {
+            tops.push(index);
+        } else {
+            tops[pile] = index;
+        }
+    }
+    let mut run = Vec::new();
+    let mut next = tops.last().copied();
+    while let Some(index) = next {
+        run.push(pairs[index]);
+        next = previous[index];
+    }
+    run.reverse();
+    run
+}
+
+/// The chars of `old` and `new` that differ, between what they start and end with in common
+pub fn changed_cols(old: &str, new: &str) -> ((usize, usize), (usize, usize)) {
+    let old: Vec<char> = old.chars().collect();
+    let new: Vec<char> = new.chars().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    ((prefix, old.len() - suffix), (prefix, new.len() - suffix))
+}
@@ -0,0 +1,197 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, bail};
+use crossterm::style::Color;
+
+use crate::{buffer::BufferId, event::ReovimEvent, task::Tasks};
+
+pub mod diff;
+#[cfg(test)]
+mod tests;
+
+use diff::Hunk;
+
+/// How a line differs from the file's version in the git index, shown in the gutter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VcsStatus {
+    Add,
+    None,
+    Deleted,
+    Modified,
+}
+
+impl VcsStatus {
+    pub fn color(&self) -> Color {
+        match self {
+            VcsStatus::Add => Color::Green,
+            VcsStatus::None => Color::Reset,
+            VcsStatus::Deleted => Color::Red,
+            VcsStatus::Modified => Color::DarkYellow,
+        }
+    }
+
+    /// The status of `line` in the new text of a diff
+    /// A deletion is marked on the line above where the old lines were, or the first line
+    pub fn of(hunks: &[Hunk], line: usize) -> VcsStatus {
+        for hunk in hunks {
+            if hunk.new.is_empty() {
+                if hunk.new.start.saturating_sub(1) == line {
+                    return VcsStatus::Deleted;
+                }
+            } else if hunk.new.contains(&line) {
+                return if hunk.old.is_empty() {
+                    VcsStatus::Add
+                } else {
+                    VcsStatus::Modified
+                };
+            }
+        }
+        VcsStatus::None
+    }
+}
+
+/// What git knows about a file, read from the repository on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VcsInfo {
+    /// The lines of the file in the index, `None` when it isn't tracked
+    pub base: Option<Vec<String>>,
+    /// The index version ends with a line break
+    pub newline_at_end: bool,
+    /// The checked out branch, or the commit when it is detached
+    pub branch: Option<String>,
+}
+
+impl VcsInfo {
+    /// The index's lines with `hunk` as it is in `lines`, and whether they end with a line break
+    pub fn with_hunk(&self, hunk: &Hunk, lines: &[String]) -> (Vec<String>, bool) {
+        let base = self.base.as_deref().unwrap_or_default();
+        let mut staged = base[..hunk.old.start].to_vec();
+        staged.extend_from_slice(&lines[hunk.new.clone()]);
+        staged.extend_from_slice(&base[hunk.old.end..]);
+        // The buffer always ends its last line, the index keeps its own ending otherwise
+        let newline_at_end = self.newline_at_end || hunk.old.end == base.len();
+        (staged, newline_at_end)
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .context("could not run git")?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(output.stdout)
+}
+
+fn git_line(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = git(dir, args).ok()?;
+    let line = String::from_utf8_lossy(&output).trim().to_string();
+    (!line.is_empty()).then_some(line)
+}
+
+/// The directory git runs in for `path` and the path's name relative to it
+fn split_path(path: &Path) -> Result<(&Path, String)> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = path
+        .file_name()
+        .context("no file name")?
+        .to_string_lossy()
+        .to_string();
+    Ok((dir, name))
+}
+
+/// Read what git has for a file, `None` when it isn't in a repository or git isn't installed
+pub fn load(path: &Path) -> Option<VcsInfo> {
+    let (dir, name) = split_path(path).ok()?;
+    if !dir.is_dir() {
+        return None;
+    }
+    git_line(dir, &["rev-parse", "--show-toplevel"])?;
+    let branch = git_line(dir, &["symbolic-ref", "--short", "-q", "HEAD"])
+        .or_else(|| git_line(dir, &["rev-parse", "--short", "HEAD"]));
+    let (base, newline_at_end) = match git(dir, &["show", &format!(":./{name}")]) {
+        Ok(contents) => {
+            let contents = String::from_utf8_lossy(&contents);
+            let lines = contents.lines().map(str::to_string).collect();
+            (Some(lines), contents.is_empty() || contents.ends_with('\n'))
+        }
+        Err(_) => (None, true),
+    };
+    Some(VcsInfo {
+        base,
+        newline_at_end,
+        branch,
+    })
+}
+
+/// Read what git has for a buffer's file on a worker, it arrives as `ReovimEvent::Vcs`
+pub fn load_in_background(tasks: &Tasks, buffer: BufferId, path: PathBuf) {
+    tasks.spawn(move || ReovimEvent::Vcs(buffer, load(&path)));
+}
+
+/// Stage `lines` for a buffer's file on a worker and read what git has for it again, it arrives as
+/// `ReovimEvent::Staged`
+pub fn stage_in_background(
+    tasks: &Tasks,
+    buffer: BufferId,
+    path: PathBuf,
+    (lines, newline_at_end): (Vec<String>, bool),
+) {
+    tasks.spawn(move || {
+        let staged = stage(&path, &lines, newline_at_end).map(|()| load(&path));
+        ReovimEvent::Staged(buffer, staged.map_err(|err| err.to_string()))
+    });
+}
+
+/// Replace the file's version in the index with `lines`, leaving the working tree alone
+pub fn stage(path: &Path, lines: &[String], newline_at_end: bool) -> Result<()> {
+    let (dir, name) = split_path(path)?;
+    let mut contents = lines.join("\n");
+    if newline_at_end && !lines.is_empty() {
+        contents.push('\n');
+    }
+    // Keep the executable bit of a tracked file
+    let mode = git_line(dir, &["ls-files", "-s", "--", &name])
+        .and_then(|entry| entry.split_whitespace().next().map(str::to_string))
+        .unwrap_or_else(|| "100644".to_string());
+
+    let mut child = Command::new("git")
+        .args(["hash-object", "-w", "--stdin", "--path", &name])
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("could not run git")?;
+    child
+        .stdin
+        .take()
+        .context("git has no stdin")?
+        .write_all(contents.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let object = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    git(
+        dir,
+        &[
+            "update-index",
+            "--add",
+            "--cacheinfo",
+            &format!("{mode},{object},{name}"),
+        ],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use proptest::prelude::*;
+
+use super::{
+    VcsInfo, VcsStatus,
+    diff::{Algorithm, Hunk, diff, diff_with},
+    load, stage,
+};
+
+fn lines(text: &str) -> Vec<String> {
+    text.lines().map(str::to_string).collect()
+}
+
+fn hunk(old: std::ops::Range<usize>, new: std::ops::Range<usize>) -> Hunk {
+    Hunk { old, new }
+}
+
+/// A repository of its own for each test with `name` committed holding `text`
+fn repository(test: &str, name: &str, text: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("reovim-vcs-{}-{test}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(name), text).unwrap();
+    for args in [
+        &["init", "-q"][..],
+        &["add", name],
+        &[
+            "-c",
+            "user.name=reovim",
+            "-c",
+            "user.email=reovim@example.com",
+            "commit",
+            "-qm",
+            "start",
+        ],
+    ] {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(&dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    }
+    dir
+}
+
+fn index(path: &Path) -> String {
+    let dir = path.parent().unwrap();
+    let name = path.file_name().unwrap().to_str().unwrap();
+    let output = Command::new("git")
+        .args(["show", &format!(":./{name}")])
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn lines_are_marked_by_the_hunk_they_are_in() {
+    let old = lines("a\nb\nc\nd\ne");
+    let new = lines("a\nB\nc\nnew\nd");
+    let hunks = diff(&old, &new);
+    assert_eq!(
+        hunks,
+        [hunk(1..2, 1..2), hunk(3..3, 3..4), hunk(4..5, 5..5)]
+    );
+    let statuses: Vec<_> = (0..new.len())
+        .map(|line| VcsStatus::of(&hunks, line))
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            VcsStatus::None,
+            VcsStatus::Modified,
+            VcsStatus::None,
+            VcsStatus::Add,
+            // `e` was removed below the last line
+            VcsStatus::Deleted,
+        ]
+    );
+    // A deletion is under the cursor on the lines either side of it
+    assert!(hunks[2].touches(4) && hunks[2].touches(5) && !hunks[2].touches(3));
+}
+
+#[test]
+fn staging_a_hunk_leaves_the_others_as_the_index_has_them() {
+    let info = VcsInfo {
+        base: Some(lines("a\nb\nc\nd")),
+        newline_at_end: false,
+        branch: None,
+    };
+    let new = lines("A\nb\nc\nd\ne");
+    let hunks = diff(info.base.as_deref().unwrap(), &new);
+    assert_eq!(hunks, [hunk(0..1, 0..1), hunk(4..4, 4..5)]);
+    assert_eq!(
+        info.with_hunk(&hunks[0], &new),
+        (lines("A\nb\nc\nd"), false)
+    );
+    // Lines added after the index's last line end with the buffer's line break
+    assert_eq!(
+        info.with_hunk(&hunks[1], &new),
+        (lines("a\nb\nc\nd\ne"), true)
+    );
+    // An untracked file's hunk is the whole file
+    let untracked = VcsInfo {
+        base: None,
+        newline_at_end: true,
+        branch: None,
+    };
+    let whole = hunk(0..0, 0..new.len());
+    assert_eq!(untracked.with_hunk(&whole, &new), (new.clone(), true));
+}
+
+#[test]
+fn stage_writes_the_index_and_not_the_file() {
+    let dir = repository("stage", "file.txt", "one\ntwo\n");
+    let path = dir.join("file.txt");
+    stage(&path, &lines("one\nTWO\nthree"), true).unwrap();
+    assert_eq!(index(&path), "one\nTWO\nthree\n");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+    let info = load(&path).unwrap();
+    assert_eq!(info.base, Some(lines("one\nTWO\nthree")));
+    assert!(info.newline_at_end);
+
+    stage(&path, &lines("one"), false).unwrap();
+    assert_eq!(index(&path), "one");
+    assert!(!load(&path).unwrap().newline_at_end);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn chars(text: &str) -> Vec<String> {
+    text.chars().map(String::from).collect()
+}
+
+/// The hunks from Myers and from patience
+fn both(old: &[String], new: &[String]) -> [Vec<Hunk>; 2] {
+    [Algorithm::Myers, Algorithm::Patience].map(|algorithm| diff_with(algorithm, old, new))
+}
+
+#[test]
+fn empty_and_identical_texts_have_no_hunks() {
+    assert_eq!(both(&[], &[]), [vec![], vec![]]);
+    let text = lines("a\nb\na");
+    assert_eq!(both(&text, &text), [vec![], vec![]]);
+}
+
+#[test]
+fn everything_inserted_or_deleted_is_one_hunk() {
+    let text = lines("a\nb\na");
+    let inserted = vec![hunk(0..0, 0..3)];
+    assert_eq!(both(&[], &text), [inserted.clone(), inserted]);
+    let deleted = vec![hunk(0..3, 0..0)];
+    assert_eq!(both(&text, &[]), [deleted.clone(), deleted]);
+    // Nothing in common is one replacement
+    let replaced = vec![hunk(0..3, 0..2)];
+    assert_eq!(both(&text, &lines("c\nd")), [replaced.clone(), replaced]);
+}
+
+#[test]
+fn myers_finds_the_shortest_edit_script() {
+    // The example from Myers' paper, five lines removed or added
+    assert_eq!(
+        diff(&chars("ABCABBA"), &chars("CBABAC")),
+        [
+            hunk(0..1, 0..1),
+            hunk(2..3, 2..2),
+            hunk(5..6, 4..4),
+            hunk(7..7, 5..6)
+        ]
+    );
+}
+
+#[test]
+fn patience_keeps_a_moved_block_together() {
+    let old = lines(
+        "void copy()\n{\n    check(src);\n    check(dst);\n\n    memcpy();\n}\n\n\
+         int check()\n{\n    if (!chunk) return 0;\n\n    return in_bounds;\n}",
+    );
+    let new = lines(
+        "int check()\n{\n    if (!chunk) return 0;\n\n    return in_bounds;\n}\n\n\
+         void copy()\n{\n    check(src);\n    check(dst);\n\n    memcpy();\n}",
+    );
+    let [myers, patience] = both(&old, &new);
+    // Myers lines up the braces and blank lines of the two functions
+    assert_eq!(
+        myers,
+        [
+            hunk(0..1, 0..1),
+            hunk(2..4, 2..3),
+            hunk(5..6, 4..5),
+            hunk(8..9, 7..8),
+            hunk(10..11, 9..11),
+            hunk(12..13, 12..13)
+        ]
+    );
+    // Patience matches the lines found once in each and moves `check` up whole
+    assert_eq!(patience, [hunk(0..0, 0..7), hunk(6..13, 13..13)]);
+}
+
+/// Lines removed and added by the shortest edit script, from the longest common subsequence
+fn shortest(old: &[String], new: &[String]) -> usize {
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for x in (0..old.len()).rev() {
+        for y in (0..new.len()).rev() {
+            common[x][y] = if old[x] == new[y] {
+                common[x + 1][y + 1] + 1
+            } else {
+                common[x + 1][y].max(common[x][y + 1])
+            };
+        }
+    }
+    old.len() + new.len() - 2 * common[0][0]
+}
+
+proptest! {
+    #[test]
+    fn hunks_turn_old_into_new(old in "[abc]{0,12}", new in "[abc]{0,12}") {
+        let (old, new) = (chars(&old), chars(&new));
+        for hunks in both(&old, &new) {
+            let mut rebuilt = Vec::new();
+            let mut at = 0;
+            for hunk in &hunks {
+                prop_assert!(hunk.old.start >= at);
+                prop_assert!(!hunk.old.is_empty() || !hunk.new.is_empty());
+                // Lines between hunks are the same on both sides
+                prop_assert_eq!(&old[at..hunk.old.start], &new[rebuilt.len()..hunk.new.start]);
+                rebuilt.extend_from_slice(&old[at..hunk.old.start]);
+                rebuilt.extend_from_slice(&new[hunk.new.clone()]);
+                at = hunk.old.end;
+            }
+            rebuilt.extend_from_slice(&old[at..]);
+            prop_assert_eq!(&rebuilt, &new);
+        }
+        let changed: usize = diff(&old, &new).iter().map(|hunk| hunk.old.len() + hunk.new.len()).sum();
+        prop_assert_eq!(changed, shortest(&old, &new));
+    }
+}
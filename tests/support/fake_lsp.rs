@@ -0,0 +1,278 @@
+//! A stand-in language server for the LSP client's tests, speaking just enough of the protocol over stdio
+//!
+//! It keeps each document's text from the incremental changes it is sent and answers from that:
+//! - diagnostics: a warning on every `TODO` and an error on every `FIXME`
+//! - hover: the word under the cursor and its whole line, so tests can check the text is in step
+//! - definition and references: the first and every whole-word occurrence of that word
+//! - rename: every occurrence replaced
+//! - formatting: trailing whitespace removed, and leading tabs made `tabSize` spaces each when `insertSpaces` is set
+//! - code actions: `TODO` to `DONE` on the lines asked about, and a command that upper-cases the first line
+//!   through `workspace/applyEdit`
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Write},
+};
+
+use serde_json::{Value, json};
+
+fn read_message(reader: &mut impl BufRead) -> Option<Value> {
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok()?;
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+fn write_message(message: Value) {
+    let body = message.to_string();
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+    stdout.flush().unwrap();
+}
+
+/// Byte offset of an LSP position, counting UTF-16 code units within the line
+fn offset(text: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap() as usize;
+    let character = position["character"].as_u64().unwrap() as usize;
+    let mut start = 0;
+    for _ in 0..line {
+        match text[start..].find('\n') {
+            Some(end) => start += end + 1,
+            None => return text.len(),
+        }
+    }
+    let line_text = text[start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (byte, character_at) in line_text.char_indices() {
+        if units >= character {
+            return start + byte;
+        }
+        units += character_at.len_utf16();
+    }
+    start + line_text.len()
+}
+
+/// The LSP position of a byte offset
+fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    json!({ "line": line, "character": character })
+}
+
+fn range(text: &str, start: usize, end: usize) -> Value {
+    json!({ "start": position(text, start), "end": position(text, end) })
+}
+
+fn is_word(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+/// The word around a byte offset, with where it starts
+fn word_at(text: &str, offset: usize) -> (usize, &str) {
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, character)| is_word(character))
+        .last()
+        .map_or(offset, |(index, _)| index);
+    let end = text[offset..]
+        .char_indices()
+        .find(|&(_, character)| !is_word(character))
+        .map_or(text.len(), |(index, _)| offset + index);
+    (start, &text[start..end])
+}
+
+/// Byte offsets of every whole-word occurrence of `word`
+fn occurrences(text: &str, word: &str) -> Vec<usize> {
+    text.match_indices(word)
+        .map(|(index, _)| index)
+        .filter(|&index| {
+            let before = text[..index].chars().next_back();
+            let after = text[index + word.len()..].chars().next();
+            !before.is_some_and(is_word) && !after.is_some_and(is_word)
+        })
+        .collect()
+}
+
+fn publish_diagnostics(uri: &str, text: &str) {
+    let mut diagnostics = Vec::new();
+    for (marker, severity) in [("TODO", 2), ("FIXME", 1)] {
+        for index in occurrences(text, marker) {
+            diagnostics.push(json!({
+                "range": range(text, index, index + marker.len()),
+                "severity": severity,
+                "message": format!("{marker} found"),
+            }));
+        }
+    }
+    write_message(json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    }));
+}
+
+fn main() {
+    let mut reader = BufReader::new(io::stdin().lock());
+    let mut documents: HashMap<String, String> = HashMap::new();
+    let mut next_request = 0;
+    while let Some(message) = read_message(&mut reader) {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": { "openClose": true, "change": 2, "save": true },
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "renameProvider": true,
+                    "codeActionProvider": true,
+                    "documentFormattingProvider": true,
+                    "executeCommandProvider": { "commands": ["fake.shout"] },
+                },
+            }),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap().to_string();
+                publish_diagnostics(&uri, &text);
+                documents.insert(uri, text);
+                continue;
+            }
+            "textDocument/didChange" => {
+                let text = documents.get_mut(&uri).unwrap();
+                for change in params["contentChanges"].as_array().unwrap() {
+                    let new_text = change["text"].as_str().unwrap();
+                    if change.get("range").is_none() {
+                        *text = new_text.to_string();
+                        continue;
+                    }
+                    let start = offset(text, &change["range"]["start"]);
+                    let end = offset(text, &change["range"]["end"]);
+                    text.replace_range(start..end, new_text);
+                }
+                publish_diagnostics(&uri, text);
+                continue;
+            }
+            "textDocument/hover" => {
+                let text = &documents[&uri];
+                let at = offset(text, &params["position"]);
+                let (_, word) = word_at(text, at);
+                let line_start = text[..at].rfind('\n').map_or(0, |index| index + 1);
+                let line = text[line_start..].split('\n').next().unwrap_or_default();
+                json!({ "contents": { "kind": "plaintext", "value": format!("{word}\n{line}") } })
+            }
+            "textDocument/definition" | "textDocument/references" => {
+                let text = &documents[&uri];
+                let (_, word) = word_at(text, offset(text, &params["position"]));
+                let mut found: Vec<Value> = occurrences(text, word)
+                    .into_iter()
+                    .map(|index| json!({ "uri": uri, "range": range(text, index, index + word.len()) }))
+                    .collect();
+                if method == "textDocument/definition" {
+                    found.truncate(1);
+                }
+                Value::Array(found)
+            }
+            "textDocument/rename" => {
+                let text = &documents[&uri];
+                let (_, word) = word_at(text, offset(text, &params["position"]));
+                let edits: Vec<Value> = occurrences(text, word)
+                    .into_iter()
+                    .map(|index| {
+                        json!({ "range": range(text, index, index + word.len()), "newText": params["newName"] })
+                    })
+                    .collect();
+                json!({ "changes": { uri: edits } })
+            }
+            "textDocument/formatting" => {
+                let text = &documents[&uri];
+                let mut edits = Vec::new();
+                let mut start = 0;
+                let tab = match params["options"]["insertSpaces"].as_bool() {
+                    Some(true) => params["options"]["tabSize"]
+                        .as_u64()
+                        .map(|size| " ".repeat(size as usize)),
+                    _ => None,
+                };
+                for line in text.split_inclusive('\n') {
+                    let content = line.trim_end_matches('\n');
+                    let trimmed = content.trim_end();
+                    let tabs = trimmed.len() - trimmed.trim_start_matches('\t').len();
+                    if let Some(tab) = tab.as_ref().filter(|_| tabs > 0) {
+                        edits.push(json!({
+                            "range": range(text, start, start + tabs),
+                            "newText": tab.repeat(tabs),
+                        }));
+                    }
+                    if trimmed.len() < content.len() {
+                        edits.push(json!({
+                            "range": range(text, start + trimmed.len(), start + content.len()),
+                            "newText": "",
+                        }));
+                    }
+                    start += line.len();
+                }
+                Value::Array(edits)
+            }
+            "textDocument/codeAction" => {
+                let text = &documents[&uri];
+                let start = offset(text, &params["range"]["start"]);
+                let end = offset(text, &params["range"]["end"]);
+                let edits: Vec<Value> = occurrences(text, "TODO")
+                    .into_iter()
+                    .filter(|&index| index >= start && index <= end)
+                    .map(|index| json!({ "range": range(text, index, index + 4), "newText": "DONE" }))
+                    .collect();
+                json!([
+                    {
+                        "title": "Mark done",
+                        "kind": "quickfix",
+                        "edit": { "documentChanges": [{ "textDocument": { "uri": uri, "version": null }, "edits": edits }] },
+                    },
+                    { "title": "Shout", "command": "fake.shout", "arguments": [uri] },
+                ])
+            }
+            "workspace/executeCommand" => {
+                let uri = params["arguments"][0].as_str().unwrap();
+                let text = &documents[uri];
+                let first = text.split('\n').next().unwrap_or_default();
+                next_request += 1;
+                write_message(json!({
+                    "jsonrpc": "2.0",
+                    "id": format!("fake-{next_request}"),
+                    "method": "workspace/applyEdit",
+                    "params": { "edit": { "changes": { uri: [{
+                        "range": range(text, 0, first.len()),
+                        "newText": first.to_uppercase(),
+                    }] } } },
+                }));
+                Value::Null
+            }
+            "shutdown" => Value::Null,
+            "exit" => return,
+            _ => continue,
+        };
+        if let Some(id) = message.get("id") {
+            write_message(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+        }
+    }
+}